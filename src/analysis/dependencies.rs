@@ -1,8 +1,12 @@
-use std::{collections::HashMap, path::PathBuf};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
 
 use crate::{
-    file::webx::WXModule,
-    reporting::error::{exit_error, DateTimeSpecifier, ERROR_CIRCULAR_DEPENDENCY},
+    engine::runtime::WXRuntimeError,
+    file::webx::{WXModule, WXScope},
+    reporting::error::ERROR_CIRCULAR_DEPENDENCY,
 };
 
 type DependencyTree = HashMap<PathBuf, Vec<PathBuf>>;
@@ -43,23 +47,90 @@ fn detect_circular_dependencies(tree: &DependencyTree) -> Vec<PathBuf> {
     circular_dependencies
 }
 
-fn analyze_circle_dependencies(modules: &[WXModule]) {
+fn analyze_circle_dependencies(modules: &[WXModule]) -> Result<(), WXRuntimeError> {
     let dependency_tree = construct_dependency_tree(modules);
     let circular_dependencies = detect_circular_dependencies(&dependency_tree);
     if !circular_dependencies.is_empty() {
-        exit_error(
-            format!(
+        return Err(WXRuntimeError {
+            code: ERROR_CIRCULAR_DEPENDENCY,
+            http_status: 500,
+            message: format!(
                 "Circular dependencies detected:\n{:?}",
                 circular_dependencies
             ),
-            ERROR_CIRCULAR_DEPENDENCY,
-            DateTimeSpecifier::None,
-        );
+        });
     }
+    Ok(())
 }
 
 /// Analyze the dependencies of a list of WebX modules.
-/// If a circular dependency is detected, an error is reported and the program exits.
-pub fn analyze_module_deps(modules: &[WXModule]) {
-    analyze_circle_dependencies(modules);
+///
+/// ## Returns
+/// The errors of all failed checks, e.g. a circular dependency.
+pub fn analyze_module_deps(modules: &[WXModule]) -> Result<(), Vec<WXRuntimeError>> {
+    analyze_circle_dependencies(modules).map_err(|err| vec![err])
+}
+
+fn scope_includes<'a>(scope: &'a WXScope, includes: &mut Vec<&'a String>) {
+    includes.extend(scope.includes.iter());
+    for nested in scope.scopes.iter() {
+        scope_includes(nested, includes);
+    }
+}
+
+fn normalize(path: &Path) -> PathBuf {
+    path.canonicalize().unwrap_or(path.to_path_buf())
+}
+
+/// The paths a module includes, resolved relative to the directory of the module.
+fn resolved_includes(module: &WXModule) -> Vec<PathBuf> {
+    let dir = module.path.inner.parent().unwrap_or(Path::new(""));
+    let mut includes = Vec::new();
+    scope_includes(&module.scope, &mut includes);
+    includes
+        .into_iter()
+        .map(|include| normalize(&dir.join(include)))
+        .collect()
+}
+
+/// Find all modules included by a given module.
+/// Included files that are not WebX modules, e.g. `.ts` or `.js` assets, are left out.
+pub fn included_modules<'a>(modules: &'a [WXModule], module: &WXModule) -> Vec<&'a WXModule> {
+    let includes = resolved_includes(module);
+    modules
+        .iter()
+        .filter(|other| includes.contains(&normalize(&other.path.inner)))
+        .collect()
+}
+
+/// The extensions of included assets executed in the runtime of the including module.
+pub const SCRIPT_ASSET_EXTENSIONS: [&str; 2] = ["ts", "js"];
+
+/// The `.ts` and `.js` assets a module includes, in the order they are included.
+pub fn included_assets(module: &WXModule) -> Vec<PathBuf> {
+    resolved_includes(module)
+        .into_iter()
+        .filter(|path| {
+            path.extension()
+                .and_then(|ext| ext.to_str())
+                .is_some_and(|ext| SCRIPT_ASSET_EXTENSIONS.contains(&ext))
+        })
+        .collect()
+}
+
+/// Find all modules that include a given file, e.g. a `.ts` or `.js` asset.
+/// Includes are resolved relative to the directory of the including module.
+///
+/// ## Arguments
+/// - `modules` - The list of WebX modules.
+/// - `file` - The path of the included file.
+///
+/// ## Returns
+/// The modules that depend on the file.
+pub fn find_dependents<'a>(modules: &'a [WXModule], file: &Path) -> Vec<&'a WXModule> {
+    let file = normalize(file);
+    modules
+        .iter()
+        .filter(|module| resolved_includes(module).contains(&file))
+        .collect()
 }
@@ -1,2 +1,5 @@
 pub mod dependencies;
+pub mod openapi;
+pub mod postman;
 pub mod routes;
+mod test;
@@ -0,0 +1,188 @@
+use serde_json::{json, Map, Value};
+
+use crate::{
+    analysis::routes::extract_flat_routes,
+    file::{
+        project::ProjectConfig,
+        webx::{
+            WXModel, WXModule, WXRouteReqBody, WXScope, WXTypedIdentifier, WXUrlPath,
+            WXUrlPathSegment,
+        },
+    },
+};
+
+/// The OpenAPI version of the generated documents.
+pub const OPENAPI_VERSION: &str = "3.0.3";
+
+/// The HTTP methods supported by OpenAPI path items.
+const OPENAPI_METHODS: [&str; 8] = [
+    "get", "put", "post", "delete", "options", "head", "patch", "trace",
+];
+
+/// Collect the models of a scope and all its nested scopes.
+pub fn collect_models<'a>(scope: &'a WXScope, models: &mut Vec<&'a WXModel>) {
+    models.extend(scope.models.iter());
+    for nested in scope.scopes.iter() {
+        collect_models(nested, models);
+    }
+}
+
+/// Map a declared WebX type to an OpenAPI schema.
+/// Model names are referenced as components, unknown types accept any value.
+///
+/// ## Example
+/// - `Int` -> `{ "type": "integer" }`
+/// - `String[]` -> `{ "type": "array", "items": { "type": "string" } }`
+/// - `Todo` -> `{ "$ref": "#/components/schemas/Todo" }`
+fn type_schema(type_: &str, models: &[&WXModel]) -> Value {
+    let type_ = type_.trim();
+    if let Some(item) = type_.strip_suffix("[]") {
+        return json!({ "type": "array", "items": type_schema(item, models) });
+    }
+    if let Some(item) = type_
+        .strip_prefix("Array<")
+        .and_then(|rest| rest.strip_suffix('>'))
+    {
+        return json!({ "type": "array", "items": type_schema(item, models) });
+    }
+    match type_.to_ascii_lowercase().as_str() {
+        "int" | "integer" => json!({ "type": "integer" }),
+        "float" | "number" | "double" => json!({ "type": "number" }),
+        "bool" | "boolean" => json!({ "type": "boolean" }),
+        "string" => json!({ "type": "string" }),
+        "date" => json!({ "type": "string", "format": "date-time" }),
+        _ if models.iter().any(|model| model.name == type_) => {
+            json!({ "$ref": format!("#/components/schemas/{}", type_) })
+        }
+        _ => json!({}),
+    }
+}
+
+/// Format a route path as an OpenAPI path template, e.g. `/todos/{id}`.
+fn path_template(path: &WXUrlPath) -> String {
+    let segments = path
+        .0
+        .iter()
+        .map(|segment| match segment {
+            WXUrlPathSegment::Literal(literal) => literal.clone(),
+            WXUrlPathSegment::Parameter(param) | WXUrlPathSegment::Optional(param) => {
+                format!("{{{}}}", param.name)
+            }
+            WXUrlPathSegment::Regex(name, _) => format!("{{{}}}", name),
+        })
+        .collect::<Vec<_>>();
+    format!("/{}", segments.join("/"))
+}
+
+fn path_parameters(path: &WXUrlPath, models: &[&WXModel]) -> Vec<Value> {
+    path.0
+        .iter()
+        .filter_map(|segment| {
+            let (name, schema) = match segment {
+                WXUrlPathSegment::Literal(_) => return None,
+                WXUrlPathSegment::Parameter(param) | WXUrlPathSegment::Optional(param) => {
+                    (&param.name, type_schema(&param.type_, models))
+                }
+                WXUrlPathSegment::Regex(name, regex) => {
+                    (name, json!({ "type": "string", "pattern": regex }))
+                }
+            };
+            // OpenAPI requires all path parameters, optional ones are described instead.
+            let mut parameter = json!({
+                "name": name,
+                "in": "path",
+                "required": true,
+                "schema": schema,
+            });
+            if let WXUrlPathSegment::Optional(_) = segment {
+                parameter["description"] = json!("Optional, may be left out of the path.");
+            }
+            Some(parameter)
+        })
+        .collect()
+}
+
+fn request_body(body: &WXRouteReqBody, models: &[&WXModel]) -> Value {
+    let (media_type, schema) = match body {
+        WXRouteReqBody::ModelReference(name) => ("application/json", type_schema(name, models)),
+        WXRouteReqBody::Definition(format, fields) => {
+            let media_type = match format.as_str() {
+                "form" => "application/x-www-form-urlencoded",
+                _ => "application/json",
+            };
+            (media_type, object_schema(fields, models))
+        }
+    };
+    json!({
+        "required": true,
+        "content": { media_type: { "schema": schema } },
+    })
+}
+
+/// An object schema with all fields required.
+fn object_schema(fields: &[WXTypedIdentifier], models: &[&WXModel]) -> Value {
+    let properties = fields
+        .iter()
+        .map(|field| (field.name.clone(), type_schema(&field.type_, models)))
+        .collect::<Map<_, _>>();
+    let mut schema = json!({ "type": "object", "properties": properties });
+    // OpenAPI 3.0 does not allow an empty list of required properties.
+    if !fields.is_empty() {
+        let required = fields.iter().map(|field| &field.name).collect::<Vec<_>>();
+        schema["required"] = json!(required);
+    }
+    schema
+}
+
+/// Generate an OpenAPI 3 document describing all routes of a project.
+///
+/// ## Arguments
+/// - `config` - The project configuration, used for the document info.
+/// - `modules` - The WebX modules of the project.
+///
+/// ## Returns
+/// The OpenAPI document as JSON.
+pub fn generate_openapi(config: &ProjectConfig, modules: &[WXModule]) -> Value {
+    let mut models = Vec::new();
+    for module in modules.iter() {
+        collect_models(&module.scope, &mut models);
+    }
+    let mut paths = Map::new();
+    for (route, path) in extract_flat_routes(modules).into_keys() {
+        let method = route.method.as_str().to_ascii_lowercase();
+        if !OPENAPI_METHODS.contains(&method.as_str()) {
+            continue; // E.g. `CONNECT` can not be described.
+        }
+        let mut operation = json!({
+            "responses": { "200": { "description": "OK" } },
+        });
+        let parameters = path_parameters(&path, &models);
+        if !parameters.is_empty() {
+            operation["parameters"] = Value::from(parameters);
+        }
+        if let Some(body) = &route.body_format {
+            operation["requestBody"] = request_body(body, &models);
+        }
+        let item = paths
+            .entry(path_template(&path))
+            .or_insert_with(|| json!({}));
+        item[method] = operation;
+    }
+    let schemas = models
+        .iter()
+        .map(|model| (model.name.clone(), object_schema(&model.fields, &models)))
+        .collect::<Map<_, _>>();
+    let mut info = json!({
+        "title": config.name,
+        "version": config.version,
+    });
+    if let Some(description) = &config.description {
+        info["description"] = Value::from(description.as_str());
+    }
+    json!({
+        "openapi": OPENAPI_VERSION,
+        "info": info,
+        "paths": paths,
+        "components": { "schemas": schemas },
+    })
+}
@@ -0,0 +1,206 @@
+use serde_json::{json, Map, Value};
+
+use crate::{
+    analysis::{openapi::collect_models, routes::extract_flat_routes},
+    engine::server::DEFAULT_HOST,
+    file::{
+        project::ProjectConfig,
+        webx::{
+            WXModel, WXModule, WXRouteReqBody, WXTypedIdentifier, WXUrlPath, WXUrlPathSegment,
+            WEBSOCKET_METHOD,
+        },
+    },
+};
+
+/// The schema of the generated Postman collections.
+pub const POSTMAN_SCHEMA: &str =
+    "https://schema.getpostman.com/json/collection/v2.1.0/collection.json";
+
+/// The variable the request URLs are relative to.
+const BASE_URL_VARIABLE: &str = "baseUrl";
+
+/// How deep models nested in example bodies are expanded,
+/// so models referencing themselves terminate.
+const MAX_EXAMPLE_DEPTH: usize = 4;
+
+/// An example value of a declared WebX type.
+/// Models are expanded into objects, unknown types are `null`.
+///
+/// ## Example
+/// - `Int` -> `0`
+/// - `String[]` -> `[""]`
+/// - `Todo` -> `{ "title": "", "completed": false }`
+fn example_value(type_: &str, models: &[&WXModel], depth: usize) -> Value {
+    let type_ = type_.trim();
+    if let Some(item) = type_.strip_suffix("[]").or_else(|| {
+        type_
+            .strip_prefix("Array<")
+            .and_then(|rest| rest.strip_suffix('>'))
+    }) {
+        return json!([example_value(item, models, depth)]);
+    }
+    match type_.to_ascii_lowercase().as_str() {
+        "int" | "integer" => json!(0),
+        "float" | "number" | "double" => json!(0.0),
+        "bool" | "boolean" => json!(false),
+        "string" => json!(""),
+        "date" => json!("1970-01-01T00:00:00Z"),
+        _ => match models.iter().find(|model| model.name == type_) {
+            Some(model) if depth < MAX_EXAMPLE_DEPTH => {
+                example_object(&model.fields, models, depth + 1)
+            }
+            _ => Value::Null,
+        },
+    }
+}
+
+fn example_object(fields: &[WXTypedIdentifier], models: &[&WXModel], depth: usize) -> Value {
+    let fields = fields
+        .iter()
+        .map(|field| {
+            let value = example_value(&field.type_, models, depth);
+            (field.name.clone(), value)
+        })
+        .collect::<Map<_, _>>();
+    Value::Object(fields)
+}
+
+/// The URL of a route relative to the base URL variable,
+/// with parameters as Postman path variables, e.g. `{{baseUrl}}/todos/:id`.
+fn request_url(path: &WXUrlPath) -> Value {
+    let mut segments = vec![];
+    let mut variables = vec![];
+    for segment in path.0.iter() {
+        match segment {
+            WXUrlPathSegment::Literal(literal) => segments.push(literal.clone()),
+            WXUrlPathSegment::Parameter(param) => {
+                segments.push(format!(":{}", param.name));
+                variables.push(json!({
+                    "key": param.name,
+                    "value": "",
+                    "description": param.type_,
+                }));
+            }
+            WXUrlPathSegment::Optional(param) => {
+                segments.push(format!(":{}", param.name));
+                variables.push(json!({
+                    "key": param.name,
+                    "value": "",
+                    "description": format!("{} (optional)", param.type_),
+                }));
+            }
+            WXUrlPathSegment::Regex(name, regex) => {
+                segments.push(format!(":{}", name));
+                variables.push(json!({
+                    "key": name,
+                    "value": "",
+                    "description": format!("Matching /{}/", regex),
+                }));
+            }
+        }
+    }
+    let host = format!("{{{{{}}}}}", BASE_URL_VARIABLE);
+    let mut url = json!({
+        "raw": format!("{}/{}", host, segments.join("/")),
+        "host": [host],
+        "path": segments,
+    });
+    if !variables.is_empty() {
+        url["variable"] = Value::from(variables);
+    }
+    url
+}
+
+/// The example body of a route, with the `Content-Type` header it is sent with.
+fn request_body(body: &WXRouteReqBody, models: &[&WXModel]) -> (Value, &'static str) {
+    let fields = match body {
+        WXRouteReqBody::ModelReference(name) => {
+            let example = example_value(name, models, 0);
+            return (json_body(&example), "application/json");
+        }
+        WXRouteReqBody::Definition(format, fields) if format == "form" => fields,
+        WXRouteReqBody::Definition(_, fields) => {
+            let example = example_object(fields, models, 0);
+            return (json_body(&example), "application/json");
+        }
+    };
+    let urlencoded = fields
+        .iter()
+        .map(|field| {
+            json!({
+                "key": field.name,
+                "value": "",
+                "type": "text",
+                "description": field.type_,
+            })
+        })
+        .collect::<Vec<_>>();
+    let body = json!({ "mode": "urlencoded", "urlencoded": urlencoded });
+    (body, "application/x-www-form-urlencoded")
+}
+
+fn json_body(example: &Value) -> Value {
+    json!({
+        "mode": "raw",
+        "raw": serde_json::to_string_pretty(example).unwrap(),
+        "options": { "raw": { "language": "json" } },
+    })
+}
+
+/// Generate a Postman v2.1 collection with a request for every route of a project.
+/// Insomnia can import these collections as well.
+///
+/// ## Arguments
+/// - `config` - The project configuration, used for the collection info and base URL.
+/// - `modules` - The WebX modules of the project.
+///
+/// ## Returns
+/// The collection as JSON.
+pub fn generate_postman(config: &ProjectConfig, modules: &[WXModule]) -> Value {
+    let mut models = Vec::new();
+    for module in modules.iter() {
+        collect_models(&module.scope, &mut models);
+    }
+    let mut routes = extract_flat_routes(modules)
+        .into_keys()
+        .filter(|(route, _)| route.method.as_str() != WEBSOCKET_METHOD)
+        .collect::<Vec<_>>();
+    routes.sort_by_cached_key(|(route, path)| (path.to_string(), route.method.to_string()));
+    let items = routes
+        .iter()
+        .map(|(route, path)| {
+            let mut request = json!({
+                "method": route.method.as_str(),
+                "header": [],
+                "url": request_url(path),
+            });
+            if let Some(body) = &route.body_format {
+                let (body, content_type) = request_body(body, &models);
+                request["header"] = json!([{ "key": "Content-Type", "value": content_type }]);
+                request["body"] = body;
+            }
+            json!({
+                "name": format!("{} {}", route.method, path),
+                "request": request,
+            })
+        })
+        .collect::<Vec<_>>();
+    let mut info = json!({
+        "name": config.name,
+        "version": config.version,
+        "schema": POSTMAN_SCHEMA,
+    });
+    if let Some(description) = &config.description {
+        info["description"] = Value::from(description.as_str());
+    }
+    let base_url = format!(
+        "http://{}:{}",
+        config.host.as_deref().unwrap_or(DEFAULT_HOST),
+        config.port
+    );
+    json!({
+        "info": info,
+        "item": items,
+        "variable": [{ "key": BASE_URL_VARIABLE, "value": base_url }],
+    })
+}
@@ -1,14 +1,27 @@
 use colored::*;
 
-use std::collections::HashMap;
+use std::{
+    collections::{HashMap, HashSet},
+    sync::OnceLock,
+};
 
 use crate::{
-    engine::runtime::WXRuntimeError,
-    file::webx::{WXInfoField, WXModule, WXRoute, WXScope, WXUrlPath, WXROOT_PATH},
+    analysis::dependencies::{included_assets, included_modules},
+    engine::{
+        runtime::WXRuntimeError,
+        stdlib::{self, WXNativeRegistry},
+    },
+    file::{
+        project::ProjectConfig,
+        webx::{
+            segment_regex, WXInfoField, WXMiddleware, WXModule, WXRoute, WXRouteHandlerCall,
+            WXScope, WXUrlPath, WEBSOCKET_METHOD, WXROOT_PATH,
+        },
+    },
     reporting::{
         error::{
-            exit_error, format_info_field, DateTimeSpecifier, ERROR_DUPLICATE_ROUTE,
-            ERROR_INVALID_ROUTE,
+            format_info_field, ERROR_DUPLICATE_ROUTE, ERROR_INVALID_ROUTE, ERROR_UNKNOWN_HANDLER,
+            ERROR_UNKNOWN_MIDDLEWARE,
         },
         route::print_route,
     },
@@ -16,24 +29,63 @@ use crate::{
 
 pub type FlatRoutes = HashMap<(WXRoute, WXUrlPath), Vec<WXInfoField>>;
 
-fn flatten_scopes(
+/// Find a middleware declaration by name,
+/// searching from the innermost enclosing scope outwards.
+fn resolve_middleware<'a>(name: &str, scopes: &[&'a WXScope]) -> Option<&'a WXMiddleware> {
+    scopes
+        .iter()
+        .rev()
+        .find_map(|scope| scope.middleware.iter().find(|m| m.name == name))
+}
+
+/// Flatten all routes of a scope and its nested scopes into `routes`.
+/// The handler calls of middleware used by a scope or any enclosing scope
+/// are prepended to the pre-handlers of its routes, outermost first.
+fn flatten_scopes<'a>(
     module_name: String,
-    scope: &WXScope,
+    scope: &'a WXScope,
     path_prefix: WXUrlPath,
+    enclosing: &[&'a WXScope],
+    middleware: &[WXRouteHandlerCall],
     routes: &mut FlatRoutes,
 ) {
+    let mut visible = enclosing.to_vec();
+    visible.push(scope);
+    let mut middleware = middleware.to_vec();
+    for name in scope.uses.iter() {
+        // Unknown middleware is reported by `analyze_unknown_middleware`.
+        if let Some(declaration) = resolve_middleware(name, &visible) {
+            middleware.extend(declaration.handlers.iter().cloned());
+        }
+    }
     for route in scope.routes.iter() {
         let flat_path = path_prefix.combine(&route.path);
-        let route_key = (route.clone(), flat_path);
+        let mut route = route.clone();
+        if !middleware.is_empty() {
+            route.pre_handlers = middleware
+                .iter()
+                .cloned()
+                .chain(route.pre_handlers)
+                .collect();
+        }
+        let info = route.info.clone();
+        let route_key = (route, flat_path);
         if let std::collections::hash_map::Entry::Vacant(entry) = routes.entry(route_key.clone()) {
-            entry.insert(vec![route.info.clone()]);
+            entry.insert(vec![info]);
         } else {
-            routes.get_mut(&route_key).unwrap().push(route.info.clone());
+            routes.get_mut(&route_key).unwrap().push(info);
         }
     }
     for sub_scope in scope.scopes.iter() {
         let sub_scope_path = path_prefix.combine(&sub_scope.path);
-        flatten_scopes(module_name.clone(), sub_scope, sub_scope_path, routes);
+        flatten_scopes(
+            module_name.clone(),
+            sub_scope,
+            sub_scope_path,
+            &visible,
+            &middleware,
+            routes,
+        );
     }
 }
 
@@ -44,12 +96,154 @@ pub fn extract_flat_routes(modules: &[WXModule]) -> FlatRoutes {
             module.path.module_name(),
             &module.scope,
             WXROOT_PATH,
+            &[],
+            &[],
             &mut routes,
         );
     }
     routes
 }
 
+fn extract_unknown_middleware<'a>(
+    module_name: &str,
+    scope: &'a WXScope,
+    path_prefix: WXUrlPath,
+    enclosing: &[&'a WXScope],
+    unknown: &mut Vec<String>,
+) {
+    let mut visible = enclosing.to_vec();
+    visible.push(scope);
+    for name in scope.uses.iter() {
+        if resolve_middleware(name, &visible).is_none() {
+            unknown.push(format!(
+                "Middleware {} used by location {} in module {} is not declared",
+                name.red(),
+                path_prefix.to_string().yellow(),
+                module_name,
+            ));
+        }
+    }
+    for sub_scope in scope.scopes.iter() {
+        let sub_scope_path = path_prefix.combine(&sub_scope.path);
+        extract_unknown_middleware(module_name, sub_scope, sub_scope_path, &visible, unknown);
+    }
+}
+
+/// Analyze that all middleware attached to a scope with `use` is declared,
+/// either in the same scope or in one of its enclosing scopes.
+pub fn analyze_unknown_middleware(modules: &[WXModule]) -> Result<(), WXRuntimeError> {
+    let mut unknown = vec![];
+    for module in modules.iter() {
+        extract_unknown_middleware(
+            &module.path.module_name(),
+            &module.scope,
+            WXROOT_PATH,
+            &[],
+            &mut unknown,
+        );
+    }
+    if !unknown.is_empty() {
+        return Err(WXRuntimeError {
+            code: ERROR_UNKNOWN_MIDDLEWARE,
+            http_status: 500,
+            message: format!(
+                "Unknown middleware detected:\n  - {}",
+                unknown.join("\n  - ")
+            ),
+        });
+    }
+    Ok(())
+}
+
+/// The names declared in JavaScript or TypeScript code, such as a global block:
+/// functions, classes, variables and properties assigned to `globalThis`.
+/// Destructured variables are not recognized.
+pub fn declared_names(code: &str) -> Vec<String> {
+    static DECLARATION: OnceLock<regex::Regex> = OnceLock::new();
+    let declaration = DECLARATION.get_or_init(|| {
+        regex::Regex::new(
+            r"(?:\bfunction\s*\*?\s*|\bclass\s+|\b(?:const|let|var)\s+|\bglobalThis\.)([A-Za-z_$][\w$]*)",
+        )
+        .unwrap()
+    });
+    declaration
+        .captures_iter(code)
+        .map(|captures| captures[1].to_string())
+        .collect()
+}
+
+fn scope_handlers(scope: &WXScope, names: &mut HashSet<String>) {
+    names.extend(scope.handlers.iter().map(|handler| handler.name.clone()));
+    for nested in scope.scopes.iter() {
+        scope_handlers(nested, names);
+    }
+}
+
+/// The handlers callable from the routes of a module:
+/// the handlers and global functions declared in the module and the modules it includes,
+/// and the functions declared in the `.ts` and `.js` assets it includes.
+/// Assets that can not be read are skipped, they fail when the module is loaded.
+fn module_handlers(modules: &[WXModule], module: &WXModule) -> HashSet<String> {
+    let mut names = HashSet::new();
+    for declaring in std::iter::once(module).chain(included_modules(modules, module)) {
+        scope_handlers(&declaring.scope, &mut names);
+        for (_, global_ts) in declaring.scope.globals() {
+            names.extend(declared_names(global_ts));
+        }
+    }
+    for asset in included_assets(module) {
+        if let Ok(source) = std::fs::read_to_string(asset) {
+            names.extend(declared_names(&source));
+        }
+    }
+    names
+}
+
+/// Analyze that every handler called by a route is declared,
+/// as a handler or global function of its module or an included module,
+/// a native function, or a function of the stdlib.
+/// Otherwise the call would only fail once the route is requested.
+pub fn analyze_unknown_handlers(
+    modules: &[WXModule],
+    natives: &WXNativeRegistry,
+) -> Result<(), WXRuntimeError> {
+    let stdlib_names = declared_names(stdlib::JAVASCRIPT);
+    let mut declared = HashMap::new();
+    let mut unknown = vec![];
+    for ((route, path), infos) in extract_flat_routes(modules).iter() {
+        let Some(module) = modules.iter().find(|module| module.path == route.info.path) else {
+            continue;
+        };
+        let handlers = declared
+            .entry(module.path.clone())
+            .or_insert_with(|| module_handlers(modules, module));
+        for call in route.pre_handlers.iter().chain(route.post_handlers.iter()) {
+            if handlers.contains(&call.name)
+                || natives.contains(&call.name)
+                || stdlib_names.contains(&call.name)
+            {
+                continue;
+            }
+            unknown.push(format!(
+                "Handler {} called by route {} ({}) is not declared",
+                call.name.red(),
+                print_route(&route.method, path),
+                format_info_field(infos.first().unwrap()),
+            ));
+        }
+    }
+    if !unknown.is_empty() {
+        // Flat routes are unordered, report them in a stable order.
+        unknown.sort();
+        return Err(WXRuntimeError {
+            code: ERROR_UNKNOWN_HANDLER,
+            http_status: 500,
+            message: format!("Unknown handlers detected:\n  - {}", unknown.join("\n  - ")),
+        });
+    }
+    Ok(())
+}
+
 pub fn extract_duplicate_routes(routes: &FlatRoutes) -> Vec<String> {
     routes
         .iter()
@@ -71,6 +265,7 @@ pub fn analyze_duplicate_routes(modules: &[WXModule]) -> Result<FlatRoutes, WXRu
     if !duplicate_routes.is_empty() {
         return Err(WXRuntimeError {
             code: ERROR_DUPLICATE_ROUTE,
+            http_status: 500,
             message: format!(
                 "Duplicate routes detected:\n  - {}",
                 duplicate_routes.join("\n  - ")
@@ -80,36 +275,152 @@ pub fn analyze_duplicate_routes(modules: &[WXModule]) -> Result<FlatRoutes, WXRu
     Ok(routes)
 }
 
-fn extract_invalid_routes(routes: &FlatRoutes) -> Vec<String> {
+/// Find routes of the same method that a request path could match both of,
+/// see `WXUrlPath::overlaps`. Such paths always resolve to the route with
+/// the higher precedence (see `WXRoute::precedence`), which is listed first.
+/// Routes ordered by their `priority(...)` annotations overlap on purpose, and are skipped.
+pub fn extract_overlapping_routes(routes: &FlatRoutes) -> Vec<String> {
+    let mut sorted = routes.iter().collect::<Vec<_>>();
+    sorted.sort_by_cached_key(|((route, path), _)| {
+        (route.method.to_string(), route.precedence(path))
+    });
+    let mut overlapping = vec![];
+    for (i, ((route, path), info)) in sorted.iter().enumerate() {
+        for ((other, other_path), other_info) in sorted[i + 1..].iter() {
+            if route.method != other.method
+                || route.priority != other.priority
+                || !path.overlaps(other_path)
+            {
+                continue;
+            }
+            let reason = if path.precedence().2 == other_path.precedence().2 {
+                "equally specific routes are resolved by name"
+            } else {
+                "literal segments take precedence over regex segments, and those over parameters"
+            };
+            overlapping.push(format!(
+                "Routes {} ({}) and {} ({}) overlap, the first takes precedence as {}",
+                print_route(&route.method, path),
+                format_info_field(info.first().unwrap()),
+                print_route(&other.method, other_path),
+                format_info_field(other_info.first().unwrap()),
+                reason,
+            ));
+        }
+    }
+    overlapping
+}
+
+/// Whether routes of a HTTP method must, must not, or may specify a request body format.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BodyFormatRule {
+    Required,
+    Forbidden,
+    Optional,
+}
+
+/// The request body format rules for all HTTP methods.
+///
+/// ## Default rules
+/// - `POST`, `PUT`, and `PATCH` require a body format.
+/// - `GET`, `HEAD`, `OPTIONS`, `CONNECT`, and `TRACE` forbid a body format.
+/// - `DELETE` forbids a body format, unless `allowDeleteBody` is set in the project config.
+/// - `ws` routes forbid a body format, as the handshake is a `GET` request.
+#[derive(Debug, Clone)]
+pub struct BodyFormatPolicy {
+    pub allow_delete_body: bool,
+}
+
+impl BodyFormatPolicy {
+    pub fn new(config: &ProjectConfig) -> Self {
+        BodyFormatPolicy {
+            allow_delete_body: config.allow_delete_body.unwrap_or(false),
+        }
+    }
+
+    pub fn rule(&self, method: &hyper::Method) -> BodyFormatRule {
+        match *method {
+            hyper::Method::POST | hyper::Method::PUT | hyper::Method::PATCH => {
+                BodyFormatRule::Required
+            }
+            hyper::Method::DELETE if self.allow_delete_body => BodyFormatRule::Optional,
+            hyper::Method::GET
+            | hyper::Method::HEAD
+            | hyper::Method::OPTIONS
+            | hyper::Method::CONNECT
+            | hyper::Method::TRACE
+            | hyper::Method::DELETE => BodyFormatRule::Forbidden,
+            _ if method.as_str() == WEBSOCKET_METHOD => BodyFormatRule::Forbidden,
+            _ => BodyFormatRule::Optional,
+        }
+    }
+}
+
+fn extract_invalid_routes(routes: &FlatRoutes, policy: &BodyFormatPolicy) -> Vec<String> {
     routes
         .iter()
-        .filter(|((route, _), _)| match route.method {
-            hyper::Method::GET | hyper::Method::DELETE => route.body_format.is_some(),
-            hyper::Method::POST | hyper::Method::PUT => route.body_format.is_none(),
-            _ => false,
-        })
-        .map(|((route, path), info)| {
-            format!(
-                "Route {} {} specify {}, but is not a POST or PUT endpoint. {}",
+        .filter_map(|((route, path), info)| {
+            let route_name = format!(
+                "{} {}",
                 route.method.to_string().green(),
-                path.to_string().yellow(),
-                route.body_format.as_ref().unwrap().to_string().red(),
-                format_info_field(info.first().unwrap()),
-            )
+                path.to_string().yellow()
+            );
+            let location = format_info_field(info.first().unwrap());
+            if let Some((pattern, err)) = path
+                .regex_patterns()
+                .find_map(|pattern| segment_regex(pattern).err().map(|err| (pattern, err)))
+            {
+                return Some(format!(
+                    "Route {} has the invalid regex segment {}: {}. {}",
+                    route_name,
+                    pattern.red(),
+                    err,
+                    location,
+                ));
+            }
+            if let Some(param) = path.misplaced_optional() {
+                return Some(format!(
+                    "Route {} has the optional parameter {} before its last segment, \
+                    but only the last segment can be optional. {}",
+                    route_name,
+                    param.name.red(),
+                    location,
+                ));
+            }
+            match (policy.rule(&route.method), &route.body_format) {
+                (BodyFormatRule::Forbidden, Some(body_format)) => Some(format!(
+                    "Route {} specify {}, but {} endpoints can not have a request body. {}",
+                    route_name,
+                    body_format.to_string().red(),
+                    route.method,
+                    location,
+                )),
+                (BodyFormatRule::Required, None) => Some(format!(
+                    "Route {} is missing a request body format, which is required for {} endpoints. {}",
+                    route_name, route.method, location,
+                )),
+                _ => None,
+            }
         })
         .collect()
 }
 
 /// Analyze the implementation of routes in a list of WebX modules.
-/// If an invalid route is detected, an error is reported and the program exits.
 /// Invalid routes include:
-/// - bad combinations of route methods and request body format types (e.g. GET + body)
-pub fn analyze_invalid_routes(modules: &[WXModule]) -> Result<(), WXRuntimeError> {
+/// - bad combinations of route methods and request body format types (e.g. GET + body),
+///   as defined by the `BodyFormatPolicy`
+/// - optional parameters before the last segment, e.g. from a scope `location /(page: Int)?`
+/// - regex segments with an invalid pattern, which could never match
+pub fn analyze_invalid_routes(
+    modules: &[WXModule],
+    policy: &BodyFormatPolicy,
+) -> Result<(), WXRuntimeError> {
     let routes = extract_flat_routes(modules);
-    let invalid_routes = extract_invalid_routes(&routes);
+    let invalid_routes = extract_invalid_routes(&routes, policy);
     if !invalid_routes.is_empty() {
         return Err(WXRuntimeError {
             code: ERROR_INVALID_ROUTE,
+            http_status: 500,
             message: format!(
                 "Invalid routes detected:\n  - {}",
                 invalid_routes.join("\n  - ")
@@ -119,19 +430,36 @@ pub fn analyze_invalid_routes(modules: &[WXModule]) -> Result<(), WXRuntimeError
     Ok(())
 }
 
-fn exit_on_err<T>(result: Result<T, WXRuntimeError>) {
-    if let Err(err) = result {
-        exit_error(err.message, err.code, DateTimeSpecifier::None);
+/// Analyze the routes of a list of WebX modules,
+/// for unknown middleware, duplicate routes and invalid routes.
+///
+/// ## Returns
+/// The errors of all failed checks, so the caller decides how to report them.
+pub fn analyze_module_routes(
+    modules: &[WXModule],
+    policy: &BodyFormatPolicy,
+) -> Result<(), Vec<WXRuntimeError>> {
+    let errors = [
+        analyze_unknown_middleware(modules),
+        analyze_duplicate_routes(modules).map(|_| ()),
+        analyze_invalid_routes(modules, policy),
+    ]
+    .into_iter()
+    .filter_map(Result::err)
+    .collect::<Vec<_>>();
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
     }
 }
 
-pub fn analyze_module_routes(modules: &[WXModule]) {
-    exit_on_err(analyze_duplicate_routes(modules));
-    exit_on_err(analyze_invalid_routes(modules));
-}
-
-pub fn verify_model_routes(modules: &[WXModule]) -> Result<FlatRoutes, WXRuntimeError> {
+pub fn verify_model_routes(
+    modules: &[WXModule],
+    policy: &BodyFormatPolicy,
+) -> Result<FlatRoutes, WXRuntimeError> {
+    analyze_unknown_middleware(modules)?;
     let routes = analyze_duplicate_routes(modules)?;
-    analyze_invalid_routes(modules)?;
+    analyze_invalid_routes(modules, policy)?;
     Ok(routes)
 }
@@ -0,0 +1,325 @@
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use crate::{
+        analysis::{
+            dependencies::{analyze_module_deps, find_dependents},
+            openapi::generate_openapi,
+            postman::{generate_postman, POSTMAN_SCHEMA},
+            routes::{
+                analyze_invalid_routes, analyze_module_routes, analyze_unknown_handlers,
+                analyze_unknown_middleware, extract_flat_routes, extract_overlapping_routes,
+                BodyFormatPolicy,
+            },
+        },
+        engine::stdlib::{WXNativeFunction, WXNativeFunctions, WXNativeRegistry},
+        file::{
+            parser::parse_webx_source,
+            project::{load_modules, load_project_config},
+            webx::{WXModule, WXUrlPathSegment, WEBSOCKET_METHOD},
+        },
+        reporting::error::{ERROR_DUPLICATE_ROUTE, ERROR_INVALID_ROUTE, ERROR_UNKNOWN_HANDLER},
+        runner::get_project_config_file_path,
+    };
+
+    fn parse(source: &str) -> Vec<WXModule> {
+        vec![parse_webx_source(&PathBuf::from("test.webx"), source).unwrap()]
+    }
+
+    #[test]
+    fn test_patch_requires_body_format() {
+        let policy = BodyFormatPolicy {
+            allow_delete_body: false,
+        };
+        let modules = parse("patch /todo json(title: String) {}\n");
+        assert!(analyze_invalid_routes(&modules, &policy).is_ok());
+        let modules = parse("patch /todo {}\n");
+        assert!(analyze_invalid_routes(&modules, &policy).is_err());
+    }
+
+    #[test]
+    fn test_invalid_regex_segment() {
+        let policy = BodyFormatPolicy {
+            allow_delete_body: false,
+        };
+        let mut modules = parse("get /api/version {}\n");
+        assert!(analyze_invalid_routes(&modules, &policy).is_ok());
+        // Regex segments are only created by the parser for wildcards.
+        modules[0].scope.routes[0].path.0[1] =
+            WXUrlPathSegment::Regex("ver".into(), "v(\\d+".into());
+        let err = analyze_invalid_routes(&modules, &policy).unwrap_err();
+        assert_eq!(err.code, ERROR_INVALID_ROUTE);
+        assert!(
+            err.message.contains("invalid regex segment"),
+            "{}",
+            err.message
+        );
+    }
+
+    #[test]
+    fn test_overlapping_routes() {
+        let modules = parse(
+            "get /user/(id: Int) {}\nget /user/me {}\nget /user/me/todos {}\npost /user/me json(name: String) {}\n",
+        );
+        let overlaps = extract_overlapping_routes(&extract_flat_routes(&modules));
+        assert_eq!(overlaps.len(), 1);
+        let overlap = &overlaps[0];
+        // The literal route is listed first, as it takes precedence.
+        let literal = overlap.find("/user/me").unwrap();
+        let parameter = overlap.find("/user/(id: Int)").unwrap();
+        assert!(literal < parameter, "{}", overlap);
+        assert!(overlap.contains("test line 2"), "{}", overlap);
+        assert!(overlap.contains("test line 1"), "{}", overlap);
+        assert!(overlap.contains("literal segments take precedence"));
+
+        let modules = parse("get /user/(id: Int) {}\nget /user/(name: String) {}\n");
+        let overlaps = extract_overlapping_routes(&extract_flat_routes(&modules));
+        assert_eq!(overlaps.len(), 1);
+        assert!(overlaps[0].contains("equally specific routes are resolved by name"));
+
+        let modules = parse("get /user/me {}\nget /users/(id: Int) {}\n");
+        assert!(extract_overlapping_routes(&extract_flat_routes(&modules)).is_empty());
+    }
+
+    #[test]
+    fn test_delete_body_format_opt_in() {
+        let modules = parse("delete /todo json(id: Int) {}\n");
+        let policy = BodyFormatPolicy {
+            allow_delete_body: false,
+        };
+        assert!(analyze_invalid_routes(&modules, &policy).is_err());
+        let policy = BodyFormatPolicy {
+            allow_delete_body: true,
+        };
+        assert!(analyze_invalid_routes(&modules, &policy).is_ok());
+        let modules = parse("delete /todo {}\n");
+        assert!(analyze_invalid_routes(&modules, &policy).is_ok());
+    }
+
+    #[test]
+    fn test_included_asset_dependents() {
+        let modules = vec![
+            parse_webx_source(
+                &PathBuf::from("webx/todo.webx"),
+                "include \"./utils.js\";\nget /todo {}\n",
+            )
+            .unwrap(),
+            parse_webx_source(&PathBuf::from("webx/index.webx"), "get / {}\n").unwrap(),
+        ];
+        let dependents = find_dependents(&modules, Path::new("webx/utils.js"));
+        assert_eq!(dependents.len(), 1);
+        assert_eq!(dependents[0].path.inner, PathBuf::from("webx/todo.webx"));
+        assert!(find_dependents(&modules, Path::new("webx/other.js")).is_empty());
+    }
+
+    #[test]
+    fn test_scope_middleware() {
+        let modules = parse(
+            r#"middleware logRequests -> log("request")
+
+get /public (<p>public</p>)
+
+location /admin {
+    middleware auth -> authenticate(user_id): user
+    use logRequests, auth
+
+    get /dashboard -> render(user)
+
+    location /users {
+        get /list -> listUsers(user)
+    }
+}
+"#,
+        );
+        assert!(analyze_unknown_middleware(&modules).is_ok());
+        let routes = extract_flat_routes(&modules);
+        let pre_handlers = |path: &str| {
+            let (route, _) = routes
+                .keys()
+                .find(|(_, flat_path)| flat_path.to_string() == path)
+                .unwrap();
+            route
+                .pre_handlers
+                .iter()
+                .map(|call| call.name.as_str())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(pre_handlers("/public"), Vec::<&str>::new());
+        assert_eq!(
+            pre_handlers("/admin/dashboard"),
+            ["log", "authenticate", "render"]
+        );
+        // Nested scopes inherit the middleware of their enclosing scopes.
+        assert_eq!(
+            pre_handlers("/admin/users/list"),
+            ["log", "authenticate", "listUsers"]
+        );
+    }
+
+    #[test]
+    fn test_unknown_middleware() {
+        // Middleware declared in a sibling scope is not visible.
+        let modules = parse(
+            "location /a {\n    middleware auth -> authenticate()\n}\nlocation /b {\n    use auth\n    get /c {}\n}\n",
+        );
+        assert!(analyze_unknown_middleware(&modules).is_err());
+    }
+
+    #[test]
+    fn test_unknown_handlers() {
+        let natives = WXNativeRegistry::default();
+        let modules = parse(
+            "global {\n    function count() { return 1; }\n}\nhandler auth(id: Int) {\n    return id;\n}\nget /a -> auth(1), count(), static(\"index.html\")\nget /b -> setHeader(\"x\", \"1\"), authh(1)\n",
+        );
+        let err = analyze_unknown_handlers(&modules, &natives).unwrap_err();
+        assert_eq!(err.code, ERROR_UNKNOWN_HANDLER);
+        assert_eq!(err.message.matches("is not declared").count(), 1);
+        assert!(err.message.contains("authh"));
+        assert!(err.message.contains("/b"));
+        // Handlers of included modules and registered native functions are declared as well.
+        let modules = vec![
+            parse_webx_source(
+                &PathBuf::from("webx/index.webx"),
+                "include \"./auth.webx\";\nget /a -> auth(1), greet(\"Ada\")\n",
+            )
+            .unwrap(),
+            parse_webx_source(
+                &PathBuf::from("webx/auth.webx"),
+                "handler auth(id: Int) {\n    return id;\n}\n",
+            )
+            .unwrap(),
+        ];
+        assert!(analyze_unknown_handlers(&modules, &natives).is_err());
+        let greet: WXNativeFunction = std::sync::Arc::new(|_| Ok("Hi".into()));
+        let mut registered = WXNativeFunctions::new();
+        registered.insert("greet".into(), greet);
+        let natives = WXNativeRegistry::new(&registered);
+        assert!(analyze_unknown_handlers(&modules, &natives).is_ok());
+    }
+
+    #[test]
+    fn test_asset_handlers() {
+        let root = std::env::temp_dir().join(format!("webx-asset-handlers-{}", std::process::id()));
+        std::fs::create_dir_all(root.join("webx")).unwrap();
+        std::fs::write(
+            root.join("webx/util.ts"),
+            "function slugify(title: string) {\n    return title.toLowerCase();\n}\n",
+        )
+        .unwrap();
+        let natives = WXNativeRegistry::default();
+        let module =
+            |source: &str| vec![parse_webx_source(&root.join("webx/index.webx"), source).unwrap()];
+        // Functions declared in included assets are callable from routes.
+        let modules = module("include \"./util.ts\";\nget /a -> slugify(\"A\")\n");
+        assert!(analyze_unknown_handlers(&modules, &natives).is_ok());
+        let modules = module("get /a -> slugify(\"A\")\n");
+        assert!(analyze_unknown_handlers(&modules, &natives).is_err());
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_analyzers_return_errors() {
+        let policy = BodyFormatPolicy {
+            allow_delete_body: false,
+        };
+        let modules = vec![
+            parse_webx_source(&PathBuf::from("a.webx"), "get /todos {}\n").unwrap(),
+            parse_webx_source(&PathBuf::from("b.webx"), "get /todos {}\npatch /todo {}\n").unwrap(),
+        ];
+        assert!(analyze_module_deps(&modules).is_ok());
+        // Every failed check is returned, instead of exiting on the first one.
+        let errors = analyze_module_routes(&modules, &policy).unwrap_err();
+        let codes = errors.iter().map(|err| err.code).collect::<Vec<_>>();
+        assert_eq!(codes, vec![ERROR_DUPLICATE_ROUTE, ERROR_INVALID_ROUTE]);
+        assert!(errors[0].message.contains("Duplicate routes detected"));
+        assert!(analyze_module_routes(&modules[..1], &policy).is_ok());
+    }
+
+    fn collect_refs<'a>(value: &'a serde_json::Value, refs: &mut Vec<&'a str>) {
+        match value {
+            serde_json::Value::Object(map) => {
+                if let Some(serde_json::Value::String(reference)) = map.get("$ref") {
+                    refs.push(reference);
+                }
+                map.values().for_each(|value| collect_refs(value, refs));
+            }
+            serde_json::Value::Array(values) => values.iter().for_each(|v| collect_refs(v, refs)),
+            _ => {}
+        }
+    }
+
+    #[test]
+    fn test_openapi_example_todo() {
+        let root = Path::new("./examples/todo/");
+        let config = load_project_config(&get_project_config_file_path(root), None);
+        let spec = generate_openapi(&config, &load_modules(root));
+        assert_eq!(spec["openapi"], "3.0.3");
+        assert_eq!(spec["info"]["title"], config.name.as_str());
+        assert_eq!(spec["info"]["version"], config.version.as_str());
+        let paths = spec["paths"].as_object().unwrap();
+        assert!(!paths.is_empty());
+        for (template, item) in paths {
+            assert!(template.starts_with('/'));
+            for operation in item.as_object().unwrap().values() {
+                assert!(operation["responses"]
+                    .as_object()
+                    .is_some_and(|r| !r.is_empty()));
+                // Every templated segment is declared as a path parameter and vice versa.
+                let declared: Vec<&str> = operation["parameters"]
+                    .as_array()
+                    .map(|params| params.iter().map(|p| p["name"].as_str().unwrap()).collect())
+                    .unwrap_or(vec![]);
+                let templated = template
+                    .split('/')
+                    .filter_map(|s| s.strip_prefix('{')?.strip_suffix('}'))
+                    .collect::<Vec<_>>();
+                assert_eq!(declared, templated, "{}", template);
+            }
+        }
+        let toggle = &paths["/todos/toggle"]["post"]["requestBody"]["content"];
+        assert_eq!(
+            toggle["application/json"]["schema"]["properties"]["id"]["type"],
+            "integer"
+        );
+        // All references resolve to component schemas.
+        let mut refs = vec![];
+        collect_refs(&spec, &mut refs);
+        for reference in refs {
+            let name = reference.strip_prefix("#/components/schemas/").unwrap();
+            assert!(spec["components"]["schemas"].get(name).is_some());
+        }
+        assert!(spec["components"]["schemas"].get("Todo").is_some());
+    }
+
+    #[test]
+    fn test_postman_example_todo() {
+        let root = Path::new("./examples/todo/");
+        let config = load_project_config(&get_project_config_file_path(root), None);
+        let modules = load_modules(root);
+        let collection = generate_postman(&config, &modules);
+        assert_eq!(collection["info"]["schema"], POSTMAN_SCHEMA);
+        assert_eq!(collection["info"]["name"], config.name.as_str());
+        let names = collection["item"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|item| item["name"].as_str().unwrap().to_string())
+            .collect::<Vec<_>>();
+        // Every HTTP route of the project is exported exactly once.
+        let routes = extract_flat_routes(&modules)
+            .into_keys()
+            .filter(|(route, _)| route.method.as_str() != WEBSOCKET_METHOD)
+            .map(|(route, path)| format!("{} {}", route.method, path))
+            .collect::<Vec<_>>();
+        assert!(!routes.is_empty());
+        assert_eq!(names.len(), routes.len());
+        for route in routes.iter() {
+            assert!(names.contains(route), "{}", route);
+        }
+        for item in collection["item"].as_array().unwrap() {
+            let raw = item["request"]["url"]["raw"].as_str().unwrap();
+            assert!(raw.starts_with("{{baseUrl}}/"), "{}", raw);
+        }
+    }
+}
@@ -0,0 +1,261 @@
+use std::{
+    path::PathBuf,
+    sync::{atomic::AtomicBool, Arc},
+    thread::JoinHandle,
+};
+
+use crate::{
+    engine::{
+        runtime::WXRuntimeError,
+        server::WXShutdown,
+        stdlib::{WXNativeFunction, WXNativeFunctions},
+    },
+    file::{project::ProjectConfig, webx::WXModule},
+    reporting::debug::info,
+    runner::{
+        analyze_modules, is_headless, load_project, open_in_browser, serve, WXConfigOverrides,
+        WXMode, WXOpener, WXProject,
+    },
+};
+
+/// Where the modules of a server come from.
+enum WXServerSource {
+    /// A project directory or a single module file.
+    Project(PathBuf),
+    /// Modules parsed by the caller, with the project root they are served from.
+    Modules(PathBuf, Vec<WXModule>),
+}
+
+/// Builds a `WebXServer` from a project or in-memory modules.
+///
+/// ## Example
+/// ```no_run
+/// let server = webx::WebXServer::builder()
+///     .project("path/to/project")
+///     .mode(webx::runner::WXMode::Prod)
+///     .build()
+///     .expect("Invalid WebX project");
+/// server.run(std::sync::Arc::new(std::sync::atomic::AtomicBool::new(true)));
+/// ```
+pub struct WebXServerBuilder {
+    source: WXServerSource,
+    mode: WXMode,
+    config: Option<ProjectConfig>,
+    config_path: Option<PathBuf>,
+    profile: Option<String>,
+    overrides: WXConfigOverrides,
+    natives: WXNativeFunctions,
+    open: bool,
+    opener: Option<WXOpener>,
+}
+
+impl WebXServerBuilder {
+    /// Serve the project in a directory, or a single `.webx` module file.
+    /// Default: the current directory.
+    pub fn project(mut self, root: impl Into<PathBuf>) -> Self {
+        self.source = WXServerSource::Project(root.into());
+        self
+    }
+
+    /// Serve already parsed modules instead of a project,
+    /// e.g. from `webx::file::parser::parse_webx_source`.
+    /// Paths of `static()` and templates are resolved relative to `root`.
+    pub fn modules(mut self, root: impl Into<PathBuf>, modules: Vec<WXModule>) -> Self {
+        self.source = WXServerSource::Modules(root.into(), modules);
+        self
+    }
+
+    /// The mode to run in, default: production.
+    pub fn mode(mut self, mode: WXMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// The configuration of in-memory modules, default: `ProjectConfig::new("webx")`.
+    /// Projects are configured by their configuration file instead.
+    pub fn config(mut self, config: ProjectConfig) -> Self {
+        self.config = Some(config);
+        self
+    }
+
+    /// The configuration file of the project, relative to the project root.
+    /// Default: `webx.config.json`.
+    pub fn config_file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.config_path = Some(path.into());
+        self
+    }
+
+    /// The name of a configuration profile to merge over the project configuration.
+    pub fn profile(mut self, profile: impl Into<String>) -> Self {
+        self.profile = Some(profile.into());
+        self
+    }
+
+    /// Configuration values taking precedence over the configuration.
+    pub fn overrides(mut self, overrides: WXConfigOverrides) -> Self {
+        self.overrides = overrides;
+        self
+    }
+
+    /// Register a native function, callable by name like any handler, e.g. `greet(user.name)`.
+    /// It is called with the evaluated arguments, and its result is bound like a handler result.
+    /// Built-in functions of the same name take precedence.
+    pub fn native(
+        mut self,
+        name: impl Into<String>,
+        function: impl Fn(&[serde_json::Value]) -> Result<serde_json::Value, String>
+            + Send
+            + Sync
+            + 'static,
+    ) -> Self {
+        let function: WXNativeFunction = Arc::new(function);
+        self.natives.insert(name.into(), function);
+        self
+    }
+
+    /// Open the server in the default browser once it is listening, default: `false`.
+    /// Only in dev mode, and not in headless environments such as CI, see `is_headless`.
+    pub fn open_browser(mut self, open: bool) -> Self {
+        self.open = open;
+        self
+    }
+
+    /// Open the server URL with a custom function instead of the default browser,
+    /// if `open_browser` is set. Unlike the browser, it is also called in headless environments.
+    pub fn opener(mut self, opener: impl Fn(&str) + Send + Sync + 'static) -> Self {
+        self.opener = Some(Arc::new(opener));
+        self
+    }
+
+    /// Load and analyze the modules.
+    ///
+    /// ## Errors
+    /// The errors of an invalid configuration, or all errors found in the modules.
+    /// Configuration files and modules that can not be read or parsed
+    /// are still reported and exit the process, like `webx run`.
+    pub fn build(self) -> Result<WebXServer, Vec<WXRuntimeError>> {
+        let project = match self.source {
+            WXServerSource::Project(root) => load_project(
+                &root,
+                self.mode,
+                self.config_path.as_deref(),
+                self.profile.as_deref(),
+                &self.overrides,
+                &self.natives,
+            )?,
+            WXServerSource::Modules(root, modules) => {
+                let mut config = self
+                    .config
+                    .unwrap_or_else(|| ProjectConfig::new("webx".into()));
+                self.overrides.apply(&mut config);
+                analyze_modules(&modules, &config, &self.natives)?;
+                WXProject {
+                    root,
+                    config,
+                    modules,
+                    watch: None,
+                }
+            }
+        };
+        let mode = self.mode;
+        let opener = match self.opener {
+            _ if !self.open || !mode.is_dev() => None,
+            Some(opener) => Some(opener),
+            None if is_headless() => {
+                info(mode, "Not opening a browser in a headless environment");
+                None
+            }
+            None => Some(Arc::new(move |url: &str| open_in_browser(mode, url)) as WXOpener),
+        };
+        Ok(WebXServer {
+            mode,
+            project,
+            natives: self.natives,
+            opener,
+        })
+    }
+}
+
+/// A WebX server, ready to run.
+pub struct WebXServer {
+    mode: WXMode,
+    project: WXProject,
+    natives: WXNativeFunctions,
+    opener: Option<WXOpener>,
+}
+
+impl WebXServer {
+    pub fn builder() -> WebXServerBuilder {
+        WebXServerBuilder {
+            source: WXServerSource::Project(PathBuf::from(".")),
+            mode: WXMode::Prod,
+            config: None,
+            config_path: None,
+            profile: None,
+            overrides: WXConfigOverrides::default(),
+            natives: WXNativeFunctions::new(),
+            open: false,
+            opener: None,
+        }
+    }
+
+    /// The configuration the server runs with.
+    pub fn config(&self) -> &ProjectConfig {
+        &self.project.config
+    }
+
+    /// Run the server in the current thread until `shutdown` is triggered,
+    /// or the given running flag is unset.
+    /// The requests in progress are finished before returning.
+    pub fn run(self, shutdown: impl Into<WXShutdown>) {
+        let project = self.project;
+        serve(
+            &project.root,
+            self.mode,
+            project.config,
+            project.modules,
+            project.watch,
+            self.natives,
+            self.opener,
+            shutdown,
+        );
+    }
+
+    /// Run the server in a background thread.
+    pub fn start(self) -> WebXHandle {
+        let shutdown = WXShutdown::default();
+        let server_shutdown = shutdown.clone();
+        let thread = std::thread::spawn(move || self.run(server_shutdown));
+        WebXHandle { shutdown, thread }
+    }
+}
+
+/// A server running in a background thread, see `WebXServer::start`.
+pub struct WebXHandle {
+    shutdown: WXShutdown,
+    thread: JoinHandle<()>,
+}
+
+impl WebXHandle {
+    /// The running flag of the server, which stops once it is unset,
+    /// e.g. from a Ctrl+C handler. Prefer `shutdown_trigger`, which stops it right away.
+    pub fn shutdown_signal(&self) -> Arc<AtomicBool> {
+        self.shutdown.running()
+    }
+
+    /// The shutdown signal of the server, to trigger from another thread.
+    pub fn shutdown_trigger(&self) -> WXShutdown {
+        self.shutdown.clone()
+    }
+
+    /// Whether the server has stopped.
+    pub fn is_finished(&self) -> bool {
+        self.thread.is_finished()
+    }
+
+    /// Stop the server and wait until the requests in progress are finished.
+    pub fn shutdown(self) {
+        self.shutdown.trigger();
+        let _ = self.thread.join();
+    }
+}
@@ -0,0 +1,96 @@
+use hmac::{digest::KeyInit, Hmac, Mac};
+use rand::{rngs::OsRng, RngCore};
+use sha2::{Digest, Sha256, Sha512};
+
+use crate::reporting::error::ERROR_HANDLER_CALL;
+
+use super::runtime::WXRuntimeError;
+
+/// The most bytes that can be generated by a single `randomBytes()` call.
+pub const MAX_RANDOM_BYTES: usize = 64 * 1024;
+
+/// The supported hash algorithms, by name.
+const ALGORITHMS: [&str; 2] = ["sha256", "sha512"];
+
+fn unsupported_algorithm(name: &str, algorithm: &str) -> WXRuntimeError {
+    WXRuntimeError {
+        message: format!(
+            "{}: unsupported algorithm '{}', expected one of: {}",
+            name,
+            algorithm,
+            ALGORITHMS.join(", ")
+        ),
+        code: ERROR_HANDLER_CALL,
+        http_status: 500,
+    }
+}
+
+/// Format bytes as a lowercase hex string.
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// The hex digest of the data with the given algorithm.
+pub fn hash(algorithm: &str, data: &[u8]) -> Result<String, WXRuntimeError> {
+    match algorithm {
+        "sha256" => Ok(to_hex(&Sha256::digest(data))),
+        "sha512" => Ok(to_hex(&Sha512::digest(data))),
+        _ => Err(unsupported_algorithm("hash", algorithm)),
+    }
+}
+
+/// The hex HMAC of the data with the given key and algorithm.
+pub fn hmac(algorithm: &str, key: &[u8], data: &[u8]) -> Result<String, WXRuntimeError> {
+    fn sign<M: Mac + KeyInit>(key: &[u8], data: &[u8]) -> String {
+        // HMAC accepts keys of any length.
+        let mut mac = <M as KeyInit>::new_from_slice(key).unwrap();
+        mac.update(data);
+        to_hex(&mac.finalize().into_bytes())
+    }
+    match algorithm {
+        "sha256" => Ok(sign::<Hmac<Sha256>>(key, data)),
+        "sha512" => Ok(sign::<Hmac<Sha512>>(key, data)),
+        _ => Err(unsupported_algorithm("hmac", algorithm)),
+    }
+}
+
+/// Compare two secrets in constant time, so the comparison does not leak
+/// how many leading bytes match.
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+/// A hex string of `len` cryptographically secure random bytes.
+pub fn random_bytes(len: usize) -> Result<String, WXRuntimeError> {
+    if len == 0 || len > MAX_RANDOM_BYTES {
+        return Err(WXRuntimeError {
+            message: format!(
+                "randomBytes: expected between 1 and {} bytes, got {}",
+                MAX_RANDOM_BYTES, len
+            ),
+            code: ERROR_HANDLER_CALL,
+            http_status: 500,
+        });
+    }
+    let mut bytes = vec![0; len];
+    OsRng.fill_bytes(&mut bytes);
+    Ok(to_hex(&bytes))
+}
+
+/// A random version 4 UUID, e.g. `1b4e28ba-2fa1-41d2-883f-0016d3cca427`.
+pub fn random_uuid() -> String {
+    let mut bytes = [0; 16];
+    OsRng.fill_bytes(&mut bytes);
+    // Set the version (4) and the variant (RFC 4122) bits.
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    let hex = to_hex(&bytes);
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[0..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..32]
+    )
+}
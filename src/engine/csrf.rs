@@ -0,0 +1,51 @@
+use hyper::{body::Bytes, Method, Request};
+
+use crate::file::project::CsrfConfig;
+
+use super::{crypto, http::requests};
+
+/// The header a CSRF token can be sent in, e.g. by `fetch()` calls.
+pub const CSRF_HEADER: &str = "X-CSRF-Token";
+
+/// The form field a CSRF token can be sent in, e.g. by HTML forms.
+pub const CSRF_FIELD: &str = "_csrf";
+
+/// Whether a request has to carry the CSRF token of its session.
+/// Only state-changing methods are protected, and exempt paths are skipped.
+/// Exempt paths ending in `*` match all paths starting with the rest.
+pub fn is_protected(config: &CsrfConfig, method: &Method, path: &str) -> bool {
+    let changes_state = matches!(
+        *method,
+        Method::POST | Method::PUT | Method::PATCH | Method::DELETE
+    );
+    let is_exempt = config
+        .exempt
+        .iter()
+        .flatten()
+        .any(|exempt| match exempt.strip_suffix('*') {
+            Some(prefix) => path.starts_with(prefix),
+            None => path == exempt,
+        });
+    changes_state && !is_exempt
+}
+
+/// Whether a request carries the expected CSRF token,
+/// either in the `X-CSRF-Token` header or the `_csrf` form field.
+pub fn verify(request: &Request<Bytes>, expected: &str) -> bool {
+    let header = request
+        .headers()
+        .get(CSRF_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string);
+    let is_form = request
+        .headers()
+        .get("Content-Type")
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("application/x-www-form-urlencoded"));
+    let token = header.or_else(|| {
+        is_form
+            .then(|| requests::form_field(request.body(), CSRF_FIELD))
+            .flatten()
+    });
+    token.is_some_and(|token| crypto::constant_time_eq(token.as_bytes(), expected.as_bytes()))
+}
@@ -0,0 +1,240 @@
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, OnceLock},
+    time::Duration,
+};
+
+use http_body_util::{BodyExt, Full, Limited};
+use hyper::{body::Bytes, Method, Request, Uri};
+use hyper_util::rt::TokioIo;
+use serde::{Deserialize, Serialize};
+use tokio::{
+    io::{AsyncRead, AsyncWrite},
+    net::TcpStream,
+};
+use tokio_rustls::{
+    rustls::{pki_types::ServerName, ClientConfig, RootCertStore},
+    TlsConnector,
+};
+
+use crate::{file::project::ProjectConfig, reporting::error::ERROR_HANDLER_CALL};
+
+use super::{http::requests::DEFAULT_MAX_BODY_SIZE, runtime::WXRuntimeError};
+
+/// The timeout of outbound requests if none is configured.
+pub const DEFAULT_FETCH_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The options of an outbound request, like the `init` object of the web `fetch()`.
+#[derive(Debug, Default, Deserialize)]
+pub struct WXFetchOptions {
+    pub method: Option<String>,
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    pub body: Option<String>,
+}
+
+/// The response to an outbound request, as seen by handlers.
+/// Header names are lowercase.
+#[derive(Debug, Serialize)]
+pub struct WXFetchResponse {
+    pub status: u16,
+    pub headers: BTreeMap<String, String>,
+    pub body: String,
+}
+
+/// The limits of outbound requests, as configured in the project.
+/// Kept in the op state of every module runtime for the `fetch` op.
+#[derive(Debug, Clone)]
+pub struct WXFetchLimits {
+    pub timeout: Duration,
+    /// The only hosts requests can be sent to, all hosts are allowed if `None`.
+    pub allowed_hosts: Option<Vec<String>>,
+    /// The maximum size of a response body in bytes, the same as for request bodies.
+    pub max_body_size: usize,
+}
+
+impl WXFetchLimits {
+    pub fn new(config: &ProjectConfig) -> Self {
+        let fetch = config.fetch.as_ref();
+        WXFetchLimits {
+            timeout: fetch
+                .and_then(|fetch| fetch.timeout_ms)
+                .map_or(DEFAULT_FETCH_TIMEOUT, Duration::from_millis),
+            allowed_hosts: fetch.and_then(|fetch| fetch.allowed_hosts.clone()),
+            max_body_size: config.max_body_size.unwrap_or(DEFAULT_MAX_BODY_SIZE),
+        }
+    }
+}
+
+impl Default for WXFetchLimits {
+    fn default() -> Self {
+        WXFetchLimits {
+            timeout: DEFAULT_FETCH_TIMEOUT,
+            allowed_hosts: None,
+            max_body_size: DEFAULT_MAX_BODY_SIZE,
+        }
+    }
+}
+
+fn fetch_error(message: String) -> WXRuntimeError {
+    WXRuntimeError {
+        message: format!("fetch: {}", message),
+        code: ERROR_HANDLER_CALL,
+        http_status: 500,
+    }
+}
+
+/// The executor sending outbound requests for all module runtimes.
+/// Module runtimes only await the result, so their threads are free meanwhile.
+fn executor() -> &'static tokio::runtime::Runtime {
+    static EXECUTOR: OnceLock<tokio::runtime::Runtime> = OnceLock::new();
+    EXECUTOR.get_or_init(|| {
+        tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(1)
+            .thread_name("webx-fetch")
+            .enable_all()
+            .build()
+            .expect("Failed to start the fetch executor")
+    })
+}
+
+/// Connects to HTTPS servers, trusting the Mozilla root certificates.
+fn tls_connector() -> &'static TlsConnector {
+    static CONNECTOR: OnceLock<TlsConnector> = OnceLock::new();
+    CONNECTOR.get_or_init(|| {
+        let mut roots = RootCertStore::empty();
+        roots.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+        let config = ClientConfig::builder()
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+        TlsConnector::from(Arc::new(config))
+    })
+}
+
+/// Send an outbound request on the shared fetch executor, see `fetch`.
+/// Can be awaited from any thread, e.g. by the `fetch` op of a module runtime.
+pub async fn fetch_in_background(
+    url: String,
+    options: WXFetchOptions,
+    limits: WXFetchLimits,
+) -> Result<WXFetchResponse, WXRuntimeError> {
+    executor()
+        .spawn(async move { fetch(&url, options, &limits).await })
+        .await
+        .map_err(|err| fetch_error(format!("request failed: {}", err)))?
+}
+
+/// Send an outbound HTTP or HTTPS request and wait for the whole response.
+///
+/// If an allowlist of hosts is configured, all other hosts are rejected.
+/// The request fails if no response is read within the configured timeout,
+/// or if the response body exceeds the maximum body size.
+pub async fn fetch(
+    url: &str,
+    options: WXFetchOptions,
+    limits: &WXFetchLimits,
+) -> Result<WXFetchResponse, WXRuntimeError> {
+    let uri = url
+        .parse::<Uri>()
+        .map_err(|err| fetch_error(format!("invalid URL '{}': {}", url, err)))?;
+    let default_port = match uri.scheme_str() {
+        Some("http") => 80,
+        Some("https") => 443,
+        _ => {
+            return Err(fetch_error(format!(
+                "unsupported URL '{}', only http and https URLs are supported",
+                url
+            )))
+        }
+    };
+    let Some(host) = uri.host() else {
+        return Err(fetch_error(format!("missing host in URL '{}'", url)));
+    };
+    let allowed_hosts = limits.allowed_hosts.as_ref();
+    if allowed_hosts.is_some_and(|hosts| !hosts.iter().any(|h| h.eq_ignore_ascii_case(host))) {
+        return Err(fetch_error(format!("host '{}' is not allowed", host)));
+    }
+    let request = build_request(&uri, options)?;
+    let address = (host.to_string(), uri.port_u16().unwrap_or(default_port));
+    let is_https = default_port == 443;
+    let response = tokio::time::timeout(limits.timeout, async {
+        let stream = TcpStream::connect(address).await?;
+        if !is_https {
+            return send(stream, request, limits.max_body_size).await;
+        }
+        // IPv6 hosts are bracketed in URLs, but not in server names.
+        let server_name = ServerName::try_from(host.trim_matches(['[', ']']).to_string())?;
+        let stream = tls_connector().connect(server_name, stream).await?;
+        send(stream, request, limits.max_body_size).await
+    });
+    response
+        .await
+        .map_err(|_| {
+            fetch_error(format!(
+                "request to '{}' timed out after {}ms",
+                url,
+                limits.timeout.as_millis()
+            ))
+        })?
+        .map_err(|err| fetch_error(format!("request to '{}' failed: {}", url, err)))
+}
+
+fn build_request(
+    uri: &Uri,
+    options: WXFetchOptions,
+) -> Result<Request<Full<Bytes>>, WXRuntimeError> {
+    let method = match &options.method {
+        Some(method) => Method::from_bytes(method.to_ascii_uppercase().as_bytes())
+            .map_err(|_| fetch_error(format!("invalid method '{}'", method)))?,
+        None => Method::GET,
+    };
+    let path = uri.path_and_query().map_or("/", |path| path.as_str());
+    let mut builder = Request::builder()
+        .method(method)
+        .uri(path)
+        .header("Host", uri.authority().unwrap().as_str())
+        .header("User-Agent", format!("webx/{}", env!("CARGO_PKG_VERSION")));
+    for (name, value) in &options.headers {
+        builder = builder.header(name, value);
+    }
+    let body = Bytes::from(options.body.unwrap_or_default());
+    builder
+        .body(Full::new(body))
+        .map_err(|err| fetch_error(format!("invalid request: {}", err)))
+}
+
+async fn send<T>(
+    stream: T,
+    request: Request<Full<Bytes>>,
+    max_body_size: usize,
+) -> Result<WXFetchResponse, Box<dyn std::error::Error + Send + Sync>>
+where
+    T: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let (mut sender, connection) =
+        hyper::client::conn::http1::handshake(TokioIo::new(stream)).await?;
+    tokio::spawn(connection);
+    let response = sender.send_request(request).await?;
+    let status = response.status().as_u16();
+    let headers = response
+        .headers()
+        .iter()
+        .map(|(name, value)| {
+            let value = String::from_utf8_lossy(value.as_bytes()).into_owned();
+            (name.to_string(), value)
+        })
+        .collect();
+    let body = Limited::new(response.into_body(), max_body_size)
+        .collect()
+        .await
+        .map_err(|err| match err.is::<http_body_util::LengthLimitError>() {
+            true => format!("response body exceeds {} bytes", max_body_size).into(),
+            false => err,
+        })?
+        .to_bytes();
+    Ok(WXFetchResponse {
+        status,
+        headers,
+        body: String::from_utf8_lossy(&body).into_owned(),
+    })
+}
@@ -1,142 +1,313 @@
 use notify::{self, Error, Event, Watcher};
+use std::collections::HashMap;
 use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::mpsc::Sender;
-use std::sync::Arc;
-use std::time::Instant;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
 use crate::engine::runtime::WXRuntimeMessage;
 use crate::file::parser::parse_webx_file;
-use crate::file::webx::WXModulePath;
+use crate::file::project::{reload_project_config, ProjectConfig};
+use crate::file::webx::{is_module_path, WXModulePath};
 use crate::reporting::debug::info;
 use crate::reporting::warning::warning;
 use crate::runner::WXMode;
-use crate::timeout_duration;
 
-struct FSWEvent {
-    pub kind: notify::EventKind,
-    pub path: WXModulePath,
-    pub timestamp: Instant,
-    is_empty_state: bool,
+/// The default debounce window for file system events.
+pub const DEFAULT_DEBOUNCE_WINDOW: Duration = Duration::from_millis(100);
+
+/// The default extensions of included assets to watch.
+pub const DEFAULT_WATCH_EXTENSIONS: [&str; 2] = ["ts", "js"];
+
+fn has_extension<S: AsRef<str>>(path: &Path, extensions: &[S]) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| extensions.iter().any(|e| e.as_ref() == ext))
 }
 
-impl FSWEvent {
-    fn new(kind: notify::EventKind, path: &Path) -> Self {
-        Self {
-            kind,
-            path: WXModulePath::new(path.to_path_buf()),
-            timestamp: Instant::now(),
-            is_empty_state: false,
-        }
+/// The extensions of the swap and backup files editors write next to the edited file.
+const EDITOR_TEMP_EXTENSIONS: [&str; 4] = ["swp", "swx", "tmp", "bak"];
+
+/// Whether a path is a hidden file, or a temporary file of an editor,
+/// e.g. `.index.webx.swp`, `.#index.webx`, `#index.webx#` or `index.webx~`.
+/// These are never parsed or treated as assets, even with a watched extension.
+pub fn is_editor_temp_file(path: &Path) -> bool {
+    let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+        return true;
+    };
+    name.starts_with(['.', '#'])
+        || name.ends_with('~')
+        || has_extension(path, &EDITOR_TEMP_EXTENSIONS)
+}
+
+/// The extensions of included assets to watch, as configured in the project.
+pub fn watch_extensions(config: &ProjectConfig) -> Vec<String> {
+    match &config.watch_extensions {
+        Some(extensions) => extensions
+            .iter()
+            .map(|ext| ext.trim_start_matches('.').to_string())
+            .collect(),
+        None => DEFAULT_WATCH_EXTENSIONS.map(String::from).to_vec(),
     }
+}
+
+/// The coalesced change of a file after a burst of file system events.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FSWChange {
+    Created,
+    Modified,
+    Removed,
+}
+
+struct FSWPending {
+    first: FSWChange,
+    last: FSWChange,
+    timestamp: Instant,
+}
 
-    fn empty() -> Self {
-        Self {
-            kind: notify::EventKind::default(),
-            path: WXModulePath::new(PathBuf::default()),
-            timestamp: Instant::now(),
-            is_empty_state: true,
+impl FSWPending {
+    fn change(&self) -> FSWChange {
+        match (self.first, self.last) {
+            (_, FSWChange::Removed) => FSWChange::Removed,
+            (FSWChange::Created, _) => FSWChange::Created,
+            _ => FSWChange::Modified,
         }
     }
+}
+
+/// Coalesces bursts of file system events per path.
+/// A path is only reported once no new events have been
+/// received for it within the debounce window.
+///
+/// ## Example
+/// Editors often save by writing a temporary file and renaming it,
+/// resulting in a create, modify, and rename event for the same path.
+/// These are all reported as a single change.
+pub struct FSWDebouncer {
+    window: Duration,
+    pending: HashMap<PathBuf, FSWPending>,
+}
 
-    fn is_duplicate(&self, earlier: &Self) -> bool {
-        if self.is_empty_state || earlier.is_empty_state {
-            return false;
+impl FSWDebouncer {
+    pub fn new(window: Duration) -> Self {
+        FSWDebouncer {
+            window,
+            pending: HashMap::new(),
         }
-        const EPSILON: u128 = 100; // ms
-        self.kind == earlier.kind
-            && self.path == earlier.path
-            && self.timestamp.duration_since(earlier.timestamp).as_millis() < EPSILON
+    }
+
+    /// Register a file system event for a path at the given time.
+    pub fn push(&mut self, kind: &notify::EventKind, path: &Path, now: Instant) {
+        let change = match kind {
+            notify::EventKind::Create(_) => FSWChange::Created,
+            notify::EventKind::Modify(_) => FSWChange::Modified,
+            notify::EventKind::Remove(_) => FSWChange::Removed,
+            _ => return,
+        };
+        self.pending
+            .entry(path.to_path_buf())
+            .and_modify(|pending| {
+                pending.last = change;
+                pending.timestamp = now;
+            })
+            .or_insert(FSWPending {
+                first: change,
+                last: change,
+                timestamp: now,
+            });
+    }
+
+    /// Take all paths that have settled at the given time.
+    pub fn drain(&mut self, now: Instant) -> Vec<(PathBuf, FSWChange)> {
+        let settled = self
+            .pending
+            .iter()
+            .filter(|(_, pending)| now.duration_since(pending.timestamp) >= self.window)
+            .map(|(path, _)| path.clone())
+            .collect::<Vec<_>>();
+        settled
+            .into_iter()
+            .filter_map(|path| {
+                let pending = self.pending.remove(&path)?;
+                Some((path, pending.change()))
+            })
+            .collect()
     }
 }
 
-pub struct WXFileWatcher {}
+pub struct WXFileWatcher {
+    mode: WXMode,
+    /// The project root, which paths in the configuration are relative to.
+    root: PathBuf,
+    source_root: PathBuf,
+    config_file: PathBuf,
+    config: ProjectConfig,
+    extensions: Vec<String>,
+    rt_tx: Sender<WXRuntimeMessage>,
+}
 
 impl WXFileWatcher {
+    /// Reload the project configuration and pass it on to the runtime.
+    /// Invalid configurations are reported, and the previous one is kept.
+    /// Changes that require a restart are only reported.
+    fn reload_config(&mut self) {
+        let new_config =
+            match reload_project_config(&self.config_file, self.config.active_profile.as_deref()) {
+                Ok(new_config) => new_config,
+                Err(err) => {
+                    warning(self.mode, format!("(FileWatcher) Error: {}", err));
+                    return;
+                }
+            };
+        if let Err(errors) = new_config.validate(&self.root) {
+            warning(
+                self.mode,
+                format!(
+                    "(FileWatcher) Invalid configuration, keeping the previous one:\n  - {}",
+                    errors.join("\n  - ")
+                ),
+            );
+            return;
+        }
+        if new_config.port != self.config.port
+            || new_config.host != self.config.host
+            || new_config.src != self.config.src
+        {
+            warning(
+                self.mode,
+                "(FileWatcher) Changing the port, host, or src requires a restart".into(),
+            );
+        }
+        self.extensions = watch_extensions(&new_config);
+        self.config = new_config.clone();
+        if let Err(err) = self.rt_tx.send(WXRuntimeMessage::Config(new_config)) {
+            warning(
+                self.mode,
+                format!("(FileWatcher) Error send Config: {}", err),
+            )
+        }
+    }
+
+    /// Send a single runtime message for the coalesced change of a path.
+    fn handle_change(&mut self, path: PathBuf, change: FSWChange) {
+        if path == self.config_file {
+            if change != FSWChange::Removed {
+                self.reload_config();
+            }
+            return;
+        }
+        if !path.starts_with(&self.source_root) {
+            return; // Ignore files outside of the source root.
+        }
+        if is_editor_temp_file(&path) {
+            return;
+        }
+        if !is_module_path(&path) {
+            if has_extension(&path, &self.extensions) {
+                // Included assets are not parsed, but reload their dependent modules.
+                if let Err(err) = self.rt_tx.send(WXRuntimeMessage::Asset(path)) {
+                    warning(
+                        self.mode,
+                        format!("(FileWatcher) Error send Asset: {}", err),
+                    )
+                }
+            }
+            return; // Ignore unrelated files.
+        }
+        // The final state on disk decides, e.g. a remove followed by a rename.
+        let change = match (change, path.exists()) {
+            (_, false) => FSWChange::Removed,
+            (FSWChange::Removed, true) => FSWChange::Modified,
+            (change, true) => change,
+        };
+        let path = WXModulePath::new(path);
+        let (message, name) = match change {
+            FSWChange::Removed => (WXRuntimeMessage::Remove(path), "Remove"),
+            FSWChange::Created | FSWChange::Modified => match parse_webx_file(&path.inner) {
+                Ok(module) if change == FSWChange::Created => {
+                    (WXRuntimeMessage::New(module), "New")
+                }
+                Ok(module) => (WXRuntimeMessage::Swap(module), "Swap"),
+                Err(err) => {
+                    warning(self.mode, format!("(FileWatcher) Error: {:?}", err));
+                    return;
+                }
+            },
+        };
+        if let Err(err) = self.rt_tx.send(message) {
+            warning(
+                self.mode,
+                format!("(FileWatcher) Error send {} module: {}", name, err),
+            )
+        }
+    }
+
     /// Registers the file watcher thread
     pub fn run(
         mode: WXMode,
+        root: PathBuf,
         source_root: PathBuf,
+        config_file: PathBuf,
+        config: ProjectConfig,
         rt_tx: Sender<WXRuntimeMessage>,
         running: Arc<AtomicBool>,
     ) {
-        let mut last_event: FSWEvent = FSWEvent::empty();
-        let mut watcher = notify::recommended_watcher(move |res: Result<Event, Error>| {
-            match res {
-                Ok(event) => {
-                    match event.kind {
-                        notify::EventKind::Create(_) => {
-                            let event = FSWEvent::new(event.kind, &event.paths[0]);
-                            if !event.is_duplicate(&last_event) {
-                                match parse_webx_file(&event.path.inner) {
-                                    Ok(module) => {
-                                        if let Err(err) = rt_tx.send(WXRuntimeMessage::New(module))
-                                        {
-                                            warning(
-                                                mode,
-                                                format!(
-                                                    "(FileWatcher) Error send New module: {}",
-                                                    err
-                                                ),
-                                            )
-                                        }
-                                    }
-                                    Err(err) => {
-                                        warning(mode, format!("(FileWatcher) Error: {:?}", err))
-                                    }
-                                }
-                            }
-                            last_event = event; // Update last event
-                        }
-                        notify::EventKind::Modify(_) => {
-                            let event = FSWEvent::new(event.kind, &event.paths[0]);
-                            if !event.is_duplicate(&last_event) {
-                                match parse_webx_file(&event.path.inner) {
-                                    Ok(module) => {
-                                        if let Err(err) = rt_tx.send(WXRuntimeMessage::Swap(module))
-                                        {
-                                            warning(
-                                                mode,
-                                                format!(
-                                                    "(FileWatcher) Error send Swap module: {}",
-                                                    err
-                                                ),
-                                            )
-                                        }
-                                    }
-                                    Err(err) => {
-                                        warning(mode, format!("(FileWatcher) Error: {:?}", err))
-                                    }
-                                }
-                            }
-                            last_event = event; // Update last event
-                        }
-                        notify::EventKind::Remove(_) => {
-                            let event = FSWEvent::new(event.kind, &event.paths[0]);
-                            if !event.is_duplicate(&last_event) {
-                                rt_tx
-                                    .send(WXRuntimeMessage::Remove(event.path.clone()))
-                                    .unwrap();
-                            }
-                            last_event = event; // Update last event
-                        }
-                        _ => (),
-                    }
+        let window = config
+            .watch_debounce_ms
+            .map(Duration::from_millis)
+            .unwrap_or(DEFAULT_DEBOUNCE_WINDOW);
+        let mut file_watcher = WXFileWatcher {
+            mode,
+            root,
+            source_root: source_root.canonicalize().unwrap_or(source_root),
+            config_file: config_file.canonicalize().unwrap_or(config_file),
+            extensions: watch_extensions(&config),
+            config,
+            rt_tx,
+        };
+        let debouncer = Arc::new(Mutex::new(FSWDebouncer::new(window)));
+        let fw_debouncer = debouncer.clone();
+        let mut watcher = notify::recommended_watcher(move |res: Result<Event, Error>| match res {
+            Ok(event) => {
+                let now = Instant::now();
+                let mut debouncer = fw_debouncer.lock().unwrap();
+                for path in event.paths.iter() {
+                    debouncer.push(&event.kind, path, now);
                 }
-                Err(err) => warning(mode, format!("watch error: {:?}", err)),
             }
+            Err(err) => warning(mode, format!("watch error: {:?}", err)),
         })
         .unwrap();
         watcher
-            .watch(&source_root, notify::RecursiveMode::Recursive)
+            .watch(&file_watcher.source_root, notify::RecursiveMode::Recursive)
             .unwrap();
+        if !file_watcher
+            .config_file
+            .starts_with(&file_watcher.source_root)
+        {
+            // Watch the directory, as editors often replace files on save.
+            if let Some(config_dir) = file_watcher.config_file.parent() {
+                if let Err(err) = watcher.watch(config_dir, notify::RecursiveMode::NonRecursive) {
+                    warning(
+                        mode,
+                        format!("(FileWatcher) Failed to watch the config file: {}", err),
+                    );
+                }
+            }
+        }
         info(mode, "Hot reloading is enabled.");
+        let poll_interval = (window / 2).max(Duration::from_millis(10));
         loop {
             if !running.load(Ordering::SeqCst) {
                 // println!("Shutting down file watcher...");
                 break;
             }
-            std::thread::sleep(timeout_duration(mode));
+            let settled = debouncer.lock().unwrap().drain(Instant::now());
+            for (path, change) in settled {
+                file_watcher.handle_change(path, change);
+            }
+            std::thread::sleep(poll_interval);
         }
     }
 }
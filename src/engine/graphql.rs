@@ -0,0 +1,668 @@
+use std::collections::{BTreeMap, HashMap};
+
+use hyper::{body::Bytes, Method, Request, Response, StatusCode};
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+
+use crate::{
+    file::webx::{WXModule, WXScope, WXTypedIdentifier},
+    runner::WXMode,
+};
+
+use super::http::{requests, responses::common_headers};
+
+/// The default path of the GraphQL endpoint.
+pub const DEFAULT_GRAPHQL_PATH: &str = "/graphql";
+
+/// The meta field resolving to the name of the type of an object.
+const TYPENAME_FIELD: &str = "__typename";
+
+/// The opening and closing quotes of a block string.
+const BLOCK_QUOTE: [char; 3] = ['"'; 3];
+
+/// A GraphQL request, sent as the JSON body of a `POST` request
+/// or as the query parameters of a `GET` request.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WXGraphQLRequest {
+    pub query: String,
+    pub variables: Option<Map<String, Value>>,
+    pub operation_name: Option<String>,
+}
+
+/// Read the GraphQL request of an HTTP request to the endpoint.
+pub fn read_request(request: &Request<Bytes>) -> Result<WXGraphQLRequest, String> {
+    match *request.method() {
+        Method::POST => serde_json::from_slice(request.body())
+            .map_err(|err| format!("Invalid GraphQL request body: {}", err)),
+        Method::GET => {
+            let params = request.uri().query().unwrap_or_default().as_bytes();
+            let query =
+                requests::form_field(params, "query").ok_or("Missing the \"query\" parameter")?;
+            let variables = match requests::form_field(params, "variables") {
+                Some(variables) => serde_json::from_str(&variables)
+                    .map_err(|err| format!("Invalid GraphQL variables: {}", err))?,
+                None => None,
+            };
+            Ok(WXGraphQLRequest {
+                query,
+                variables,
+                operation_name: requests::form_field(params, "operationName"),
+            })
+        }
+        _ => Err(format!(
+            "GraphQL requests must be sent with GET or POST, got {}",
+            request.method()
+        )),
+    }
+}
+
+/// The kind of a GraphQL operation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WXGraphQLOperationType {
+    Query,
+    Mutation,
+    Subscription,
+}
+
+/// A field selected by a GraphQL operation.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WXGraphQLField {
+    pub alias: Option<String>,
+    pub name: String,
+    pub arguments: Map<String, Value>,
+    /// The selected subfields, empty for scalar fields.
+    pub selection: Vec<WXGraphQLField>,
+}
+
+impl WXGraphQLField {
+    /// The key of the field in the result, its alias if given.
+    pub fn key(&self) -> &str {
+        self.alias.as_deref().unwrap_or(&self.name)
+    }
+}
+
+/// An executable GraphQL operation, with its variables substituted.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WXGraphQLOperation {
+    pub operation_type: WXGraphQLOperationType,
+    pub selection: Vec<WXGraphQLField>,
+}
+
+/// Parse a GraphQL document and select the operation to execute.
+/// Variables are substituted while parsing, falling back to their default values.
+///
+/// ## Errors
+/// A message if the document is invalid, uses unsupported features such as fragments,
+/// or the operation to execute is ambiguous.
+pub fn parse_operation(
+    source: &str,
+    operation_name: Option<&str>,
+    variables: &Map<String, Value>,
+) -> Result<WXGraphQLOperation, String> {
+    let mut parser = WXGraphQLParser {
+        chars: source.chars().collect(),
+        pos: 0,
+        provided: variables,
+        variables: Map::new(),
+    };
+    let mut operations = vec![];
+    while parser.peek().is_some() {
+        operations.push(parser.parse_definition()?);
+    }
+    match operation_name {
+        Some(name) => operations
+            .into_iter()
+            .find(|(operation, _)| operation.as_deref() == Some(name))
+            .map(|(_, operation)| operation)
+            .ok_or_else(|| format!("Unknown operation named \"{}\".", name)),
+        None if operations.len() > 1 => {
+            Err("Must provide operation name if query contains multiple operations.".to_string())
+        }
+        None => operations
+            .pop()
+            .map(|(_, operation)| operation)
+            .ok_or_else(|| parser.unexpected("an operation")),
+    }
+}
+
+fn is_name_start(c: char) -> bool {
+    c.is_ascii_alphabetic() || c == '_'
+}
+
+/// A parser of executable GraphQL documents.
+struct WXGraphQLParser<'a> {
+    chars: Vec<char>,
+    pos: usize,
+    /// The variables sent with the request.
+    provided: &'a Map<String, Value>,
+    /// The variables of the operation being parsed.
+    variables: Map<String, Value>,
+}
+
+impl WXGraphQLParser<'_> {
+    /// Skip whitespace, commas, and comments.
+    fn skip_ignored(&mut self) {
+        while let Some(&c) = self.chars.get(self.pos) {
+            match c {
+                '#' => {
+                    while self.chars.get(self.pos).is_some_and(|c| *c != '\n') {
+                        self.pos += 1;
+                    }
+                }
+                ',' | '\u{feff}' => self.pos += 1,
+                c if c.is_whitespace() => self.pos += 1,
+                _ => break,
+            }
+        }
+    }
+
+    /// The next significant character, without consuming it.
+    fn peek(&mut self) -> Option<char> {
+        self.skip_ignored();
+        self.chars.get(self.pos).copied()
+    }
+
+    fn unexpected(&self, expected: &str) -> String {
+        match self.chars.get(self.pos) {
+            Some(c) => format!("Syntax Error: Expected {}, found \"{}\".", expected, c),
+            None => format!("Syntax Error: Expected {}, found <EOF>.", expected),
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), String> {
+        if self.peek() != Some(expected) {
+            return Err(self.unexpected(&format!("\"{}\"", expected)));
+        }
+        self.pos += 1;
+        Ok(())
+    }
+
+    fn parse_name(&mut self) -> Result<String, String> {
+        if !self.peek().is_some_and(is_name_start) {
+            return Err(self.unexpected("a name"));
+        }
+        let start = self.pos;
+        while self
+            .chars
+            .get(self.pos)
+            .is_some_and(|c| c.is_ascii_alphanumeric() || *c == '_')
+        {
+            self.pos += 1;
+        }
+        Ok(self.chars[start..self.pos].iter().collect())
+    }
+
+    /// Parse an operation, either a `query { ... }` with an optional name
+    /// and variable definitions, or the `{ ... }` shorthand of a query.
+    fn parse_definition(&mut self) -> Result<(Option<String>, WXGraphQLOperation), String> {
+        self.variables = self.provided.clone();
+        if self.peek() == Some('{') {
+            let selection = self.parse_selection_set()?;
+            let operation_type = WXGraphQLOperationType::Query;
+            return Ok((
+                None,
+                WXGraphQLOperation {
+                    operation_type,
+                    selection,
+                },
+            ));
+        }
+        let operation_type = match self.parse_name()?.as_str() {
+            "query" => WXGraphQLOperationType::Query,
+            "mutation" => WXGraphQLOperationType::Mutation,
+            "subscription" => WXGraphQLOperationType::Subscription,
+            "fragment" => return Err("Fragments are not supported".to_string()),
+            keyword => return Err(format!("Syntax Error: Unexpected name \"{}\".", keyword)),
+        };
+        let name = match self.peek() {
+            Some(c) if is_name_start(c) => Some(self.parse_name()?),
+            _ => None,
+        };
+        if self.peek() == Some('(') {
+            self.parse_variable_definitions()?;
+        }
+        let selection = self.parse_selection_set()?;
+        Ok((
+            name,
+            WXGraphQLOperation {
+                operation_type,
+                selection,
+            },
+        ))
+    }
+
+    /// Parse the variable definitions of an operation, e.g. `($id: Int!, $limit: Int = 10)`.
+    /// Only the defined variables are available to the operation.
+    fn parse_variable_definitions(&mut self) -> Result<(), String> {
+        self.expect('(')?;
+        let mut defined = Map::new();
+        while self.peek() != Some(')') {
+            self.expect('$')?;
+            let name = self.parse_name()?;
+            self.expect(':')?;
+            let type_ = self.parse_type()?;
+            let default = if self.peek() == Some('=') {
+                self.pos += 1;
+                Some(self.parse_value()?)
+            } else {
+                None
+            };
+            match self.provided.get(&name).cloned().or(default) {
+                Some(value) => {
+                    defined.insert(name, value);
+                }
+                None if type_.ends_with('!') => {
+                    return Err(format!(
+                        "Variable \"${}\" of required type \"{}\" was not provided.",
+                        name, type_
+                    ))
+                }
+                None => {
+                    defined.insert(name, Value::Null);
+                }
+            }
+        }
+        self.pos += 1;
+        self.variables = defined;
+        Ok(())
+    }
+
+    /// Parse a variable type, e.g. `Int`, `[String!]!`.
+    fn parse_type(&mut self) -> Result<String, String> {
+        let mut type_ = if self.peek() == Some('[') {
+            self.pos += 1;
+            let item = self.parse_type()?;
+            self.expect(']')?;
+            format!("[{}]", item)
+        } else {
+            self.parse_name()?
+        };
+        if self.peek() == Some('!') {
+            self.pos += 1;
+            type_.push('!');
+        }
+        Ok(type_)
+    }
+
+    fn parse_selection_set(&mut self) -> Result<Vec<WXGraphQLField>, String> {
+        self.expect('{')?;
+        let mut fields = vec![self.parse_field()?];
+        while self.peek() != Some('}') {
+            fields.push(self.parse_field()?);
+        }
+        self.pos += 1;
+        Ok(fields)
+    }
+
+    /// Parse a field, e.g. `author: user(id: 1) { name }`.
+    fn parse_field(&mut self) -> Result<WXGraphQLField, String> {
+        if self.peek() == Some('.') {
+            return Err("Fragments are not supported".to_string());
+        }
+        let mut name = self.parse_name()?;
+        let mut alias = None;
+        if self.peek() == Some(':') {
+            self.pos += 1;
+            alias = Some(std::mem::replace(&mut name, self.parse_name()?));
+        }
+        let mut arguments = Map::new();
+        if self.peek() == Some('(') {
+            self.pos += 1;
+            while self.peek() != Some(')') {
+                let argument = self.parse_name()?;
+                self.expect(':')?;
+                arguments.insert(argument, self.parse_value()?);
+            }
+            self.pos += 1;
+        }
+        if self.peek() == Some('@') {
+            return Err("Directives are not supported".to_string());
+        }
+        let selection = if self.peek() == Some('{') {
+            self.parse_selection_set()?
+        } else {
+            vec![]
+        };
+        Ok(WXGraphQLField {
+            alias,
+            name,
+            arguments,
+            selection,
+        })
+    }
+
+    /// Parse an argument value as JSON.
+    /// Enum values are passed on as strings.
+    fn parse_value(&mut self) -> Result<Value, String> {
+        match self.peek() {
+            Some('$') => {
+                self.pos += 1;
+                let name = self.parse_name()?;
+                self.variables
+                    .get(&name)
+                    .cloned()
+                    .ok_or_else(|| format!("Variable \"${}\" is not defined.", name))
+            }
+            Some('"') => self.parse_string().map(Value::String),
+            Some('[') => {
+                self.pos += 1;
+                let mut items = vec![];
+                while self.peek() != Some(']') {
+                    items.push(self.parse_value()?);
+                }
+                self.pos += 1;
+                Ok(Value::Array(items))
+            }
+            Some('{') => {
+                self.pos += 1;
+                let mut fields = Map::new();
+                while self.peek() != Some('}') {
+                    let name = self.parse_name()?;
+                    self.expect(':')?;
+                    fields.insert(name, self.parse_value()?);
+                }
+                self.pos += 1;
+                Ok(Value::Object(fields))
+            }
+            Some(c) if c == '-' || c.is_ascii_digit() => self.parse_number(),
+            Some(c) if is_name_start(c) => Ok(match self.parse_name()?.as_str() {
+                "true" => Value::Bool(true),
+                "false" => Value::Bool(false),
+                "null" => Value::Null,
+                name => Value::String(name.to_string()),
+            }),
+            _ => Err(self.unexpected("a value")),
+        }
+    }
+
+    fn parse_number(&mut self) -> Result<Value, String> {
+        let start = self.pos;
+        if self.chars.get(self.pos) == Some(&'-') {
+            self.pos += 1;
+        }
+        let mut is_float = false;
+        while let Some(&c) = self.chars.get(self.pos) {
+            match c {
+                '0'..='9' => {}
+                '.' | 'e' | 'E' => is_float = true,
+                '+' | '-' if is_float => {}
+                _ => break,
+            }
+            self.pos += 1;
+        }
+        let number = self.chars[start..self.pos].iter().collect::<String>();
+        let value = if is_float {
+            number
+                .parse::<f64>()
+                .ok()
+                .and_then(serde_json::Number::from_f64)
+                .map(Value::Number)
+        } else {
+            number.parse::<i64>().ok().map(Value::from)
+        };
+        value.ok_or_else(|| format!("Syntax Error: Invalid number \"{}\".", number))
+    }
+
+    fn parse_string(&mut self) -> Result<String, String> {
+        let unterminated = || "Syntax Error: Unterminated string.".to_string();
+        if self.chars[self.pos..].starts_with(&BLOCK_QUOTE) {
+            self.pos += BLOCK_QUOTE.len();
+            let start = self.pos;
+            while !self.chars[self.pos..].starts_with(&BLOCK_QUOTE) {
+                if self.pos == self.chars.len() {
+                    return Err(unterminated());
+                }
+                self.pos += 1;
+            }
+            let text = self.chars[start..self.pos].iter().collect();
+            self.pos += BLOCK_QUOTE.len();
+            return Ok(text);
+        }
+        self.pos += 1;
+        let mut text = String::new();
+        loop {
+            let Some(&c) = self.chars.get(self.pos) else {
+                return Err(unterminated());
+            };
+            self.pos += 1;
+            match c {
+                '"' => return Ok(text),
+                '\n' | '\r' => return Err(unterminated()),
+                '\\' => {
+                    let escaped = self.chars.get(self.pos).copied();
+                    self.pos += 1;
+                    match escaped {
+                        Some('"') => text.push('"'),
+                        Some('\\') => text.push('\\'),
+                        Some('/') => text.push('/'),
+                        Some('b') => text.push('\u{8}'),
+                        Some('f') => text.push('\u{c}'),
+                        Some('n') => text.push('\n'),
+                        Some('r') => text.push('\r'),
+                        Some('t') => text.push('\t'),
+                        Some('u') => {
+                            let hex = self
+                                .chars
+                                .get(self.pos..self.pos + 4)
+                                .unwrap_or_default()
+                                .iter()
+                                .collect::<String>();
+                            self.pos += 4;
+                            let c = u32::from_str_radix(&hex, 16)
+                                .ok()
+                                .and_then(char::from_u32)
+                                .ok_or_else(|| {
+                                    format!("Syntax Error: Invalid unicode escape \"\\u{}\".", hex)
+                                })?;
+                            text.push(c);
+                        }
+                        _ => return Err("Syntax Error: Invalid escape sequence.".to_string()),
+                    }
+                }
+                c => text.push(c),
+            }
+        }
+    }
+}
+
+/// The GraphQL schema of a project.
+///
+/// Object types are derived from the models of all modules.
+/// The fields of the root `Query` type are configured with their WebX types,
+/// e.g. `User` or `User[]`, and all other types are scalars.
+#[derive(Debug)]
+pub struct WXGraphQLSchema {
+    /// The fields of the object types by name.
+    objects: HashMap<String, Vec<WXTypedIdentifier>>,
+    /// The fields of the root `Query` type with their types.
+    query: BTreeMap<String, String>,
+}
+
+fn collect_models(scope: &WXScope, objects: &mut HashMap<String, Vec<WXTypedIdentifier>>) {
+    for model in scope.models.iter() {
+        objects.insert(model.name.clone(), model.fields.clone());
+    }
+    for nested in scope.scopes.iter() {
+        collect_models(nested, objects);
+    }
+}
+
+impl WXGraphQLSchema {
+    pub fn new(modules: &[WXModule], query: BTreeMap<String, String>) -> Self {
+        let mut objects = HashMap::new();
+        for module in modules.iter() {
+            collect_models(&module.scope, &mut objects);
+        }
+        WXGraphQLSchema { objects, query }
+    }
+
+    /// The name and fields of the object type of a field, looking through lists.
+    /// Returns `None` for scalars.
+    fn object_type<'a>(&'a self, type_: &'a str) -> Option<(&'a str, &'a [WXTypedIdentifier])> {
+        let type_ = type_.trim();
+        let item = type_.strip_suffix("[]").or_else(|| {
+            type_
+                .strip_prefix("Array<")
+                .and_then(|rest| rest.strip_suffix('>'))
+        });
+        match item {
+            Some(item) => self.object_type(item),
+            None => self
+                .objects
+                .get_key_value(type_)
+                .map(|(name, fields)| (name.as_str(), fields.as_slice())),
+        }
+    }
+
+    /// Check the selection of an operation against the schema.
+    /// Returns a message for every invalid field.
+    pub fn validate(&self, operation: &WXGraphQLOperation) -> Vec<String> {
+        if operation.operation_type != WXGraphQLOperationType::Query {
+            return vec![format!(
+                "{:?} operations are not supported, only queries",
+                operation.operation_type
+            )];
+        }
+        let mut errors = vec![];
+        for field in operation.selection.iter() {
+            match self.query.get(&field.name) {
+                Some(type_) => self.validate_selection(field, type_, &mut errors),
+                None if field.name == TYPENAME_FIELD => {}
+                None => errors.push(format!(
+                    "Cannot query field \"{}\" on type \"Query\".",
+                    field.name
+                )),
+            }
+        }
+        errors
+    }
+
+    fn validate_selection(&self, field: &WXGraphQLField, type_: &str, errors: &mut Vec<String>) {
+        let Some((object, fields)) = self.object_type(type_) else {
+            if !field.selection.is_empty() {
+                errors.push(format!(
+                    "Field \"{}\" must not have a selection since type \"{}\" has no subfields.",
+                    field.name, type_
+                ));
+            }
+            return;
+        };
+        if field.selection.is_empty() {
+            errors.push(format!(
+                "Field \"{}\" of type \"{}\" must have a selection of subfields.",
+                field.name, type_
+            ));
+        }
+        for selected in field.selection.iter() {
+            match fields.iter().find(|field| field.name == selected.name) {
+                Some(field) => self.validate_selection(selected, &field.type_, errors),
+                None if selected.name == TYPENAME_FIELD => {}
+                None => errors.push(format!(
+                    "Cannot query field \"{}\" on type \"{}\".",
+                    selected.name, object
+                )),
+            }
+        }
+    }
+
+    /// Select the fields of a resolved value of the given type.
+    /// Values that do not match the shape of an object type are `null`.
+    fn project(&self, type_: &str, value: Value, selection: &[WXGraphQLField]) -> Value {
+        let Some((object, fields)) = self.object_type(type_) else {
+            return value;
+        };
+        match value {
+            Value::Array(items) => items
+                .into_iter()
+                .map(|item| self.project(type_, item, selection))
+                .collect(),
+            Value::Object(values) => {
+                let mut result = Map::new();
+                for selected in selection.iter() {
+                    let value = if selected.name == TYPENAME_FIELD {
+                        Value::String(object.to_string())
+                    } else {
+                        let type_ = fields
+                            .iter()
+                            .find(|field| field.name == selected.name)
+                            .map_or("", |field| field.type_.as_str());
+                        let value = values.get(&selected.name).cloned().unwrap_or_default();
+                        self.project(type_, value, &selected.selection)
+                    };
+                    result.insert(selected.key().to_string(), value);
+                }
+                Value::Object(result)
+            }
+            _ => Value::Null,
+        }
+    }
+}
+
+/// The `{ errors }` envelope of a request that could not be executed.
+pub fn errors(messages: &[String]) -> Value {
+    let errors = messages
+        .iter()
+        .map(|message| json!({ "message": message }))
+        .collect::<Vec<_>>();
+    json!({ "errors": errors })
+}
+
+/// Execute a GraphQL request against the schema.
+/// The root fields are resolved by `resolve` with their name and arguments,
+/// and their subfields are selected from the resolved values.
+///
+/// ## Returns
+/// The status code and the `{ data, errors }` envelope of the response.
+/// Invalid requests are answered with `400 Bad Request` and only `errors`,
+/// while fields that failed to resolve are `null` and listed in `errors`.
+pub fn execute(
+    schema: &WXGraphQLSchema,
+    request: &WXGraphQLRequest,
+    mut resolve: impl FnMut(&str, &Map<String, Value>) -> Result<Value, String>,
+) -> (StatusCode, Value) {
+    let variables = request.variables.clone().unwrap_or_default();
+    let operation = match parse_operation(
+        &request.query,
+        request.operation_name.as_deref(),
+        &variables,
+    ) {
+        Ok(operation) => operation,
+        Err(message) => return (StatusCode::BAD_REQUEST, errors(&[message])),
+    };
+    let invalid = schema.validate(&operation);
+    if !invalid.is_empty() {
+        return (StatusCode::BAD_REQUEST, errors(&invalid));
+    }
+    let mut data = Map::new();
+    let mut errors = vec![];
+    for field in operation.selection.iter() {
+        let value = match schema.query.get(&field.name) {
+            Some(type_) => match resolve(&field.name, &field.arguments) {
+                Ok(value) => schema.project(type_, value, &field.selection),
+                Err(message) => {
+                    errors.push(json!({ "message": message, "path": [field.key()] }));
+                    Value::Null
+                }
+            },
+            None => Value::String("Query".to_string()),
+        };
+        data.insert(field.key().to_string(), value);
+    }
+    let mut response = json!({ "data": data });
+    if !errors.is_empty() {
+        response["errors"] = Value::Array(errors);
+    }
+    (StatusCode::OK, response)
+}
+
+/// A JSON response of the GraphQL endpoint.
+pub fn response(mode: WXMode, status: StatusCode, body: &Value) -> Response<Bytes> {
+    let body = body.to_string();
+    common_headers(mode)
+        .status(status)
+        .header("Content-Type", "application/json")
+        .header("Content-Length", body.len().to_string())
+        .body(Bytes::from(body))
+        .unwrap()
+}
@@ -0,0 +1,86 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+use hyper::{body::Bytes, Response, StatusCode};
+
+use crate::{file::project::ProjectConfig, runner::WXMode};
+
+use super::http::responses::common_headers;
+
+/// The default path of the liveness probe.
+pub const DEFAULT_LIVENESS_PATH: &str = "/healthz";
+/// The default path of the readiness probe.
+pub const DEFAULT_READINESS_PATH: &str = "/readyz";
+
+/// Liveness and readiness probes for load balancers and orchestrators.
+/// These are handled by the server before route resolution,
+/// so they can not be shadowed by user routes.
+#[derive(Debug)]
+pub struct WXHealth {
+    pub liveness_path: String,
+    pub readiness_path: String,
+    /// Set by the runtime once all modules are loaded.
+    ready: Arc<AtomicBool>,
+}
+
+impl WXHealth {
+    pub fn new(liveness_path: &str, readiness_path: &str, ready: Arc<AtomicBool>) -> Self {
+        WXHealth {
+            liveness_path: liveness_path.to_string(),
+            readiness_path: readiness_path.to_string(),
+            ready,
+        }
+    }
+
+    /// Create the health probes if enabled in the project configuration.
+    pub fn from_config(config: &ProjectConfig, ready: Arc<AtomicBool>) -> Option<Self> {
+        let health = config.health.as_ref().filter(|health| health.enabled)?;
+        Some(Self::new(
+            health
+                .liveness_path
+                .as_deref()
+                .unwrap_or(DEFAULT_LIVENESS_PATH),
+            health
+                .readiness_path
+                .as_deref()
+                .unwrap_or(DEFAULT_READINESS_PATH),
+            ready,
+        ))
+    }
+
+    /// Respond to a probe, or `None` if the path is not a probe.
+    ///
+    /// ## Responses
+    /// - liveness: always `200 OK` once the server is up.
+    /// - readiness: `200 OK` once all modules are loaded, `503 Service Unavailable` before.
+    pub fn respond(&self, mode: WXMode, path: &str) -> Option<Response<Bytes>> {
+        let ready = if path == self.liveness_path {
+            true
+        } else if path == self.readiness_path {
+            self.ready.load(Ordering::SeqCst)
+        } else {
+            return None;
+        };
+        let (status, body) = if ready {
+            (StatusCode::OK, "ok")
+        } else {
+            (StatusCode::SERVICE_UNAVAILABLE, "unavailable")
+        };
+        let body = serde_json::json!({
+            "status": body,
+            "version": env!("CARGO_PKG_VERSION"),
+        })
+        .to_string();
+        Some(
+            common_headers(mode)
+                .status(status)
+                .header("Content-Type", "application/json")
+                .header("Content-Length", body.len().to_string())
+                .header("Cache-Control", "no-store")
+                .body(Bytes::from(body))
+                .unwrap(),
+        )
+    }
+}
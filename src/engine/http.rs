@@ -1,18 +1,585 @@
+use std::{
+    io::{self, Seek, SeekFrom},
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use hyper::{
+    body::{Body, Bytes, Frame, SizeHint},
+    HeaderMap,
+};
+use tokio::{
+    io::{AsyncRead, ReadBuf},
+    sync::mpsc::Receiver,
+};
+
+use crate::file::project::ProjectConfig;
+
+/// The maximum size of a chunk read from a streamed file.
+pub const FILE_CHUNK_SIZE: usize = 64 * 1024;
+
+/// A file streamed in chunks of at most `FILE_CHUNK_SIZE` bytes.
+/// The length is known upfront, so it is sent with a `Content-Length`.
+#[derive(Debug)]
+pub struct WXFileBody {
+    file: tokio::fs::File,
+    remaining: u64,
+}
+
+impl WXFileBody {
+    pub fn new(file: std::fs::File, len: u64) -> Self {
+        WXFileBody {
+            file: tokio::fs::File::from_std(file),
+            remaining: len,
+        }
+    }
+
+    /// The number of bytes left to send.
+    pub fn remaining(&self) -> u64 {
+        self.remaining
+    }
+
+    /// Only send the `len` bytes from `start` on, e.g. for a range request.
+    /// Must be called before any chunk is read.
+    pub fn slice(self, start: u64, len: u64) -> io::Result<Self> {
+        let mut file = self
+            .file
+            .try_into_std()
+            .map_err(|_| io::Error::other("file is already being read"))?;
+        file.seek(SeekFrom::Start(start))?;
+        Ok(WXFileBody {
+            file: tokio::fs::File::from_std(file),
+            remaining: len,
+        })
+    }
+
+    fn poll_chunk(&mut self, cx: &mut Context<'_>) -> Poll<Option<io::Result<Bytes>>> {
+        if self.remaining == 0 {
+            return Poll::Ready(None);
+        }
+        let mut chunk = vec![0; FILE_CHUNK_SIZE.min(self.remaining as usize)];
+        let mut buf = ReadBuf::new(&mut chunk);
+        match Pin::new(&mut self.file).poll_read(cx, &mut buf) {
+            Poll::Pending => Poll::Pending,
+            Poll::Ready(Err(err)) => Poll::Ready(Some(Err(err))),
+            Poll::Ready(Ok(())) if buf.filled().is_empty() => {
+                Poll::Ready(Some(Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "file was truncated while sent",
+                ))))
+            }
+            Poll::Ready(Ok(())) => {
+                let read = buf.filled().len();
+                self.remaining -= read as u64;
+                chunk.truncate(read);
+                Poll::Ready(Some(Ok(Bytes::from(chunk))))
+            }
+        }
+    }
+}
+
+/// The body of a response sent by the server.
+/// Most responses are sent at once, while files and event streams
+/// are written chunk by chunk without buffering them in memory.
+#[derive(Debug)]
+pub enum WXResponseBody {
+    Full(Option<Bytes>),
+    File(WXFileBody),
+    /// Dropped by the server when the client disconnects,
+    /// which closes the channel for the producing route.
+    EventStream(Receiver<Bytes>),
+}
+
+impl WXResponseBody {
+    /// The complete body, or `None` for streamed bodies.
+    pub fn bytes(&self) -> Option<&[u8]> {
+        match self {
+            WXResponseBody::Full(bytes) => Some(bytes.as_deref().unwrap_or_default()),
+            WXResponseBody::File(_) | WXResponseBody::EventStream(_) => None,
+        }
+    }
+}
+
+impl Default for WXResponseBody {
+    fn default() -> Self {
+        WXResponseBody::Full(None)
+    }
+}
+
+impl From<Bytes> for WXResponseBody {
+    fn from(bytes: Bytes) -> Self {
+        WXResponseBody::Full(Some(bytes).filter(|bytes| !bytes.is_empty()))
+    }
+}
+
+impl From<WXFileBody> for WXResponseBody {
+    fn from(file: WXFileBody) -> Self {
+        WXResponseBody::File(file)
+    }
+}
+
+impl From<String> for WXResponseBody {
+    fn from(body: String) -> Self {
+        Bytes::from(body).into()
+    }
+}
+
+impl Body for WXResponseBody {
+    type Data = Bytes;
+    type Error = io::Error;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        match self.get_mut() {
+            WXResponseBody::Full(bytes) => Poll::Ready(bytes.take().map(|b| Ok(Frame::data(b)))),
+            WXResponseBody::File(file) => file
+                .poll_chunk(cx)
+                .map(|chunk| chunk.map(|chunk| chunk.map(Frame::data))),
+            WXResponseBody::EventStream(events) => events
+                .poll_recv(cx)
+                .map(|event| event.map(|b| Ok(Frame::data(b)))),
+        }
+    }
+
+    fn is_end_stream(&self) -> bool {
+        match self {
+            WXResponseBody::Full(bytes) => bytes.is_none(),
+            WXResponseBody::File(file) => file.remaining == 0,
+            WXResponseBody::EventStream(_) => false,
+        }
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        match self {
+            WXResponseBody::Full(bytes) => {
+                SizeHint::with_exact(bytes.as_ref().map_or(0, |b| b.len() as u64))
+            }
+            WXResponseBody::File(file) => SizeHint::with_exact(file.remaining),
+            WXResponseBody::EventStream(_) => SizeHint::default(),
+        }
+    }
+}
+
+/// Headers whose values are masked in debug logs by default.
+pub const DEFAULT_REDACTED_HEADERS: [&str; 3] = ["authorization", "cookie", "set-cookie"];
+
+/// The lowercase names of all headers to mask in debug logs,
+/// the defaults and those configured in `redactHeaders`.
+/// Redaction is disabled only if `logSecrets` is explicitly enabled.
+pub fn redacted_headers(config: &ProjectConfig) -> Vec<String> {
+    if config.log_secrets.unwrap_or(false) {
+        return vec![];
+    }
+    let mut headers = DEFAULT_REDACTED_HEADERS.map(String::from).to_vec();
+    for header in config.redact_headers.iter().flatten() {
+        let header = header.to_ascii_lowercase();
+        if !headers.contains(&header) {
+            headers.push(header);
+        }
+    }
+    headers
+}
+
+/// Serialize headers line by line, masking the values of the `redact` headers.
+fn serialize_headers(headers: &HeaderMap, redact: &[String]) -> String {
+    let mut result = String::new();
+    for (header, value) in headers {
+        let value = if redact.iter().any(|name| name == header.as_str()) {
+            "***"
+        } else {
+            value.to_str().unwrap_or("")
+        };
+        result.push_str(&format!("{}: {}\r\n", header, value));
+    }
+    result
+}
+
 pub mod requests {
+    use std::net::{IpAddr, SocketAddr};
+
+    use chrono::{DateTime, FixedOffset};
+    use http_body_util::{BodyExt, LengthLimitError, Limited};
+    use hyper::{
+        body::{Body, Bytes},
+        HeaderMap,
+    };
+
+    /// The default maximum size of a request body in bytes.
+    pub const DEFAULT_MAX_BODY_SIZE: usize = 1024 * 1024;
+
+    /// An error while reading a request body.
+    #[derive(Debug)]
+    pub enum WXBodyError {
+        /// The body exceeds the maximum body size.
+        TooLarge,
+        /// The body could not be read from the connection.
+        Read(String),
+    }
 
-    use hyper::body::Incoming;
+    /// Read a request body into memory, up to `limit` bytes.
+    /// Reading is aborted as soon as the limit is exceeded,
+    /// or upfront if the `Content-Length` header already exceeds it.
+    pub async fn read_body<B>(
+        headers: &HeaderMap,
+        body: B,
+        limit: usize,
+    ) -> Result<Bytes, WXBodyError>
+    where
+        B: Body,
+        B::Error: Into<Box<dyn std::error::Error + Send + Sync>>,
+    {
+        let content_length = headers
+            .get(hyper::header::CONTENT_LENGTH)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.parse::<u64>().ok());
+        if content_length.is_some_and(|length| length > limit as u64) {
+            return Err(WXBodyError::TooLarge);
+        }
+        match Limited::new(body, limit).collect().await {
+            Ok(collected) => Ok(collected.to_bytes()),
+            Err(err) if err.downcast_ref::<LengthLimitError>().is_some() => {
+                Err(WXBodyError::TooLarge)
+            }
+            Err(err) => Err(WXBodyError::Read(err.to_string())),
+        }
+    }
+
+    /// Parse a single forwarded node, e.g. `192.0.2.60`, `"[2001:db8::17]:4711"` or `10.0.0.1:80`.
+    fn parse_forwarded_node(node: &str) -> Option<IpAddr> {
+        let node = node.trim().trim_matches('"');
+        if let Some(rest) = node.strip_prefix('[') {
+            return rest.split(']').next()?.parse().ok();
+        }
+        node.parse::<IpAddr>()
+            .ok()
+            .or_else(|| node.parse::<SocketAddr>().ok().map(|addr| addr.ip()))
+    }
+
+    /// The entry of a comma-separated list of forwarded addresses added by the outermost
+    /// of `hops` trusted proxies, each of which appends the address it received from.
+    /// Entries further left are set by the client, and can not be trusted.
+    fn trusted_entry(value: &str, hops: usize) -> Option<&str> {
+        let entries = value.split(',').collect::<Vec<_>>();
+        let index = entries.len().saturating_sub(hops);
+        entries.get(index).copied()
+    }
+
+    /// The client of the `Forwarded` header (RFC 7239), behind `hops` trusted proxies.
+    fn forwarded_for(value: &str, hops: usize) -> Option<IpAddr> {
+        let entry = trusted_entry(value, hops)?;
+        entry.split(';').find_map(|pair| {
+            let (key, value) = pair.split_once('=')?;
+            if key.trim().eq_ignore_ascii_case("for") {
+                parse_forwarded_node(value)
+            } else {
+                None
+            }
+        })
+    }
 
-    pub fn serialize(request: &hyper::Request<Incoming>) -> String {
+    /// Whether a request was made over HTTPS.
+    /// When `trust_proxy` is set, the `X-Forwarded-Proto` header of a reverse proxy is honored.
+    pub fn is_https<T>(request: &hyper::Request<T>, trust_proxy: bool) -> bool {
+        if request.uri().scheme() == Some(&hyper::http::uri::Scheme::HTTPS) {
+            return true;
+        }
+        trust_proxy
+            && request
+                .headers()
+                .get("X-Forwarded-Proto")
+                .and_then(|value| value.to_str().ok())
+                .is_some_and(|proto| proto.trim().eq_ignore_ascii_case("https"))
+    }
+
+    /// Get the value of a cookie sent with a request.
+    pub fn cookie<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+        headers
+            .get_all("Cookie")
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(';'))
+            .filter_map(|pair| pair.trim().split_once('='))
+            .find(|(key, _)| *key == name)
+            .map(|(_, value)| value)
+    }
+
+    /// Get the value of a field of an `application/x-www-form-urlencoded` body.
+    pub fn form_field(body: &[u8], name: &str) -> Option<String> {
+        String::from_utf8_lossy(body)
+            .split('&')
+            .filter_map(|pair| pair.split_once('='))
+            .find(|(key, _)| decode_form_component(key) == name)
+            .map(|(_, value)| decode_form_component(value))
+    }
+
+    /// Get all fields of an `application/x-www-form-urlencoded` body or query string, in order.
+    /// Fields without a value, e.g. `?debug`, are empty.
+    pub fn form_fields(body: &[u8]) -> Vec<(String, String)> {
+        String::from_utf8_lossy(body)
+            .split('&')
+            .filter(|pair| !pair.is_empty())
+            .map(|pair| {
+                let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+                (decode_form_component(key), decode_form_component(value))
+            })
+            .collect()
+    }
+
+    /// Decode a percent-encoded form component, where `+` is a space.
+    fn decode_form_component(component: &str) -> String {
+        let mut bytes = Vec::with_capacity(component.len());
+        let mut rest = component.as_bytes();
+        while let Some((&byte, tail)) = rest.split_first() {
+            let hex = tail
+                .get(..2)
+                .and_then(|hex| std::str::from_utf8(hex).ok())
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+            match (byte, hex) {
+                (b'%', Some(decoded)) => {
+                    bytes.push(decoded);
+                    rest = &tail[2..];
+                    continue;
+                }
+                (b'+', _) => bytes.push(b' '),
+                _ => bytes.push(byte),
+            }
+            rest = tail;
+        }
+        String::from_utf8_lossy(&bytes).into_owned()
+    }
+
+    /// Get the `Sec-WebSocket-Key` of a WebSocket handshake request,
+    /// or `None` if the request is not a valid upgrade to a WebSocket.
+    pub fn websocket_key<T>(request: &hyper::Request<T>) -> Option<&str> {
+        let has_token = |name: &str, token: &str| {
+            request
+                .headers()
+                .get_all(name)
+                .iter()
+                .filter_map(|value| value.to_str().ok())
+                .flat_map(|value| value.split(','))
+                .any(|value| value.trim().eq_ignore_ascii_case(token))
+        };
+        if request.method() != hyper::Method::GET
+            || !has_token("Connection", "upgrade")
+            || !has_token("Upgrade", "websocket")
+        {
+            return None;
+        }
+        request
+            .headers()
+            .get("Sec-WebSocket-Key")
+            .and_then(|value| value.to_str().ok())
+    }
+
+    /// Whether the client already has the current version of a response,
+    /// given its entity tag and last modification date (RFC 9110, section 13.1).
+    /// `If-None-Match` takes precedence, and `If-Modified-Since` is only
+    /// considered in its absence.
+    pub fn is_fresh(
+        headers: &HeaderMap,
+        etag: Option<&str>,
+        last_modified: Option<DateTime<FixedOffset>>,
+    ) -> bool {
+        if let Some(if_none_match) = headers.get("If-None-Match") {
+            let Some(etag) = etag else {
+                return false;
+            };
+            // Entity tags are compared weakly for `If-None-Match`.
+            let weak = |tag: &str| tag.trim().trim_start_matches("W/").to_string();
+            return if_none_match.to_str().is_ok_and(|tags| {
+                tags.split(',')
+                    .any(|tag| tag.trim() == "*" || weak(tag) == weak(etag))
+            });
+        }
+        let if_modified_since = headers
+            .get("If-Modified-Since")
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| DateTime::parse_from_rfc2822(value).ok());
+        match (if_modified_since, last_modified) {
+            (Some(since), Some(modified)) => modified <= since,
+            _ => false,
+        }
+    }
+
+    /// The byte range requested by a `Range` header, see `byte_range`.
+    #[derive(Debug, PartialEq, Eq)]
+    pub enum WXByteRange {
+        /// The whole representation is sent, as there is no single valid byte range.
+        Full,
+        /// The inclusive first and last byte to send.
+        Partial(u64, u64),
+        /// The range starts after the end of the representation.
+        Unsatisfiable,
+    }
+
+    /// The byte range requested for a representation of `len` bytes (RFC 9110, section 14).
+    /// Invalid ranges, other units and multiple ranges are ignored,
+    /// so the whole representation is sent instead.
+    ///
+    /// ## Example
+    /// - `bytes=0-99` -> the first 100 bytes
+    /// - `bytes=100-` -> all bytes from offset 100
+    /// - `bytes=-100` -> the last 100 bytes
+    pub fn byte_range(headers: &HeaderMap, len: u64) -> WXByteRange {
+        let Some(range) = headers
+            .get("Range")
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.trim().strip_prefix("bytes="))
+            .filter(|range| !range.contains(','))
+        else {
+            return WXByteRange::Full;
+        };
+        let Some((first, last)) = range.trim().split_once('-') else {
+            return WXByteRange::Full;
+        };
+        let (first, last) = (first.trim(), last.trim());
+        let parse = |value: &str| value.parse::<u64>().ok();
+        match (first.is_empty(), last.is_empty()) {
+            // A suffix of the representation.
+            (true, false) => match parse(last) {
+                Some(0) => WXByteRange::Unsatisfiable,
+                Some(_) if len == 0 => WXByteRange::Unsatisfiable,
+                Some(suffix) => WXByteRange::Partial(len.saturating_sub(suffix), len - 1),
+                None => WXByteRange::Full,
+            },
+            (false, _) => {
+                let Some(first) = parse(first) else {
+                    return WXByteRange::Full;
+                };
+                let last = match last.is_empty() {
+                    true => u64::MAX,
+                    false => match parse(last) {
+                        Some(last) if last >= first => last,
+                        _ => return WXByteRange::Full,
+                    },
+                };
+                if first >= len {
+                    WXByteRange::Unsatisfiable
+                } else {
+                    WXByteRange::Partial(first, last.min(len - 1))
+                }
+            }
+            (true, true) => WXByteRange::Full,
+        }
+    }
+
+    /// The media type the client prefers out of the offered ones,
+    /// according to the `Accept` header of a request (RFC 9110, section 12.5.1).
+    ///
+    /// Each offered type is weighted by the most specific media range matching it,
+    /// and ties go to the type offered first.
+    /// Without an `Accept` header, the first offered type is preferred.
+    /// Returns `None` if the client accepts none of the offered types.
+    pub fn preferred_type<'a>(headers: &HeaderMap, offered: &[&'a str]) -> Option<&'a str> {
+        let ranges = headers
+            .get_all("Accept")
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .filter_map(|range| {
+                let mut params = range.split(';');
+                let media_range = params.next()?.trim().to_ascii_lowercase();
+                let quality = params
+                    .filter_map(|param| param.split_once('='))
+                    .find(|(key, _)| key.trim().eq_ignore_ascii_case("q"))
+                    .map_or(Some(1.0), |(_, q)| q.trim().parse::<f32>().ok())?;
+                Some((media_range, quality))
+            })
+            .collect::<Vec<_>>();
+        if ranges.is_empty() {
+            return offered.first().copied();
+        }
+        let weight = |offered: &str| {
+            let (main_type, _) = offered.split_once('/').unwrap_or((offered, ""));
+            ranges
+                .iter()
+                .filter_map(|(range, quality)| {
+                    let specificity = match range.split_once('/') {
+                        _ if range.eq_ignore_ascii_case(offered) => 2,
+                        Some((range_type, "*")) if range_type.eq_ignore_ascii_case(main_type) => 1,
+                        Some(("*", "*")) => 0,
+                        _ => return None,
+                    };
+                    Some((specificity, *quality))
+                })
+                .max_by_key(|(specificity, _)| *specificity)
+                .map_or(0.0, |(_, quality)| quality)
+        };
+        // Reversed, as the last of equally weighted types is the maximum.
+        offered
+            .iter()
+            .rev()
+            .map(|offered| (*offered, weight(offered)))
+            .filter(|(_, quality)| *quality > 0.0)
+            .max_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(offered, _)| offered)
+    }
+
+    /// Get the IP address of the client that sent a request.
+    ///
+    /// Behind `trusted_proxies` reverse proxies, the client is taken from the
+    /// `Forwarded` or `X-Forwarded-For` headers, as added by the outermost proxy.
+    /// The entries left of it are ignored, as they can be set by anyone.
+    /// Without trusted proxies the headers are ignored, and the address of the socket peer is used.
+    pub fn client_ip(headers: &HeaderMap, peer: SocketAddr, trusted_proxies: usize) -> IpAddr {
+        if trusted_proxies == 0 {
+            return peer.ip();
+        }
+        let header = |name: &str| headers.get(name).and_then(|value| value.to_str().ok());
+        header("Forwarded")
+            .and_then(|value| forwarded_for(value, trusted_proxies))
+            .or_else(|| {
+                header("X-Forwarded-For")
+                    .and_then(|value| trusted_entry(value, trusted_proxies))
+                    .and_then(parse_forwarded_node)
+            })
+            .unwrap_or(peer.ip())
+    }
+
+    /// Whether a request carries a body to be bound.
+    /// `POST`, `PUT` and `PATCH` requests always do,
+    /// `DELETE` requests only if they declare a non-empty body.
+    pub fn has_body<T>(request: &hyper::Request<T>) -> bool {
+        match *request.method() {
+            hyper::Method::POST | hyper::Method::PUT | hyper::Method::PATCH => true,
+            hyper::Method::DELETE => {
+                let headers = request.headers();
+                headers.contains_key(hyper::header::TRANSFER_ENCODING)
+                    || headers
+                        .get(hyper::header::CONTENT_LENGTH)
+                        .and_then(|value| value.to_str().ok())
+                        .and_then(|value| value.parse::<u64>().ok())
+                        .is_some_and(|length| length > 0)
+            }
+            _ => false,
+        }
+    }
+
+    /// The header carrying the id of a request.
+    pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+    /// The id of a request given in its `X-Request-Id` header, e.g. by a proxy.
+    /// Only ids of up to 128 visible ASCII characters are honored, as they are logged.
+    pub fn request_id(headers: &HeaderMap) -> Option<String> {
+        let id = headers.get(REQUEST_ID_HEADER)?.to_str().ok()?;
+        let valid = !id.is_empty() && id.len() <= 128 && id.bytes().all(|b| b.is_ascii_graphic());
+        valid.then(|| id.to_string())
+    }
+
+    /// Serialize a request for debug logging.
+    /// The values of the `redact` headers are masked.
+    pub fn serialize<T: std::fmt::Debug>(request: &hyper::Request<T>, redact: &[String]) -> String {
         let mut result = format!(
             "{} {} {:?}\r\n",
             request.method(),
             request.uri(),
             request.version()
         );
-        for (header, value) in request.headers() {
-            result.push_str(&format!("{}: {}\r\n", header, value.to_str().unwrap_or("")));
-        }
-        if matches!(*request.method(), hyper::Method::POST | hyper::Method::PUT) {
+        result.push_str(&super::serialize_headers(request.headers(), redact));
+        if has_body(request) {
             result.push_str("\r\n");
             result.push_str(&format!("{:?}", request.body()));
         }
@@ -21,10 +588,124 @@ pub mod requests {
 }
 
 pub mod responses {
+    use std::{
+        cell::Cell,
+        path::{Path, PathBuf},
+        time::{Duration, SystemTime},
+    };
+
     use deno_core::v8::{self, Global, HandleScope, Local, Value};
-    use hyper::{body::Bytes, Method, Response};
+    use hyper::{
+        body::Bytes, header::HeaderValue, http::response::Builder, HeaderMap, Method, Response,
+        StatusCode,
+    };
+    use tokio::sync::mpsc::Receiver;
+
+    use crate::{
+        file::project::{CorsConfig, ErrorPagesConfig, SecurityHeadersConfig},
+        reporting::warning::warning,
+        runner::WXMode,
+    };
+
+    use super::{requests::WXByteRange, WXFileBody, WXResponseBody};
+
+    /// Custom error page templates, loaded once at startup.
+    #[derive(Debug, Clone, Default)]
+    pub struct WXErrorPages {
+        pub not_found: Option<String>,
+        pub internal_server_error: Option<String>,
+    }
+
+    impl WXErrorPages {
+        /// Load all configured error pages relative to the project root.
+        /// Pages that fail to load fall back to the built-in defaults.
+        pub fn load(root: &Path, config: Option<&ErrorPagesConfig>, mode: WXMode) -> Self {
+            let Some(config) = config else {
+                return Self::default();
+            };
+            let read = |path: &Option<PathBuf>| {
+                let path = path.as_ref()?;
+                match std::fs::read_to_string(root.join(path)) {
+                    Ok(page) => Some(page),
+                    Err(err) => {
+                        warning(
+                            mode,
+                            format!(
+                                "Failed to load error page '{}', using the default: {}",
+                                path.display(),
+                                err
+                            ),
+                        );
+                        None
+                    }
+                }
+            };
+            WXErrorPages {
+                not_found: read(&config.not_found),
+                internal_server_error: read(&config.internal_server_error),
+            }
+        }
+    }
+
+    /// Substitute all `{{key}}` placeholders in an error page template.
+    /// The values are HTML-escaped, as they can contain parts of the request,
+    /// and are inserted in a single pass, so placeholders within them are left as they are.
+    /// Placeholders without a value are kept.
+    fn fill_error_page(template: &str, values: &[(&str, &str)]) -> String {
+        let mut page = String::with_capacity(template.len());
+        let mut rest = template;
+        while let Some(start) = rest.find("{{") {
+            page.push_str(&rest[..start]);
+            let after = &rest[start + 2..];
+            let value = after.find("}}").and_then(|end| {
+                let key = &after[..end];
+                let (_, value) = values.iter().find(|(name, _)| *name == key)?;
+                Some((end, value))
+            });
+            match value {
+                Some((end, value)) => {
+                    page.push_str(&escape_html(value));
+                    rest = &after[end + 2..];
+                }
+                None => {
+                    page.push_str("{{");
+                    rest = after;
+                }
+            }
+        }
+        page.push_str(rest);
+        page
+    }
+
+    fn error_page<T: From<String>>(status: StatusCode, body: String, mode: WXMode) -> Response<T> {
+        common_headers(mode)
+            .status(status)
+            .header("Content-Type", "text/html; charset=utf-8")
+            .header("Content-Length", body.len().to_string())
+            .body(T::from(body))
+            .unwrap()
+    }
+
+    thread_local! {
+        /// The clock of the `Date` header of responses built on this thread.
+        static CLOCK: Cell<fn() -> SystemTime> = Cell::new(SystemTime::now);
+    }
 
-    use crate::runner::WXMode;
+    /// Build all responses on this thread within `f` with the given clock.
+    /// Makes the `Date` header deterministic in tests.
+    pub fn with_clock<R>(clock: fn() -> SystemTime, f: impl FnOnce() -> R) -> R {
+        let previous = CLOCK.with(|current| current.replace(clock));
+        let result = f();
+        CLOCK.with(|current| current.set(previous));
+        result
+    }
+
+    /// A response builder with the headers shared by all responses.
+    pub fn common_headers(mode: WXMode) -> Builder {
+        Response::builder()
+            .header("Server", server_header(mode))
+            .header("Date", http_date(CLOCK.with(Cell::get)()))
+    }
 
     pub fn server_header(mode: WXMode) -> String {
         if mode.is_dev() {
@@ -42,32 +723,25 @@ pub mod responses {
         }
     }
 
-    pub fn serialize(response: &Response<Bytes>) -> String {
+    /// Serialize a response for debug logging.
+    /// The values of the `redact` headers are masked.
+    pub fn serialize(response: &Response<WXResponseBody>, redact: &[String]) -> String {
         let mut result = format!("HTTP/1.1 {}\r\n", response.status());
-        for (header, value) in response.headers() {
-            result.push_str(&format!("{}: {}\r\n", header, value.to_str().unwrap_or("")));
-        }
+        result.push_str(&super::serialize_headers(response.headers(), redact));
         result.push_str("\r\n");
-        if let Ok(body) = String::from_utf8(response.body().to_vec()) {
-            result.push_str(&body);
-        } else {
-            result.push_str("<Failed to serialize>");
+        match response.body().bytes().map(std::str::from_utf8) {
+            Some(Ok(body)) => result.push_str(body),
+            Some(Err(_)) => result.push_str("<Failed to serialize>"),
+            None => result.push_str("<Streamed body>"),
         }
         result
     }
 
     pub fn ok_html<T>(body: T, len: usize, mode: WXMode) -> Response<T> {
-        Response::builder()
+        common_headers(mode)
             .status(hyper::StatusCode::OK)
-            // .header("Access-Control-Allow-Origin", "*")
             .header("Content-Type", "text/html; charset=utf-8")
             .header("Content-Length", len.to_string())
-            .header("Connection", "close")
-            .header("Server", server_header(mode))
-            .header("Date", chrono::Utc::now().to_rfc2822())
-            // .header("Cache-Control", "no-cache")
-            // .header("Pragma", "no-cache")
-            // .header("Expires", "0")
             .body(body)
             .unwrap()
     }
@@ -77,21 +751,139 @@ pub mod responses {
         let value = v8::json::stringify(scope, local).expect("Failed to serialize JSON value");
         let json = value.to_rust_string_lossy(scope);
         let bytes = Bytes::from(json);
-        Response::builder()
+        common_headers(mode)
             .status(hyper::StatusCode::OK)
-            // .header("Access-Control-Allow-Origin", "*")
             .header("Content-Type", "application/json")
             .header("Content-Length", bytes.len().to_string())
-            .header("Connection", "close")
-            .header("Server", server_header(mode))
-            .header("Date", chrono::Utc::now().to_rfc2822())
-            // .header("Cache-Control", "no-cache")
-            // .header("Pragma", "no-cache")
-            // .header("Expires", "0")
             .body(bytes)
             .unwrap()
     }
 
+    pub fn ok_text(body: Bytes, mode: WXMode) -> Response<Bytes> {
+        common_headers(mode)
+            .status(hyper::StatusCode::OK)
+            .header("Content-Type", "text/plain; charset=utf-8")
+            .header("Content-Length", body.len().to_string())
+            .body(body)
+            .unwrap()
+    }
+
+    /// The `Strict-Transport-Security` policy sent when HSTS is enabled.
+    const HSTS_POLICY: &str = "max-age=31536000; includeSubDomains";
+
+    /// Add the configured security headers to a response.
+    /// HSTS is only sent over HTTPS, as browsers ignore it over plain HTTP.
+    pub fn with_security_headers<T>(
+        mode: WXMode,
+        mut response: Response<T>,
+        config: Option<&SecurityHeadersConfig>,
+        is_https: bool,
+    ) -> Response<T> {
+        let Some(config) = config else {
+            return response;
+        };
+        let mut headers: Vec<(&'static str, &str)> = vec![];
+        if config.hsts.unwrap_or(false) && is_https {
+            headers.push(("Strict-Transport-Security", HSTS_POLICY));
+        }
+        if let Some(csp) = &config.csp {
+            headers.push(("Content-Security-Policy", csp.as_str()));
+        }
+        if let Some(frame_options) = &config.frame_options {
+            headers.push(("X-Frame-Options", frame_options.as_str()));
+        }
+        if config.no_sniff.unwrap_or(false) {
+            headers.push(("X-Content-Type-Options", "nosniff"));
+        }
+        for (name, value) in headers {
+            match HeaderValue::from_str(value) {
+                Ok(value) => {
+                    response.headers_mut().insert(name, value);
+                }
+                Err(_) => warning(
+                    mode,
+                    format!("Invalid value for the {} header: {:?}", name, value),
+                ),
+            }
+        }
+        response
+    }
+
+    /// Respond to an `OPTIONS` request with the allowed methods of a resource.
+    /// Includes the CORS preflight headers if CORS is configured.
+    pub fn options(
+        mode: WXMode,
+        allow: &[Method],
+        cors: Option<&CorsConfig>,
+        request_headers: Option<&str>,
+    ) -> Response<Bytes> {
+        let allow = allow_header(allow);
+        let mut builder = common_headers(mode)
+            .status(StatusCode::NO_CONTENT)
+            .header("Allow", &allow);
+        if let Some(cors) = cors {
+            builder = builder
+                .header("Access-Control-Allow-Origin", &cors.allow_origin)
+                .header("Access-Control-Allow-Methods", &allow);
+            if let Some(request_headers) = request_headers {
+                builder = builder.header("Access-Control-Allow-Headers", request_headers);
+            }
+        }
+        builder.body(Bytes::new()).unwrap()
+    }
+
+    /// The response to a request for a path only routed for other methods.
+    /// The `Allow` header lists the methods of the path.
+    pub fn method_not_allowed(mode: WXMode, method: &Method, allow: &[Method]) -> Response<Bytes> {
+        let message = format!(
+            "{} is not allowed, allowed methods: {}",
+            method,
+            allow_header(allow)
+        );
+        method_not_allowed_with(mode, message, allow)
+    }
+
+    /// The response to a request with a method not allowed for the resource, with a message.
+    pub fn method_not_allowed_with(
+        mode: WXMode,
+        message: String,
+        allow: &[Method],
+    ) -> Response<Bytes> {
+        let allow = allow_header(allow);
+        let mut response = error_status(mode, StatusCode::METHOD_NOT_ALLOWED, message);
+        if let Ok(value) = HeaderValue::from_str(&allow) {
+            response.headers_mut().insert("Allow", value);
+        }
+        response
+    }
+
+    /// The value of an `Allow` header, e.g. `GET, HEAD, OPTIONS`.
+    fn allow_header(allow: &[Method]) -> String {
+        allow
+            .iter()
+            .map(|method| method.as_str())
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    /// Permanently redirect the client to another location.
+    pub fn moved_permanently(mode: WXMode, location: &str) -> Response<Bytes> {
+        common_headers(mode)
+            .status(StatusCode::MOVED_PERMANENTLY)
+            .header("Location", location)
+            .header("Content-Length", "0")
+            .body(Bytes::new())
+            .unwrap()
+    }
+
+    /// An empty `204 No Content` response.
+    pub fn no_content(mode: WXMode) -> Response<Bytes> {
+        common_headers(mode)
+            .status(StatusCode::NO_CONTENT)
+            .body(Bytes::new())
+            .unwrap()
+    }
+
     pub fn not_found_default_webx(mode: WXMode, method: &Method, url: String) -> Response<String> {
         let body = format!(
             r#"<html>
@@ -110,25 +902,30 @@ pub mod responses {
     </body>
 </html>"#,
             method,
-            url,
+            escape_html(&url),
             server_banner(mode)
         );
-        Response::builder()
-            .status(hyper::StatusCode::NOT_FOUND)
-            // .header("Access-Control-Allow-Origin", "*")
-            .header("Content-Type", "text/html; charset=utf-8")
-            .header("Content-Length", body.len().to_string())
-            .header("Connection", "close")
-            .header("Server", server_header(mode))
-            .header("Date", chrono::Utc::now().to_rfc2822())
-            // .header("Cache-Control", "no-cache")
-            // .header("Pragma", "no-cache")
-            // .header("Expires", "0")
-            .body(body)
-            .unwrap()
+        error_page(StatusCode::NOT_FOUND, body, mode)
     }
 
+    /// The built-in 500 page.
+    /// The error message is only exposed in development mode.
     pub fn internal_server_error_default_webx(mode: WXMode, message: String) -> Response<Bytes> {
+        let debugging = if mode.is_dev() {
+            format!(
+                r#"
+        <h2>Debugging Information</h2>
+        <p>
+            <strong>Message:</strong>
+            <pre>
+{}
+            </pre>
+        </p>"#,
+                escape_html(&message)
+            )
+        } else {
+            String::new()
+        };
         let body = format!(
             r#"<html>
     <head>
@@ -139,33 +936,522 @@ pub mod responses {
         <p>
             The server encountered an internal error and was unable to complete your request. <br>
             Either the server is overloaded or there is an error in the application.
-        </p>
-        <h2>Debugging Information</h2>
-        <p>
-            <strong>Message:</strong>
-            <pre>
-{}
-            </pre>
-        </p>
+        </p>{}
         <hr>
         <address>{}</address>
     </body>
 </html>"#,
-            message,
+            debugging,
             server_banner(mode)
         );
-        Response::builder()
-            .status(hyper::StatusCode::INTERNAL_SERVER_ERROR)
-            // .header("Access-Control-Allow-Origin", "*")
-            .header("Content-Type", "text/html; charset=utf-8")
+        error_page(StatusCode::INTERNAL_SERVER_ERROR, body, mode)
+    }
+
+    /// Where a failing route is declared in its `.webx` module.
+    #[derive(Debug, Clone)]
+    pub struct WXErrorLocation {
+        pub file: PathBuf,
+        pub line: usize,
+    }
+
+    /// The number of source lines shown before and after the failing line.
+    const OVERLAY_CONTEXT_LINES: usize = 3;
+
+    /// Render the 500 page for a route that failed at the given location.
+    ///
+    /// In development mode, the built-in page is replaced by an error overlay
+    /// with the message, the file and line of the route, and a source snippet.
+    /// Custom error pages and production mode are unaffected.
+    pub fn route_error(
+        mode: WXMode,
+        pages: &WXErrorPages,
+        message: String,
+        location: &WXErrorLocation,
+    ) -> Response<Bytes> {
+        if !mode.is_dev() || pages.internal_server_error.is_some() {
+            return internal_server_error(mode, pages, message);
+        }
+        let body = format!(
+            r#"<html>
+    <head>
+        <title>500 Internal Server Error</title>
+        <style>
+            body {{ margin: 0; padding: 2em; background: #1e1e1e; color: #ddd; font-family: monospace; }}
+            h1 {{ color: #ff5555; }}
+            pre {{ padding: 1em; background: #2b2b2b; overflow-x: auto; }}
+            .location {{ color: #8be9fd; }}
+            .highlight {{ display: inline-block; width: 100%; background: #5a1d1d; }}
+        </style>
+    </head>
+    <body>
+        <h1>500 Internal Server Error</h1>
+        <pre class="message">{}</pre>
+        <p class="location">{}:{}</p>
+        <pre class="snippet">{}</pre>
+        <hr>
+        <address>{}</address>
+    </body>
+</html>"#,
+            escape_html(&message),
+            escape_html(&location.file.display().to_string()),
+            location.line,
+            source_snippet(location),
+            server_banner(mode)
+        );
+        error_page(StatusCode::INTERNAL_SERVER_ERROR, body, mode)
+    }
+
+    /// The numbered source lines around the location, with the failing line highlighted.
+    /// Empty if the source file can not be read.
+    fn source_snippet(location: &WXErrorLocation) -> String {
+        let Ok(source) = std::fs::read_to_string(&location.file) else {
+            return String::new();
+        };
+        let first = location.line.saturating_sub(OVERLAY_CONTEXT_LINES).max(1);
+        source
+            .lines()
+            .enumerate()
+            .map(|(i, line)| (i + 1, line))
+            .skip(first - 1)
+            .take(location.line + OVERLAY_CONTEXT_LINES + 1 - first)
+            .map(|(number, line)| {
+                let line = format!("{:>4} | {}", number, escape_html(line));
+                if number == location.line {
+                    format!(r#"<span class="highlight">{}</span>"#, line)
+                } else {
+                    line
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// Escape text for use in HTML content and attribute values.
+    pub fn escape_html(text: &str) -> String {
+        text.replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;")
+            .replace('"', "&quot;")
+            .replace('\'', "&#39;")
+    }
+
+    /// Respond with `403 Forbidden` without exposing the reason to the client.
+    pub fn forbidden(mode: WXMode) -> Response<Bytes> {
+        let body = format!(
+            r#"<html>
+    <head>
+        <title>403 Forbidden</title>
+    </head>
+    <body>
+        <h1>403 Forbidden</h1>
+        <p>You don't have permission to access this resource.</p>
+        <hr>
+        <address>{}</address>
+    </body>
+</html>"#,
+            server_banner(mode)
+        );
+        error_page(StatusCode::FORBIDDEN, body, mode)
+    }
+
+    /// The page of any other error status a route failed with, e.g. `422 Unprocessable Entity`.
+    /// The error message is only exposed in development mode.
+    pub fn error_status(mode: WXMode, status: StatusCode, message: String) -> Response<Bytes> {
+        let title = format!(
+            "{} {}",
+            status.as_u16(),
+            status.canonical_reason().unwrap_or("Error")
+        );
+        let debugging = if mode.is_dev() {
+            format!("\n        <pre>{}</pre>", escape_html(&message))
+        } else {
+            String::new()
+        };
+        let body = format!(
+            r#"<html>
+    <head>
+        <title>{}</title>
+    </head>
+    <body>
+        <h1>{}</h1>{}
+        <hr>
+        <address>{}</address>
+    </body>
+</html>"#,
+            title,
+            title,
+            debugging,
+            server_banner(mode)
+        );
+        error_page(status, body, mode)
+    }
+
+    /// The response to an error raised by `error()` in a route.
+    /// The message is meant for the user and exposed in all modes,
+    /// as `{ "error": message }` if the client prefers JSON.
+    pub fn user_error(
+        mode: WXMode,
+        status: StatusCode,
+        message: &str,
+        prefers_json: bool,
+    ) -> Response<Bytes> {
+        if prefers_json {
+            let body = Bytes::from(serde_json::json!({ "error": message }).to_string());
+            return common_headers(mode)
+                .status(status)
+                .header("Content-Type", "application/json")
+                .header("Content-Length", body.len().to_string())
+                .body(body)
+                .unwrap();
+        }
+        let title = format!(
+            "{} {}",
+            status.as_u16(),
+            status.canonical_reason().unwrap_or("Error")
+        );
+        let body = format!(
+            r#"<html>
+    <head>
+        <title>{}</title>
+    </head>
+    <body>
+        <h1>{}</h1>
+        <p>{}</p>
+        <hr>
+        <address>{}</address>
+    </body>
+</html>"#,
+            title,
+            title,
+            escape_html(message),
+            server_banner(mode)
+        );
+        error_page(status, body, mode)
+    }
+
+    /// Respond with metrics in the Prometheus text exposition format.
+    pub fn metrics(mode: WXMode, body: String) -> Response<Bytes> {
+        common_headers(mode)
+            .status(StatusCode::OK)
+            .header("Content-Type", "text/plain; version=0.0.4; charset=utf-8")
             .header("Content-Length", body.len().to_string())
-            .header("Connection", "close")
-            .header("Server", server_header(mode))
-            .header("Date", chrono::Utc::now().to_rfc2822())
-            // .header("Cache-Control", "no-cache")
-            // .header("Pragma", "no-cache")
-            // .header("Expires", "0")
             .body(Bytes::from(body))
             .unwrap()
     }
+
+    /// Accept a WebSocket handshake with the derived `Sec-WebSocket-Accept` key.
+    pub fn switching_protocols(mode: WXMode, accept: &str) -> Response<Bytes> {
+        common_headers(mode)
+            .status(StatusCode::SWITCHING_PROTOCOLS)
+            .header("Connection", "Upgrade")
+            .header("Upgrade", "websocket")
+            .header("Sec-WebSocket-Accept", accept)
+            .body(Bytes::new())
+            .unwrap()
+    }
+
+    /// Guess the `Content-Type` of a file from its extension.
+    pub fn content_type(path: &Path) -> &'static str {
+        let extension = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(|ext| ext.to_ascii_lowercase());
+        match extension.as_deref() {
+            Some("html" | "htm") => "text/html; charset=utf-8",
+            Some("css") => "text/css; charset=utf-8",
+            Some("js" | "mjs") => "text/javascript; charset=utf-8",
+            Some("json") => "application/json",
+            Some("txt") => "text/plain; charset=utf-8",
+            Some("svg") => "image/svg+xml",
+            Some("png") => "image/png",
+            Some("jpg" | "jpeg") => "image/jpeg",
+            Some("gif") => "image/gif",
+            Some("webp") => "image/webp",
+            Some("ico") => "image/x-icon",
+            Some("wasm") => "application/wasm",
+            Some("pdf") => "application/pdf",
+            _ => "application/octet-stream",
+        }
+    }
+
+    /// Format a point in time as an HTTP date, e.g. `Sun, 06 Nov 1994 08:49:37 GMT`.
+    pub fn http_date(time: SystemTime) -> String {
+        chrono::DateTime::<chrono::Utc>::from(time)
+            .format("%a, %d %b %Y %H:%M:%S GMT")
+            .to_string()
+    }
+
+    /// Respond with a file, streamed in chunks instead of read into memory.
+    pub fn file(
+        mode: WXMode,
+        file: std::fs::File,
+        len: u64,
+        content_type: &str,
+        modified: Option<SystemTime>,
+    ) -> Response<WXResponseBody> {
+        let mut builder = common_headers(mode)
+            .status(StatusCode::OK)
+            .header("Content-Type", content_type)
+            .header("Content-Length", len.to_string())
+            .header("Accept-Ranges", "bytes");
+        if let Some(modified) = modified {
+            builder = builder.header("Last-Modified", http_date(modified));
+        }
+        builder
+            .body(WXResponseBody::File(WXFileBody::new(file, len)))
+            .unwrap()
+    }
+
+    /// A strong entity tag for a response body.
+    /// The body is hashed with FNV-1a, which unlike the std hasher
+    /// is stable across builds and restarts of the server.
+    pub fn etag(body: &[u8]) -> String {
+        let hash = body.iter().fold(0xcbf29ce484222325_u64, |hash, byte| {
+            (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+        });
+        format!("\"{:x}-{:016x}\"", body.len(), hash)
+    }
+
+    /// Answer a conditional `GET` request.
+    /// Successful responses held in memory are tagged with an `ETag`, and
+    /// `304 Not Modified` is sent without a body if the client is up to date.
+    pub fn conditional(
+        request_headers: &HeaderMap,
+        mut response: Response<WXResponseBody>,
+    ) -> Response<WXResponseBody> {
+        if response.status() != StatusCode::OK {
+            return response;
+        }
+        let etag = response.body().bytes().map(etag);
+        if let Some(etag) = &etag {
+            response
+                .headers_mut()
+                .insert("ETag", HeaderValue::from_str(etag).unwrap());
+        }
+        let last_modified = response
+            .headers()
+            .get("Last-Modified")
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| chrono::DateTime::parse_from_rfc2822(value).ok());
+        if !super::requests::is_fresh(request_headers, etag.as_deref(), last_modified) {
+            return response;
+        }
+        let (mut parts, _) = response.into_parts();
+        parts.status = StatusCode::NOT_MODIFIED;
+        parts.headers.remove("Content-Type");
+        parts.headers.remove("Content-Length");
+        Response::from_parts(parts, WXResponseBody::default())
+    }
+
+    /// Answer a range request for a streamed file with `206 Partial Content`,
+    /// or `416 Range Not Satisfiable` if the range starts after the end of the file.
+    /// Other responses are unchanged, and so are files modified since the `If-Range` date.
+    pub fn byte_range(
+        request_headers: &HeaderMap,
+        response: Response<WXResponseBody>,
+    ) -> Response<WXResponseBody> {
+        let len = match response.body() {
+            WXResponseBody::File(file) if response.status() == StatusCode::OK => file.remaining(),
+            _ => return response,
+        };
+        if let Some(if_range) = request_headers.get("If-Range") {
+            if response.headers().get("Last-Modified") != Some(if_range) {
+                return response;
+            }
+        }
+        let (mut parts, body) = response.into_parts();
+        let WXResponseBody::File(file) = body else {
+            unreachable!("only file responses are sliced");
+        };
+        match super::requests::byte_range(request_headers, len) {
+            WXByteRange::Full => Response::from_parts(parts, file.into()),
+            WXByteRange::Partial(first, last) => {
+                let file = match file.slice(first, last - first + 1) {
+                    Ok(file) => file,
+                    Err(_) => {
+                        parts.status = StatusCode::INTERNAL_SERVER_ERROR;
+                        parts.headers.remove("Content-Type");
+                        parts.headers.insert("Content-Length", HeaderValue::from(0));
+                        return Response::from_parts(parts, WXResponseBody::default());
+                    }
+                };
+                parts.status = StatusCode::PARTIAL_CONTENT;
+                let content_range = format!("bytes {}-{}/{}", first, last, len);
+                parts.headers.insert(
+                    "Content-Range",
+                    HeaderValue::from_str(&content_range).unwrap(),
+                );
+                parts
+                    .headers
+                    .insert("Content-Length", HeaderValue::from(file.remaining()));
+                Response::from_parts(parts, file.into())
+            }
+            WXByteRange::Unsatisfiable => {
+                parts.status = StatusCode::RANGE_NOT_SATISFIABLE;
+                let content_range = format!("bytes */{}", len);
+                parts.headers.insert(
+                    "Content-Range",
+                    HeaderValue::from_str(&content_range).unwrap(),
+                );
+                parts.headers.remove("Content-Type");
+                parts.headers.insert("Content-Length", HeaderValue::from(0));
+                Response::from_parts(parts, WXResponseBody::default())
+            }
+        }
+    }
+
+    /// The `Cache-Control` header of a route response.
+    /// Responses must be revalidated, unless the route allows caching for a duration.
+    pub fn cache_control(max_age: Option<Duration>) -> HeaderValue {
+        match max_age {
+            Some(max_age) => {
+                HeaderValue::from_str(&format!("public, max-age={}", max_age.as_secs())).unwrap()
+            }
+            None => HeaderValue::from_static("no-cache"),
+        }
+    }
+
+    /// Respond with a stream of server-sent events.
+    /// The connection is kept open until the route ends the stream,
+    /// the client disconnects, or the request timeout is reached.
+    pub fn event_stream(mode: WXMode, events: Receiver<Bytes>) -> Response<WXResponseBody> {
+        common_headers(mode)
+            .status(StatusCode::OK)
+            .header("Content-Type", "text/event-stream")
+            .header("Cache-Control", "no-cache")
+            .body(WXResponseBody::EventStream(events))
+            .unwrap()
+    }
+
+    /// Format a single server-sent event frame.
+    /// Multi-line data is split into one `data:` line per line.
+    ///
+    /// ## Example
+    /// ```text
+    /// event: update
+    /// id: 1
+    /// data: hello
+    ///
+    /// ```
+    pub fn event_frame(event: Option<&str>, id: Option<&str>, data: &str) -> Bytes {
+        // Line breaks would end the field early.
+        let field = |value: &str| value.replace(['\r', '\n'], "");
+        let mut frame = String::new();
+        if let Some(event) = event {
+            frame.push_str(&format!("event: {}\n", field(event)));
+        }
+        if let Some(id) = id {
+            frame.push_str(&format!("id: {}\n", field(id)));
+        }
+        for line in data.lines() {
+            frame.push_str(&format!("data: {}\n", line));
+        }
+        if data.is_empty() {
+            frame.push_str("data: \n");
+        }
+        frame.push('\n');
+        Bytes::from(frame)
+    }
+
+    /// The path of the live-reload event stream in development mode.
+    pub const LIVE_RELOAD_PATH: &str = "/__webx/livereload";
+
+    /// Reloads the page once the server signals a recompiled route map.
+    fn live_reload_script(path: &str) -> String {
+        format!(
+            r#"<script>new EventSource("{}").addEventListener("reload", () => location.reload());</script>"#,
+            path
+        )
+    }
+
+    /// Inject the live-reload script into a `text/html` response,
+    /// right before the closing `</body>` tag if there is one.
+    /// The script listens to the event stream at `path`, see `LIVE_RELOAD_PATH`.
+    /// Other and streamed responses are returned unchanged.
+    pub fn inject_live_reload(
+        response: Response<WXResponseBody>,
+        path: &str,
+    ) -> Response<WXResponseBody> {
+        let is_html = response
+            .headers()
+            .get("Content-Type")
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|value| value.starts_with("text/html"));
+        let Some(body) = response.body().bytes().filter(|_| is_html) else {
+            return response;
+        };
+        let body = String::from_utf8_lossy(body);
+        let at = body.rfind("</body>").unwrap_or(body.len());
+        let body = format!("{}{}{}", &body[..at], live_reload_script(path), &body[at..]);
+        let (mut parts, _) = response.into_parts();
+        parts
+            .headers
+            .insert("Content-Length", HeaderValue::from(body.len()));
+        Response::from_parts(parts, WXResponseBody::from(body))
+    }
+
+    /// Respond with `413 Payload Too Large` when a request body exceeds the limit.
+    pub fn payload_too_large(mode: WXMode, limit: usize) -> Response<Bytes> {
+        let body = format!(
+            r#"<html>
+    <head>
+        <title>413 Payload Too Large</title>
+    </head>
+    <body>
+        <h1>413 Payload Too Large</h1>
+        <p>The request body exceeds the maximum size of {} bytes.</p>
+        <hr>
+        <address>{}</address>
+    </body>
+</html>"#,
+            limit,
+            server_banner(mode)
+        );
+        error_page(StatusCode::PAYLOAD_TOO_LARGE, body, mode)
+    }
+
+    /// Render the project 404 page, or the built-in page if none is configured.
+    /// The requested URL is only exposed in development mode.
+    pub fn not_found(
+        mode: WXMode,
+        pages: &WXErrorPages,
+        method: &Method,
+        url: String,
+    ) -> Response<String> {
+        let Some(template) = &pages.not_found else {
+            return not_found_default_webx(mode, method, url);
+        };
+        let url = if mode.is_dev() { url } else { String::new() };
+        let body = fill_error_page(
+            template,
+            &[
+                ("method", method.as_str()),
+                ("url", &url),
+                ("server", &server_banner(mode)),
+            ],
+        );
+        error_page(StatusCode::NOT_FOUND, body, mode)
+    }
+
+    /// Render the project 500 page, or the built-in page if none is configured.
+    /// The error message is only exposed in development mode.
+    pub fn internal_server_error(
+        mode: WXMode,
+        pages: &WXErrorPages,
+        message: String,
+    ) -> Response<Bytes> {
+        let Some(template) = &pages.internal_server_error else {
+            return internal_server_error_default_webx(mode, message);
+        };
+        let message = if mode.is_dev() {
+            message
+        } else {
+            String::new()
+        };
+        let body = fill_error_page(
+            template,
+            &[("message", &message), ("server", &server_banner(mode))],
+        );
+        error_page(StatusCode::INTERNAL_SERVER_ERROR, body, mode)
+    }
 }
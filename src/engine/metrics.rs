@@ -0,0 +1,167 @@
+use std::{
+    collections::BTreeMap,
+    fmt::Write,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    time::Duration,
+};
+
+use crate::file::project::ProjectConfig;
+
+/// The default path of the metrics endpoint.
+pub const DEFAULT_METRICS_PATH: &str = "/metrics";
+
+/// The upper bounds of the route duration histogram buckets in seconds.
+const DURATION_BUCKETS: [f64; 11] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+/// The route label of requests that did not match any route.
+const UNMATCHED_ROUTE: &str = "unmatched";
+
+/// The route pattern a response was produced by.
+/// Attached to responses as an extension by the runtime.
+#[derive(Debug, Clone)]
+pub struct WXRouteLabel(pub String);
+
+#[derive(Debug, Default)]
+struct WXHistogram {
+    /// Non-cumulative count per bucket, the last one being `+Inf`.
+    buckets: [u64; DURATION_BUCKETS.len() + 1],
+    sum: f64,
+    count: u64,
+}
+
+impl WXHistogram {
+    fn observe(&mut self, seconds: f64) {
+        let bucket = DURATION_BUCKETS
+            .iter()
+            .position(|bound| seconds <= *bound)
+            .unwrap_or(DURATION_BUCKETS.len());
+        self.buckets[bucket] += 1;
+        self.sum += seconds;
+        self.count += 1;
+    }
+}
+
+/// A concurrency-safe registry of server metrics,
+/// shared by all server tasks.
+#[derive(Debug, Default)]
+pub struct WXMetrics {
+    /// The path the metrics are served on.
+    pub path: String,
+    requests_total: AtomicU64,
+    responses_total: Mutex<BTreeMap<u16, u64>>,
+    /// Route durations by method and route pattern.
+    route_durations: Mutex<BTreeMap<(String, String), WXHistogram>>,
+}
+
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+impl WXMetrics {
+    pub fn new(path: &str) -> Self {
+        WXMetrics {
+            path: path.to_string(),
+            ..Default::default()
+        }
+    }
+
+    /// Create the metrics registry if enabled in the project configuration.
+    pub fn from_config(config: &ProjectConfig) -> Option<Self> {
+        let metrics = config.metrics.as_ref().filter(|metrics| metrics.enabled)?;
+        Some(Self::new(
+            metrics.path.as_deref().unwrap_or(DEFAULT_METRICS_PATH),
+        ))
+    }
+
+    /// Count an incoming request.
+    pub fn record_request(&self) {
+        self.requests_total.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Record the status and duration of a response.
+    pub fn record_response(
+        &self,
+        method: &hyper::Method,
+        route: Option<&str>,
+        status: u16,
+        duration: Duration,
+    ) {
+        *self
+            .responses_total
+            .lock()
+            .unwrap()
+            .entry(status)
+            .or_default() += 1;
+        let route = route.unwrap_or(UNMATCHED_ROUTE).to_string();
+        self.route_durations
+            .lock()
+            .unwrap()
+            .entry((method.to_string(), route))
+            .or_default()
+            .observe(duration.as_secs_f64());
+    }
+
+    /// Render all metrics in the Prometheus text exposition format.
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        let _ = writeln!(out, "# HELP webx_requests_total Total number of requests.");
+        let _ = writeln!(out, "# TYPE webx_requests_total counter");
+        let _ = writeln!(
+            out,
+            "webx_requests_total {}",
+            self.requests_total.load(Ordering::Relaxed)
+        );
+        let _ = writeln!(
+            out,
+            "# HELP webx_responses_total Total number of responses by status code."
+        );
+        let _ = writeln!(out, "# TYPE webx_responses_total counter");
+        for (status, count) in self.responses_total.lock().unwrap().iter() {
+            let _ = writeln!(
+                out,
+                "webx_responses_total{{status=\"{}\"}} {}",
+                status, count
+            );
+        }
+        let _ = writeln!(
+            out,
+            "# HELP webx_route_duration_seconds Request duration by route."
+        );
+        let _ = writeln!(out, "# TYPE webx_route_duration_seconds histogram");
+        for ((method, route), histogram) in self.route_durations.lock().unwrap().iter() {
+            let labels = format!("method=\"{}\",route=\"{}\"", method, escape_label(route));
+            let mut cumulative = 0;
+            for (i, count) in histogram.buckets.iter().enumerate() {
+                cumulative += count;
+                let le = DURATION_BUCKETS
+                    .get(i)
+                    .map(|bound| bound.to_string())
+                    .unwrap_or("+Inf".into());
+                let _ = writeln!(
+                    out,
+                    "webx_route_duration_seconds_bucket{{{},le=\"{}\"}} {}",
+                    labels, le, cumulative
+                );
+            }
+            let _ = writeln!(
+                out,
+                "webx_route_duration_seconds_sum{{{}}} {}",
+                labels, histogram.sum
+            );
+            let _ = writeln!(
+                out,
+                "webx_route_duration_seconds_count{{{}}} {}",
+                labels, histogram.count
+            );
+        }
+        out
+    }
+}
@@ -1,6 +1,16 @@
+mod crypto;
+mod csrf;
+mod fetch;
 pub mod filewatcher;
+mod graphql;
+pub mod health;
 mod http;
+pub mod metrics;
+pub mod pool;
 pub mod runtime;
 pub mod server;
-mod stdlib;
+mod session;
+pub mod stdlib;
+mod template;
 mod test;
+pub mod transpiler;
@@ -0,0 +1,337 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{Receiver, RecvTimeoutError, Sender},
+        Arc, Mutex, MutexGuard, PoisonError,
+    },
+    thread::JoinHandle,
+    time::Duration,
+};
+
+use hyper::body::Bytes;
+
+use crate::{
+    file::webx::{websocket_method, WXModule, WXModulePath},
+    reporting::warning::warning,
+    runner::{timeout_duration, WXMode},
+};
+
+use super::{
+    runtime::{WXRouteMap, WXRuntime, WXRuntimeInfo, WXRuntimeMessage},
+    session::{WXMemorySessionStore, WXSessionStore, WXSharedSessionStore},
+};
+
+/// How often the pool checks whether all workers are ready.
+const READY_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// The shard of the modules a runtime worker executes,
+/// and the state it shares with the other workers.
+///
+/// ## Assignment
+/// Modules are assigned round-robin in the order they are first loaded.
+/// The pool and every worker receive the module messages in the same order,
+/// so they all assign the same modules to the same workers without sharing the assignment.
+#[derive(Clone)]
+pub struct WXRuntimeShard {
+    /// The index of this worker, the primary worker has index 0.
+    index: usize,
+    /// The channels of all workers by index, empty for a single runtime.
+    workers: Vec<Sender<WXRuntimeMessage>>,
+    /// The module paths in the order they were first loaded.
+    assigned: Vec<WXModulePath>,
+    /// The sessions of clients, shared by all workers.
+    pub sessions: WXSharedSessionStore,
+}
+
+impl WXRuntimeShard {
+    /// A single runtime executing all modules.
+    pub fn single() -> Self {
+        WXRuntimeShard {
+            index: 0,
+            workers: Vec::new(),
+            assigned: Vec::new(),
+            sessions: Arc::new(Mutex::new(WXMemorySessionStore::default())),
+        }
+    }
+
+    /// The index of this worker.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// Lock the session store shared by all workers.
+    /// A route that panicked while holding the lock only failed its own request,
+    /// so the store stays usable instead of failing every later request.
+    pub fn lock_sessions(&self) -> MutexGuard<'_, dyn WXSessionStore + Send + 'static> {
+        self.sessions.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// The number of workers the modules are sharded across.
+    pub fn count(&self) -> usize {
+        self.workers.len().max(1)
+    }
+
+    /// Whether this worker reports the diagnostics shared by all workers,
+    /// and answers the requests not owned by any module.
+    pub fn is_primary(&self) -> bool {
+        self.index == 0
+    }
+
+    /// The worker executing a module, assigning new modules to the next worker.
+    pub fn assign(&mut self, module_path: &WXModulePath) -> usize {
+        match self.owner(module_path) {
+            Some(worker) => worker,
+            None => {
+                self.assigned.push(module_path.clone());
+                (self.assigned.len() - 1) % self.count()
+            }
+        }
+    }
+
+    /// The worker executing a module, if it has been assigned.
+    pub fn owner(&self, module_path: &WXModulePath) -> Option<usize> {
+        self.assigned
+            .iter()
+            .position(|path| path == module_path)
+            .map(|position| position % self.count())
+    }
+
+    /// Whether this worker executes a module, assigning new modules to the next worker.
+    pub fn owns(&mut self, module_path: &WXModulePath) -> bool {
+        self.assign(module_path) == self.index
+    }
+
+    /// Call a GraphQL resolver in the module of another worker, and wait for its result.
+    /// Only the primary worker calls other workers, so they never wait on each other.
+    pub fn resolve(
+        &self,
+        module_path: &WXModulePath,
+        name: &str,
+        arguments: &serde_json::Map<String, serde_json::Value>,
+    ) -> Result<Option<serde_json::Value>, String> {
+        let Some(worker) = self
+            .owner(module_path)
+            .filter(|worker| *worker != self.index)
+            .and_then(|worker| self.workers.get(worker))
+        else {
+            return Ok(None);
+        };
+        let (respond_to, result) = std::sync::mpsc::channel();
+        let message = WXRuntimeMessage::Resolve {
+            module_path: module_path.clone(),
+            name: name.to_string(),
+            arguments: arguments.clone(),
+            respond_to,
+        };
+        if worker.send(message).is_err() {
+            return Err(format!(
+                "The runtime of module '{}' has stopped",
+                module_path.module_name()
+            ));
+        }
+        result.recv().unwrap_or_else(|_| {
+            Err(format!(
+                "The runtime of module '{}' has stopped",
+                module_path.module_name()
+            ))
+        })
+    }
+}
+
+/// Dispatches the messages of the server and file watcher to the runtime workers.
+/// Every worker owns the JS runtimes of a disjoint subset of the modules,
+/// so routes of independent modules are executed in parallel.
+struct WXRuntimePool {
+    mode: WXMode,
+    info: WXRuntimeInfo,
+    messages: Receiver<WXRuntimeMessage>,
+    shard: WXRuntimeShard,
+    source_modules: Vec<WXModule>,
+    /// The route map of all modules, to look up the module owning a request.
+    routes: WXRouteMap,
+}
+
+impl WXRuntimePool {
+    /// Rebuild the route map after the modules have changed.
+    /// Compile errors are reported by the primary worker, the previous route map is kept.
+    fn recompile(&mut self) {
+        if let Ok(routes) = WXRouteMap::from_modules(
+            &self.source_modules,
+            &self.info.body_policy,
+            self.info.case_sensitive_routes,
+        ) {
+            self.routes = routes
+                .with_base_path(&self.info.base_path)
+                .with_trailing_slash(self.info.trailing_slash);
+        }
+    }
+
+    /// The worker owning the route of a request.
+    /// Requests without a route are answered by the primary worker.
+    fn route_worker(&self, method: &hyper::Method, req: &hyper::Request<Bytes>) -> usize {
+        if self.info.is_graphql_request(req) {
+            return 0;
+        }
+        self.routes
+            .resolve(method, req.uri())
+            .found()
+            .and_then(|(_, _, route)| self.shard.owner(route.module_path()))
+            .unwrap_or(0)
+    }
+
+    fn send(&self, worker: usize, message: WXRuntimeMessage) {
+        if self.shard.workers[worker].send(message).is_err() {
+            warning(self.mode, format!("Runtime worker {} has stopped", worker));
+        }
+    }
+
+    fn broadcast(&self, message: impl Fn() -> WXRuntimeMessage) {
+        for worker in 0..self.shard.count() {
+            self.send(worker, message());
+        }
+    }
+
+    /// Replace a module in the route map, assigning new modules to the next worker.
+    fn update_module(&mut self, module: &WXModule) {
+        self.shard.assign(&module.path);
+        self.source_modules.retain(|m| m.path != module.path);
+        self.source_modules.push(module.clone());
+        self.recompile();
+    }
+
+    /// Forward a request or WebSocket message to the worker it belongs to.
+    fn dispatch(&self, message: WXRuntimeMessage) {
+        let worker = match &message {
+            WXRuntimeMessage::ExecuteRoute { request, .. } => {
+                self.route_worker(request.method(), request)
+            }
+            WXRuntimeMessage::OpenSocket { request, .. } => {
+                self.route_worker(&websocket_method(), request)
+            }
+            // Every worker assigns socket ids congruent to its index.
+            WXRuntimeMessage::SocketMessage { id, .. } | WXRuntimeMessage::SocketClosed { id } => {
+                *id as usize % self.shard.count()
+            }
+            _ => 0,
+        };
+        self.send(worker, message);
+    }
+
+    /// Forward messages to the workers until the server and file watcher have stopped.
+    /// Module and configuration changes are sent to all workers.
+    fn run(&mut self, running: &AtomicBool) {
+        while running.load(Ordering::SeqCst) {
+            let message = match self.messages.recv_timeout(timeout_duration(self.mode)) {
+                Ok(message) => message,
+                Err(RecvTimeoutError::Timeout) => continue,
+                Err(RecvTimeoutError::Disconnected) => break,
+            };
+            match message {
+                WXRuntimeMessage::New(module) => {
+                    self.update_module(&module);
+                    self.broadcast(|| WXRuntimeMessage::New(module.clone()));
+                }
+                WXRuntimeMessage::Swap(module) => {
+                    self.update_module(&module);
+                    self.broadcast(|| WXRuntimeMessage::Swap(module.clone()));
+                }
+                WXRuntimeMessage::Remove(path) => {
+                    self.source_modules.retain(|m| m.path != path);
+                    self.recompile();
+                    self.broadcast(|| WXRuntimeMessage::Remove(path.clone()));
+                }
+                WXRuntimeMessage::Asset(path) => {
+                    self.broadcast(|| WXRuntimeMessage::Asset(path.clone()))
+                }
+                WXRuntimeMessage::Config(config) => {
+                    let project_root = self.info.project_root.clone();
+                    self.info = WXRuntimeInfo::new(&project_root, &config, self.mode);
+                    self.recompile();
+                    self.broadcast(|| WXRuntimeMessage::Config(config.clone()));
+                }
+                WXRuntimeMessage::Shutdown => break,
+                message => self.dispatch(message),
+            }
+        }
+    }
+}
+
+/// Run the runtime of a project in the current thread until it is stopped.
+///
+/// ## Workers
+/// With more than one worker, the modules are sharded across that many runtime threads,
+/// and this thread dispatches the messages to the worker owning the module of each route.
+/// The JS runtimes are never shared, as they are **not** thread-safe.
+///
+/// ## Readiness
+/// `ready` is set once all workers have compiled their modules.
+pub fn run_runtime(
+    messages: Receiver<WXRuntimeMessage>,
+    mode: WXMode,
+    info: WXRuntimeInfo,
+    modules: Vec<WXModule>,
+    workers: usize,
+    running: Arc<AtomicBool>,
+    ready: Arc<AtomicBool>,
+) {
+    if workers <= 1 {
+        let mut runtime = WXRuntime::new(messages, mode, info);
+        runtime.load_modules(modules);
+        runtime.run(running, ready);
+        return;
+    }
+    let (senders, receivers): (Vec<_>, Vec<_>) =
+        (0..workers).map(|_| std::sync::mpsc::channel()).unzip();
+    let mut shard = WXRuntimeShard {
+        workers: senders,
+        ..WXRuntimeShard::single()
+    };
+    let mut workers_ready = Vec::new();
+    let mut handles: Vec<JoinHandle<()>> = Vec::new();
+    for (index, receiver) in receivers.into_iter().enumerate() {
+        let worker_ready = Arc::new(AtomicBool::new(false));
+        workers_ready.push(worker_ready.clone());
+        let shard = WXRuntimeShard {
+            index,
+            ..shard.clone()
+        };
+        let (info, modules, running) = (info.clone(), modules.clone(), running.clone());
+        let handle = std::thread::Builder::new()
+            .name(format!("webx-runtime-{}", index))
+            .spawn(move || {
+                let mut runtime = WXRuntime::sharded(receiver, mode, info, shard);
+                runtime.load_modules(modules);
+                runtime.run(running, worker_ready);
+            })
+            .expect("Failed to spawn runtime worker");
+        handles.push(handle);
+    }
+    for module in modules.iter() {
+        shard.assign(&module.path);
+    }
+    let mut pool = WXRuntimePool {
+        mode,
+        info,
+        messages,
+        shard,
+        source_modules: modules,
+        routes: WXRouteMap::new(),
+    };
+    pool.recompile();
+    while running.load(Ordering::SeqCst)
+        && !workers_ready
+            .iter()
+            .all(|ready| ready.load(Ordering::SeqCst))
+        && !handles.iter().any(|handle| handle.is_finished())
+    {
+        std::thread::sleep(READY_POLL_INTERVAL);
+    }
+    ready.store(true, Ordering::SeqCst);
+    pool.run(&running);
+    pool.broadcast(|| WXRuntimeMessage::Shutdown);
+    for (index, handle) in handles.into_iter().enumerate() {
+        if handle.join().is_err() {
+            warning(mode, format!("Failed to stop runtime worker {}", index));
+        }
+    }
+}
@@ -1,47 +1,81 @@
 use std::{
     collections::HashMap,
     fmt::Display,
-    net::SocketAddr,
-    path::Path,
+    net::{IpAddr, SocketAddr},
+    panic::AssertUnwindSafe,
+    path::{Path, PathBuf},
     rc::Rc,
     sync::{
         atomic::{AtomicBool, Ordering},
-        mpsc::Receiver,
-        Arc,
+        mpsc::{Receiver, RecvTimeoutError},
+        Arc, OnceLock,
     },
+    time::{Duration, Instant},
 };
 
 use deno_core::{
+    error::JsError,
+    serde_v8,
     v8::{self, Global, Local, Value},
-    JsRuntime, RuntimeOptions,
+    JsRuntime, JsRuntimeForSnapshot, RuntimeOptions, Snapshot,
 };
 use hyper::body::Bytes;
+use serde::Deserialize;
 
 use crate::{
-    analysis::routes::{verify_model_routes, FlatRoutes},
-    file::webx::{
-        WXBody, WXBodyType, WXModule, WXModulePath, WXRouteHandlerCall, WXTypedIdentifier,
-        WXUrlPath, WXUrlPathSegment,
+    analysis::{
+        dependencies::{find_dependents, included_assets},
+        openapi::collect_models,
+        routes::{
+            extract_flat_routes, extract_overlapping_routes, verify_model_routes, BodyFormatPolicy,
+            FlatRoutes,
+        },
+    },
+    file::{
+        project::{
+            CorsConfig, CsrfConfig, GraphQLConfig, ProjectConfig, SessionConfig,
+            TrailingSlashPolicy,
+        },
+        webx::{
+            segment_regex, websocket_method, WXBody, WXBodyType, WXErrorHandler, WXModel, WXModule,
+            WXModulePath, WXRouteHandlerCall, WXRouteReqBody, WXTypedIdentifier, WXUrlPath,
+            WXUrlPathSegment, WEBSOCKET_METHOD, WILDCARD,
+        },
     },
     reporting::{
-        debug::info,
-        error::{error_code, exit_error},
-        route::print_route,
+        debug::{info, is_info_enabled, is_quiet, with_request_id},
+        error::{error_code, exit_error, ERROR_BAD_REQUEST, ERROR_EXEC_ROUTE, ERROR_FORBIDDEN},
+        route::format_route_table,
         warning::warning,
     },
-    runner::WXMode,
-    timeout_duration,
+    runner::{timeout_duration, WXMode},
 };
 
 use super::{
-    http::responses::{self, ok_html, ok_json},
-    stdlib,
+    csrf,
+    fetch::WXFetchLimits,
+    graphql::{self, WXGraphQLSchema},
+    http::{
+        redacted_headers, requests,
+        responses::{self, ok_html, ok_json, ok_text, WXErrorLocation, WXErrorPages},
+        WXResponseBody,
+    },
+    metrics::WXRouteLabel,
+    pool::WXRuntimeShard,
+    session::WXRTSession,
+    stdlib::{self, WXNativeRegistry},
+    template,
+    transpiler::transpile_ts,
 };
 
 /// A runtime error.
 #[derive(Debug, PartialEq, Clone)]
 pub struct WXRuntimeError {
+    /// The internal error code, e.g. `ERROR_HANDLER_CALL`.
     pub code: i32,
+    /// The HTTP status of the response to a request failing with the error.
+    /// Set by the `error(message, status)` stdlib function, `500` otherwise.
+    pub http_status: u16,
     pub message: String,
 }
 
@@ -68,21 +102,240 @@ impl WXRTContext {
     pub fn bind(&mut self, key: &str, value: Global<Value>) {
         self.values.insert(key.to_string(), value);
     }
+
+    /// The names of the bound values, sorted.
+    pub fn names(&self) -> Vec<&str> {
+        let mut names = self.values.keys().map(String::as_str).collect::<Vec<_>>();
+        names.sort();
+        names
+    }
+
+    /// Bind a JSON value, converted to a value of the runtime of `scope`.
+    pub fn bind_json(
+        &mut self,
+        scope: &mut v8::HandleScope,
+        key: &str,
+        value: &serde_json::Value,
+    ) -> Result<(), WXRuntimeError> {
+        let local = serde_v8::to_v8(scope, value).map_err(|err| WXRuntimeError {
+            code: 500,
+            http_status: 500,
+            message: format!("Failed to bind '{}': {}", key, err),
+        })?;
+        self.bind(key, Global::new(scope, local));
+        Ok(())
+    }
+}
+
+/// The `req` object bound for the handlers and body of a route.
+///
+/// ```typescript
+/// {
+///     method: string,                  // e.g. "GET"
+///     path: string,                    // e.g. "/todos/1"
+///     params: Record<string, any>,     // The path parameters, see `WXRouteParams`
+///     query: Record<string, string>,   // The query fields, repeated fields keep their last value
+///     body: any,                       // Parsed JSON or form fields by the `Content-Type`,
+///                                      // the text of any other body, or `null` if empty,
+///                                      // see `request_body`
+///     headers: Record<string, string>, // By lowercase name, repeated headers joined by ", "
+///     ip: string,                      // The client IP address
+///     id: string | null,               // The request id, see `WXSvc::call`
+/// }
+/// ```
+fn request_value(
+    req: &hyper::Request<Bytes>,
+    params: WXRouteParams,
+    body: serde_json::Value,
+    ip: IpAddr,
+) -> serde_json::Value {
+    let query = requests::form_fields(req.uri().query().unwrap_or_default().as_bytes());
+    let headers = req
+        .headers()
+        .keys()
+        .map(|name| {
+            let values = req
+                .headers()
+                .get_all(name)
+                .iter()
+                .map(|value| String::from_utf8_lossy(value.as_bytes()).into_owned())
+                .collect::<Vec<_>>();
+            (name.to_string(), serde_json::Value::from(values.join(", ")))
+        })
+        .collect::<serde_json::Map<_, _>>();
+    serde_json::json!({
+        "method": req.method().as_str(),
+        "path": req.uri().path(),
+        "params": params,
+        "query": string_fields(query),
+        "body": body,
+        "headers": headers,
+        "ip": ip.to_string(),
+        "id": requests::request_id(req.headers()),
+    })
+}
+
+fn string_fields(fields: Vec<(String, String)>) -> serde_json::Map<String, serde_json::Value> {
+    fields
+        .into_iter()
+        .map(|(name, value)| (name, serde_json::Value::from(value)))
+        .collect()
+}
+
+/// The declared format of a request body, with model references resolved to their fields.
+#[derive(Debug, Clone)]
+struct WXRTBodyFormat {
+    /// Whether the body is URL-encoded form fields instead of JSON.
+    form: bool,
+    fields: Vec<WXTypedIdentifier>,
+}
+
+impl WXRTBodyFormat {
+    fn new(format: &WXRouteReqBody, models: &[&WXModel]) -> Self {
+        match format {
+            WXRouteReqBody::Definition(name, fields) => WXRTBodyFormat {
+                form: name == "form",
+                fields: fields.clone(),
+            },
+            WXRouteReqBody::ModelReference(name) => WXRTBodyFormat {
+                form: false,
+                fields: models
+                    .iter()
+                    .find(|model| model.name == *name)
+                    .map(|model| model.fields.clone())
+                    .unwrap_or_default(),
+            },
+        }
+    }
+}
+
+/// Whether a JSON value is of a declared WebX type.
+/// Only primitives and arrays are checked, models and other types accept any value.
+fn matches_type(type_: &str, value: &serde_json::Value) -> bool {
+    let type_ = type_.trim();
+    if type_.ends_with("[]") || type_.starts_with("Array<") {
+        return value.is_array();
+    }
+    match type_.to_ascii_lowercase().as_str() {
+        "int" | "integer" => value.is_i64() || value.is_u64(),
+        "float" | "number" | "double" => value.is_number(),
+        "bool" | "boolean" => value.is_boolean(),
+        "string" => value.is_string(),
+        _ => true,
+    }
+}
+
+/// The body of a request, see `request_value`.
+///
+/// Without a body format, the body is parsed by its `Content-Type`, falling back to its text.
+/// With a body format, the body must be a JSON object, or form fields for `form(...)`,
+/// with all declared fields of their declared type. Form fields are converted to their type.
+///
+/// ## Error
+/// Describes why the body does not match the body format.
+fn request_body(
+    req: &hyper::Request<Bytes>,
+    format: Option<&WXRTBodyFormat>,
+) -> Result<serde_json::Value, String> {
+    let body = req.body();
+    let Some(format) = format else {
+        let content_type = req
+            .headers()
+            .get("Content-Type")
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+        let text = || serde_json::Value::from(String::from_utf8_lossy(body).into_owned());
+        return Ok(if body.is_empty() {
+            serde_json::Value::Null
+        } else if content_type.starts_with("application/x-www-form-urlencoded") {
+            serde_json::Value::Object(string_fields(requests::form_fields(body)))
+        } else if content_type.starts_with("application/json") {
+            serde_json::from_slice(body).unwrap_or_else(|_| text())
+        } else {
+            text()
+        });
+    };
+    let mut fields = if format.form {
+        string_fields(requests::form_fields(body))
+    } else {
+        match serde_json::from_slice(body) {
+            Ok(serde_json::Value::Object(fields)) => fields,
+            Ok(_) => return Err("expected a JSON object".into()),
+            Err(err) => return Err(format!("malformed JSON: {}", err)),
+        }
+    };
+    for field in format.fields.iter() {
+        let Some(value) = fields.get_mut(&field.name) else {
+            return Err(format!("missing field '{}'", field.name));
+        };
+        if format.form {
+            *value = param_value(&field.type_, value.as_str().unwrap_or_default());
+        }
+        if !matches_type(&field.type_, value) {
+            return Err(format!(
+                "field '{}' is not of type {}",
+                field.name, field.type_
+            ));
+        }
+    }
+    Ok(serde_json::Value::Object(fields))
+}
+
+/// The parameter list binding the context values by name, e.g. `id, req`,
+/// in the order of the arguments passed by `call_with_context`.
+fn context_params(ctx: &WXRTContext) -> String {
+    ctx.names().join(", ")
 }
 
-fn init_context<'a>(
-    scope: &'a mut v8::HandleScope,
+/// Execute a script evaluating to a function with the parameters of `context_params`,
+/// and call it with the context values as its arguments.
+///
+/// The values are only visible to the function, so they neither leak into later requests
+/// nor replace globals of the module runtime of the same name.
+fn call_with_context(
+    rt: &mut JsRuntime,
+    name: &'static str,
+    source: String,
     ctx: &WXRTContext,
-) -> v8::Local<'a, v8::Context> {
-    let js_ctx = v8::Context::new(scope);
-    let global = js_ctx.global(scope);
-    for (key, value) in ctx.values.iter() {
-        // Maybe replace key with Symbol instead?
-        let key = v8::String::new(scope, key).unwrap();
-        let value = Local::new(scope, value);
-        global.set(scope, key.into(), value);
+) -> Result<Global<Value>, WXRuntimeError> {
+    let function = rt
+        .execute_script(name, source.into())
+        .map_err(|err| WXRuntimeError {
+            code: 500,
+            http_status: 500,
+            message: err.to_string(),
+        })?;
+    let scope = &mut rt.handle_scope();
+    let function = Local::new(scope, function);
+    let Ok(function) = Local::<v8::Function>::try_from(function) else {
+        return Err(WXRuntimeError {
+            code: 500,
+            http_status: 500,
+            message: format!("Script {} did not evaluate to a function", name),
+        });
+    };
+    let args = ctx
+        .names()
+        .into_iter()
+        .map(|key| Local::new(scope, &ctx.values[key]))
+        .collect::<Vec<_>>();
+    let scope = &mut v8::TryCatch::new(scope);
+    let this = v8::undefined(scope).into();
+    match function.call(scope, this, &args) {
+        Some(value) => Ok(Global::new(scope, value)),
+        None => {
+            let message = match scope.exception() {
+                Some(exception) => JsError::from_v8_exception(scope, exception).to_string(),
+                None => "Execution was terminated".into(),
+            };
+            Err(WXRuntimeError {
+                code: 500,
+                http_status: 500,
+                message,
+            })
+        }
     }
-    js_ctx
 }
 
 fn eval_js_expression(
@@ -90,19 +343,73 @@ fn eval_js_expression(
     rt: &mut JsRuntime,
     ctx: &WXRTContext,
 ) -> Result<Global<Value>, WXRuntimeError> {
-    {
-        let mut scope = rt.handle_scope();
-        init_context(&mut scope, ctx);
-    }
-    let val = rt.execute_script("[webx expression]", expr.into());
-    match val {
-        Ok(val) => Ok(val),
-        Err(err) => Err(WXRuntimeError {
+    let function = format!("(({}) => ({}))", context_params(ctx), expr);
+    call_with_context(rt, "[webx expression]", function, ctx).map_err(|err| WXRuntimeError {
+        message: format!("Expression threw an error:\n{}", err.message),
+        ..err
+    })
+}
+
+/// Drive the event loop until the given value is settled if it is a promise.
+/// All other values are returned as is.
+///
+/// This allows handlers and route bodies to be `async` and use `await`.
+pub fn resolve_promise(
+    rt: &mut JsRuntime,
+    value: Global<Value>,
+) -> Result<Global<Value>, WXRuntimeError> {
+    let is_promise = {
+        let scope = &mut rt.handle_scope();
+        Local::new(scope, &value).is_promise()
+    };
+    if !is_promise {
+        return Ok(value);
+    }
+    let executor = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()?;
+    executor
+        .block_on(rt.resolve_value(value))
+        .map_err(|err| WXRuntimeError {
             code: 500,
-            message: format!("Expression threw an error:\n{}", err),
-        }),
+            http_status: 500,
+            message: format!("Promise was rejected:\n{}", err),
+        })
+}
+
+/// Call the handler `name` of a module with the arguments of a GraphQL field as an object.
+/// Returns `None` if the module does not define the handler.
+fn call_resolver(
+    rt: &mut JsRuntime,
+    name: &str,
+    arguments: &serde_json::Map<String, serde_json::Value>,
+) -> Result<Option<serde_json::Value>, String> {
+    let is_defined = rt
+        .execute_script(
+            "[webx graphql]",
+            format!("typeof {} === \"function\"", name).into(),
+        )
+        .map_err(|err| err.to_string())?;
+    let is_defined = {
+        let scope = &mut rt.handle_scope();
+        Local::new(scope, is_defined).is_true()
+    };
+    if !is_defined {
+        return Ok(None);
     }
+    let js_call = format!("{}({})", name, serde_json::Value::from(arguments.clone()));
+    let value = rt
+        .execute_script("[webx graphql resolver]", js_call.into())
+        .map_err(|err| err.to_string())
+        .and_then(|value| resolve_promise(rt, value).map_err(|err| err.message))
+        .map_err(|err| format!("Handler '{}' threw an error:\n{}", name, err))?;
+    let scope = &mut rt.handle_scope();
+    let local = Local::new(scope, value);
+    serde_v8::from_v8::<serde_json::Value>(scope, local)
+        .map(Some)
+        .map_err(|err| format!("Handler '{}' returned an invalid value: {}", name, err))
 }
+
 impl WXRouteHandlerCall {
     /// Execute the handler in the given context and return the result.
     fn execute(
@@ -113,8 +420,24 @@ impl WXRouteHandlerCall {
     ) -> Result<Global<Value>, WXRuntimeError> {
         match self.try_execute_native_script(rt, ctx, info) {
             Some(result) => result,
-            None => self.execute_user_script(rt),
+            None => self.execute_user_script(rt, ctx),
+        }
+    }
+
+    /// Execute the handler as the last one of a route. \
+    /// Native handlers producing files are streamed instead of read into memory,
+    /// and data for content negotiation is sent in the representation the client prefers.
+    fn execute_result(
+        &self,
+        ctx: &WXRTContext,
+        rt: &mut JsRuntime,
+        info: &WXRuntimeInfo,
+    ) -> Result<WXRouteResult, WXRuntimeError> {
+        if !stdlib::produces_result(&self.name) {
+            return self.execute(ctx, rt, info).map(WXRouteResult::Js);
         }
+        let args = self.native_arguments(rt, ctx)?;
+        stdlib::call_result(&self.name, &args, rt, info)
     }
 
     fn extract_arguments(
@@ -134,6 +457,7 @@ impl WXRouteHandlerCall {
                 Err(err) => {
                     return Err(WXRuntimeError {
                         code: 500,
+                        http_status: 500,
                         message: format!(
                             "Handler '{}' expected an array, got: {:?} and failed with error: {:?}",
                             self.name, local_args, err
@@ -145,7 +469,16 @@ impl WXRouteHandlerCall {
             let len = arr_args.length() as usize;
 
             for i in 0..len {
-                let arg = arr_args.get_index(scope, i as u32).unwrap();
+                let Some(arg) = arr_args.get_index(scope, i as u32) else {
+                    return Err(WXRuntimeError {
+                        code: 500,
+                        http_status: 500,
+                        message: format!(
+                            "Handler '{}' failed to read argument at index {}",
+                            self.name, i
+                        ),
+                    });
+                };
                 let global_arg = Global::new(scope, arg);
                 js_args.push(global_arg);
             }
@@ -160,40 +493,161 @@ impl WXRouteHandlerCall {
         ctx: &WXRTContext,
         info: &WXRuntimeInfo,
     ) -> Option<Result<Global<Value>, WXRuntimeError>> {
-        let global_args = match eval_js_expression(format!("[{}]", self.args), rt, ctx) {
-            Ok(val) => val,
-            Err(err) => {
-                return Some(Err(WXRuntimeError {
-                    code: 500,
-                    message: format!("Handler '{}' threw an error:\n{}", self.name, err),
-                }))
-            }
-        };
-        let js_args = match self.extract_arguments(global_args, rt) {
+        let js_args = match self.native_arguments(rt, ctx) {
             Ok(args) => args,
             Err(err) => return Some(Err(err)),
         };
-        stdlib::try_call(&self.name, &js_args, rt, info)
+        info.natives.call(&self.name, &js_args, rt, info)
+    }
+
+    /// Evaluate the arguments of the handler call in the given context.
+    fn native_arguments(
+        &self,
+        rt: &mut JsRuntime,
+        ctx: &WXRTContext,
+    ) -> Result<Vec<Global<Value>>, WXRuntimeError> {
+        let global_args =
+            eval_js_expression(format!("[{}]", self.args), rt, ctx).map_err(|err| {
+                WXRuntimeError {
+                    code: 500,
+                    http_status: 500,
+                    message: format!("Arguments threw an error:\n{}", err),
+                }
+            })?;
+        self.extract_arguments(global_args, rt)
     }
 
-    fn execute_user_script(&self, rt: &mut JsRuntime) -> Result<Global<Value>, WXRuntimeError> {
-        let js_call = format!("{}({})", self.name, self.args);
-        let call_res = rt.execute_script("[webx handler call]", js_call.into());
-        call_res.map_err(|e| WXRuntimeError {
+    fn execute_user_script(
+        &self,
+        rt: &mut JsRuntime,
+        ctx: &WXRTContext,
+    ) -> Result<Global<Value>, WXRuntimeError> {
+        let js_call = format!(
+            "(({}) => {}({}))",
+            context_params(ctx),
+            self.name,
+            self.args
+        );
+        let value = call_with_context(rt, "[webx handler call]", js_call, ctx)?;
+        resolve_promise(rt, value).map_err(|e| WXRuntimeError {
             code: 500,
-            message: format!("Handler '{}' threw an error:\n{}", self.name, e),
+            http_status: 500,
+            message: e.to_string(),
         })
     }
 }
 
+impl WXErrorHandler {
+    /// Execute the error handler with the given error bound
+    /// and return a response with the HTTP status of the error.
+    fn execute(
+        &self,
+        err: &WXRuntimeError,
+        rt: &mut JsRuntime,
+        mode: WXMode,
+    ) -> Result<hyper::Response<WXResponseBody>, WXRuntimeError> {
+        let value = match self.body.body_type {
+            WXBodyType::Tsx | WXBodyType::Raw => WXRouteResult::Html(self.body.body.clone()),
+            WXBodyType::Ts | WXBodyType::Json | WXBodyType::Text => {
+                // The error is the only argument, so the transpiled handler can be cached.
+                let error = serde_json::json!({
+                    "code": err.code,
+                    "status": err.http_status,
+                    "message": err.message,
+                });
+                let mut ctx = WXRTContext::new();
+                ctx.bind_json(&mut rt.handle_scope(), &self.binding, &error)?;
+                let js_call = transpile_ts(&format!(
+                    "(({}) => {{\n{}\n}})",
+                    context_params(&ctx),
+                    self.body.body
+                ))?;
+                let value = call_with_context(rt, "[webx error handler]", js_call, &ctx)
+                    .and_then(|value| resolve_promise(rt, value))
+                    .map_err(|err| WXRuntimeError {
+                        message: format!("Error handler threw an error:\n{}", err.message),
+                        ..err
+                    })?;
+                WXRouteResult::from_body(&self.body.body_type, value)
+            }
+        };
+        let mut response = WXRTRoute::to_response(
+            value,
+            &mut rt.handle_scope(),
+            mode,
+            hyper::HeaderMap::new(),
+            None,
+            None,
+            None,
+        );
+        *response.status_mut() = hyper::StatusCode::from_u16(err.http_status)
+            .unwrap_or(hyper::StatusCode::INTERNAL_SERVER_ERROR);
+        Ok(response)
+    }
+}
+
+/// The values of the path parameters and regex segments of a matched route, by name,
+/// including the capture groups of regex segments.
+/// Parameters of a number or boolean type are converted when the value parses as one.
+pub type WXRouteParams = serde_json::Map<String, serde_json::Value>;
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum WXPathResolution {
     None,
-    Perfect(WXRTContext),
-    Partial(WXRTContext),
+    /// Every segment of the route matches a segment of the path.
+    Perfect(WXRouteParams),
+    /// The path leaves out the optional last parameter of the route,
+    /// or is matched by the trailing wildcard of a catch-all route.
+    Partial(WXRouteParams),
 }
 
+/// The value of a path parameter of the given type.
+fn param_value(type_: &str, value: &str) -> serde_json::Value {
+    let converted = match type_.to_ascii_lowercase().as_str() {
+        "int" | "integer" => value.parse::<i64>().ok().map(serde_json::Value::from),
+        "float" | "number" | "double" => value.parse::<f64>().ok().map(serde_json::Value::from),
+        "bool" | "boolean" => value.parse::<bool>().ok().map(serde_json::Value::from),
+        _ => None,
+    };
+    converted.unwrap_or_else(|| serde_json::Value::from(value))
+}
+
+/// The capture groups of a regex segment, bound as `<segment>_<index>`,
+/// and by their own name for named groups, e.g. `ver_1` for `(ver: /v(\d+)/)`.
+/// Groups that did not participate in the match are `null`.
+fn regex_captures(
+    segment_name: &str,
+    regex: &regex::Regex,
+    captures: &regex::Captures,
+) -> WXRouteParams {
+    let mut params = WXRouteParams::new();
+    for (index, name) in regex.capture_names().enumerate().skip(1) {
+        let value = captures
+            .get(index)
+            .map_or(serde_json::Value::Null, |group| {
+                serde_json::Value::from(group.as_str())
+            });
+        if let Some(name) = name {
+            params.insert(name.to_string(), value.clone());
+        }
+        params.insert(format!("{}_{}", segment_name, index), value);
+    }
+    params
+}
+
+/// The compiled regex segments of route paths, by pattern, see `segment_regex`.
+/// Compiled once when the route map is built, instead of on every request.
+pub type WXSegmentRegexes = HashMap<String, regex::Regex>;
+
 impl WXUrlPath {
+    /// Compile the regex segments of the path.
+    /// Invalid patterns are left out, as they are rejected by `analyze_module_routes`.
+    pub fn compile_regexes(&self) -> WXSegmentRegexes {
+        self.regex_patterns()
+            .filter_map(|pattern| Some((pattern.to_string(), segment_regex(pattern).ok()?)))
+            .collect()
+    }
+
     fn get_url_segments(url: &hyper::Uri) -> Vec<&str> {
         url.path()
             .split('/')
@@ -202,63 +656,462 @@ impl WXUrlPath {
             .collect::<Vec<_>>()
     }
 
-    pub fn matches<'a, 'b: 'a>(&self, url: &hyper::Uri) -> WXPathResolution {
+    /// Match a request URL against the route path.
+    /// Literal segments are compared ignoring ASCII case unless `case_sensitive` is set,
+    /// parameters and regex segments are unaffected.
+    /// Regex segments are matched with the compiled `regexes`, and never match without one.
+    pub fn matches(
+        &self,
+        url: &hyper::Uri,
+        case_sensitive: bool,
+        regexes: &WXSegmentRegexes,
+    ) -> WXPathResolution {
         let url = WXUrlPath::get_url_segments(url);
         let url_count = url.len();
-        // dbg!(url.clone().collect::<Vec<_>>(), url_count, self.segments());
-        let mut bindings = WXRTContext::new();
-        let mut isolate = v8::Isolate::new(Default::default());
-        let mut scope = v8::HandleScope::new(&mut isolate);
+        let mut params = WXRouteParams::new();
 
-        let match_segment = |(pattern, part): (&WXUrlPathSegment, &&str)| -> bool {
+        let mut match_segment = |(pattern, part): (&WXUrlPathSegment, &&str)| -> bool {
             match pattern {
-                WXUrlPathSegment::Literal(literal) => literal.as_str() == *part,
-                WXUrlPathSegment::Parameter(WXTypedIdentifier { name, type_: _ }) => {
+                WXUrlPathSegment::Literal(literal) if case_sensitive => literal.as_str() == *part,
+                WXUrlPathSegment::Literal(literal) => literal.eq_ignore_ascii_case(part),
+                WXUrlPathSegment::Parameter(WXTypedIdentifier { name, type_ })
+                | WXUrlPathSegment::Optional(WXTypedIdentifier { name, type_ }) => {
                     // TODO: Check type.
-                    let js_value: Local<'_, Value> =
-                        v8::String::new(&mut scope, part).unwrap().into();
-                    let js_value: Global<v8::Value> = Global::new(&mut scope, js_value);
-                    bindings.bind(name, js_value);
+                    params.insert(name.clone(), param_value(type_, part));
                     true
                 }
                 WXUrlPathSegment::Regex(regex_name, regex) => {
-                    let re = regex::Regex::new(regex).unwrap();
-                    if re.is_match(part) {
-                        let js_value: Local<'_, Value> =
-                            v8::String::new(&mut scope, part).unwrap().into();
-                        let js_value: Global<v8::Value> = Global::new(&mut scope, js_value);
-                        bindings.bind(regex_name, js_value);
-                        true
-                    } else {
-                        false
-                    }
+                    // Wildcards match any segment.
+                    let captures = match regexes.get(regex) {
+                        Some(re) => match re.captures(part) {
+                            Some(captures) => regex_captures(regex_name, re, &captures),
+                            None => return false,
+                        },
+                        None if regex == WILDCARD => WXRouteParams::new(),
+                        None => return false,
+                    };
+                    params.insert(regex_name.clone(), serde_json::Value::from(*part));
+                    params.extend(captures);
+                    true
                 }
             }
         };
 
-        if self.segments() == url_count {
-            if self.0.iter().zip(&url).all(match_segment) {
-                return WXPathResolution::Perfect(bindings);
+        if let Some(name) = self.catch_all() {
+            // The wildcard is bound to the rest of the path, one segment or more.
+            let prefix = self.segments() - 1;
+            if url_count > prefix && self.0[..prefix].iter().zip(&url).all(&mut match_segment) {
+                params.insert(name.to_string(), url[prefix..].join("/").into());
+                return WXPathResolution::Partial(params);
+            }
+        } else if self.segments() == url_count {
+            if self.0.iter().zip(&url).all(&mut match_segment) {
+                return WXPathResolution::Perfect(params);
+            }
+        } else if self.segments() == url_count + 1 {
+            // Only an optional last parameter may be left out, it is bound as `null`.
+            if let Some(WXUrlPathSegment::Optional(param)) = self.0.last() {
+                if self.0.iter().zip(&url).all(&mut match_segment) {
+                    params.insert(param.name.clone(), serde_json::Value::Null);
+                    return WXPathResolution::Partial(params);
+                }
             }
-        } else if self.segments() > url_count
-            && self
-                .0
-                .iter()
-                .zip(url.iter().chain(std::iter::repeat(&"")))
-                .all(match_segment)
-            && url_count == self.segments() - 1
-        {
-            return WXPathResolution::Partial(bindings);
         }
 
         WXPathResolution::None
     }
 }
 
+/// Why `TRACE` and `CONNECT` are disabled in production unless `allowTraceConnect` is set.
+const DISABLED_METHOD_REASON: &str = "TRACE echoes requests including their cookies and \
+    credentials, enabling cross-site tracing (XST), and CONNECT can tunnel to other hosts. \
+    Set \"allowTraceConnect\" in the project configuration to allow them";
+
+/// The path browsers request the icon of a site from, relative to the base path.
+const FAVICON_PATH: &str = "/favicon.ico";
+
+/// How long clients may cache the configured favicon.
+const FAVICON_MAX_AGE: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// The literal path segments of a configured base path, e.g. `/app/v1`.
+/// Empty and trailing slashes are ignored, so `/` is the root path.
+pub fn parse_base_path(base_path: &str) -> WXUrlPath {
+    WXUrlPath(
+        base_path
+            .split('/')
+            .filter(|segment| !segment.is_empty())
+            .map(|segment| WXUrlPathSegment::Literal(segment.to_string()))
+            .collect(),
+    )
+}
+
+/// Strip the body of a `HEAD` response while keeping all headers,
+/// including the `Content-Length` of the original body.
+pub(crate) fn strip_head_body<T: Default>(
+    is_head: bool,
+    response: hyper::Response<T>,
+) -> hyper::Response<T> {
+    if is_head {
+        response.map(|_| T::default())
+    } else {
+        response
+    }
+}
+
+/// The outcome of applying the trailing slash policy to a request path.
+#[derive(Debug, PartialEq, Eq)]
+pub enum WXSlashResolution {
+    /// Resolve the request path as is.
+    Resolve,
+    /// Redirect to the canonical location, if it resolves.
+    Redirect(String),
+}
+
+/// Apply the trailing slash policy to a request path.
+/// The canonical form of a path has no trailing slash, except for the root `/`.
+/// Under the `strict` policy, paths are resolved as is and only match routes declared
+/// with the same trailing slash, see `WXRouteMap::with_trailing_slash`.
+pub fn resolve_trailing_slash(policy: TrailingSlashPolicy, url: &hyper::Uri) -> WXSlashResolution {
+    let path = url.path();
+    if path == "/" || !path.ends_with('/') {
+        return WXSlashResolution::Resolve;
+    }
+    match policy {
+        TrailingSlashPolicy::Ignore | TrailingSlashPolicy::Strict => WXSlashResolution::Resolve,
+        TrailingSlashPolicy::Redirect => {
+            let canonical = path.trim_end_matches('/');
+            let canonical = if canonical.is_empty() { "/" } else { canonical };
+            match url.query() {
+                Some(query) => WXSlashResolution::Redirect(format!("{}?{}", canonical, query)),
+                None => WXSlashResolution::Redirect(canonical.to_string()),
+            }
+        }
+    }
+}
+
 /// Possible values produced by a handler or route body.
 pub enum WXRouteResult {
     Html(String),
     Js(Global<Value>),
+    /// A value returned by a `json` body, always serialized as JSON.
+    Json(Global<Value>),
+    /// A value returned by a `text` body, always sent as plain text.
+    Text(Global<Value>),
+    /// A value produced by the `negotiate` handler, sent as JSON,
+    /// or rendered with the HTML template if the client prefers HTML.
+    Data {
+        value: Global<Value>,
+        template: Option<String>,
+    },
+    /// A file streamed from disk, produced by the `static` handler.
+    File {
+        file: std::fs::File,
+        len: u64,
+        content_type: &'static str,
+        modified: Option<std::time::SystemTime>,
+    },
+    /// Server-sent event frames, produced by a route returning a generator.
+    EventStream(tokio::sync::mpsc::Receiver<Bytes>),
+    /// A user-facing error raised by `error()`, which stops the handler chain.
+    Error {
+        status: u16,
+        message: String,
+    },
+}
+
+impl WXRouteResult {
+    /// Wrap the value returned by a code body of the given type.
+    fn from_body(body_type: &WXBodyType, value: Global<Value>) -> Self {
+        match body_type {
+            WXBodyType::Json => WXRouteResult::Json(value),
+            WXBodyType::Text => WXRouteResult::Text(value),
+            _ => WXRouteResult::Js(value),
+        }
+    }
+
+    /// The JS value of the result, if it has one that can be bound to a name.
+    fn value(&self) -> Option<&Global<Value>> {
+        match self {
+            WXRouteResult::Js(v)
+            | WXRouteResult::Json(v)
+            | WXRouteResult::Text(v)
+            | WXRouteResult::Data { value: v, .. } => Some(v),
+            _ => None,
+        }
+    }
+}
+
+/// The number of event frames buffered per stream before the generator is paused.
+const EVENT_STREAM_BUFFER: usize = 16;
+/// How often open event streams are polled for new events.
+const EVENT_STREAM_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Call a method of a JS object without arguments.
+fn call_method(
+    rt: &mut JsRuntime,
+    object: &Global<Value>,
+    name: &str,
+) -> Result<Global<Value>, WXRuntimeError> {
+    let scope = &mut rt.handle_scope();
+    let scope = &mut v8::TryCatch::new(scope);
+    let receiver = Local::new(scope, object);
+    let Some(key) = v8::String::new(scope, name) else {
+        return Err(WXRuntimeError {
+            code: 500,
+            http_status: 500,
+            message: format!("Failed to allocate the method name '{}'", name),
+        });
+    };
+    let key: Local<'_, Value> = key.into();
+    let method = receiver
+        .to_object(scope)
+        .and_then(|object| object.get(scope, key))
+        .and_then(|method| Local::<'_, v8::Function>::try_from(method).ok());
+    let Some(method) = method else {
+        return Err(WXRuntimeError {
+            code: 500,
+            http_status: 500,
+            message: format!("'{}' is not a function", name),
+        });
+    };
+    match method.call(scope, receiver, &[]) {
+        Some(value) => Ok(Global::new(scope, value)),
+        None => {
+            let message = scope
+                .exception()
+                .map(|err| err.to_rust_string_lossy(scope))
+                .unwrap_or_default();
+            Err(WXRuntimeError {
+                code: 500,
+                http_status: 500,
+                message: format!("'{}' threw an error:\n{}", name, message),
+            })
+        }
+    }
+}
+
+/// Format a value yielded by an event stream generator.
+/// Objects with a `data` field may also set the `event` and `id` fields,
+/// strings are sent as is, and all other values as JSON.
+fn to_event_frame(value: &serde_json::Value) -> Bytes {
+    let field = |value: &serde_json::Value| match value {
+        serde_json::Value::String(s) => s.clone(),
+        value => value.to_string(),
+    };
+    match value {
+        serde_json::Value::Object(event) if event.contains_key("data") => {
+            let name = event.get("event").map(field);
+            let id = event.get("id").map(field);
+            responses::event_frame(name.as_deref(), id.as_deref(), &field(&event["data"]))
+        }
+        value => responses::event_frame(None, None, &field(value)),
+    }
+}
+
+/// An open server-sent event stream.
+/// The runtime pulls events from the generator returned by a route
+/// and sends them to the client connection through a channel.
+///
+/// ## Note
+/// Pulling an event blocks the runtime until the generator yields,
+/// so generators should not wait long between events.
+struct WXRTEventStream {
+    module_path: WXModulePath,
+    generator: Global<Value>,
+    events: tokio::sync::mpsc::Sender<Bytes>,
+}
+
+impl WXRTEventStream {
+    /// Open an event stream if the route result is a (async) generator.
+    fn open(
+        module_path: &WXModulePath,
+        result: &WXRouteResult,
+        rt: &mut JsRuntime,
+    ) -> Option<(Self, tokio::sync::mpsc::Receiver<Bytes>)> {
+        let WXRouteResult::Js(value) = result else {
+            return None;
+        };
+        if !Local::new(&mut rt.handle_scope(), value).is_generator_object() {
+            return None;
+        }
+        let (events, rx) = tokio::sync::mpsc::channel(EVENT_STREAM_BUFFER);
+        let stream = WXRTEventStream {
+            module_path: module_path.clone(),
+            generator: value.clone(),
+            events,
+        };
+        Some((stream, rx))
+    }
+
+    /// Pull the next event from the generator, if the client is ready for it.
+    /// Returns `false` once the stream is closed.
+    fn poll(&self, rt: &mut JsRuntime, mode: WXMode) -> bool {
+        if self.events.is_closed() {
+            // The client disconnected, let the generator run its cleanup.
+            let closed = call_method(rt, &self.generator, "return")
+                .and_then(|value| resolve_promise(rt, value));
+            if let Err(err) = closed {
+                warning(mode, format!("Event stream failed to close: {}", err));
+            }
+            return false;
+        }
+        if self.events.capacity() == 0 {
+            return true; // Wait for the client to catch up.
+        }
+        let next = call_method(rt, &self.generator, "next")
+            .and_then(|value| resolve_promise(rt, value))
+            .and_then(|value| {
+                let scope = &mut rt.handle_scope();
+                let local = Local::new(scope, &value);
+                let json = v8::json::stringify(scope, local)
+                    .map(|json| json.to_rust_string_lossy(scope))
+                    .unwrap_or_default();
+                serde_json::from_str::<serde_json::Value>(&json).map_err(|err| WXRuntimeError {
+                    code: 500,
+                    http_status: 500,
+                    message: format!("Invalid event: {}", err),
+                })
+            });
+        match next {
+            Ok(next) if next["done"].as_bool().unwrap_or(true) => false,
+            Ok(next) => self.events.try_send(to_event_frame(&next["value"])).is_ok(),
+            Err(err) => {
+                warning(mode, format!("Event stream threw an error:\n{}", err));
+                false
+            }
+        }
+    }
+}
+
+/// A frame sent by the runtime to an open WebSocket connection.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WXSocketFrame {
+    Text(String),
+    Close,
+}
+
+/// The outcome of a WebSocket handshake request.
+pub enum WXSocketOpen {
+    /// The connection is accepted, frames to send are received through `frames`.
+    Accepted {
+        id: u64,
+        frames: tokio::sync::mpsc::UnboundedReceiver<WXSocketFrame>,
+    },
+    /// The handshake is rejected with the given response.
+    Rejected(hyper::Response<WXResponseBody>),
+}
+
+/// An open WebSocket connection of a `ws` route.
+/// The JS side of the socket lives in the runtime of the route module.
+struct WXRTSocket {
+    module_path: WXModulePath,
+    frames: tokio::sync::mpsc::UnboundedSender<WXSocketFrame>,
+}
+
+/// The messages queued by a JS socket since the last drain.
+#[derive(Deserialize)]
+struct WXRTSocketOutbox {
+    messages: Vec<String>,
+    closed: bool,
+}
+
+/// Execute a script of the stdlib socket API and resolve its result.
+fn execute_socket_script(
+    rt: &mut JsRuntime,
+    script: String,
+) -> Result<Global<Value>, WXRuntimeError> {
+    let value = rt
+        .execute_script("[webx socket]", script.into())
+        .map_err(|err| WXRuntimeError {
+            code: 500,
+            http_status: 500,
+            message: err.to_string(),
+        })?;
+    resolve_promise(rt, value)
+}
+
+/// Forget the headers set by handlers of earlier requests.
+fn open_response_headers(rt: &mut JsRuntime) -> Result<(), WXRuntimeError> {
+    rt.execute_script("[webx response]", "__webx_response.open()".into())
+        .map_err(|err| WXRuntimeError {
+            code: 500,
+            http_status: 500,
+            message: format!("Response script threw an error:\n{}", err),
+        })?;
+    Ok(())
+}
+
+/// The response to a request whose route failed with an error, by the HTTP status of the error.
+/// The project error pages are used for `404` and `500` errors.
+fn error_response(
+    mode: WXMode,
+    info: &WXRuntimeInfo,
+    req: &hyper::Request<Bytes>,
+    err: WXRuntimeError,
+    location: &WXErrorLocation,
+) -> hyper::Response<WXResponseBody> {
+    let status = hyper::StatusCode::from_u16(err.http_status)
+        .unwrap_or(hyper::StatusCode::INTERNAL_SERVER_ERROR);
+    match status {
+        hyper::StatusCode::INTERNAL_SERVER_ERROR => {
+            responses::route_error(mode, &info.error_pages, err.message, location)
+                .map(WXResponseBody::from)
+        }
+        hyper::StatusCode::NOT_FOUND => {
+            responses::not_found(mode, &info.error_pages, req.method(), req.uri().to_string())
+                .map(WXResponseBody::from)
+        }
+        hyper::StatusCode::FORBIDDEN => responses::forbidden(mode).map(WXResponseBody::from),
+        status => responses::error_status(mode, status, err.message).map(WXResponseBody::from),
+    }
+}
+
+/// An error raised by `error()` in a handler or route body.
+#[derive(Debug, Deserialize)]
+struct WXUserError {
+    status: u16,
+    message: String,
+}
+
+/// The last `error()` raised by the handlers of the request in progress.
+fn user_error(rt: &mut JsRuntime) -> Option<WXUserError> {
+    let error = rt
+        .execute_script("[webx response]", "__webx_response.error()".into())
+        .ok()?;
+    let scope = &mut rt.handle_scope();
+    let error = Local::new(scope, error);
+    serde_v8::from_v8::<Option<WXUserError>>(scope, error).ok()?
+}
+
+/// Take the headers set by the handlers of the request in progress with `setHeader()`.
+fn close_response_headers(rt: &mut JsRuntime) -> Result<hyper::HeaderMap, WXRuntimeError> {
+    let headers = rt
+        .execute_script("[webx response]", "__webx_response.close()".into())
+        .map_err(|err| WXRuntimeError {
+            code: 500,
+            http_status: 500,
+            message: format!("Response script threw an error:\n{}", err),
+        })?;
+    let headers = {
+        let scope = &mut rt.handle_scope();
+        Local::new(scope, headers).to_rust_string_lossy(scope)
+    };
+    let headers = serde_json::from_str::<Vec<(String, String)>>(&headers).unwrap_or_default();
+    headers
+        .into_iter()
+        .map(|(name, value)| {
+            let invalid = || WXRuntimeError {
+                code: 500,
+                http_status: 500,
+                message: format!("setHeader: invalid header '{}: {}'", name, value),
+            };
+            let header_name =
+                hyper::header::HeaderName::from_bytes(name.as_bytes()).map_err(|_| invalid())?;
+            let header_value =
+                hyper::header::HeaderValue::from_bytes(value.as_bytes()).map_err(|_| invalid())?;
+            Ok((header_name, header_value))
+        })
+        .collect()
 }
 
 /// A runtime flat-route.
@@ -269,29 +1122,85 @@ pub struct WXRTRoute {
     // TODO: - global typescript code
     // TODO: - models ORM and types
     module_path: WXModulePath,
+    /// The line of the route in its module, shown in dev error reports.
+    line: usize,
+    /// Whether the route is declared with a trailing slash, see `WXRouteMap::with_trailing_slash`.
+    trailing_slash: bool,
+    /// How long clients may cache successful responses.
+    cache: Option<Duration>,
+    /// The request body is validated against it before the handlers run.
+    body_format: Option<WXRTBodyFormat>,
     body: Option<WXBody>,
     pre_handlers: Vec<WXRouteHandlerCall>,
     post_handlers: Vec<WXRouteHandlerCall>,
 }
 
 impl WXRTRoute {
+    /// The module defining the route.
+    pub fn module_path(&self) -> &WXModulePath {
+        &self.module_path
+    }
+
+    /// The line of the route in its module.
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    /// Where the route is declared, e.g. `webx/index.webx:23`.
+    fn location(&self) -> String {
+        format!("{}:{}", self.module_path.display_path(), self.line)
+    }
+
+    /// Attach the name of a failed handler and the location of the route to its error.
+    fn handler_error(&self, handler: &WXRouteHandlerCall, err: WXRuntimeError) -> WXRuntimeError {
+        WXRuntimeError {
+            message: format!(
+                "Handler `{}` in {} threw an error:\n{}",
+                handler.name,
+                self.location(),
+                err.message
+            ),
+            ..err
+        }
+    }
+
     fn execute_body(
         &self,
-        _ctx: &mut WXRTContext,
-        _rt: &mut JsRuntime,
+        ctx: &mut WXRTContext,
+        rt: &mut JsRuntime,
         _info: &WXRuntimeInfo,
     ) -> Result<WXRouteResult, WXRuntimeError> {
         let Some(body) = &self.body else {
             return Err(WXRuntimeError {
                 code: 500,
+                http_status: 500,
                 message: "Route body is empty".into(),
             });
         };
         match body.body_type {
-            WXBodyType::Ts => todo!("TS body type is not supported yet"),
+            // Wrapped in an async function to allow top-level `await`.
+            WXBodyType::Ts | WXBodyType::Json | WXBodyType::Text => {
+                let js_body = transpile_ts(&format!(
+                    "(async ({}) => {{\n{}\n}})",
+                    context_params(ctx),
+                    body.body
+                ))?;
+                let value = call_with_context(rt, "[webx route body]", js_body, ctx)
+                    .and_then(|value| resolve_promise(rt, value))
+                    .map_err(|err| WXRuntimeError {
+                        message: format!(
+                            "Route body in {} threw an error:\n{}",
+                            self.location(),
+                            err.message
+                        ),
+                        ..err
+                    })?;
+                Ok(WXRouteResult::from_body(&body.body_type, value))
+            }
             // TODO: - Resolve bindings, render and execute JSX (dynamic)
             // TODO: - Use JSX runtime to render JSX
             WXBodyType::Tsx => Ok(WXRouteResult::Html(body.body.clone())),
+            WXBodyType::Raw => Ok(WXRouteResult::Html(body.body.clone())),
         }
     }
 
@@ -302,43 +1211,93 @@ impl WXRTRoute {
         rt: &mut JsRuntime,
         info: &WXRuntimeInfo,
     ) -> Option<Result<WXRouteResult, WXRuntimeError>> {
-        let mut handlers = handlers.iter();
-        for _ in 0..self.pre_handlers.len() - 1 {
-            let handler = handlers.next().unwrap();
+        let (last, handlers) = handlers.split_last()?;
+        for handler in handlers {
             let result = match handler.execute(ctx, rt, info) {
                 Ok(result) => result,
-                Err(err) => return Some(Err(err)),
+                Err(err) => return Some(Err(self.handler_error(handler, err))),
             };
             if let Some(output) = &handler.output {
                 ctx.bind(output, result);
             }
         }
-        handlers
-            .last()
-            .map(|last| last.execute(ctx, rt, info).map(WXRouteResult::Js))
+        let result = last
+            .execute_result(ctx, rt, info)
+            .map_err(|err| self.handler_error(last, err));
+        // The output of the last pre-handler is read by the body and the post-handlers.
+        if let (Ok(result), Some(output)) = (&result, &last.output) {
+            if let Some(value) = result.value() {
+                ctx.bind(output, value.clone());
+            }
+        }
+        Some(result)
     }
 
-    fn bind_out(ctx: &mut WXRTContext, value: WXRouteResult, scope: &mut v8::HandleScope) {
+    fn bind_out(
+        ctx: &mut WXRTContext,
+        value: WXRouteResult,
+        scope: &mut v8::HandleScope,
+    ) -> Result<(), WXRuntimeError> {
         match value {
             WXRouteResult::Html(s) => {
-                let handle: Local<'_, v8::Value> =
-                    v8::String::new(scope, s.as_str()).unwrap().into();
+                let Some(handle) = v8::String::new(scope, s.as_str()) else {
+                    return Err(WXRuntimeError {
+                        code: 500,
+                        http_status: 500,
+                        message: "Failed to allocate route body output".into(),
+                    });
+                };
+                let handle: Local<'_, v8::Value> = handle.into();
                 ctx.bind("out", v8::Global::new(scope, handle))
             }
-            WXRouteResult::Js(v) => ctx.bind("out", v),
+            WXRouteResult::Js(v)
+            | WXRouteResult::Json(v)
+            | WXRouteResult::Text(v)
+            | WXRouteResult::Data { value: v, .. } => ctx.bind("out", v),
+            WXRouteResult::File { .. } => {
+                return Err(WXRuntimeError {
+                    code: 500,
+                    http_status: 500,
+                    message: "Streamed files can not be passed to post-handlers".into(),
+                })
+            }
+            WXRouteResult::EventStream(_) => {
+                return Err(WXRuntimeError {
+                    code: 500,
+                    http_status: 500,
+                    message: "Event streams can not be passed to post-handlers".into(),
+                })
+            }
+            WXRouteResult::Error { .. } => {
+                return Err(WXRuntimeError {
+                    code: 500,
+                    http_status: 500,
+                    message: "Errors can not be passed to post-handlers".into(),
+                })
+            }
         }
+        Ok(())
     }
 
+    /// Turn a route result into a response.
+    /// The headers set by handlers are added, replacing default headers of the same name.
+    /// Given the headers of a cacheable request, conditional requests are answered.
+    /// Given the headers of the request, data results are sent in the representation
+    /// the client prefers by its `Accept` header.
     fn to_response(
         value: WXRouteResult,
         scope: &mut v8::HandleScope,
         mode: WXMode,
-    ) -> hyper::Response<hyper::body::Bytes> {
-        match value {
+        headers: hyper::HeaderMap,
+        cache: Option<Duration>,
+        request_headers: Option<&hyper::HeaderMap>,
+        conditional: Option<&hyper::HeaderMap>,
+    ) -> hyper::Response<WXResponseBody> {
+        let mut response = match value {
             WXRouteResult::Html(body) => {
                 let body = hyper::body::Bytes::from(body);
                 let len = body.len();
-                ok_html(body, len, mode)
+                ok_html(body, len, mode).map(WXResponseBody::from)
             }
             WXRouteResult::Js(value) => {
                 if let Ok(str_val) =
@@ -346,15 +1305,86 @@ impl WXRTRoute {
                 {
                     let str = hyper::body::Bytes::from(str_val.to_rust_string_lossy(scope));
                     let len = str.len();
-                    ok_html(str, len, mode)
+                    ok_html(str, len, mode).map(WXResponseBody::from)
                 } else {
-                    ok_json(&value, scope, mode)
+                    ok_json(&value, scope, mode).map(WXResponseBody::from)
                 }
             }
+            WXRouteResult::Json(value) => ok_json(&value, scope, mode).map(WXResponseBody::from),
+            WXRouteResult::Text(value) => {
+                let local = Local::new(scope, value);
+                let text = match local.to_string(scope) {
+                    Some(text) => text.to_rust_string_lossy(scope),
+                    None => String::new(),
+                };
+                ok_text(hyper::body::Bytes::from(text), mode).map(WXResponseBody::from)
+            }
+            WXRouteResult::Data { value, template } => {
+                let prefers_html = template.is_some()
+                    && request_headers
+                        .and_then(|headers| {
+                            requests::preferred_type(headers, &["application/json", "text/html"])
+                        })
+                        .is_some_and(|preferred| preferred == "text/html");
+                let mut response = match template {
+                    Some(template) if prefers_html => {
+                        let local = Local::new(scope, &value);
+                        let data = serde_v8::from_v8::<serde_json::Value>(scope, local)
+                            .unwrap_or(serde_json::Value::Null);
+                        let html = Bytes::from(template::render_template(&template, &data));
+                        let len = html.len();
+                        ok_html(html, len, mode).map(WXResponseBody::from)
+                    }
+                    _ => ok_json(&value, scope, mode).map(WXResponseBody::from),
+                };
+                response
+                    .headers_mut()
+                    .insert("Vary", hyper::header::HeaderValue::from_static("Accept"));
+                response
+            }
+            WXRouteResult::File {
+                file,
+                len,
+                content_type,
+                modified,
+            } => responses::file(mode, file, len, content_type, modified),
+            WXRouteResult::EventStream(events) => {
+                let mut response = responses::event_stream(mode, events);
+                response.headers_mut().extend(headers);
+                return response;
+            }
+            WXRouteResult::Error { status, message } => {
+                let status =
+                    hyper::StatusCode::from_u16(status).unwrap_or(hyper::StatusCode::BAD_REQUEST);
+                let prefers_json = request_headers
+                    .and_then(|headers| {
+                        requests::preferred_type(headers, &["text/html", "application/json"])
+                    })
+                    .is_some_and(|preferred| preferred == "application/json");
+                let mut response = responses::user_error(mode, status, &message, prefers_json)
+                    .map(WXResponseBody::from);
+                response
+                    .headers_mut()
+                    .insert("Vary", hyper::header::HeaderValue::from_static("Accept"));
+                response.headers_mut().extend(headers);
+                return response;
+            }
+        };
+        response
+            .headers_mut()
+            .insert("Cache-Control", responses::cache_control(cache));
+        response.headers_mut().extend(headers);
+        match conditional {
+            Some(request_headers) => responses::byte_range(
+                request_headers,
+                responses::conditional(request_headers, response),
+            ),
+            None => response,
         }
     }
 
-    /// Execute the route and return a HTTP response.
+    /// Execute the route and return its result.
+    /// A handler or body raising `error()` stops the chain with an error result.
     ///
     /// ## Note
     /// This function will **not** check if the route is valid.
@@ -364,172 +1394,477 @@ impl WXRTRoute {
         ctx: &mut WXRTContext,
         rt: &mut JsRuntime,
         info: &WXRuntimeInfo,
-        mode: WXMode,
-    ) -> Result<hyper::Response<hyper::body::Bytes>, WXRuntimeError> {
-        // TODO: Refactor this function to combine all logic into a better structure.
-        let has_pre_handlers: bool = !self.pre_handlers.is_empty();
-        let has_body: bool = self.body.is_some();
-        let has_post_handlers: bool = !self.post_handlers.is_empty();
+    ) -> Result<WXRouteResult, WXRuntimeError> {
+        self.execute_chain(ctx, rt, info)
+            .or_else(|err| match user_error(rt) {
+                Some(WXUserError { status, message }) => {
+                    Ok(WXRouteResult::Error { status, message })
+                }
+                None => Err(err),
+            })
+    }
+
+    /// Execute the pre-handlers, body, and post-handlers of the route in order,
+    /// stopping at the first one that fails.
+    fn execute_chain(
+        &self,
+        ctx: &mut WXRTContext,
+        rt: &mut JsRuntime,
+        info: &WXRuntimeInfo,
+    ) -> Result<WXRouteResult, WXRuntimeError> {
+        // TODO: Refactor this function to combine all logic into a better structure.
+        let has_pre_handlers: bool = !self.pre_handlers.is_empty();
+        let has_body: bool = self.body.is_some();
+        let has_post_handlers: bool = !self.post_handlers.is_empty();
         return match (has_pre_handlers, has_body, has_post_handlers) {
 			// All three are present, execute pre-handlers, body, and post-handlers.
             (true, true, true) => {
-                self.execute_handlers(&self.pre_handlers, ctx, rt, info);
+                self.execute_handlers(&self.pre_handlers, ctx, rt, info).unwrap()?;
                 let value = self.execute_body(ctx, rt, info)?;
-				Self::bind_out(ctx, value, &mut rt.handle_scope());
-                Ok(Self::to_response(
-                    self.execute_handlers(&self.post_handlers, ctx, rt, info)
-                        .unwrap()?,
-                    &mut rt.handle_scope(),
-                    mode,
-                ))
+				Self::bind_out(ctx, value, &mut rt.handle_scope())?;
+                self.execute_handlers(&self.post_handlers, ctx, rt, info)
+                    .unwrap()
             }
 			// Execute pre-handlers and body.
 			(true, true, false) => {
-                self.execute_handlers(&self.pre_handlers, ctx, rt, info);
-                Ok(Self::to_response(
-                    self.execute_body(ctx, rt, info)?,
-                    &mut rt.handle_scope(),
-                    mode,
-                ))
+                self.execute_handlers(&self.pre_handlers, ctx, rt, info).unwrap()?;
+                self.execute_body(ctx, rt, info)
 			}
 			// Execute pre and post-handlers.
 			(true, false, true) => {
-                self.execute_handlers(&self.pre_handlers, ctx, rt, info);
-                Ok(Self::to_response(
-                    self.execute_handlers(&self.post_handlers, ctx, rt, info).unwrap()?,
-                    &mut rt.handle_scope(),
-                    mode,
-                ))
+                self.execute_handlers(&self.pre_handlers, ctx, rt, info).unwrap()?;
+                self.execute_handlers(&self.post_handlers, ctx, rt, info).unwrap()
 			}
 			// Execute only pre-handlers.
-			(true, false, false) => Ok(Self::to_response(
-				self.execute_handlers(&self.pre_handlers, ctx, rt, info).unwrap()?,
-				&mut rt.handle_scope(),
-				mode,
-			)),
+			(true, false, false) => self.execute_handlers(&self.pre_handlers, ctx, rt, info).unwrap(),
 			// Execute body and post-handlers.
 			(false, true, true) => {
                 let value = self.execute_body(ctx, rt, info)?;
-				Self::bind_out(ctx, value, &mut rt.handle_scope());
-                Ok(Self::to_response(
-                    self.execute_handlers(&self.post_handlers, ctx, rt, info)
-                        .unwrap()?,
-                    &mut rt.handle_scope(),
-                    mode,
-                ))
+				Self::bind_out(ctx, value, &mut rt.handle_scope())?;
+                self.execute_handlers(&self.post_handlers, ctx, rt, info)
+                    .unwrap()
             }
 			// Execute only body
-            (false, true, false) => Ok(Self::to_response(
-                self.execute_body(ctx, rt, info)?,
-                &mut rt.handle_scope(),
-                mode,
-            )),
+            (false, true, false) => self.execute_body(ctx, rt, info),
 			// Execute only post-handlers
-            (false, false, true) => Ok(Self::to_response(
-				self.execute_handlers(&self.post_handlers, ctx, rt, info).unwrap()?,
-				&mut rt.handle_scope(),
-				mode,
-			)),
+            (false, false, true) => self.execute_handlers(&self.post_handlers, ctx, rt, info).unwrap(),
             (false, false, false) => Err(WXRuntimeError {
                 code: 500,
+                http_status: 500,
                 message: format!("Route execution not implemented for: pre_handlers={}, body={}, post_handlers={}", has_pre_handlers, has_body, has_post_handlers),
             }),
         };
     }
 }
 
-type WXMethodMapInner = HashMap<WXUrlPath, WXRTRoute>;
+/// The routes of a single method, in resolution order.
+type WXMethodMapInner = Vec<(WXUrlPath, WXRTRoute)>;
 type WXRouteMapInner = HashMap<hyper::Method, WXMethodMapInner>;
 
 /// This is a map of all routes in the project.
-/// The key is the route path, and the value is the route.
+/// Routes are grouped by method, and each group is kept sorted by
+/// precedence (see `WXUrlPath::precedence`) once the map is built, so that the most
+/// specific routes are matched first without sorting on every request.
 /// This map requires that **all routes are unique**.
 /// This is enforced by the `analyze_module_routes` function.
 #[derive(Debug, Clone)]
-pub struct WXRouteMap(WXRouteMapInner);
+pub struct WXRouteMap {
+    routes: WXRouteMapInner,
+    case_sensitive: bool,
+    /// Only match paths with a trailing slash to routes declared with one, and vice versa.
+    strict_trailing_slash: bool,
+    regexes: WXSegmentRegexes,
+}
 
 impl WXRouteMap {
-    fn new() -> Self {
-        WXRouteMap(HashMap::new())
+    pub(crate) fn new() -> Self {
+        WXRouteMap {
+            routes: HashMap::new(),
+            case_sensitive: true,
+            strict_trailing_slash: false,
+            regexes: HashMap::new(),
+        }
     }
 
     /// Create a new route map from a list of modules.
-    fn from_modules(modules: &[WXModule]) -> Result<Self, WXRuntimeError> {
-        let routes: FlatRoutes = verify_model_routes(modules)?;
+    pub fn from_modules(
+        modules: &[WXModule],
+        policy: &BodyFormatPolicy,
+        case_sensitive: bool,
+    ) -> Result<Self, WXRuntimeError> {
+        let routes: FlatRoutes = verify_model_routes(modules, policy)?;
+        let mut models = Vec::new();
+        for module in modules.iter() {
+            collect_models(&module.scope, &mut models);
+        }
         let mut route_map: WXRouteMapInner = HashMap::new();
-        // Insert all routes into each method map category.
-        for ((route, path), _) in routes {
-            route_map.entry(route.method.clone()).or_default().insert(
-                path.clone(),
+        let mut regexes = WXSegmentRegexes::new();
+        // Insert all routes into each method map category, by precedence, most specific first.
+        let mut routes = routes.into_keys().collect::<Vec<_>>();
+        routes.sort_by_cached_key(|(route, path)| route.precedence(path));
+        for (route, path) in routes {
+            regexes.extend(path.compile_regexes());
+            route_map.entry(route.method.clone()).or_default().push((
+                path,
                 WXRTRoute {
                     module_path: route.info.path,
+                    line: route.info.line,
+                    trailing_slash: route.trailing_slash,
+                    cache: route.cache,
+                    body_format: route
+                        .body_format
+                        .as_ref()
+                        .map(|format| WXRTBodyFormat::new(format, &models)),
                     body: route.body,
                     pre_handlers: route.pre_handlers,
                     post_handlers: route.post_handlers,
                 },
-            );
+            ));
         }
-        Ok(WXRouteMap(route_map))
+        Ok(WXRouteMap {
+            routes: route_map,
+            case_sensitive,
+            strict_trailing_slash: false,
+            regexes,
+        })
+    }
+
+    /// Serve all routes under a base path, e.g. `/todos` becomes `/app/todos`.
+    /// Requests outside of the base path no longer match any route.
+    /// Prefixing every path keeps the precedence order of the routes.
+    pub fn with_base_path(mut self, base_path: &WXUrlPath) -> Self {
+        for routes in self.routes.values_mut() {
+            for (path, _) in routes.iter_mut() {
+                *path = base_path.combine(path);
+            }
+        }
+        self
+    }
+
+    /// Apply the trailing slash policy to the routes.
+    /// Under the `strict` policy, a path with a trailing slash such as `/about/`
+    /// only matches routes declared with one, such as `get /about/`, and vice versa.
+    /// Otherwise routes match paths with and without a trailing slash alike.
+    pub fn with_trailing_slash(mut self, policy: TrailingSlashPolicy) -> Self {
+        self.strict_trailing_slash = policy == TrailingSlashPolicy::Strict;
+        self
     }
 
     /// Get a route from the route map.
-    /// This function will return `None` if the route does not exist.
+    /// `HEAD` requests without an explicit `head` route fall back to the matching `GET` route.
+    /// If there is no route for the method, the routes of all other methods are checked
+    /// to tell a path without any route from a path only routed for other methods.
     ///
     /// ## Note
     /// This function will **not** check for duplicate routes.
     /// This is done in the `analyze_module_routes` function.
-    fn resolve(
+    pub fn resolve(&self, method: &hyper::Method, path: &hyper::Uri) -> WXRouteResolution<'_> {
+        let resolved = match self.resolve_method(method, path) {
+            None if method == hyper::Method::HEAD => self.resolve_method(&hyper::Method::GET, path),
+            resolved => resolved,
+        };
+        if let Some((route_path, params, route)) = resolved {
+            return WXRouteResolution::Found(route_path, params, route);
+        }
+        let allow = self.allowed_methods(path);
+        if allow.is_empty() {
+            WXRouteResolution::NotFound
+        } else {
+            WXRouteResolution::MethodNotAllowed(allow)
+        }
+    }
+
+    /// The route paths of a method, in the order they are resolved.
+    pub fn paths(&self, method: &hyper::Method) -> Vec<&WXUrlPath> {
+        self.routes
+            .get(method)
+            .map(|routes| routes.iter().map(|(path, _)| path).collect())
+            .unwrap_or_default()
+    }
+
+    /// All routes of the map, grouped by method in alphabetical order,
+    /// and the routes of each method in the order they are resolved.
+    pub fn iter_routes(&self) -> impl Iterator<Item = (&hyper::Method, &WXUrlPath, &WXRTRoute)> {
+        let mut methods = self.routes.iter().collect::<Vec<_>>();
+        methods.sort_by(|(a, _), (b, _)| a.as_str().cmp(b.as_str()));
+        methods.into_iter().flat_map(|(method, routes)| {
+            routes
+                .iter()
+                .map(move |(path, route)| (method, path, route))
+        })
+    }
+
+    /// The number of routes of all methods.
+    pub fn count(&self) -> usize {
+        self.routes.values().map(Vec::len).sum()
+    }
+
+    /// Get all methods with a route matching the given path.
+    /// `HEAD` is implied by `GET`, and `OPTIONS` is always allowed.
+    fn allowed_methods(&self, path: &hyper::Uri) -> Vec<hyper::Method> {
+        let mut methods = self
+            .routes
+            .keys()
+            .filter(|method| method.as_str() != WEBSOCKET_METHOD)
+            .filter(|method| self.resolve_method(method, path).is_some())
+            .cloned()
+            .collect::<Vec<_>>();
+        if methods.is_empty() {
+            return methods;
+        }
+        if methods.contains(&hyper::Method::GET) && !methods.contains(&hyper::Method::HEAD) {
+            methods.push(hyper::Method::HEAD);
+        }
+        if !methods.contains(&hyper::Method::OPTIONS) {
+            methods.push(hyper::Method::OPTIONS);
+        }
+        methods.sort_by(|a, b| a.as_str().cmp(b.as_str()));
+        methods
+    }
+
+    fn resolve_method(
         &self,
         method: &hyper::Method,
         path: &hyper::Uri,
-    ) -> Option<(&WXUrlPath, WXRTContext, &WXRTRoute)> {
-        let routes = self.0.get(method)?;
-        // Sort all routes by path length in descending order.
-        // This is required to ensure that the most specific routes are matched first.
-        let mut routes: Vec<(&WXUrlPath, &WXRTRoute)> = routes.iter().collect();
-        routes.sort_by(|(a, _), (b, _)| b.segments().cmp(&a.segments()));
-        // Go through all routes and try to match the path.
+    ) -> Option<(&WXUrlPath, WXRouteParams, &WXRTRoute)> {
+        let routes = self.routes.get(method)?;
+        let trailing_slash = path.path() != "/" && path.path().ends_with('/');
+        // Go through all routes, most specific first, and try to match the path.
         let mut best_match = None;
         for (route_path, route) in routes {
-            match route_path.matches(path) {
+            if self.strict_trailing_slash && route.trailing_slash != trailing_slash {
+                continue;
+            }
+            match route_path.matches(path, self.case_sensitive, &self.regexes) {
                 WXPathResolution::None => continue,
-                WXPathResolution::Perfect(bindings) => {
-                    best_match = Some((route_path, bindings, route));
+                WXPathResolution::Perfect(params) => {
+                    best_match = Some((route_path, params, route));
                     break;
                 }
-                WXPathResolution::Partial(bindings) => {
-                    best_match = Some((route_path, bindings, route));
+                // Routes leaving out their optional parameter only match
+                // when no route matches the path perfectly.
+                WXPathResolution::Partial(params) if best_match.is_none() => {
+                    best_match = Some((route_path, params, route));
                 }
+                WXPathResolution::Partial(_) => {}
             }
         }
         best_match
     }
 }
 
+/// The result of resolving a request in the route map.
+#[derive(Debug)]
+pub enum WXRouteResolution<'a> {
+    /// The route matching the method and path, with the path parameters.
+    Found(&'a WXUrlPath, WXRouteParams, &'a WXRTRoute),
+    /// The path only matches routes of other methods, which are allowed instead.
+    MethodNotAllowed(Vec<hyper::Method>),
+    /// No route matches the path.
+    NotFound,
+}
+
+impl<'a> WXRouteResolution<'a> {
+    /// The matching route, if any.
+    pub fn found(self) -> Option<(&'a WXUrlPath, WXRouteParams, &'a WXRTRoute)> {
+        match self {
+            WXRouteResolution::Found(path, params, route) => Some((path, params, route)),
+            _ => None,
+        }
+    }
+}
+
 /// Channel message for the runtime.
 pub enum WXRuntimeMessage {
     New(WXModule),
     Swap(WXModule),
     Remove(WXModulePath),
+    /// A non-module file included by modules has changed.
+    Asset(PathBuf),
+    Config(ProjectConfig),
     ExecuteRoute {
-        request: hyper::Request<hyper::body::Incoming>,
+        request: hyper::Request<Bytes>,
         addr: SocketAddr,
-        respond_to: tokio::sync::oneshot::Sender<
-            Result<hyper::Response<http_body_util::Full<hyper::body::Bytes>>, WXRuntimeError>,
-        >,
+        respond_to:
+            tokio::sync::oneshot::Sender<Result<hyper::Response<WXResponseBody>, WXRuntimeError>>,
+    },
+    /// A WebSocket handshake request for a `ws` route.
+    OpenSocket {
+        request: hyper::Request<Bytes>,
+        addr: SocketAddr,
+        respond_to: tokio::sync::oneshot::Sender<WXSocketOpen>,
+    },
+    /// A text message received by an open WebSocket connection.
+    SocketMessage {
+        id: u64,
+        message: String,
     },
+    /// An open WebSocket connection was closed by either side.
+    SocketClosed {
+        id: u64,
+    },
+    /// Call a GraphQL resolver in a module owned by this runtime worker.
+    Resolve {
+        module_path: WXModulePath,
+        name: String,
+        arguments: serde_json::Map<String, serde_json::Value>,
+        respond_to: std::sync::mpsc::Sender<Result<Option<serde_json::Value>, String>>,
+    },
+    /// Stop the runtime right away, instead of once the running flag is polled.
+    Shutdown,
 }
 #[derive(Clone)]
 pub struct WXRuntimeInfo {
     pub project_root: Box<Path>,
+    pub error_pages: WXErrorPages,
+    pub cors: Option<CorsConfig>,
+    pub body_policy: BodyFormatPolicy,
+    pub trust_proxy: bool,
+    /// The number of reverse proxies the client address is forwarded by, 0 unless `trust_proxy`.
+    pub trusted_proxies: usize,
+    pub trailing_slash: TrailingSlashPolicy,
+    pub case_sensitive_routes: bool,
+    /// The path all routes are served under, empty for the root path.
+    pub base_path: WXUrlPath,
+    pub redact_headers: Vec<String>,
+    pub env_allowlist: Option<Vec<String>>,
+    /// The limits of `fetch()`, also kept in the op state of every module runtime.
+    pub fetch: WXFetchLimits,
+    pub session: Option<SessionConfig>,
+    pub csrf: Option<CsrfConfig>,
+    pub graphql: Option<GraphQLConfig>,
+    /// The configured icon served at `/favicon.ico`.
+    pub favicon: Option<PathBuf>,
+    /// Serve `TRACE` and `CONNECT` routes, always in dev mode and in production if allowed.
+    pub allow_trace_connect: bool,
+    /// Exit in production when the global scope of a module throws.
+    pub exit_on_module_error: bool,
+    /// The native functions callable from handlers.
+    pub natives: Arc<WXNativeRegistry>,
 }
 
 impl WXRuntimeInfo {
-    pub fn new(project_root: &Path) -> Self {
+    pub fn new(project_root: &Path, config: &ProjectConfig, mode: WXMode) -> Self {
         WXRuntimeInfo {
             project_root: project_root.to_path_buf().into_boxed_path(),
+            error_pages: WXErrorPages::load(project_root, config.error_pages.as_ref(), mode),
+            cors: config.cors.clone(),
+            body_policy: BodyFormatPolicy::new(config),
+            trust_proxy: config.trust_proxy.unwrap_or(false),
+            trusted_proxies: match config.trust_proxy {
+                Some(true) => config.trusted_proxies.unwrap_or(1),
+                _ => 0,
+            },
+            trailing_slash: config.trailing_slash.unwrap_or_default(),
+            case_sensitive_routes: config.case_sensitive_routes.unwrap_or(true),
+            base_path: parse_base_path(config.base_path.as_deref().unwrap_or("/")),
+            redact_headers: redacted_headers(config),
+            env_allowlist: config.env_allowlist.clone(),
+            fetch: WXFetchLimits::new(config),
+            session: config.session.clone(),
+            csrf: config.csrf.clone().filter(|csrf| csrf.enabled),
+            graphql: config.graphql.clone(),
+            favicon: config.favicon.as_ref().map(|path| project_root.join(path)),
+            allow_trace_connect: !mode.is_prod() || config.allow_trace_connect.unwrap_or(false),
+            exit_on_module_error: config.exit_on_module_error.unwrap_or(false),
+            natives: Arc::default(),
         }
     }
+
+    /// Whether requests with a method are answered with `405 Method Not Allowed` regardless of
+    /// the routes, see `DISABLED_METHOD_REASON`.
+    pub fn is_disabled_method(&self, method: &hyper::Method) -> bool {
+        !self.allow_trace_connect
+            && matches!(*method, hyper::Method::TRACE | hyper::Method::CONNECT)
+    }
+
+    /// A path of the server under the base path, e.g. `/app/favicon.ico`.
+    pub fn base_url(&self, path: &str) -> String {
+        match self.base_path.segments() {
+            0 => path.to_string(),
+            _ => format!("{}{}", self.base_path, path),
+        }
+    }
+
+    /// Whether a request is answered by the GraphQL endpoint instead of a route.
+    /// The endpoint is served under the base path.
+    pub fn is_graphql_request(&self, req: &hyper::Request<Bytes>) -> bool {
+        self.graphql.as_ref().is_some_and(|config| {
+            let path = config
+                .path
+                .as_deref()
+                .unwrap_or(graphql::DEFAULT_GRAPHQL_PATH);
+            req.uri().path() == self.base_url(path)
+                && matches!(*req.method(), hyper::Method::GET | hyper::Method::POST)
+        })
+    }
+
+    /// Whether a request is a browser fetching the favicon.
+    fn is_favicon_request(&self, req: &hyper::Request<Bytes>) -> bool {
+        req.uri().path() == self.base_url(FAVICON_PATH)
+            && matches!(*req.method(), hyper::Method::GET | hyper::Method::HEAD)
+    }
+}
+
+/// A V8 startup snapshot with the stdlib already loaded.
+/// Created once and shared by all module runtimes.
+static STDLIB_SNAPSHOT: OnceLock<&'static [u8]> = OnceLock::new();
+
+/// Execute the stdlib once in a snapshot runtime and serialize its heap.
+fn create_stdlib_snapshot(mode: WXMode) -> &'static [u8] {
+    let time_start = Instant::now();
+    let mut rt = JsRuntimeForSnapshot::new(RuntimeOptions {
+        module_loader: Some(Rc::new(deno_core::FsModuleLoader)),
+        extensions: vec![stdlib::webx_stdlib::init_ops()],
+        ..Default::default()
+    });
+    // Load WebX Standard Library
+    if let Err(err) = rt.execute_script(
+        "[webx stdlib]",
+        deno_core::FastString::Static(stdlib::JAVASCRIPT),
+    ) {
+        exit_error(
+            format!("Failed to execute stdlib:\n{}", err),
+            500,
+            mode.date_specifier(),
+        );
+    }
+    let snapshot: &'static [u8] = Box::leak(Box::<[u8]>::from(&*rt.snapshot()));
+    info(
+        mode,
+        &format!(
+            "Created stdlib snapshot ({} KiB) in {:?}",
+            snapshot.len() / 1024,
+            time_start.elapsed()
+        ),
+    );
+    snapshot
+}
+
+/// Create a new JavaScript runtime initialized from the stdlib snapshot.
+/// This avoids re-executing the stdlib for every module runtime.
+pub fn new_stdlib_js_runtime(mode: WXMode) -> JsRuntime {
+    let snapshot = STDLIB_SNAPSHOT.get_or_init(|| create_stdlib_snapshot(mode));
+    JsRuntime::new(RuntimeOptions {
+        module_loader: Some(Rc::new(deno_core::FsModuleLoader)),
+        startup_snapshot: Some(Snapshot::Static(snapshot)),
+        extensions: vec![stdlib::webx_stdlib::init_ops()],
+        ..Default::default()
+    })
+}
+
+/// Run `f`, catching a panic raised by it instead of unwinding the runtime thread.
+/// Returns the message of the panic, see `WXRuntime::try_execute_route`.
+pub(crate) fn catch_panic<T>(f: impl FnOnce() -> T) -> Result<T, String> {
+    std::panic::catch_unwind(AssertUnwindSafe(f)).map_err(|panic| {
+        if let Some(msg) = panic.downcast_ref::<&str>() {
+            msg.to_string()
+        } else if let Some(msg) = panic.downcast_ref::<String>() {
+            msg.clone()
+        } else {
+            "unknown panic".to_string()
+        }
+    })
 }
 
 /// The WebX runtime.
@@ -552,10 +1887,30 @@ pub struct WXRuntime {
     /// functions, and other constructs will persist between script executions
     /// as long as they are run in the same runtime instance.
     modules: HashMap<WXModulePath, deno_core::JsRuntime>,
+    /// Open server-sent event streams, polled between messages.
+    event_streams: Vec<WXRTEventStream>,
+    /// Open WebSocket connections by id.
+    sockets: HashMap<u64, WXRTSocket>,
+    next_socket_id: u64,
+    /// Browsers listening for live-reload events in dev mode.
+    live_reload: Vec<tokio::sync::mpsc::Sender<Bytes>>,
+    /// The modules this runtime executes, and the sessions of clients.
+    shard: WXRuntimeShard,
 }
 
 impl WXRuntime {
     pub fn new(rx: Receiver<WXRuntimeMessage>, mode: WXMode, info: WXRuntimeInfo) -> Self {
+        Self::sharded(rx, mode, info, WXRuntimeShard::single())
+    }
+
+    /// A runtime worker executing only the modules of its shard, see `pool::run_runtime`.
+    /// The routes of all modules are compiled, so any worker can reject unknown requests.
+    pub fn sharded(
+        rx: Receiver<WXRuntimeMessage>,
+        mode: WXMode,
+        info: WXRuntimeInfo,
+        shard: WXRuntimeShard,
+    ) -> Self {
         WXRuntime {
             source_modules: Vec::new(),
             routes: WXRouteMap::new(),
@@ -563,6 +1918,18 @@ impl WXRuntime {
             mode,
             info,
             modules: HashMap::new(),
+            event_streams: Vec::new(),
+            sockets: HashMap::new(),
+            next_socket_id: shard.index() as u64,
+            live_reload: Vec::new(),
+            shard,
+        }
+    }
+
+    /// Log a message once, by the primary worker if modules are sharded.
+    fn announce(&self, message: &str) {
+        if self.shard.is_primary() {
+            info(self.mode, message);
         }
     }
 
@@ -577,6 +1944,11 @@ impl WXRuntime {
         modules.into_iter().for_each(|m| self.load_module(m));
     }
 
+    /// The route map the runtime currently resolves requests with.
+    pub fn routes(&self) -> &WXRouteMap {
+        &self.routes
+    }
+
     /// Load a single module into the runtime.
     /// This function will **NOT** recompile the route map.
     /// To recompile the route map, either:
@@ -587,60 +1959,303 @@ impl WXRuntime {
     /// ## Note
     /// Only call this function once per module.
     /// This should **NOT** be called when hot-swapping modules.
+    ///
+    /// ## Error
+    /// A module whose global scope throws is reported and **not** loaded,
+    /// so its routes are skipped while the other modules are served.
+    /// In production, the process exits instead if `exitOnModuleError` is configured.
     pub fn load_module(&mut self, module: WXModule) {
-        let rt = self.new_module_js_runtime(&module);
-        self.modules.insert(module.path.clone(), rt);
-        self.source_modules.push(module);
+        if !self.shard.owns(&module.path) {
+            // Another worker executes the module, only its routes are compiled.
+            self.source_modules.push(module);
+            return;
+        }
+        match self.new_module_js_runtime(&module) {
+            Ok(rt) => {
+                self.modules.insert(module.path.clone(), rt);
+                self.source_modules.push(module);
+            }
+            Err(err) => {
+                if self.mode.is_prod() && self.info.exit_on_module_error {
+                    exit_error(err.message, err.code, self.mode.date_specifier());
+                }
+                error_code(
+                    format!("{}\nThe routes of the module are skipped.", err.message),
+                    err.code,
+                    self.mode.date_specifier(),
+                );
+            }
+        }
     }
 
     fn remove_module(&mut self, module_path: &WXModulePath) {
+        self.close_event_streams(module_path);
+        self.close_sockets(module_path);
         self.modules.remove(module_path);
         self.source_modules.retain(|m| m.path != *module_path);
     }
 
-    /// Initialize the JavaScript runtime with the stdlib.
-    fn new_js_runtime(&mut self) -> JsRuntime {
-        let mut rt = JsRuntime::new(RuntimeOptions {
-            module_loader: Some(Rc::new(deno_core::FsModuleLoader)),
-            // extensions: vec![stdlib::init()],
-            ..Default::default()
+    /// Re-initialize the JS runtimes of all modules including the given file.
+    /// The source modules and route map are unchanged.
+    fn reload_dependents(&mut self, file: &Path) {
+        let dependents = find_dependents(&self.source_modules, file)
+            .into_iter()
+            .cloned()
+            .collect::<Vec<_>>();
+        for module in dependents {
+            if !self.shard.owns(&module.path) {
+                continue;
+            }
+            info(
+                self.mode,
+                &format!(
+                    "Reloaded module: {} (changed {:?})",
+                    module.path.module_name(),
+                    file
+                ),
+            );
+            let rt = match self.new_module_js_runtime(&module) {
+                Ok(rt) => rt,
+                Err(err) => {
+                    error_code(
+                        format!("{}\nThe previous state of the module is kept.", err.message),
+                        err.code,
+                        self.mode.date_specifier(),
+                    );
+                    continue;
+                }
+            };
+            self.close_event_streams(&module.path);
+            self.close_sockets(&module.path);
+            self.modules.insert(module.path.clone(), rt);
+        }
+    }
+
+    /// Close all event streams of a module before its JS runtime is dropped.
+    fn close_event_streams(&mut self, module_path: &WXModulePath) {
+        self.event_streams
+            .retain(|stream| stream.module_path != *module_path);
+    }
+
+    /// Close all WebSocket connections of a module before its JS runtime is dropped.
+    fn close_sockets(&mut self, module_path: &WXModulePath) {
+        self.sockets.retain(|_, socket| {
+            if socket.module_path != *module_path {
+                return true;
+            }
+            let _ = socket.frames.send(WXSocketFrame::Close);
+            false
         });
-        // Load WebX Standard Library
-        if let Err(err) = rt.execute_script(
-            "[webx stdlib]",
-            deno_core::FastString::Static(stdlib::JAVASCRIPT),
-        ) {
-            exit_error(
-                format!("Failed to execute stdlib:\n{}", err),
-                500,
-                self.mode.date_specifier(),
+    }
+
+    /// Open a WebSocket connection on the `ws` route matching the handshake request.
+    /// The route body is executed once, with the new connection bound to `socket`.
+    fn open_socket(&mut self, req: hyper::Request<Bytes>, addr: SocketAddr) -> WXSocketOpen {
+        let Some((_, _, route)) = self.routes.resolve(&websocket_method(), req.uri()).found()
+        else {
+            return WXSocketOpen::Rejected(self.not_found(&req, addr));
+        };
+        let module_path = route.module_path.clone();
+        let id = self.next_socket_id;
+        // Ids are unique across workers, as each worker only assigns ids congruent to its index.
+        self.next_socket_id += self.shard.count() as u64;
+        let opened = match &route.body {
+            Some(body) if body.body_type == WXBodyType::Ts => transpile_ts(&format!(
+                "(async (socket) => {{\n{}\n}})(globalThis.__webx_sockets.create({}))",
+                body.body, id
+            )),
+            _ => Err(WXRuntimeError {
+                code: 500,
+                http_status: 500,
+                message: "WebSocket routes require a code body".into(),
+            }),
+        };
+        let rt = self.modules.get_mut(&module_path).unwrap();
+        if let Err(err) = opened.and_then(|js| execute_socket_script(rt, js)) {
+            let _ = execute_socket_script(rt, format!("globalThis.__webx_sockets.close({})", id));
+            let message = format!("WebSocket route {} failed to open:\n{}", req.uri(), err);
+            error_code(message.clone(), err.code, self.mode.date_specifier());
+            let response =
+                responses::internal_server_error(self.mode, &self.info.error_pages, message);
+            return WXSocketOpen::Rejected(response.map(WXResponseBody::from));
+        }
+        let (frames, rx) = tokio::sync::mpsc::unbounded_channel();
+        self.sockets.insert(
+            id,
+            WXRTSocket {
+                module_path,
+                frames,
+            },
+        );
+        info(self.mode, &format!("WebSocket {} opened by: {}", id, addr));
+        self.flush_socket(id);
+        WXSocketOpen::Accepted { id, frames: rx }
+    }
+
+    /// Send all messages queued by a JS socket to its connection.
+    fn flush_socket(&mut self, id: u64) {
+        let Some(socket) = self.sockets.get(&id) else {
+            return;
+        };
+        let Some(rt) = self.modules.get_mut(&socket.module_path) else {
+            return;
+        };
+        let outbox = execute_socket_script(rt, format!("globalThis.__webx_sockets.drain({})", id))
+            .and_then(|value| {
+                let scope = &mut rt.handle_scope();
+                let json = Local::new(scope, &value).to_rust_string_lossy(scope);
+                serde_json::from_str::<WXRTSocketOutbox>(&json).map_err(|err| WXRuntimeError {
+                    code: 500,
+                    http_status: 500,
+                    message: err.to_string(),
+                })
+            });
+        match outbox {
+            Ok(outbox) => {
+                for message in outbox.messages {
+                    let _ = socket.frames.send(WXSocketFrame::Text(message));
+                }
+                if outbox.closed {
+                    let _ = socket.frames.send(WXSocketFrame::Close);
+                }
+            }
+            Err(err) => warning(
+                self.mode,
+                format!("WebSocket {} failed to send messages: {}", id, err),
+            ),
+        }
+    }
+
+    /// Pass a message received by a WebSocket connection to its `onMessage` handlers.
+    fn socket_message(&mut self, id: u64, message: String) {
+        let Some(socket) = self.sockets.get(&id) else {
+            return;
+        };
+        let Some(rt) = self.modules.get_mut(&socket.module_path) else {
+            return;
+        };
+        let script = format!(
+            "globalThis.__webx_sockets.dispatch({}, {})",
+            id,
+            serde_json::Value::from(message)
+        );
+        if let Err(err) = execute_socket_script(rt, script) {
+            warning(
+                self.mode,
+                format!("WebSocket {} handler threw an error:\n{}", id, err),
             );
         }
-        info(self.mode, "Initialized stdlib");
+        self.flush_socket(id);
+    }
+
+    /// Run the `onClose` handlers of a WebSocket connection once it is closed.
+    fn socket_closed(&mut self, id: u64) {
+        let Some(socket) = self.sockets.remove(&id) else {
+            return;
+        };
+        info(self.mode, &format!("WebSocket {} closed", id));
+        let Some(rt) = self.modules.get_mut(&socket.module_path) else {
+            return;
+        };
+        let script = format!("globalThis.__webx_sockets.close({})", id);
+        if let Err(err) = execute_socket_script(rt, script) {
+            warning(
+                self.mode,
+                format!("WebSocket {} close handler threw an error:\n{}", id, err),
+            );
+        }
+    }
+
+    /// Pull the next event from every open event stream.
+    /// Streams are closed once the generator is done or throws,
+    /// or when the client has disconnected.
+    fn poll_event_streams(&mut self) {
+        let mode = self.mode;
+        self.event_streams
+            .retain(|stream| match self.modules.get_mut(&stream.module_path) {
+                Some(rt) => stream.poll(rt, mode),
+                None => false,
+            });
+    }
+
+    /// Initialize the JavaScript runtime with the stdlib.
+    fn new_js_runtime(&mut self) -> JsRuntime {
+        let time_start = Instant::now();
+        let mut rt = new_stdlib_js_runtime(self.mode);
+        rt.op_state().borrow_mut().put(self.info.fetch.clone());
+        info(
+            self.mode,
+            &format!(
+                "Initialized stdlib from snapshot in {:?}",
+                time_start.elapsed()
+            ),
+        );
         rt
     }
 
-    /// Initialize the module and execute the global scope
-    fn new_module_js_runtime(&mut self, module: &WXModule) -> JsRuntime {
+    /// Initialize the module, execute its included `.ts` and `.js` assets,
+    /// and then the global scopes, in the order of `WXScope::globals`.
+    /// Fails if an asset can not be read or a script throws,
+    /// leaving the runtime half-initialized.
+    fn new_module_js_runtime(&mut self, module: &WXModule) -> Result<JsRuntime, WXRuntimeError> {
         let mut rt = self.new_js_runtime();
-        if let Err(err) =
-            rt.execute_script("[global scope]", module.scope.global_ts.to_owned().into())
-        {
-            error_code(
-                format!(
-                    "Failed to execute global scope for module '{}':\n{}",
-                    module.path.module_name(),
-                    err
-                ),
-                500,
-                self.mode.date_specifier(),
-            );
+        for asset in included_assets(module) {
+            std::fs::read_to_string(&asset)
+                .map_err(WXRuntimeError::from)
+                .and_then(|source| match asset.extension() {
+                    Some(ext) if ext == "ts" => transpile_ts(&source),
+                    _ => Ok(source),
+                })
+                .and_then(|js| {
+                    rt.execute_script("[included asset]", js.into())
+                        .map_err(|err| WXRuntimeError {
+                            code: 500,
+                            http_status: 500,
+                            message: err.to_string(),
+                        })
+                })
+                .map_err(|err| WXRuntimeError {
+                    message: format!(
+                        "Failed to execute included asset {:?} for module '{}':\n{}",
+                        asset,
+                        module.path.module_name(),
+                        err.message
+                    ),
+                    ..err
+                })?;
+        }
+        for (path, global_ts) in module.scope.globals() {
+            transpile_ts(global_ts)
+                .and_then(|js| {
+                    rt.execute_script("[global scope]", js.into())
+                        .map_err(|err| WXRuntimeError {
+                            code: 500,
+                            http_status: 500,
+                            message: err.to_string(),
+                        })
+                })
+                .map_err(|err| WXRuntimeError {
+                    message: match path.segments() {
+                        0 => format!(
+                            "Failed to execute global scope for module '{}':\n{}",
+                            module.path.module_name(),
+                            err.message
+                        ),
+                        _ => format!(
+                            "Failed to execute global scope of location '{}' for module '{}':\n{}",
+                            path,
+                            module.path.module_name(),
+                            err.message
+                        ),
+                    },
+                    ..err
+                })?;
         }
         info(
             self.mode,
             &format!("Initialized module '{}'", module.path.module_name()),
         );
-        rt
+        Ok(rt)
     }
 
     /// Tries to recompile all loaded modules at once and replace the runtime route map.
@@ -653,29 +2268,55 @@ impl WXRuntime {
     /// This function will **throw and error** if the route map cannot be compiled
     /// from the current source modules, and will **not** replace the current route map.
     /// However, the program will **continue to run with the old route map**.
-    fn recompile(&mut self) {
-        match WXRouteMap::from_modules(&self.source_modules) {
-            Ok(routes) => self.routes = routes,
-            Err(err) => error_code(err.message, err.code, self.mode.date_specifier()),
+    /// Returns whether the route map was replaced.
+    fn recompile(&mut self) -> bool {
+        match WXRouteMap::from_modules(
+            &self.source_modules,
+            &self.info.body_policy,
+            self.info.case_sensitive_routes,
+        ) {
+            Ok(routes) => {
+                self.routes = routes
+                    .with_base_path(&self.info.base_path)
+                    .with_trailing_slash(self.info.trailing_slash)
+            }
+            Err(err) => {
+                if self.shard.is_primary() {
+                    error_code(err.message, err.code, self.mode.date_specifier());
+                }
+                return false;
+            }
+        }
+        if !self.shard.is_primary() {
+            return true;
+        }
+        for overlap in extract_overlapping_routes(&extract_flat_routes(&self.source_modules)) {
+            warning(self.mode, overlap);
         }
-        if self.mode.is_dev() && self.mode.debug_level().is_high() {
+        if self.mode.is_dev() && self.mode.debug_level().is_high() && !is_quiet() {
             // Print the route map in dev mode.
             info(self.mode, "Route map:");
-            let routes: Vec<(&hyper::Method, &WXUrlPath)> = self
+            let routes: Vec<(&hyper::Method, &WXUrlPath, String)> = self
+                .routes
                 .routes
-                .0
                 .iter()
                 .flat_map(|(method, method_map)| {
                     method_map
                         .iter()
-                        .map(|(path, _)| (method, path))
-                        .collect::<Vec<_>>()
+                        .map(move |(path, route)| (method, path, route.module_path.module_name()))
                 })
                 .collect();
-            for (method, path) in routes {
-                println!(" - {}", print_route(method, path));
-            }
+            println!("{}", format_route_table(&routes));
         }
+        true
+    }
+
+    /// Tell all connected browsers to reload the page.
+    /// Disconnected browsers are dropped.
+    fn live_reload(&mut self) {
+        let frame = responses::event_frame(Some("reload"), None, "");
+        self.live_reload
+            .retain(|client| client.try_send(frame.clone()).is_ok() || !client.is_closed());
     }
 
     /// Main runtime loop.
@@ -691,89 +2332,539 @@ impl WXRuntime {
     /// ## Note
     /// This is **required** as `deno_core::JsRuntime` is **not** thread-safe
     /// and cannot be shared between threads.
-    pub fn run(&mut self, running: Arc<AtomicBool>) {
+    ///
+    /// ## Readiness
+    /// `ready` is set once the loaded modules are compiled and requests are handled.
+    pub fn run(&mut self, running: Arc<AtomicBool>, ready: Arc<AtomicBool>) {
         self.recompile();
+        ready.store(true, Ordering::SeqCst);
         loop {
             if !running.load(Ordering::SeqCst) {
                 // println!("Shutting down runtime...");
                 break; // Exit the loop and stop the runtime.
             }
-            if let Ok(msg) = self.messages.recv_timeout(timeout_duration(self.mode)) {
+            let wait = if self.event_streams.is_empty() {
+                timeout_duration(self.mode)
+            } else {
+                EVENT_STREAM_POLL_INTERVAL
+            };
+            let msg = match self.messages.recv_timeout(wait) {
+                Ok(msg) => Some(msg),
+                Err(RecvTimeoutError::Timeout) => None,
+                // The server and file watcher have stopped.
+                Err(RecvTimeoutError::Disconnected) => break,
+            };
+            if let Some(msg) = msg {
                 match msg {
                     WXRuntimeMessage::New(module) => {
-                        info(
-                            self.mode,
-                            &format!("New module: {}", module.path.module_name()),
-                        );
+                        self.announce(&format!("New module: {}", module.path.module_name()));
                         self.load_module(module);
-                        self.recompile();
+                        if self.recompile() {
+                            self.live_reload();
+                        }
                     }
                     WXRuntimeMessage::Swap(module) => {
-                        info(
-                            self.mode,
-                            &format!("Reloaded module: {}", module.path.module_name()),
-                        );
+                        self.announce(&format!("Reloaded module: {}", module.path.module_name()));
                         // Module JS runtime is persistent between hot-swaps.
                         self.remove_module(&module.path);
                         self.load_module(module);
-                        self.recompile();
+                        if self.recompile() {
+                            self.live_reload();
+                        }
                     }
                     WXRuntimeMessage::Remove(path) => {
-                        info(
-                            self.mode,
-                            &format!("Removed module: {}", path.module_name()),
-                        );
+                        self.announce(&format!("Removed module: {}", path.module_name()));
                         self.remove_module(&path);
+                        if self.recompile() {
+                            self.live_reload();
+                        }
+                    }
+                    WXRuntimeMessage::Asset(path) => self.reload_dependents(&path),
+                    WXRuntimeMessage::Config(config) => {
+                        self.announce("Reloaded project configuration");
+                        let project_root = self.info.project_root.clone();
+                        let natives = self.info.natives.clone();
+                        self.info = WXRuntimeInfo::new(&project_root, &config, self.mode);
+                        self.info.natives = natives;
+                        for rt in self.modules.values_mut() {
+                            rt.op_state().borrow_mut().put(self.info.fetch.clone());
+                        }
                         self.recompile();
                     }
                     WXRuntimeMessage::ExecuteRoute {
                         request,
                         addr,
                         respond_to,
-                    } => respond_to.send(self.execute_route(request, addr)).unwrap(),
+                    } => {
+                        // Tag the logs of the request with the id assigned by the server.
+                        let result = match requests::request_id(request.headers()) {
+                            Some(id) => {
+                                with_request_id(&id, || self.try_execute_route(request, addr))
+                            }
+                            None => self.try_execute_route(request, addr),
+                        };
+                        if respond_to.send(result).is_err() {
+                            warning(
+                                self.mode,
+                                format!("Client {} disconnected before the response", addr),
+                            );
+                        }
+                    }
+                    WXRuntimeMessage::OpenSocket {
+                        request,
+                        addr,
+                        respond_to,
+                    } => {
+                        if let Err(WXSocketOpen::Accepted { id, .. }) =
+                            respond_to.send(self.open_socket(request, addr))
+                        {
+                            self.socket_closed(id);
+                        }
+                    }
+                    WXRuntimeMessage::SocketMessage { id, message } => {
+                        self.socket_message(id, message)
+                    }
+                    WXRuntimeMessage::SocketClosed { id } => self.socket_closed(id),
+                    WXRuntimeMessage::Resolve {
+                        module_path,
+                        name,
+                        arguments,
+                        respond_to,
+                    } => {
+                        let result = match self.modules.get_mut(&module_path) {
+                            Some(rt) => call_resolver(rt, &name, &arguments),
+                            None => Ok(None),
+                        };
+                        let _ = respond_to.send(result);
+                    }
+                    WXRuntimeMessage::Shutdown => break,
                 }
             }
+            self.poll_event_streams();
+        }
+    }
+
+    /// Execute a route and convert any panic raised while doing so into a
+    /// `500 Internal Server Error` response for that single request.
+    /// This keeps the runtime thread alive for all subsequent requests.
+    ///
+    /// In dev mode, HTML responses reload the page after a hot-swap.
+    fn try_execute_route(
+        &mut self,
+        req: hyper::Request<Bytes>,
+        addr: SocketAddr,
+    ) -> Result<hyper::Response<WXResponseBody>, WXRuntimeError> {
+        let live_reload = self.mode.is_dev() && req.method() == hyper::Method::GET;
+        let live_reload_path = self.info.base_url(responses::LIVE_RELOAD_PATH);
+        if live_reload && req.uri().path() == live_reload_path {
+            let (client, events) = tokio::sync::mpsc::channel(1);
+            self.live_reload.push(client);
+            return Ok(responses::event_stream(self.mode, events));
+        }
+        let mut response = self.try_execute_route_inner(req, addr)?;
+        if live_reload {
+            response = responses::inject_live_reload(response, &live_reload_path);
+        }
+        Ok(response)
+    }
+
+    fn try_execute_route_inner(
+        &mut self,
+        req: hyper::Request<Bytes>,
+        addr: SocketAddr,
+    ) -> Result<hyper::Response<WXResponseBody>, WXRuntimeError> {
+        // Only formatted if the route panics.
+        let (method, uri) = (req.method().clone(), req.uri().clone());
+        match catch_panic(|| self.execute_route(req, addr)) {
+            Ok(result) => result,
+            Err(message) => {
+                let message = format!("Route {} {} panicked: {}", method, uri, message);
+                error_code(
+                    message.clone(),
+                    ERROR_EXEC_ROUTE,
+                    self.mode.date_specifier(),
+                );
+                let response =
+                    responses::internal_server_error(self.mode, &self.info.error_pages, message);
+                Ok(response.map(WXResponseBody::from))
+            }
         }
     }
 
     fn execute_route(
         &mut self,
-        req: hyper::Request<hyper::body::Incoming>,
+        req: hyper::Request<Bytes>,
         addr: SocketAddr,
-    ) -> Result<hyper::Response<http_body_util::Full<Bytes>>, WXRuntimeError> {
-        if let Some((_path, mut ctx, route)) = self.routes.resolve(req.method(), req.uri()) {
-            let module_runtime = self.modules.get_mut(&route.module_path).unwrap();
-            let route_result = route.execute(&mut ctx, module_runtime, &self.info, self.mode);
-            let response = match route_result {
-                Ok(response) => response,
+    ) -> Result<hyper::Response<WXResponseBody>, WXRuntimeError> {
+        let is_head = req.method() == hyper::Method::HEAD;
+        if self.info.is_graphql_request(&req) {
+            return Ok(self.execute_graphql(&req, addr));
+        }
+        if self.info.is_disabled_method(req.method()) {
+            return Ok(self.disabled_method(&req, addr));
+        }
+        if let Some(response) = self.serve_favicon(&req, addr) {
+            return Ok(response);
+        }
+        match resolve_trailing_slash(self.info.trailing_slash, req.uri()) {
+            WXSlashResolution::Resolve => {}
+            WXSlashResolution::Redirect(location) => {
+                // Only redirect to resources that exist.
+                let resolves = location
+                    .parse::<hyper::Uri>()
+                    .is_ok_and(|uri| !self.routes.allowed_methods(&uri).is_empty());
+                if !resolves {
+                    return Ok(self.not_found(&req, addr));
+                }
+                let response = responses::moved_permanently(self.mode, &location);
+                info(
+                    self.mode,
+                    &format!("{} response to: {}", response.status(), addr),
+                );
+                return Ok(response.map(WXResponseBody::from));
+            }
+        }
+        let (route_path, params, route) = match self.routes.resolve(req.method(), req.uri()) {
+            WXRouteResolution::Found(route_path, params, route) => (route_path, params, route),
+            WXRouteResolution::MethodNotAllowed(mut allow)
+                if req.method() == hyper::Method::OPTIONS =>
+            {
+                allow.retain(|method| !self.info.is_disabled_method(method));
+                let request_headers = req
+                    .headers()
+                    .get("Access-Control-Request-Headers")
+                    .and_then(|value| value.to_str().ok());
+                let response =
+                    responses::options(self.mode, &allow, self.info.cors.as_ref(), request_headers);
+                info(
+                    self.mode,
+                    &format!("{} response to: {}", response.status(), addr),
+                );
+                return Ok(response.map(WXResponseBody::from));
+            }
+            WXRouteResolution::MethodNotAllowed(mut allow) => {
+                allow.retain(|method| !self.info.is_disabled_method(method));
+                warning(
+                    self.mode,
+                    format!("No {} route for: {}", req.method(), req.uri().path()),
+                );
+                let response = responses::method_not_allowed(self.mode, req.method(), &allow);
+                info(
+                    self.mode,
+                    &format!("{} response to: {}", response.status(), addr),
+                );
+                let response = strip_head_body(is_head, response);
+                return Ok(response.map(WXResponseBody::from));
+            }
+            WXRouteResolution::NotFound => return Ok(self.not_found(&req, addr)),
+        };
+        let Some(module_runtime) = self.modules.get_mut(&route.module_path) else {
+            // The module is executed by another worker, or failed to initialize.
+            return Ok(self.not_found(&req, addr));
+        };
+        let mut ctx = WXRTContext::new();
+        let body = request_body(&req, route.body_format.as_ref());
+        {
+            let client_ip = requests::client_ip(req.headers(), addr, self.info.trusted_proxies);
+            let scope = &mut module_runtime.handle_scope();
+            // The declared body fields, path parameters and client IP are bound by name as well.
+            if let (Some(format), Ok(serde_json::Value::Object(fields))) =
+                (&route.body_format, &body)
+            {
+                for field in format.fields.iter() {
+                    if let Some(value) = fields.get(&field.name) {
+                        ctx.bind_json(scope, &field.name, value)?;
+                    }
+                }
+            }
+            for (name, value) in params.iter() {
+                ctx.bind_json(scope, name, value)?;
+            }
+            ctx.bind_json(scope, "clientIp", &client_ip.to_string().into())?;
+            let body_value = body.clone().unwrap_or_default();
+            ctx.bind_json(
+                scope,
+                "req",
+                &request_value(&req, params, body_value, client_ip),
+            )?;
+        }
+        let session = match &self.info.session {
+            Some(config) => Some(WXRTSession::open(
+                config,
+                &mut *self.shard.lock_sessions(),
+                req.headers(),
+                module_runtime,
+                &mut ctx,
+            )?),
+            None => None,
+        };
+        open_response_headers(module_runtime)?;
+        // Reject invalid bodies before any handler runs.
+        let mut rejection = body.err().map(|err| WXRuntimeError {
+            code: ERROR_BAD_REQUEST,
+            http_status: 400,
+            message: format!(
+                "Invalid request body for {} {}: {}",
+                req.method(),
+                req.uri(),
+                err
+            ),
+        });
+        if let (Some(config), Some(session)) = (&self.info.csrf, &session) {
+            let token = session.csrf_token(module_runtime, &mut ctx)?;
+            // Exempt paths are relative to the base path, like the routes.
+            let path = req.uri().path();
+            let path = path.strip_prefix(&self.info.base_url("")).unwrap_or(path);
+            if csrf::is_protected(config, req.method(), path) && !csrf::verify(&req, &token) {
+                rejection = Some(WXRuntimeError {
+                    code: ERROR_FORBIDDEN,
+                    http_status: 403,
+                    message: format!("Invalid CSRF token for {} {}", req.method(), req.uri()),
+                });
+            }
+        }
+        let error_handler = self
+            .source_modules
+            .iter()
+            .find(|m| m.path == route.module_path)
+            .and_then(|m| m.scope.error_handler.as_ref());
+        let route_result = match rejection {
+            Some(err) => Err(err),
+            None => route.execute(&mut ctx, module_runtime, &self.info),
+        }
+        .map(
+            |result| match WXRTEventStream::open(&route.module_path, &result, module_runtime) {
+                Some((stream, events)) => {
+                    self.event_streams.push(stream);
+                    WXRouteResult::EventStream(events)
+                }
+                None => result,
+            },
+        )
+        .and_then(|result| match result {
+            // Let the error handler of the module handle errors raised by `error()`.
+            WXRouteResult::Error { status, message } if error_handler.is_some() => {
+                Err(WXRuntimeError {
+                    code: ERROR_EXEC_ROUTE,
+                    http_status: status,
+                    message,
+                })
+            }
+            result => Ok(result),
+        });
+        let headers = close_response_headers(module_runtime);
+        let cacheable = matches!(*req.method(), hyper::Method::GET | hyper::Method::HEAD);
+        let mut response =
+            match route_result.and_then(|result| headers.map(|headers| (result, headers))) {
+                Ok((result, headers)) => WXRTRoute::to_response(
+                    result,
+                    &mut module_runtime.handle_scope(),
+                    self.mode,
+                    headers,
+                    route.cache,
+                    Some(req.headers()),
+                    cacheable.then(|| req.headers()),
+                ),
+                Err(err) if err.code == ERROR_FORBIDDEN => {
+                    warning(self.mode, err.message);
+                    responses::forbidden(self.mode).map(WXResponseBody::from)
+                }
                 Err(err) => {
-                    error_code(
-                        err.message.to_string(),
-                        err.code,
-                        self.mode.date_specifier(),
-                    );
-                    responses::internal_server_error_default_webx(self.mode, err.message)
+                    if err.http_status < 500 {
+                        warning(self.mode, err.message.to_string());
+                    } else {
+                        error_code(
+                            err.message.to_string(),
+                            err.code,
+                            self.mode.date_specifier(),
+                        );
+                    }
+                    let location = WXErrorLocation {
+                        file: route.module_path.inner.clone(),
+                        line: route.line,
+                    };
+                    match error_handler.map(|h| h.execute(&err, module_runtime, self.mode)) {
+                        Some(Ok(response)) => response,
+                        Some(Err(handler_err)) => {
+                            error_code(
+                                handler_err.message,
+                                handler_err.code,
+                                self.mode.date_specifier(),
+                            );
+                            error_response(self.mode, &self.info, &req, err, &location)
+                        }
+                        None => error_response(self.mode, &self.info, &req, err, &location),
+                    }
                 }
             };
+        if let (Some(session), Some(config)) = (session, &self.info.session) {
+            let is_https = requests::is_https(&req, self.info.trust_proxy);
+            let store = &mut *self.shard.lock_sessions();
+            if let Some(cookie) = session.close(config, store, module_runtime, is_https)? {
+                response.headers_mut().append("Set-Cookie", cookie);
+            }
+        }
+        // Responses are only serialized when they are logged.
+        if is_info_enabled(self.mode) {
             if self.mode.debug_level().is_max() {
                 info(
                     self.mode,
-                    &format!("Response to: {}\n{}", addr, responses::serialize(&response)),
+                    &format!(
+                        "Response to: {}\n{}",
+                        addr,
+                        responses::serialize(&response, &self.info.redact_headers)
+                    ),
                 );
             } else if self.mode.debug_level().is_high() {
                 info(self.mode, &format!("Response to: {}", addr));
             }
+        }
 
-            Ok(response.map(http_body_util::Full::from))
-        } else {
-            warning(self.mode, format!("No route match: {}", req.uri().path()));
-            let response =
-                responses::not_found_default_webx(self.mode, req.method(), req.uri().to_string());
+        let mut response = strip_head_body(is_head, response);
+        response
+            .extensions_mut()
+            .insert(WXRouteLabel(route_path.to_string()));
+        Ok(response)
+    }
+
+    /// Answer a request to the GraphQL endpoint.
+    /// The root fields are resolved by the handler of the same name,
+    /// in the first module defining it.
+    fn execute_graphql(
+        &mut self,
+        req: &hyper::Request<Bytes>,
+        addr: SocketAddr,
+    ) -> hyper::Response<WXResponseBody> {
+        let query = self
+            .info
+            .graphql
+            .as_ref()
+            .and_then(|config| config.query.clone())
+            .unwrap_or_default();
+        let schema = WXGraphQLSchema::new(&self.source_modules, query);
+        let (status, body) = match graphql::read_request(req) {
+            Ok(request) => graphql::execute(&schema, &request, |name, arguments| {
+                for module in self.source_modules.iter() {
+                    // Modules of other workers are resolved by their worker.
+                    let value = match self.modules.get_mut(&module.path) {
+                        Some(rt) => call_resolver(rt, name, arguments)?,
+                        None => self.shard.resolve(&module.path, name, arguments)?,
+                    };
+                    if let Some(value) = value {
+                        return Ok(value);
+                    }
+                }
+                Err(format!("No handler named '{}' resolves the field", name))
+            }),
+            Err(message) => (hyper::StatusCode::BAD_REQUEST, graphql::errors(&[message])),
+        };
+        info(self.mode, &format!("{} response to: {}", status, addr));
+        graphql::response(self.mode, status, &body).map(WXResponseBody::from)
+    }
+
+    /// Refuse a `TRACE` or `CONNECT` request in production, even if a route matches it.
+    fn disabled_method(
+        &self,
+        req: &hyper::Request<Bytes>,
+        addr: SocketAddr,
+    ) -> hyper::Response<WXResponseBody> {
+        let message = format!(
+            "{} is disabled in production: {}",
+            req.method(),
+            DISABLED_METHOD_REASON
+        );
+        warning(
+            self.mode,
+            format!(
+                "Refused {} request for {}: {}",
+                req.method(),
+                req.uri().path(),
+                DISABLED_METHOD_REASON
+            ),
+        );
+        let mut allow = self.routes.allowed_methods(req.uri());
+        allow.retain(|method| !self.info.is_disabled_method(method));
+        let response = responses::method_not_allowed_with(self.mode, message, &allow);
+        info(
+            self.mode,
+            &format!("{} response to: {}", response.status(), addr),
+        );
+        response.map(WXResponseBody::from)
+    }
+
+    /// Serve the configured favicon, before any route is resolved.
+    fn serve_favicon(
+        &self,
+        req: &hyper::Request<Bytes>,
+        addr: SocketAddr,
+    ) -> Option<hyper::Response<WXResponseBody>> {
+        let favicon = self.info.favicon.as_ref()?;
+        if !self.info.is_favicon_request(req) {
+            return None;
+        }
+        let response = match std::fs::File::open(favicon).and_then(|file| {
+            let metadata = file.metadata()?;
+            Ok((file, metadata))
+        }) {
+            Ok((file, metadata)) => {
+                let mut response = responses::file(
+                    self.mode,
+                    file,
+                    metadata.len(),
+                    responses::content_type(favicon),
+                    metadata.modified().ok(),
+                );
+                response.headers_mut().insert(
+                    "Cache-Control",
+                    responses::cache_control(Some(FAVICON_MAX_AGE)),
+                );
+                responses::byte_range(
+                    req.headers(),
+                    responses::conditional(req.headers(), response),
+                )
+            }
+            Err(err) => {
+                warning(
+                    self.mode,
+                    format!("Failed to read favicon '{}': {}", favicon.display(), err),
+                );
+                return None;
+            }
+        };
+        info(
+            self.mode,
+            &format!("{} response to: {}", response.status(), addr),
+        );
+        let is_head = req.method() == hyper::Method::HEAD;
+        Some(strip_head_body(is_head, response))
+    }
+
+    fn not_found(
+        &self,
+        req: &hyper::Request<Bytes>,
+        addr: SocketAddr,
+    ) -> hyper::Response<WXResponseBody> {
+        let is_head = req.method() == hyper::Method::HEAD;
+        if self.info.is_favicon_request(req) {
+            // Browsers request a favicon on their own, which is not worth a warning.
+            let response = responses::no_content(self.mode);
             info(
                 self.mode,
                 &format!("{} response to: {}", response.status(), addr),
             );
-            Ok(response.map(http_body_util::Full::from))
+            return response.map(WXResponseBody::from);
         }
+        warning(self.mode, format!("No route match: {}", req.uri().path()));
+        let response = responses::not_found(
+            self.mode,
+            &self.info.error_pages,
+            req.method(),
+            req.uri().to_string(),
+        );
+        info(
+            self.mode,
+            &format!("{} response to: {}", response.status(), addr),
+        );
+        strip_head_body(is_head, response).map(WXResponseBody::from)
     }
 }
@@ -1,43 +1,138 @@
 use std::{
     future::Future,
-    net::SocketAddr,
+    net::{SocketAddr, ToSocketAddrs},
+    path::Path,
     pin::Pin,
     sync::{
         atomic::{AtomicBool, Ordering},
         mpsc::Sender,
         Arc,
     },
+    time::{Duration, Instant},
 };
 
-use http_body_util::Full;
+use futures_util::{FutureExt, SinkExt, StreamExt};
 use hyper::{
     body::{Bytes, Incoming},
+    header::HeaderValue,
     server::conn::http1,
-    service::Service,
+    service::{HttpService, Service},
     Request, Response,
 };
-use hyper_util::rt::TokioIo;
-use tokio::time::timeout;
+use hyper_util::rt::{TokioIo, TokioTimer};
+use tokio::{
+    sync::{mpsc::UnboundedReceiver, watch, Notify, Semaphore},
+    task::JoinSet,
+    time::timeout,
+};
+use tokio_tungstenite::{
+    tungstenite::{handshake::derive_accept_key, protocol::Role, Message},
+    WebSocketStream,
+};
 
 use crate::{
-    file::project::ProjectConfig,
+    file::project::{ProjectConfig, SecurityHeadersConfig},
     reporting::{
-        debug::info,
+        debug::{info, is_info_enabled, with_request_id},
         error::{error_code, ERROR_EXEC_ROUTE},
+        warning::warning,
+    },
+    runner::{timeout_duration, WXMode, WXOpener},
+};
+
+use super::{
+    crypto::random_uuid,
+    health::WXHealth,
+    http::{
+        redacted_headers,
+        requests::{self, WXBodyError},
+        responses, WXResponseBody,
     },
-    runner::WXMode,
-    timeout_duration,
+    metrics::{WXMetrics, WXRouteLabel},
+    runtime::{WXRuntimeError, WXRuntimeMessage, WXSocketFrame, WXSocketOpen},
 };
 
-use super::runtime::{WXRuntimeError, WXRuntimeMessage};
+/// How often the server checks whether the running flag of a shutdown signal was unset.
+/// Triggering the signal wakes the server immediately instead.
+const SHUTDOWN_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// The most connections served at once if not configured.
+pub const DEFAULT_MAX_CONNECTIONS: usize = 1024;
+
+/// The host the server listens on if none is configured.
+pub const DEFAULT_HOST: &str = "localhost";
 
 /// A failable type.
 pub type WXFailable<T> = Result<T, WXRuntimeError>;
 
+/// The signal to shut down a server.
+///
+/// Triggering the signal wakes the server as soon as it is waiting for connections.
+/// For compatibility, the server also shuts down once the `running` flag of the signal
+/// is unset directly, although it only notices this within `SHUTDOWN_POLL_INTERVAL`.
+#[derive(Debug, Clone)]
+pub struct WXShutdown {
+    running: Arc<AtomicBool>,
+    notify: Arc<Notify>,
+}
+
+impl WXShutdown {
+    /// A signal for the given running flag, which is unset once the signal is triggered.
+    pub fn new(running: Arc<AtomicBool>) -> Self {
+        WXShutdown {
+            running,
+            notify: Arc::new(Notify::new()),
+        }
+    }
+
+    /// Shut down the server, e.g. from a Ctrl+C handler.
+    pub fn trigger(&self) {
+        self.running.store(false, Ordering::SeqCst);
+        self.notify.notify_waiters();
+    }
+
+    /// Whether the signal was triggered or the running flag was unset.
+    pub fn is_triggered(&self) -> bool {
+        !self.running.load(Ordering::SeqCst)
+    }
+
+    /// The running flag of the signal.
+    pub fn running(&self) -> Arc<AtomicBool> {
+        self.running.clone()
+    }
+
+    /// Wait until the signal is triggered or the running flag is unset.
+    pub async fn triggered(&self) {
+        loop {
+            let notified = self.notify.notified();
+            tokio::pin!(notified);
+            // Register before checking the flag, so no trigger is missed in between.
+            notified.as_mut().enable();
+            if self.is_triggered() {
+                return;
+            }
+            let _ = timeout(SHUTDOWN_POLL_INTERVAL, notified).await;
+        }
+    }
+}
+
+impl Default for WXShutdown {
+    fn default() -> Self {
+        WXShutdown::new(Arc::new(AtomicBool::new(true)))
+    }
+}
+
+impl From<Arc<AtomicBool>> for WXShutdown {
+    fn from(running: Arc<AtomicBool>) -> Self {
+        WXShutdown::new(running)
+    }
+}
+
 impl From<std::io::Error> for WXRuntimeError {
     fn from(err: std::io::Error) -> Self {
         WXRuntimeError {
             code: 500,
+            http_status: 500,
             message: format!("IO error: {}", err),
         }
     }
@@ -46,102 +141,516 @@ impl From<std::io::Error> for WXRuntimeError {
 /// The WebX web server.
 pub struct WXServer {
     mode: WXMode,
-    _config: ProjectConfig,
+    config: ProjectConfig,
     runtime_tx: Arc<Sender<WXRuntimeMessage>>,
+    /// Set by the runtime once all modules are loaded.
+    ready: Arc<AtomicBool>,
+    /// Opens the URL of the server once it is listening.
+    opener: Option<WXOpener>,
 }
 
 impl WXServer {
-    pub fn new(mode: WXMode, config: ProjectConfig, rt_tx: Sender<WXRuntimeMessage>) -> Self {
+    pub fn new(
+        mode: WXMode,
+        config: ProjectConfig,
+        rt_tx: Sender<WXRuntimeMessage>,
+        ready: Arc<AtomicBool>,
+    ) -> Self {
         WXServer {
             mode,
-            _config: config,
+            config,
             runtime_tx: Arc::new(rt_tx),
+            ready,
+            opener: None,
         }
     }
 
-    fn ports(&self) -> Vec<u16> {
-        if self.mode.is_dev() {
-            vec![8080]
+    /// Open the URL of the server with `opener` once it is listening, e.g. in a browser.
+    /// Nothing is opened when listening on a Unix socket.
+    pub fn open_when_listening(&mut self, opener: WXOpener) {
+        self.opener = Some(opener);
+    }
+
+    /// The URL of the server for a browser, on `localhost` if listening on all interfaces.
+    fn browser_url(&self) -> String {
+        let host = match self.host() {
+            "0.0.0.0" | "::" | "[::]" => DEFAULT_HOST,
+            host => host,
+        };
+        if host.contains(':') && !host.starts_with('[') {
+            format!("http://[{}]:{}/", host, self.config.port)
         } else {
-            vec![80, 443]
+            format!("http://{}:{}/", host, self.config.port)
         }
     }
 
-    fn addrs(&self) -> Vec<std::net::SocketAddr> {
-        self.ports()
-            .iter()
-            .map(|port| SocketAddr::from(([127, 0, 0, 1], *port)))
-            .collect::<Vec<_>>()
+    fn host(&self) -> &str {
+        self.config.host.as_deref().unwrap_or(DEFAULT_HOST)
+    }
+
+    /// The addresses the configured host and port resolve to.
+    /// The server listens on the first one that can be bound.
+    pub fn addrs(&self) -> WXFailable<Vec<SocketAddr>> {
+        let host = self.host();
+        (host, self.config.port)
+            .to_socket_addrs()
+            .map(|addrs| addrs.collect())
+            .map_err(|err| WXRuntimeError {
+                code: 500,
+                http_status: 500,
+                message: format!("Failed to resolve host '{}': {}", host, err),
+            })
+    }
+
+    /// The configured Unix socket to listen on instead of the host and port.
+    fn unix_socket(&self) -> Option<&Path> {
+        self.config
+            .listen
+            .as_ref()
+            .and_then(|listen| listen.unix.as_deref())
+    }
+
+    /// Listen on the configured Unix socket, or the host and port.
+    async fn bind(&self) -> WXFailable<WXListener> {
+        match self.unix_socket() {
+            Some(path) => bind_unix(path),
+            None => {
+                let listener = tokio::net::TcpListener::bind(&self.addrs()?[..]).await?;
+                Ok(WXListener::Tcp(listener))
+            }
+        }
     }
 
     fn log_startup(&mut self) {
+        let address = match self.unix_socket() {
+            Some(path) => format!("unix:{}", path.display()),
+            None => format!("http://{}:{}", self.host(), self.config.port),
+        };
         info(
             self.mode,
-            &format!(
-                "WebX server is listening on: {}",
-                self.ports()
-                    .iter()
-                    .map(|p| format!("http://localhost:{}", p))
-                    .collect::<Vec<_>>()
-                    .join(", ")
-            ),
+            &format!("WebX server is listening on: {}", address),
         );
+        if let (Some(opener), None) = (self.opener.take(), self.unix_socket()) {
+            opener(&self.browser_url());
+        }
     }
 
-    /// Starts the WebX web server and listens for incoming requests in its own thread.
-    pub fn run(&mut self, running: Arc<AtomicBool>) -> WXFailable<()> {
-        // Multi-threading pool via asynchronous tokio worker threads.
-        let runtime = tokio::runtime::Builder::new_multi_thread()
-            .thread_name("webx-server")
-            .enable_all()
-            .build()
-            // .worker_threads(4)
-            .unwrap();
-        runtime.block_on(self.run_async(running))?;
+    /// The multi-threaded tokio runtime serving the connections,
+    /// with the configured number of worker threads or one per CPU core.
+    pub fn build_runtime(&self) -> WXFailable<tokio::runtime::Runtime> {
+        let mut builder = tokio::runtime::Builder::new_multi_thread();
+        builder.thread_name("webx-server").enable_all();
+        if let Some(threads) = self.config.worker_threads {
+            builder.worker_threads(threads);
+        }
+        Ok(builder.build()?)
+    }
+
+    /// Starts the WebX web server and listens for incoming requests in its own thread,
+    /// until `shutdown` is triggered.
+    pub fn run(&mut self, shutdown: impl Into<WXShutdown>) -> WXFailable<()> {
+        let runtime = self.build_runtime()?;
+        info(
+            self.mode,
+            &format!(
+                "Serving connections with {} worker thread(s)",
+                runtime.metrics().num_workers()
+            ),
+        );
+        runtime.block_on(self.run_async(shutdown.into()))?;
         runtime.shutdown_background(); // Shutdown the runtime.
         Ok(())
     }
 
-    async fn run_async(&mut self, running: Arc<AtomicBool>) -> WXFailable<()> {
-        let listener = tokio::net::TcpListener::bind(&self.addrs()[..]).await?;
-        let svc = WXSvc::new(self.mode, self.runtime_tx.clone());
+    async fn run_async(&mut self, shutdown: WXShutdown) -> WXFailable<()> {
+        let listener = self.bind().await?;
+        let limits = WXConnectionLimits::new(&self.config);
+        let svc = WXSvc::new(
+            self.mode,
+            self.runtime_tx.clone(),
+            &self.config,
+            self.ready.clone(),
+        );
         self.log_startup();
+        let (shutdown_tx, shutdown_rx) = watch::channel(false);
+        let mut connections = JoinSet::new();
+        let max_connections = self
+            .config
+            .max_connections
+            .unwrap_or(DEFAULT_MAX_CONNECTIONS);
+        let slots = Arc::new(Semaphore::new(max_connections));
         loop {
-            if !running.load(Ordering::SeqCst) {
-                // println!("Shutting down web server...");
-                return Ok(()); // Shutdown the server.
+            if shutdown.is_triggered() {
+                break; // Stop accepting connections.
             }
-            let (stream, addr) = match timeout(timeout_duration(self.mode), listener.accept()).await
-            {
-                Ok(Ok((stream, addr))) => (stream, addr),
-                Ok(Err(err)) => {
-                    eprintln!("Failed to accept connection: {}", err);
-                    continue;
+            // Forget connections that have been served.
+            while let Some(Some(_)) = connections.join_next().now_or_never() {}
+            // Only accept connections while there are free slots,
+            // further clients wait in the backlog of the listener.
+            let slot = tokio::select! {
+                slot = slots.clone().acquire_owned() => match slot {
+                    Ok(slot) => slot,
+                    Err(_) => continue,
+                },
+                _ = shutdown.triggered() => break,
+            };
+            let (stream, addr) = tokio::select! {
+                accepted = listener.accept() => match accepted {
+                    Ok((stream, addr)) => (stream, addr),
+                    Err(err) => {
+                        warning(self.mode, format!("Failed to accept connection: {}", err));
+                        continue;
+                    }
+                },
+                _ = shutdown.triggered() => break,
+            };
+            let svc = svc.clone_with_address(addr);
+            let shutdown = shutdown_rx.clone();
+            let served: Pin<Box<dyn Future<Output = WXFailable<()>> + Send>> = match stream {
+                WXStream::Tcp(stream) => {
+                    Box::pin(Self::serve(TokioIo::new(stream), svc, limits, shutdown))
+                }
+                #[cfg(unix)]
+                WXStream::Unix(stream) => {
+                    Box::pin(Self::serve(TokioIo::new(stream), svc, limits, shutdown))
                 }
-                Err(_) => continue,
             };
-            tokio::spawn(Self::serve(
-                TokioIo::new(stream),
-                svc.clone_with_address(addr),
-            ));
+            connections.spawn(async move {
+                let result = served.await;
+                drop(slot); // Free the slot once the connection is closed.
+                result
+            });
+        }
+        drop(listener);
+        self.drain(connections, shutdown_tx).await;
+        Ok(())
+    }
+
+    /// Let the open connections finish the requests in progress, then close them.
+    /// Connections still open after the shutdown timeout are aborted.
+    async fn drain(&self, mut connections: JoinSet<WXFailable<()>>, shutdown: watch::Sender<bool>) {
+        if connections.is_empty() {
+            return;
+        }
+        let _ = shutdown.send(true);
+        let shutdown_timeout = timeout_duration(self.mode);
+        let drained = async { while connections.join_next().await.is_some() {} };
+        if timeout(shutdown_timeout, drained).await.is_err() {
+            warning(
+                self.mode,
+                format!(
+                    "Closing {} connection(s) that did not finish within {:?}",
+                    connections.len(),
+                    shutdown_timeout
+                ),
+            );
+            connections.abort_all();
         }
     }
 
     /// Serves a single connection.
     /// This is the main entry point for each connection to the server
     /// and simply passes the connection to the request handler `WXSvc` service.
-    async fn serve(io: TokioIo<tokio::net::TcpStream>, svc: WXSvc) -> WXFailable<()> {
+    async fn serve<T>(
+        io: TokioIo<T>,
+        svc: WXSvc,
+        limits: WXConnectionLimits,
+        shutdown: watch::Receiver<bool>,
+    ) -> WXFailable<()>
+    where
+        T: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin + Send + 'static,
+    {
         let addr = svc
             .address
             .expect("No address found while serving connection.");
-        if let Err(err) = http1::Builder::new().serve_connection(io, svc).await {
+        serve_connection(io, svc, limits, shutdown)
+            .await
+            .map_err(|message| WXRuntimeError {
+                code: 500,
+                http_status: 500,
+                message: format!("failed to serve connection {}: {}", addr, message),
+            })
+    }
+}
+
+/// The peer address of connections over a Unix socket, which have no IP address.
+#[cfg(unix)]
+const UNIX_PEER_ADDR: SocketAddr = SocketAddr::V4(std::net::SocketAddrV4::new(
+    std::net::Ipv4Addr::LOCALHOST,
+    0,
+));
+
+/// A socket the server listens on.
+enum WXListener {
+    Tcp(tokio::net::TcpListener),
+    /// A Unix socket at the path, which is removed once the listener is dropped.
+    #[cfg(unix)]
+    Unix(tokio::net::UnixListener, std::path::PathBuf),
+}
+
+/// An accepted connection.
+enum WXStream {
+    Tcp(tokio::net::TcpStream),
+    #[cfg(unix)]
+    Unix(tokio::net::UnixStream),
+}
+
+impl WXListener {
+    async fn accept(&self) -> std::io::Result<(WXStream, SocketAddr)> {
+        match self {
+            WXListener::Tcp(listener) => {
+                let (stream, addr) = listener.accept().await?;
+                Ok((WXStream::Tcp(stream), addr))
+            }
+            #[cfg(unix)]
+            WXListener::Unix(listener, _) => {
+                let (stream, _) = listener.accept().await?;
+                Ok((WXStream::Unix(stream), UNIX_PEER_ADDR))
+            }
+        }
+    }
+}
+
+#[cfg(unix)]
+impl Drop for WXListener {
+    fn drop(&mut self) {
+        if let WXListener::Unix(_, path) = self {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
+
+/// Listen on a Unix socket at the path.
+/// A stale socket left behind by a previous server is replaced,
+/// but not a socket another server is still listening on, nor any other file.
+#[cfg(unix)]
+fn bind_unix(path: &Path) -> WXFailable<WXListener> {
+    use std::os::unix::fs::FileTypeExt;
+
+    let failed = |reason: &str| WXRuntimeError {
+        code: 500,
+        http_status: 500,
+        message: format!("Failed to listen on '{}': {}", path.display(), reason),
+    };
+    if let Ok(metadata) = std::fs::symlink_metadata(path) {
+        if !metadata.file_type().is_socket() {
+            return Err(failed("the file exists and is not a socket"));
+        }
+        if std::os::unix::net::UnixStream::connect(path).is_ok() {
+            return Err(failed("another server is listening on the socket"));
+        }
+        std::fs::remove_file(path)?;
+    }
+    let listener = tokio::net::UnixListener::bind(path).map_err(|err| failed(&err.to_string()))?;
+    Ok(WXListener::Unix(listener, path.to_path_buf()))
+}
+
+#[cfg(not(unix))]
+fn bind_unix(path: &Path) -> WXFailable<WXListener> {
+    Err(WXRuntimeError {
+        code: 500,
+        http_status: 500,
+        message: format!(
+            "Failed to listen on '{}': Unix sockets are not supported on this platform",
+            path.display()
+        ),
+    })
+}
+
+/// The default time a client has to send the complete request headers.
+pub const DEFAULT_HEADER_READ_TIMEOUT: Duration = Duration::from_secs(10);
+/// The default time a request may take to be answered, see `respond_within`.
+pub const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(60);
+/// The default maximum size of the request headers in bytes.
+pub const DEFAULT_MAX_HEADER_SIZE: usize = 64 * 1024;
+
+/// Limits applied to every connection.
+/// These protect the server against slow clients trickling bytes (slowloris)
+/// and oversized headers, which would otherwise hold a connection indefinitely.
+#[derive(Debug, Clone, Copy)]
+pub struct WXConnectionLimits {
+    /// The time a client has to send the complete headers of a request.
+    /// Also closes idle keep-alive connections that send no further request.
+    pub header_read_timeout: Duration,
+    pub max_header_size: usize,
+    /// Whether a connection is reused for multiple requests (HTTP/1.1 keep-alive).
+    pub keep_alive: bool,
+}
+
+impl WXConnectionLimits {
+    pub fn new(config: &ProjectConfig) -> Self {
+        WXConnectionLimits {
+            header_read_timeout: config
+                .header_read_timeout_ms
+                .map(Duration::from_millis)
+                .unwrap_or(DEFAULT_HEADER_READ_TIMEOUT),
+            // Hyper requires a buffer of at least 8 KiB.
+            max_header_size: config
+                .max_header_size
+                .unwrap_or(DEFAULT_MAX_HEADER_SIZE)
+                .max(8192),
+            keep_alive: config.keep_alive.unwrap_or(true),
+        }
+    }
+}
+
+/// The configured time a request may take to be answered, see `respond_within`.
+fn request_timeout(config: &ProjectConfig) -> Duration {
+    config
+        .request_timeout_ms
+        .map(Duration::from_millis)
+        .unwrap_or(DEFAULT_REQUEST_TIMEOUT)
+}
+
+/// Answer with `503 Service Unavailable` if the response is not ready within `request_timeout`.
+///
+/// Only producing the response is limited, not sending its body,
+/// so event streams and large files are served for as long as they take.
+pub async fn respond_within(
+    mode: WXMode,
+    request_timeout: Duration,
+    response: impl Future<Output = Result<Response<WXResponseBody>, WXRuntimeError>>,
+) -> Result<Response<WXResponseBody>, WXRuntimeError> {
+    match timeout(request_timeout, response).await {
+        Ok(result) => result,
+        Err(_) => {
+            let message = format!("Request was not answered within {:?}", request_timeout);
+            warning(mode, message.clone());
+            let response =
+                responses::error_status(mode, hyper::StatusCode::SERVICE_UNAVAILABLE, message);
+            Ok(response.map(WXResponseBody::from))
+        }
+    }
+}
+
+/// Serve a single HTTP/1 connection within the given limits.
+/// The connection is closed once a limit is exceeded,
+/// or after the first response if keep-alive is disabled.
+///
+/// Once `shutdown` is set, the request in progress is finished
+/// and the connection is closed instead of kept alive.
+pub async fn serve_connection<I, S, B>(
+    io: I,
+    svc: S,
+    limits: WXConnectionLimits,
+    mut shutdown: watch::Receiver<bool>,
+) -> Result<(), String>
+where
+    I: hyper::rt::Read + hyper::rt::Write + Unpin + Send + 'static,
+    S: HttpService<Incoming, ResBody = B>,
+    S::Error: Into<Box<dyn std::error::Error + Send + Sync>>,
+    B: hyper::body::Body + 'static,
+    B::Error: Into<Box<dyn std::error::Error + Send + Sync>>,
+{
+    let connection = http1::Builder::new()
+        .timer(TokioTimer::new())
+        .header_read_timeout(limits.header_read_timeout)
+        .keep_alive(limits.keep_alive)
+        .max_buf_size(limits.max_header_size)
+        .serve_connection(io, svc)
+        .with_upgrades();
+    let mut connection = std::pin::pin!(connection);
+    tokio::select! {
+        result = connection.as_mut() => return result.map_err(|err| format!("{:?}", err)),
+        Ok(_) = shutdown.wait_for(|shutdown| *shutdown) => {}
+    }
+    connection.as_mut().graceful_shutdown();
+    connection.await.map_err(|err| format!("{:?}", err))
+}
+
+/// Perform the WebSocket handshake for a `ws` route,
+/// and bridge the connection to the runtime once it is upgraded.
+async fn upgrade_websocket(
+    mode: WXMode,
+    addr: SocketAddr,
+    runtime_tx: Arc<Sender<WXRuntimeMessage>>,
+    mut req: Request<Incoming>,
+) -> Result<Response<WXResponseBody>, WXRuntimeError> {
+    let key = requests::websocket_key(&req).unwrap_or_default();
+    let accept = derive_accept_key(key.as_bytes());
+    let on_upgrade = hyper::upgrade::on(&mut req);
+    let (parts, _) = req.into_parts();
+    let (tx, rx) = tokio::sync::oneshot::channel();
+    let opened = match runtime_tx.send(WXRuntimeMessage::OpenSocket {
+        request: Request::from_parts(parts, Bytes::new()),
+        addr,
+        respond_to: tx,
+    }) {
+        Ok(()) => rx.await.map_err(|err| err.to_string()),
+        Err(err) => Err(err.to_string()),
+    };
+    let (id, frames) = match opened {
+        Ok(WXSocketOpen::Accepted { id, frames }) => (id, frames),
+        Ok(WXSocketOpen::Rejected(response)) => return Ok(response),
+        Err(err) => {
+            let error_msg = format!("Failed to open WebSocket due to: {}", err);
+            error_code(error_msg.clone(), ERROR_EXEC_ROUTE, mode.date_specifier());
             return Err(WXRuntimeError {
                 code: 500,
-                message: format!("failed to serve connection {}: {:?}", addr, err),
+                http_status: 500,
+                message: error_msg,
             });
         }
-        Ok(())
+    };
+    tokio::spawn(async move {
+        match on_upgrade.await {
+            Ok(upgraded) => {
+                serve_websocket(TokioIo::new(upgraded), id, frames, runtime_tx, mode).await
+            }
+            Err(err) => {
+                warning(mode, format!("WebSocket {} upgrade failed: {}", id, err));
+                let _ = runtime_tx.send(WXRuntimeMessage::SocketClosed { id });
+            }
+        }
+    });
+    Ok(responses::switching_protocols(mode, &accept).map(WXResponseBody::from))
+}
+
+/// Bridge an upgraded WebSocket connection to its socket in the runtime.
+/// Received text messages are passed on to the runtime, and frames sent
+/// by the runtime are written to the connection, until either side closes it.
+pub async fn serve_websocket<I>(
+    io: I,
+    id: u64,
+    mut frames: UnboundedReceiver<WXSocketFrame>,
+    runtime_tx: Arc<Sender<WXRuntimeMessage>>,
+    mode: WXMode,
+) where
+    I: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin,
+{
+    let mut socket = WebSocketStream::from_raw_socket(io, Role::Server, None).await;
+    loop {
+        tokio::select! {
+            frame = frames.recv() => match frame {
+                Some(WXSocketFrame::Text(text)) => {
+                    if socket.send(Message::Text(text)).await.is_err() {
+                        break;
+                    }
+                }
+                // The route closed the socket, or its module was unloaded.
+                Some(WXSocketFrame::Close) | None => {
+                    let _ = socket.close(None).await;
+                    break;
+                }
+            },
+            message = socket.next() => match message {
+                Some(Ok(Message::Text(message))) => {
+                    let message = WXRuntimeMessage::SocketMessage { id, message };
+                    if runtime_tx.send(message).is_err() {
+                        break;
+                    }
+                }
+                Some(Ok(Message::Binary(_))) => warning(
+                    mode,
+                    format!("WebSocket {} ignored an unsupported binary message", id),
+                ),
+                // Pings and the closing handshake are answered by the stream.
+                Some(Ok(_)) => {}
+                Some(Err(_)) | None => break,
+            },
+        }
     }
+    let _ = runtime_tx.send(WXRuntimeMessage::SocketClosed { id });
 }
 
 /// The WebX server context.
@@ -153,14 +662,43 @@ struct WXSvc {
     mode: WXMode,
     address: Option<SocketAddr>,
     runtime_tx: Arc<Sender<WXRuntimeMessage>>,
+    /// The maximum size of a request body in bytes.
+    max_body_size: usize,
+    /// The time a request may take to be answered, see `respond_within`.
+    request_timeout: Duration,
+    /// Headers masked in debug logs.
+    redact_headers: Arc<Vec<String>>,
+    /// The metrics registry, if enabled.
+    metrics: Option<Arc<WXMetrics>>,
+    /// The health probes, if enabled.
+    health: Option<Arc<WXHealth>>,
+    /// The security headers added to every response.
+    security_headers: Option<Arc<SecurityHeadersConfig>>,
+    /// Whether the `X-Forwarded-Proto` header of a reverse proxy is honored.
+    trust_proxy: bool,
 }
 
 impl WXSvc {
-    pub fn new(mode: WXMode, rt_tx: Arc<Sender<WXRuntimeMessage>>) -> Self {
+    /// The service for a project, setting the runtime ready flag for the readiness probe.
+    pub fn new(
+        mode: WXMode,
+        rt_tx: Arc<Sender<WXRuntimeMessage>>,
+        config: &ProjectConfig,
+        ready: Arc<AtomicBool>,
+    ) -> Self {
         WXSvc {
             mode,
             address: None, // Get the address from the request.
             runtime_tx: rt_tx,
+            max_body_size: config
+                .max_body_size
+                .unwrap_or(requests::DEFAULT_MAX_BODY_SIZE),
+            request_timeout: request_timeout(config),
+            redact_headers: Arc::new(redacted_headers(config)),
+            metrics: WXMetrics::from_config(config).map(Arc::new),
+            health: WXHealth::from_config(config, ready).map(Arc::new),
+            security_headers: config.security_headers.clone().map(Arc::new),
+            trust_proxy: config.trust_proxy.unwrap_or(false),
         }
     }
 
@@ -170,15 +708,18 @@ impl WXSvc {
         new
     }
 
-    fn _ok(&self, text: String) -> Result<Response<Full<Bytes>>, hyper::Error> {
-        Ok(Response::new(Full::new(Bytes::from(text))))
+    fn _ok(&self, text: String) -> Result<Response<WXResponseBody>, hyper::Error> {
+        Ok(Response::new(WXResponseBody::from(text)))
     }
 }
 
+type WXSvcFuture =
+    Pin<Box<dyn Future<Output = Result<Response<WXResponseBody>, WXRuntimeError>> + Send>>;
+
 impl Service<Request<Incoming>> for WXSvc {
-    type Response = Response<Full<Bytes>>;
+    type Response = Response<WXResponseBody>;
     type Error = WXRuntimeError;
-    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+    type Future = WXSvcFuture;
 
     /// The WebX server request handler.
     /// This is the main entry point for all requests.
@@ -192,52 +733,156 @@ impl Service<Request<Incoming>> for WXSvc {
     /// - Respond back to the client.
     ///
     /// But most importantly, it will communicate with the WebX engine and runtimes.
-    fn call(&self, req: Request<Incoming>) -> Self::Future {
-        if self.mode.debug_level().is_max() {
-            info(
-                self.mode,
-                &format!(
-                    "Request from: {}\n{}",
-                    self.address.unwrap(),
-                    super::http::requests::serialize(&req)
-                ),
-            );
-        } else if self.mode.debug_level().is_high() {
-            info(
-                self.mode,
-                &format!("Request from: {}", self.address.unwrap()),
-            );
+    ///
+    /// ## Request id
+    /// Every request is tagged with the id in its `X-Request-Id` header, or a new random id.
+    /// The id is included in the logs of the request, bound as `req.id` in the runtime,
+    /// and echoed back in the `X-Request-Id` header of the response.
+    ///
+    /// ## Security headers
+    /// The configured security headers are added to every response,
+    /// including those generated by the server itself, e.g. `413 Payload Too Large`.
+    fn call(&self, mut req: Request<Incoming>) -> Self::Future {
+        let request_id = requests::request_id(req.headers()).unwrap_or_else(random_uuid);
+        let header = HeaderValue::from_str(&request_id).expect("request ids are visible ASCII");
+        req.headers_mut()
+            .insert(requests::REQUEST_ID_HEADER, header.clone());
+        let mode = self.mode;
+        let is_https = requests::is_https(&req, self.trust_proxy);
+        let security_headers = self.security_headers.clone();
+        let response = with_request_id(&request_id, || self.respond(req, request_id.clone()));
+        Box::pin(async move {
+            let mut response = response.await?;
+            response
+                .headers_mut()
+                .insert(requests::REQUEST_ID_HEADER, header);
+            Ok(responses::with_security_headers(
+                mode,
+                response,
+                security_headers.as_deref(),
+                is_https,
+            ))
+        })
+    }
+}
+
+impl WXSvc {
+    /// Respond to a request tagged with the given id, see `call`.
+    fn respond(&self, req: Request<Incoming>, request_id: String) -> WXSvcFuture {
+        // Requests are only serialized when they are logged.
+        if is_info_enabled(self.mode) {
+            if self.mode.debug_level().is_max() {
+                info(
+                    self.mode,
+                    &format!(
+                        "Request from: {}\n{}",
+                        self.address.unwrap(),
+                        requests::serialize(&req, &self.redact_headers)
+                    ),
+                );
+            } else if self.mode.debug_level().is_high() {
+                info(
+                    self.mode,
+                    &format!("Request from: {}", self.address.unwrap()),
+                );
+            }
+        }
+        let mode = self.mode;
+        let addr = self.address.unwrap();
+        let max_body_size = self.max_body_size;
+        let runtime_tx = self.runtime_tx.clone();
+        // Probes are handled before route resolution and are not counted.
+        if let Some(response) = self
+            .health
+            .as_ref()
+            .and_then(|health| health.respond(mode, req.uri().path()))
+        {
+            return Box::pin(async move { Ok(response.map(WXResponseBody::from)) });
+        }
+        if let Some(metrics) = &self.metrics {
+            if req.method() == hyper::Method::GET && req.uri().path() == metrics.path {
+                // Not counted, to avoid feedback from scraping.
+                let response = responses::metrics(mode, metrics.render());
+                return Box::pin(async move { Ok(response.map(WXResponseBody::from)) });
+            }
         }
-        let date_spec = self.mode.date_specifier();
-        // Send the actor RPC request via channels to the runtime.
-        let (tx, rx) = tokio::sync::oneshot::channel();
-        if let Err(err) = self.runtime_tx.send(WXRuntimeMessage::ExecuteRoute {
-            request: req,
-            addr: self.address.unwrap(),
-            respond_to: tx,
-        }) {
-            let error_msg = format!("Failed to execute route due to: {}", err);
-            error_code(error_msg.clone(), ERROR_EXEC_ROUTE, date_spec);
-            Box::pin(async move {
-                Err(WXRuntimeError {
+        let method = req.method().clone();
+        let response = async move {
+            if requests::websocket_key(&req).is_some() {
+                return upgrade_websocket(mode, addr, runtime_tx, req).await;
+            }
+            // Read the request body before handing it to the single-threaded runtime.
+            let (parts, body) = req.into_parts();
+            let body = match requests::read_body(&parts.headers, body, max_body_size).await {
+                Ok(body) => body,
+                Err(WXBodyError::TooLarge) => {
+                    with_request_id(&request_id, || {
+                        warning(
+                            mode,
+                            format!("Request body from {} exceeds {} bytes", addr, max_body_size),
+                        )
+                    });
+                    let response = responses::payload_too_large(mode, max_body_size);
+                    return Ok(response.map(WXResponseBody::from));
+                }
+                Err(WXBodyError::Read(err)) => {
+                    let message = format!("Failed to read request body from {}: {}", addr, err);
+                    with_request_id(&request_id, || warning(mode, message.clone()));
+                    let response =
+                        responses::error_status(mode, hyper::StatusCode::BAD_REQUEST, message);
+                    return Ok(response.map(WXResponseBody::from));
+                }
+            };
+            // Send the actor RPC request via channels to the runtime.
+            let (tx, rx) = tokio::sync::oneshot::channel();
+            if let Err(err) = runtime_tx.send(WXRuntimeMessage::ExecuteRoute {
+                request: Request::from_parts(parts, body),
+                addr,
+                respond_to: tx,
+            }) {
+                let error_msg = format!("Failed to execute route due to: {}", err);
+                with_request_id(&request_id, || {
+                    error_code(error_msg.clone(), ERROR_EXEC_ROUTE, mode.date_specifier())
+                });
+                return Err(WXRuntimeError {
                     code: 500,
+                    http_status: 500,
                     message: error_msg,
-                })
-            })
-        } else {
-            Box::pin(async move {
-                match rx.await {
-                    Ok(value) => value,
-                    Err(err) => {
-                        let error_msg = format!("Failed to execute route due to: {}", err);
-                        error_code(error_msg.clone(), ERROR_EXEC_ROUTE, date_spec);
-                        Err(WXRuntimeError {
-                            code: 500,
-                            message: error_msg,
-                        })
-                    }
+                });
+            }
+            match rx.await {
+                Ok(value) => value,
+                Err(err) => {
+                    let error_msg = format!("Failed to execute route due to: {}", err);
+                    with_request_id(&request_id, || {
+                        error_code(error_msg.clone(), ERROR_EXEC_ROUTE, mode.date_specifier())
+                    });
+                    Err(WXRuntimeError {
+                        code: 500,
+                        http_status: 500,
+                        message: error_msg,
+                    })
                 }
-            })
-        }
+            }
+        };
+        let response = respond_within(mode, self.request_timeout, response);
+        let Some(metrics) = self.metrics.clone() else {
+            return Box::pin(response);
+        };
+        metrics.record_request();
+        let start = Instant::now();
+        Box::pin(async move {
+            let result = response.await;
+            let (status, route) = match &result {
+                Ok(response) => (
+                    response.status().as_u16(),
+                    response.extensions().get::<WXRouteLabel>().cloned(),
+                ),
+                Err(_) => (500, None),
+            };
+            let route = route.as_ref().map(|label| label.0.as_str());
+            metrics.record_response(&method, route, status, start.elapsed());
+            result
+        })
     }
 }
@@ -0,0 +1,227 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use deno_core::{
+    v8::{Global, Local, Value},
+    JsRuntime,
+};
+use hyper::{header::HeaderValue, HeaderMap};
+use serde::Deserialize;
+
+use crate::file::project::{parse_duration, SessionConfig};
+
+use super::{
+    crypto,
+    http::requests,
+    runtime::{WXRTContext, WXRuntimeError},
+};
+
+/// The name of the cookie holding the signed session id.
+pub const SESSION_COOKIE: &str = "webx_session";
+
+/// How long sessions last after they were last written, if not configured.
+pub const DEFAULT_SESSION_MAX_AGE: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// The number of random bytes in a session id.
+const SESSION_ID_BYTES: usize = 32;
+
+/// The number of random bytes in a CSRF token.
+const CSRF_TOKEN_BYTES: usize = 32;
+
+/// A store of session data by session id.
+/// The data is the JSON serialized `session` object of the handlers.
+pub trait WXSessionStore {
+    /// The data of a session, unless it does not exist or has expired.
+    fn load(&mut self, id: &str) -> Option<String>;
+    /// Store the data of a session, replacing any previous data.
+    fn save(&mut self, id: &str, data: String, max_age: Duration);
+    /// Remove a session.
+    fn remove(&mut self, id: &str);
+}
+
+/// A session store shared by the runtime workers.
+pub type WXSharedSessionStore = Arc<Mutex<dyn WXSessionStore + Send>>;
+
+/// An in-memory session store.
+/// Sessions are lost when the server restarts.
+#[derive(Debug, Default)]
+pub struct WXMemorySessionStore {
+    sessions: HashMap<String, (String, Instant)>,
+}
+
+impl WXSessionStore for WXMemorySessionStore {
+    fn load(&mut self, id: &str) -> Option<String> {
+        match self.sessions.get(id) {
+            Some((data, expires)) if *expires > Instant::now() => Some(data.clone()),
+            Some(_) => {
+                self.sessions.remove(id);
+                None
+            }
+            None => None,
+        }
+    }
+
+    fn save(&mut self, id: &str, data: String, max_age: Duration) {
+        let now = Instant::now();
+        self.sessions.retain(|_, (_, expires)| *expires > now);
+        self.sessions.insert(id.to_string(), (data, now + max_age));
+    }
+
+    fn remove(&mut self, id: &str) {
+        self.sessions.remove(id);
+    }
+}
+
+/// The signed cookie value of a session id, `<id>.<hmac>`.
+pub fn sign_session_id(secret: &str, id: &str) -> String {
+    let signature = crypto::hmac("sha256", secret.as_bytes(), id.as_bytes()).unwrap();
+    format!("{}.{}", id, signature)
+}
+
+/// The session id of a signed cookie value, if the signature is valid.
+pub fn verify_session_id<'a>(secret: &str, value: &'a str) -> Option<&'a str> {
+    let (id, _) = value.split_once('.')?;
+    let expected = sign_session_id(secret, id);
+    crypto::constant_time_eq(expected.as_bytes(), value.as_bytes()).then_some(id)
+}
+
+/// What the handlers did with the session of a request.
+#[derive(Deserialize)]
+struct WXRTSessionState {
+    data: serde_json::Value,
+    regenerate: bool,
+    destroy: bool,
+}
+
+/// The session of a request in progress.
+pub struct WXRTSession {
+    /// The id of an existing session, `None` for a new one.
+    id: Option<String>,
+}
+
+impl WXRTSession {
+    /// Look up the session of a request by its cookie, and bind it as `session`.
+    /// A new, empty session is started if there is no valid session cookie.
+    pub fn open(
+        config: &SessionConfig,
+        store: &mut dyn WXSessionStore,
+        headers: &HeaderMap,
+        rt: &mut JsRuntime,
+        ctx: &mut WXRTContext,
+    ) -> Result<Self, WXRuntimeError> {
+        let existing = requests::cookie(headers, SESSION_COOKIE)
+            .and_then(|value| verify_session_id(&config.secret, value))
+            .and_then(|id| Some((id.to_string(), store.load(id)?)));
+        let (id, data) = match existing {
+            Some((id, data)) => (Some(id), data),
+            None => (None, "{}".to_string()),
+        };
+        let script = format!(
+            "__webx_session.open({})",
+            serde_json::to_string(&data).unwrap()
+        );
+        let session = execute_session_script(rt, script)?;
+        ctx.bind("session", session);
+        Ok(WXRTSession { id })
+    }
+
+    /// The CSRF token of the session, created on first use.
+    /// Bound as `csrfToken`, so it can be embedded in forms.
+    pub fn csrf_token(
+        &self,
+        rt: &mut JsRuntime,
+        ctx: &mut WXRTContext,
+    ) -> Result<String, WXRuntimeError> {
+        let script = format!(
+            "__webx_session.csrfToken(\"{}\")",
+            crypto::random_bytes(CSRF_TOKEN_BYTES)?
+        );
+        let token = execute_session_script(rt, script)?;
+        ctx.bind("csrfToken", token.clone());
+        let scope = &mut rt.handle_scope();
+        Ok(Local::new(scope, token).to_rust_string_lossy(scope))
+    }
+
+    /// Write the session back to the store.
+    /// Returns the `Set-Cookie` header if the client has to update its session cookie.
+    ///
+    /// New sessions are only stored once data is written to them,
+    /// and the id of a session is replaced when `session.regenerate()` was called.
+    pub fn close(
+        self,
+        config: &SessionConfig,
+        store: &mut dyn WXSessionStore,
+        rt: &mut JsRuntime,
+        is_https: bool,
+    ) -> Result<Option<HeaderValue>, WXRuntimeError> {
+        let state = execute_session_script(rt, "__webx_session.close()".to_string())?;
+        let state = {
+            let scope = &mut rt.handle_scope();
+            Local::new(scope, state).to_rust_string_lossy(scope)
+        };
+        let state =
+            serde_json::from_str::<WXRTSessionState>(&state).map_err(|err| WXRuntimeError {
+                code: 500,
+                http_status: 500,
+                message: format!("Failed to read the session: {}", err),
+            })?;
+        let max_age = config
+            .max_age
+            .as_deref()
+            .and_then(parse_duration)
+            .unwrap_or(DEFAULT_SESSION_MAX_AGE);
+        if state.destroy {
+            let Some(id) = &self.id else {
+                return Ok(None);
+            };
+            store.remove(id);
+            return Ok(Some(session_cookie("", Duration::ZERO, is_https)));
+        }
+        let is_empty = state.data.as_object().is_some_and(|data| data.is_empty());
+        let id = match &self.id {
+            None if is_empty => return Ok(None),
+            Some(id) if !state.regenerate => {
+                store.save(id, state.data.to_string(), max_age);
+                return Ok(None);
+            }
+            Some(id) => {
+                // Rotate the id, so a leaked id does not carry over new privileges.
+                store.remove(id);
+                crypto::random_bytes(SESSION_ID_BYTES)?
+            }
+            None => crypto::random_bytes(SESSION_ID_BYTES)?,
+        };
+        store.save(&id, state.data.to_string(), max_age);
+        let value = sign_session_id(&config.secret, &id);
+        Ok(Some(session_cookie(&value, max_age, is_https)))
+    }
+}
+
+/// The `Set-Cookie` header of a session cookie.
+fn session_cookie(value: &str, max_age: Duration, is_https: bool) -> HeaderValue {
+    let secure = if is_https { "; Secure" } else { "" };
+    let cookie = format!(
+        "{}={}; Path=/; Max-Age={}; HttpOnly; SameSite=Lax{}",
+        SESSION_COOKIE,
+        value,
+        max_age.as_secs(),
+        secure
+    );
+    HeaderValue::from_str(&cookie).unwrap()
+}
+
+/// Execute a script of the stdlib session API.
+fn execute_session_script(
+    rt: &mut JsRuntime,
+    script: String,
+) -> Result<Global<Value>, WXRuntimeError> {
+    rt.execute_script("[webx session]", script.into())
+        .map_err(|err| WXRuntimeError {
+            code: 500,
+            http_status: 500,
+            message: format!("Session script threw an error:\n{}", err),
+        })
+}
@@ -8,4 +8,169 @@
 		},
 		static: (path) => Deno.readTextFileSync(path)
 	};
+
+	// Send an outbound HTTP or HTTPS request, resolves to `{ status, headers, body }`.
+	// Runs in the background, so concurrent requests can be awaited together.
+	globalThis.fetch = (url, options = {}) => Deno.core.opAsync("op_webx_fetch", String(url), options);
+
+	// Open WebSocket connections of `ws` routes by id.
+	// Outgoing messages are queued until drained by the runtime.
+	const sockets = new Map();
+	globalThis.__webx_sockets = {
+		create: (id) => {
+			const socket = {
+				id,
+				outbox: [],
+				closed: false,
+				messageHandlers: [],
+				closeHandlers: [],
+				onMessage: (handler) => socket.messageHandlers.push(handler),
+				onClose: (handler) => socket.closeHandlers.push(handler),
+				send: (message) => {
+					if (socket.closed) return;
+					socket.outbox.push(typeof message === "string" ? message : JSON.stringify(message));
+				},
+				close: () => {
+					socket.closed = true;
+				}
+			};
+			sockets.set(id, socket);
+			return socket;
+		},
+		dispatch: async (id, message) => {
+			const socket = sockets.get(id);
+			if (!socket) return;
+			for (const handler of socket.messageHandlers) await handler(message);
+		},
+		close: async (id) => {
+			const socket = sockets.get(id);
+			if (!socket) return;
+			sockets.delete(id);
+			socket.closed = true;
+			for (const handler of socket.closeHandlers) await handler();
+		},
+		drain: (id) => {
+			const socket = sockets.get(id);
+			if (!socket) return JSON.stringify({ messages: [], closed: true });
+			return JSON.stringify({ messages: socket.outbox.splice(0), closed: socket.closed });
+		}
+	};
+
+	// The session of the request in progress, opened and closed by the runtime.
+	// `regenerate()` and `destroy()` are not enumerable, so they are not stored.
+	let session = null;
+	let sessionState = { regenerate: false, destroy: false };
+	globalThis.__webx_session = {
+		open: (data) => {
+			sessionState = { regenerate: false, destroy: false };
+			session = JSON.parse(data);
+			Object.defineProperties(session, {
+				regenerate: { value: () => { sessionState.regenerate = true; } },
+				destroy: { value: () => { sessionState.destroy = true; } }
+			});
+			return session;
+		},
+		csrfToken: (token) => {
+			if (typeof session.csrfToken !== "string") session.csrfToken = token;
+			return session.csrfToken;
+		},
+		close: () => {
+			const state = { data: session ?? {}, ...sessionState };
+			session = null;
+			return JSON.stringify(state);
+		}
+	};
+
+	// Headers set by the handlers of the request in progress, applied to its response.
+	// Setting a header again replaces its previous value.
+	let responseHeaders = [];
+	// Framing and hop-by-hop headers are managed by the server, not by handlers.
+	const reservedHeaders = [
+		"connection",
+		"content-length",
+		"keep-alive",
+		"proxy-connection",
+		"te",
+		"trailer",
+		"transfer-encoding",
+		"upgrade",
+	];
+	globalThis.setHeader = (name, value) => {
+		name = String(name);
+		value = String(value);
+		if (!/^[!#$%&'*+\-.^_`|~0-9A-Za-z]+$/.test(name)) {
+			throw new TypeError(`setHeader: invalid header name '${name}'`);
+		}
+		if (/[\r\n\0]/.test(value)) {
+			throw new TypeError(`setHeader: invalid value for header '${name}'`);
+		}
+		const key = name.toLowerCase();
+		if (reservedHeaders.includes(key)) {
+			throw new TypeError(`setHeader: the '${name}' header is set by the server`);
+		}
+		responseHeaders = responseHeaders.filter(([other]) => other.toLowerCase() !== key);
+		responseHeaders.push([name, value]);
+	};
+	// The last `error()` raised by the request in progress, as `{ status, message }`.
+	let userError = null;
+	// Stop the handler chain of the request with a user-facing error response.
+	globalThis.error = (message, status = 400) => {
+		if (!Number.isInteger(status) || status < 400 || status > 599) {
+			throw new TypeError(`error: expected an error status between 400 and 599, got '${status}'`);
+		}
+		userError = { status, message: String(message) };
+		const error = new Error(userError.message);
+		error.status = status;
+		throw error;
+	};
+	globalThis.__webx_response = {
+		open: () => {
+			responseHeaders = [];
+			userError = null;
+		},
+		close: () => JSON.stringify(responseHeaders.splice(0)),
+		error: () => userError
+	};
+
+	// Date helpers, defined as plain globals so user code of the same name replaces them.
+	const toDate = (name, date) => {
+		const value = date instanceof Date ? date : new Date(date);
+		if (date === undefined || date === null || Number.isNaN(value.getTime())) {
+			throw new TypeError(`${name}: invalid date '${date}'`);
+		}
+		return value;
+	};
+	const timeUnits = [
+		["year", 365 * 24 * 60 * 60],
+		["month", 30 * 24 * 60 * 60],
+		["week", 7 * 24 * 60 * 60],
+		["day", 24 * 60 * 60],
+		["hour", 60 * 60],
+		["minute", 60],
+		["second", 1]
+	];
+	// The time between a date and now in words, e.g. "2 minutes ago" or "in 3 days".
+	globalThis.getTimeDiff = (date, now = new Date()) => {
+		const seconds = Math.round((toDate("getTimeDiff", now) - toDate("getTimeDiff", date)) / 1000);
+		const elapsed = Math.abs(seconds);
+		if (elapsed < 10) return "just now";
+		const [unit, size] = timeUnits.find(([, size]) => elapsed >= size);
+		const count = Math.floor(elapsed / size);
+		const amount = `${count} ${unit}${count === 1 ? "" : "s"}`;
+		return seconds > 0 ? `${amount} ago` : `in ${amount}`;
+	};
+	// Format a date in UTC by the tokens `YYYY`, `MM`, `DD`, `HH`, `mm`, and `ss`.
+	globalThis.formatDate = (date, format = "YYYY-MM-DD") => {
+		date = toDate("formatDate", date);
+		const pad = (value) => String(value).padStart(2, "0");
+		const tokens = {
+			YYYY: String(date.getUTCFullYear()),
+			MM: pad(date.getUTCMonth() + 1),
+			DD: pad(date.getUTCDate()),
+			HH: pad(date.getUTCHours()),
+			mm: pad(date.getUTCMinutes()),
+			ss: pad(date.getUTCSeconds())
+		};
+		return String(format).replace(/YYYY|MM|DD|HH|mm|ss/g, (token) => tokens[token]);
+	};
 })(globalThis);
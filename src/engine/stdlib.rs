@@ -1,11 +1,95 @@
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    ops::RangeInclusive,
+    path::{Path, PathBuf},
+    rc::Rc,
+    sync::Arc,
+};
+
 use deno_core::{
+    error::AnyError,
+    op2, serde_v8,
     v8::{self, Global, Local, Value},
-    JsRuntime,
+    JsRuntime, OpState,
+};
+
+use crate::reporting::error::{ERROR_FORBIDDEN, ERROR_HANDLER_CALL};
+
+use super::{
+    crypto,
+    fetch::{self, WXFetchLimits, WXFetchOptions, WXFetchResponse},
+    http::responses,
+    runtime::{WXRouteResult, WXRuntimeError, WXRuntimeInfo},
+    template,
 };
 
-use crate::reporting::error::ERROR_HANDLER_CALL;
+/// A native function registered by an embedding application.
+/// Called with the evaluated handler arguments, its result is passed on like that of any handler.
+pub type WXNativeFunction =
+    Arc<dyn Fn(&[serde_json::Value]) -> Result<serde_json::Value, String> + Send + Sync>;
+
+/// The registered native functions by name.
+pub type WXNativeFunctions = HashMap<String, WXNativeFunction>;
+
+/// Resolve a path relative to the project root for the native function `name`.
+/// Absolute paths, null bytes, and paths escaping the project root are rejected.
+pub fn resolve_project_path(
+    name: &str,
+    root: &Path,
+    relative_path: &str,
+) -> Result<PathBuf, WXRuntimeError> {
+    let forbidden = || WXRuntimeError {
+        message: format!("{}: access to '{}' is forbidden", name, relative_path),
+        code: ERROR_FORBIDDEN,
+        http_status: 403,
+    };
+    let relative = Path::new(relative_path);
+    if relative_path.contains('\0') || relative.is_absolute() || relative.has_root() {
+        return Err(forbidden());
+    }
+    let root = root.canonicalize().map_err(|err| WXRuntimeError {
+        message: format!("{}: failed to resolve the project root: {}", name, err),
+        code: ERROR_HANDLER_CALL,
+        http_status: 500,
+    })?;
+    let path = root
+        .join(relative_path)
+        .canonicalize()
+        .map_err(|_| WXRuntimeError {
+            message: format!("{}: failed to read file '{}'", name, relative_path),
+            code: ERROR_HANDLER_CALL,
+            http_status: 500,
+        })?;
+    if !path.starts_with(&root) {
+        return Err(forbidden());
+    }
+    Ok(path)
+}
 
-use super::runtime::{WXRuntimeError, WXRuntimeInfo};
+/// Read a string argument of a native function.
+fn string_argument(
+    scope: &mut v8::HandleScope,
+    name: &str,
+    arg: &Global<Value>,
+    expected: &str,
+) -> Result<String, WXRuntimeError> {
+    let local = Local::new(scope, arg);
+    match Local::<'_, v8::String>::try_from(local) {
+        Ok(value) => Ok(value.to_rust_string_lossy(scope)),
+        Err(_) => Err(WXRuntimeError {
+            message: format!("{}: expected {}, got '{:?}'", name, expected, arg),
+            code: ERROR_HANDLER_CALL,
+            http_status: 500,
+        }),
+    }
+}
+
+/// Create a JavaScript string result of a native function.
+fn string_value(scope: &mut v8::HandleScope, value: &str) -> Global<Value> {
+    let local: Local<'_, v8::Value> = v8::String::new(scope, value).unwrap().into();
+    Global::new(scope, local)
+}
 
 /// Serve static content from the filesystem.
 ///
@@ -21,7 +105,7 @@ fn webx_static(
     let local_relative_path = Local::new(scope, global_relative_path);
     if let Ok(path) = Local::<'_, v8::String>::try_from(local_relative_path) {
         let path = path.to_rust_string_lossy(scope);
-        let file = std::fs::read(info.project_root.join(path.clone()));
+        let file = std::fs::read(resolve_project_path("static", &info.project_root, &path)?);
         if let Ok(file) = file {
             let content = String::from_utf8(file).unwrap();
             let local: Local<'_, v8::Value> = v8::String::new(scope, &content).unwrap().into();
@@ -30,54 +114,424 @@ fn webx_static(
             return Err(WXRuntimeError {
                 message: format!("static: failed to read file '{}'", path),
                 code: ERROR_HANDLER_CALL,
+                http_status: 500,
             });
         }
     }
     Err(WXRuntimeError {
         message: format!("static: failed to read file '{:?}'", global_relative_path),
         code: ERROR_HANDLER_CALL,
+        http_status: 500,
+    })
+}
+
+/// Open static content for streaming from the filesystem.
+/// The file is sent in chunks rather than read into memory.
+///
+/// # Arguments
+/// - `path`: The path to the file to serve relative to the project root.
+fn webx_static_stream(
+    global_relative_path: &Global<Value>,
+    rt: &mut JsRuntime,
+    info: &WXRuntimeInfo,
+) -> Result<WXRouteResult, WXRuntimeError> {
+    let scope = &mut rt.handle_scope();
+    let local_relative_path = Local::new(scope, global_relative_path);
+    let Ok(path) = Local::<'_, v8::String>::try_from(local_relative_path) else {
+        return Err(WXRuntimeError {
+            message: format!("static: failed to read file '{:?}'", global_relative_path),
+            code: ERROR_HANDLER_CALL,
+            http_status: 500,
+        });
+    };
+    let path = path.to_rust_string_lossy(scope);
+    let resolved = resolve_project_path("static", &info.project_root, &path)?;
+    let failed = |_| WXRuntimeError {
+        message: format!("static: failed to read file '{}'", path),
+        code: ERROR_HANDLER_CALL,
+        http_status: 500,
+    };
+    let file = std::fs::File::open(&resolved).map_err(failed)?;
+    let metadata = file.metadata().map_err(failed)?;
+    Ok(WXRouteResult::File {
+        file,
+        len: metadata.len(),
+        content_type: responses::content_type(&resolved),
+        modified: metadata.modified().ok(),
+    })
+}
+
+/// Read an environment variable.
+/// If an allowlist is configured, only the listed variables can be read.
+pub fn read_env_var(
+    name: &str,
+    allowlist: Option<&[String]>,
+) -> Result<Option<String>, WXRuntimeError> {
+    if allowlist.is_some_and(|allowlist| !allowlist.iter().any(|allowed| allowed == name)) {
+        return Err(WXRuntimeError {
+            message: format!("env: access to '{}' is not allowed", name),
+            code: ERROR_HANDLER_CALL,
+            http_status: 500,
+        });
+    }
+    Ok(std::env::var(name).ok())
+}
+
+/// Read an environment variable.
+///
+/// # Arguments
+/// - `name`: The name of the environment variable.
+/// - `default`: The value if the variable is not set, defaults to `undefined`.
+fn webx_env(
+    args: &[Global<Value>],
+    rt: &mut JsRuntime,
+    info: &WXRuntimeInfo,
+) -> Result<Global<Value>, WXRuntimeError> {
+    let scope = &mut rt.handle_scope();
+    let name = string_argument(scope, "env", &args[0], "a variable name")?;
+    match read_env_var(&name, info.env_allowlist.as_deref())? {
+        Some(value) => Ok(string_value(scope, &value)),
+        None => match args.get(1) {
+            Some(default) => Ok(default.clone()),
+            None => {
+                let local: Local<'_, v8::Value> = v8::undefined(scope).into();
+                Ok(Global::new(scope, local))
+            }
+        },
+    }
+}
+
+/// Hash data with a cryptographic hash function.
+/// Resolves to the hex digest.
+///
+/// # Arguments
+/// - `algorithm`: Either `sha256` or `sha512`.
+/// - `data`: The string to hash.
+fn webx_hash(args: &[Global<Value>], rt: &mut JsRuntime) -> Result<Global<Value>, WXRuntimeError> {
+    let scope = &mut rt.handle_scope();
+    let algorithm = string_argument(scope, "hash", &args[0], "an algorithm name")?;
+    let data = string_argument(scope, "hash", &args[1], "a string")?;
+    let digest = crypto::hash(&algorithm, data.as_bytes())?;
+    Ok(string_value(scope, &digest))
+}
+
+/// Sign data with a keyed-hash message authentication code.
+/// Resolves to the hex HMAC.
+///
+/// # Arguments
+/// - `algorithm`: Either `sha256` or `sha512`.
+/// - `key`: The secret key.
+/// - `data`: The string to sign.
+fn webx_hmac(args: &[Global<Value>], rt: &mut JsRuntime) -> Result<Global<Value>, WXRuntimeError> {
+    let scope = &mut rt.handle_scope();
+    let algorithm = string_argument(scope, "hmac", &args[0], "an algorithm name")?;
+    let key = string_argument(scope, "hmac", &args[1], "a key")?;
+    let data = string_argument(scope, "hmac", &args[2], "a string")?;
+    let mac = crypto::hmac(&algorithm, key.as_bytes(), data.as_bytes())?;
+    Ok(string_value(scope, &mac))
+}
+
+/// Generate cryptographically secure random bytes.
+/// Resolves to the bytes as a hex string.
+///
+/// # Arguments
+/// - `len`: The number of bytes to generate.
+fn webx_random_bytes(
+    args: &[Global<Value>],
+    rt: &mut JsRuntime,
+) -> Result<Global<Value>, WXRuntimeError> {
+    let scope = &mut rt.handle_scope();
+    let local_len = Local::new(scope, &args[0]);
+    let len = local_len
+        .is_number()
+        .then(|| local_len.integer_value(scope))
+        .flatten()
+        .and_then(|len| usize::try_from(len).ok())
+        .ok_or_else(|| WXRuntimeError {
+            message: format!("randomBytes: expected a length, got '{:?}'", args[0]),
+            code: ERROR_HANDLER_CALL,
+            http_status: 500,
+        })?;
+    let bytes = crypto::random_bytes(len)?;
+    Ok(string_value(scope, &bytes))
+}
+
+/// Render an HTML template file from the project with the given data.
+/// Placeholders like `{{ name }}` are replaced by HTML escaped values of the data,
+/// and `{{ raw(name) }}` by unescaped values.
+///
+/// # Arguments
+/// - `path`: The path to the template relative to the project root.
+/// - `data`: The object to take the values from. Optional.
+fn webx_render(
+    args: &[Global<Value>],
+    rt: &mut JsRuntime,
+    info: &WXRuntimeInfo,
+) -> Result<Global<Value>, WXRuntimeError> {
+    let scope = &mut rt.handle_scope();
+    let path = string_argument(scope, "render", &args[0], "a template path")?;
+    let resolved = resolve_project_path("render", &info.project_root, &path)?;
+    let template = template::load_template(&resolved).map_err(|err| WXRuntimeError {
+        message: format!("render: failed to read template '{}': {}", path, err),
+        code: ERROR_HANDLER_CALL,
+        http_status: 500,
+    })?;
+    let data = match args.get(1) {
+        Some(data) => {
+            let local_data = Local::new(scope, data);
+            serde_v8::from_v8::<serde_json::Value>(scope, local_data).map_err(|err| {
+                WXRuntimeError {
+                    message: format!("render: invalid data: {}", err),
+                    code: ERROR_HANDLER_CALL,
+                    http_status: 500,
+                }
+            })?
+        }
+        None => serde_json::Value::Null,
+    };
+    let html = template::render_template(&template, &data);
+    Ok(string_value(scope, &html))
+}
+
+/// Mark data for content negotiation, as JSON or as HTML rendered with a template.
+/// The representation is chosen by the `Accept` header of the request.
+///
+/// # Arguments
+/// - `data`: The value to send.
+/// - `template`: The path to an HTML template relative to the project root. Optional.
+fn webx_negotiate(
+    args: &[Global<Value>],
+    rt: &mut JsRuntime,
+    info: &WXRuntimeInfo,
+) -> Result<WXRouteResult, WXRuntimeError> {
+    let template = match args.get(1) {
+        Some(path) => {
+            let scope = &mut rt.handle_scope();
+            let path = string_argument(scope, "negotiate", path, "a template path")?;
+            let resolved = resolve_project_path("negotiate", &info.project_root, &path)?;
+            let template = template::load_template(&resolved).map_err(|err| WXRuntimeError {
+                message: format!("negotiate: failed to read template '{}': {}", path, err),
+                code: ERROR_HANDLER_CALL,
+                http_status: 500,
+            })?;
+            Some(template)
+        }
+        None => None,
+    };
+    Ok(WXRouteResult::Data {
+        value: args[0].clone(),
+        template,
     })
 }
 
-/// Try to call a native function by name. \
-/// TODO: Figure out if this should be replaced with a JS extension.
-pub fn try_call(
+/// Whether the native function produces its own route result
+/// when called as the last handler of a route.
+pub fn produces_result(name: &str) -> bool {
+    matches!(name, "static" | "negotiate")
+}
+
+/// Call a native function producing its own route result by name.
+pub fn call_result(
     name: &str,
     args: &[Global<Value>],
     rt: &mut JsRuntime,
     info: &WXRuntimeInfo,
-) -> Option<Result<Global<Value>, WXRuntimeError>> {
+) -> Result<WXRouteResult, WXRuntimeError> {
     let assert_args = |n: usize| {
         if args.len() != n {
             return Err(WXRuntimeError {
                 message: format!("{}: expected {} arguments, got {}", name, n, args.len()),
                 code: ERROR_HANDLER_CALL,
+                http_status: 500,
             });
         }
         Ok(())
     };
+    match name {
+        "static" => assert_args(1).and_then(|_| webx_static_stream(&args[0], rt, info)),
+        "negotiate" => {
+            assert_args(args.len().clamp(1, 2)).and_then(|_| webx_negotiate(args, rt, info))
+        }
+        _ => Err(WXRuntimeError {
+            message: format!("{}: does not produce a route result", name),
+            code: ERROR_HANDLER_CALL,
+            http_status: 500,
+        }),
+    }
+}
 
-    Some(match name {
-        "static" => assert_args(1).and_then(|_| webx_static(&args[0], rt, info)),
-        _ => return None,
-    })
+/// The implementation of a native function, called with its evaluated arguments.
+pub type WXNativeCall = Box<
+    dyn Fn(
+            &[Global<Value>],
+            &mut JsRuntime,
+            &WXRuntimeInfo,
+        ) -> Result<Global<Value>, WXRuntimeError>
+        + Send
+        + Sync,
+>;
+
+/// A native function, callable by name like any handler.
+struct WXNative {
+    /// The numbers of arguments the function accepts.
+    arity: RangeInclusive<usize>,
+    call: WXNativeCall,
+}
+
+/// The native functions callable from handlers,
+/// the built-ins and those registered by an embedding application.
+pub struct WXNativeRegistry {
+    functions: HashMap<String, WXNative>,
+}
+
+impl Default for WXNativeRegistry {
+    /// Only the built-in native functions.
+    fn default() -> Self {
+        Self::new(&WXNativeFunctions::new())
+    }
+}
+
+impl WXNativeRegistry {
+    /// The built-in native functions and the registered ones.
+    /// Built-ins take precedence over registered functions of the same name.
+    pub fn new(registered: &WXNativeFunctions) -> Self {
+        let mut registry = WXNativeRegistry {
+            functions: HashMap::new(),
+        };
+        for (name, function) in registered.iter() {
+            let (label, function) = (name.clone(), function.clone());
+            registry.register(
+                name,
+                0..=usize::MAX,
+                Box::new(move |args, rt, _| call_native(&label, &function, args, rt)),
+            );
+        }
+        registry.register(
+            "static",
+            1..=1,
+            Box::new(|args, rt, info| webx_static(&args[0], rt, info)),
+        );
+        registry.register("env", 1..=2, Box::new(webx_env));
+        registry.register("render", 1..=2, Box::new(webx_render));
+        // Passes the data on when not the last handler of a route.
+        registry.register(
+            "negotiate",
+            1..=2,
+            Box::new(|args, _, _| Ok(args[0].clone())),
+        );
+        registry.register("hash", 2..=2, Box::new(|args, rt, _| webx_hash(args, rt)));
+        registry.register("hmac", 3..=3, Box::new(|args, rt, _| webx_hmac(args, rt)));
+        registry.register(
+            "randomBytes",
+            1..=1,
+            Box::new(|args, rt, _| webx_random_bytes(args, rt)),
+        );
+        registry.register(
+            "randomUUID",
+            0..=0,
+            Box::new(|_, rt, _| {
+                let scope = &mut rt.handle_scope();
+                Ok(string_value(scope, &crypto::random_uuid()))
+            }),
+        );
+        registry
+    }
+
+    /// Register a native function, replacing any function of the same name.
+    ///
+    /// ## Arguments
+    /// - `name` - The name the function is called by.
+    /// - `arity` - The numbers of arguments the function accepts.
+    /// - `call` - The implementation, only called with an accepted number of arguments.
+    pub fn register(&mut self, name: &str, arity: RangeInclusive<usize>, call: WXNativeCall) {
+        self.functions
+            .insert(name.to_string(), WXNative { arity, call });
+    }
+
+    /// Whether a native function of the given name is registered.
+    pub fn contains(&self, name: &str) -> bool {
+        self.functions.contains_key(name)
+    }
+
+    /// Try to call a native function by name.
+    /// Calls with a number of arguments the function does not accept fail.
+    pub fn call(
+        &self,
+        name: &str,
+        args: &[Global<Value>],
+        rt: &mut JsRuntime,
+        info: &WXRuntimeInfo,
+    ) -> Option<Result<Global<Value>, WXRuntimeError>> {
+        let native = self.functions.get(name)?;
+        if !native.arity.contains(&args.len()) {
+            let expected = args.len().clamp(*native.arity.start(), *native.arity.end());
+            return Some(Err(WXRuntimeError {
+                message: format!(
+                    "{}: expected {} arguments, got {}",
+                    name,
+                    expected,
+                    args.len()
+                ),
+                code: ERROR_HANDLER_CALL,
+                http_status: 500,
+            }));
+        }
+        Some((native.call)(args, rt, info))
+    }
+}
+
+/// Call a registered native function, converting the arguments and result from and to JSON.
+fn call_native(
+    name: &str,
+    function: &WXNativeFunction,
+    args: &[Global<Value>],
+    rt: &mut JsRuntime,
+) -> Result<Global<Value>, WXRuntimeError> {
+    let scope = &mut rt.handle_scope();
+    let args = args
+        .iter()
+        .map(|arg| {
+            let local = Local::new(scope, arg);
+            serde_v8::from_v8::<serde_json::Value>(scope, local)
+        })
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|err| WXRuntimeError {
+            message: format!("{}: invalid arguments: {}", name, err),
+            code: ERROR_HANDLER_CALL,
+            http_status: 500,
+        })?;
+    let result = function(&args).map_err(|err| WXRuntimeError {
+        message: format!("{}: {}", name, err),
+        code: ERROR_HANDLER_CALL,
+        http_status: 500,
+    })?;
+    let local = serde_v8::to_v8(scope, result).map_err(|err| WXRuntimeError {
+        message: format!("{}: invalid result: {}", name, err),
+        code: ERROR_HANDLER_CALL,
+        http_status: 500,
+    })?;
+    Ok(Global::new(scope, local))
+}
+
+/// Send an outbound HTTP or HTTPS request, see `fetch::fetch`.
+/// Called by `fetch()` in the stdlib, which returns the promise of the response.
+#[op2(async)]
+#[serde]
+async fn op_webx_fetch(
+    state: Rc<RefCell<OpState>>,
+    #[string] url: String,
+    #[serde] options: WXFetchOptions,
+) -> Result<WXFetchResponse, AnyError> {
+    let limits = state
+        .borrow()
+        .try_borrow::<WXFetchLimits>()
+        .cloned()
+        .unwrap_or_default();
+    fetch::fetch_in_background(url, options, limits)
+        .await
+        .map_err(|err| AnyError::msg(err.message))
 }
 
-// #[op]
-// async fn op_webx_static(relative_path: String) -> Result<String, AnyError> {
-//     let file = std::fs::read_to_string(relative_path).await?;
-//     Ok(file)
-// }
-
-// pub fn init() -> Extension {
-//     Extension {
-//         name: "webx stdlib",
-//         ops: vec![].into(), //  vec![op_webx_static::decl()],
-//         esm_files: include_js_files!(stdlib "src/engine/stdlib.js",)
-//             .to_vec()
-//             .into(),
-//         ..Default::default()
-//     }
-// }
+deno_core::extension!(webx_stdlib, ops = [op_webx_fetch]);
 
 pub const JAVASCRIPT: &str = include_str!("./stdlib.js");
@@ -0,0 +1,69 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{Mutex, OnceLock},
+    time::SystemTime,
+};
+
+use serde_json::Value;
+
+use super::http::responses::escape_html;
+
+/// Templates keyed by their path, with the modification time they were read at.
+static TEMPLATE_CACHE: OnceLock<Mutex<HashMap<PathBuf, (SystemTime, String)>>> = OnceLock::new();
+
+/// Read a template file.
+/// Templates are cached until the file is modified, so edits are picked up in dev mode.
+pub fn load_template(path: &Path) -> std::io::Result<String> {
+    let modified = std::fs::metadata(path)?.modified()?;
+    let cache = TEMPLATE_CACHE.get_or_init(Default::default);
+    if let Some((cached_at, template)) = cache.lock().unwrap().get(path) {
+        if *cached_at == modified {
+            return Ok(template.clone());
+        }
+    }
+    let template = std::fs::read_to_string(path)?;
+    cache
+        .lock()
+        .unwrap()
+        .insert(path.to_path_buf(), (modified, template.clone()));
+    Ok(template)
+}
+
+/// Substitute all `{{ name }}` placeholders in a template with values of the data.
+///
+/// Names can be dotted paths into nested objects, e.g. `{{ user.name }}`.
+/// Values are HTML escaped, unless the name is wrapped in `raw()`, e.g. `{{ raw(body) }}`.
+/// Missing and `null` values are rendered as empty strings,
+/// and objects and arrays as JSON.
+pub fn render_template(template: &str, data: &Value) -> String {
+    let mut html = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        let Some(end) = rest[start..].find("}}") else {
+            break;
+        };
+        html.push_str(&rest[..start]);
+        let name = rest[start + 2..start + end].trim();
+        match name
+            .strip_prefix("raw(")
+            .and_then(|name| name.strip_suffix(')'))
+        {
+            Some(name) => html.push_str(&lookup(data, name.trim())),
+            None => html.push_str(&escape_html(&lookup(data, name))),
+        }
+        rest = &rest[start + end + 2..];
+    }
+    html.push_str(rest);
+    html
+}
+
+/// The value at a dotted path in the data, as text.
+fn lookup(data: &Value, name: &str) -> String {
+    let value = name.split('.').try_fold(data, |value, key| value.get(key));
+    match value {
+        None | Some(Value::Null) => String::new(),
+        Some(Value::String(text)) => text.clone(),
+        Some(value) => value.to_string(),
+    }
+}
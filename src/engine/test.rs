@@ -1,12 +1,56 @@
 #[cfg(test)]
 mod tests {
-    use std::{path::Path, sync::Arc};
+    use std::{
+        path::{Path, PathBuf},
+        sync::{atomic::AtomicBool, Arc},
+    };
 
     use crate::{
-        analysis::{dependencies::analyze_module_deps, routes::analyze_module_routes},
-        engine::runtime::{WXRuntime, WXRuntimeInfo},
-        file::project::{load_modules, load_project_config},
-        runner::{get_project_config_file_path, DebugLevel, WXMode},
+        analysis::{
+            dependencies::analyze_module_deps,
+            routes::{analyze_module_routes, BodyFormatPolicy},
+        },
+        engine::{
+            crypto,
+            fetch::{fetch, WXFetchLimits},
+            filewatcher::{is_editor_temp_file, FSWChange, FSWDebouncer, WXFileWatcher},
+            graphql::{parse_operation, WXGraphQLOperationType},
+            health::WXHealth,
+            http::{
+                redacted_headers, requests,
+                responses::{self, WXErrorPages},
+                WXResponseBody,
+            },
+            metrics::WXMetrics,
+            pool::run_runtime,
+            runtime::{
+                new_stdlib_js_runtime, parse_base_path, resolve_promise, resolve_trailing_slash,
+                WXPathResolution, WXRouteMap, WXRuntime, WXRuntimeInfo, WXRuntimeMessage,
+                WXSlashResolution, WXSocketOpen,
+            },
+            server::{
+                respond_within, serve_connection, serve_websocket, WXConnectionLimits, WXServer,
+            },
+            stdlib::{
+                read_env_var, resolve_project_path, WXNativeFunction, WXNativeFunctions,
+                WXNativeRegistry,
+            },
+            template::render_template,
+            transpiler::{transpile_ts, TranspileCache, TRANSPILE_CACHE_SIZE},
+        },
+        file::{
+            parser::parse_webx_source,
+            project::{
+                load_modules, load_project_config, CorsConfig, CsrfConfig, GraphQLConfig,
+                ProjectConfig, SecurityHeadersConfig, SessionConfig, TrailingSlashPolicy,
+            },
+            webx::{WXModule, WXUrlPath, WXUrlPathSegment},
+        },
+        reporting::error::{ERROR_FORBIDDEN, ERROR_UNKNOWN_HANDLER},
+        runner::{
+            get_project_config_file_path, is_module_file, load_module_file_project, serve,
+            timeout_duration, DebugLevel, WXConfigOverrides, WXMode,
+        },
     };
 
     /// Kill the runtime after `TIMEOUT` seconds.
@@ -21,31 +65,3875 @@ mod tests {
     fn test_example_todo() {
         let mode = WXMode::Dev(DebugLevel::Max);
         let root = Path::new("examples/todo");
-        let config = load_project_config(&get_project_config_file_path(root));
-        let source_root = if let Some(src) = &config.src {
-            root.join(src)
-        } else {
-            root.to_path_buf()
-        };
+        let config = load_project_config(&get_project_config_file_path(root), None);
+        let source_root = config.source_root(root);
         let webx_modules = load_modules(&source_root);
-        analyze_module_deps(&webx_modules);
-        analyze_module_routes(&webx_modules);
-        let (_, dummy_rx) = std::sync::mpsc::channel();
+        analyze_module_deps(&webx_modules).unwrap();
+        analyze_module_routes(&webx_modules, &BodyFormatPolicy::new(&config)).unwrap();
+        let (_dummy_tx, dummy_rx) = std::sync::mpsc::channel();
         if KILL_AFTER_TIMEOUT {
             let running = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(true));
             let runtime_running = running.clone();
-            std::thread::spawn(move || {
-                let mut runtime = WXRuntime::new(dummy_rx, mode, WXRuntimeInfo::new(root));
+            let runtime = std::thread::spawn(move || {
+                let mut runtime =
+                    WXRuntime::new(dummy_rx, mode, WXRuntimeInfo::new(root, &config, mode));
                 runtime.load_modules(webx_modules);
-                runtime.run(runtime_running);
+                runtime.run(runtime_running, Arc::new(AtomicBool::new(false)));
             });
             std::thread::sleep(std::time::Duration::from_secs(TIMEOUT));
             running.store(false, std::sync::atomic::Ordering::Relaxed);
-            std::process::exit(0);
+            runtime.join().unwrap();
         } else {
-            let mut runtime = WXRuntime::new(dummy_rx, mode, WXRuntimeInfo::new(root));
+            let mut runtime =
+                WXRuntime::new(dummy_rx, mode, WXRuntimeInfo::new(root, &config, mode));
             runtime.load_modules(webx_modules);
-            runtime.run(Arc::new(std::sync::atomic::AtomicBool::new(true)));
+            runtime.run(
+                Arc::new(AtomicBool::new(true)),
+                Arc::new(AtomicBool::new(false)),
+            );
+        }
+    }
+
+    #[test]
+    fn test_catch_panic() {
+        use crate::engine::runtime::catch_panic;
+
+        assert_eq!(catch_panic(|| 42), Ok(42));
+        assert_eq!(
+            catch_panic::<()>(|| panic!("boom")),
+            Err("boom".to_string())
+        );
+        let status = 500;
+        assert_eq!(
+            catch_panic::<()>(|| panic!("failed with {}", status)),
+            Err("failed with 500".to_string())
+        );
+        // Nothing is left over from the previous panic.
+        assert_eq!(catch_panic(|| "ok"), Ok("ok"));
+    }
+
+    #[test]
+    fn test_head_response() {
+        let file = std::env::temp_dir().join(format!("webx-head-{}.webx", std::process::id()));
+        std::fs::write(&file, "get /about {}\n").unwrap();
+        let module = crate::file::parser::parse_webx_file(&file);
+        std::fs::remove_file(&file).unwrap();
+        let policy = crate::analysis::routes::BodyFormatPolicy {
+            allow_delete_body: false,
+        };
+        let routes =
+            crate::engine::runtime::WXRouteMap::from_modules(&[module.unwrap()], &policy, true)
+                .unwrap();
+        let resolves = |method: hyper::Method, path: &str| {
+            routes
+                .resolve(&method, &path.parse().unwrap())
+                .found()
+                .is_some()
+        };
+        // HEAD falls back to the GET route of the same path.
+        assert!(resolves(hyper::Method::HEAD, "/about"));
+        assert!(!resolves(hyper::Method::HEAD, "/missing"));
+        assert!(!resolves(hyper::Method::POST, "/about"));
+
+        let response = || {
+            hyper::Response::builder()
+                .header("Content-Type", "text/plain")
+                .header("Content-Length", "10")
+                .body("About WebX".to_string())
+                .unwrap()
+        };
+        // A HEAD response keeps the headers of the GET response, but not its body.
+        let head = crate::engine::runtime::strip_head_body(true, response());
+        assert_eq!(head.headers()["Content-Type"], "text/plain");
+        assert_eq!(head.headers()["Content-Length"], "10");
+        assert_eq!(head.body(), "");
+        let get = crate::engine::runtime::strip_head_body(false, response());
+        assert_eq!(get.body(), "About WebX");
+    }
+
+    #[test]
+    fn test_custom_error_pages() {
+        let mode = WXMode::Dev(DebugLevel::Max);
+        let pages = WXErrorPages {
+            not_found: Some("<h1>Missing {{method}} {{url}}</h1>".into()),
+            internal_server_error: Some("<h1>Oops: {{message}}</h1>".into()),
+        };
+        let response = responses::not_found(mode, &pages, &hyper::Method::GET, "/a".into());
+        assert_eq!(response.status(), hyper::StatusCode::NOT_FOUND);
+        assert_eq!(response.body(), "<h1>Missing GET /a</h1>");
+        let response = responses::internal_server_error(mode, &pages, "boom".into());
+        assert_eq!(response.status(), hyper::StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(response.body(), "<h1>Oops: boom</h1>");
+        // Debug details are hidden in production.
+        let response = responses::internal_server_error(WXMode::Prod, &pages, "boom".into());
+        assert_eq!(response.body(), "<h1>Oops: </h1>");
+        let response = responses::not_found(WXMode::Prod, &pages, &hyper::Method::GET, "/a".into());
+        assert_eq!(response.body(), "<h1>Missing GET </h1>");
+        // Values are escaped, and placeholders within them are not substituted.
+        let response = responses::not_found(
+            mode,
+            &pages,
+            &hyper::Method::GET,
+            "/<script>alert(1)</script>".into(),
+        );
+        assert_eq!(
+            response.body(),
+            "<h1>Missing GET /&lt;script&gt;alert(1)&lt;/script&gt;</h1>"
+        );
+        let response = responses::internal_server_error(mode, &pages, "<b>{{server}}</b>".into());
+        assert_eq!(
+            response.body(),
+            "<h1>Oops: &lt;b&gt;{{server}}&lt;/b&gt;</h1>"
+        );
+    }
+
+    #[test]
+    fn test_default_error_pages() {
+        let mode = WXMode::Dev(DebugLevel::Max);
+        let pages = WXErrorPages::default();
+        let response = responses::not_found(mode, &pages, &hyper::Method::GET, "/a".into());
+        assert!(response.body().contains("404 Not Found"));
+        let response = responses::internal_server_error(mode, &pages, "boom".into());
+        assert!(String::from_utf8_lossy(response.body()).contains("boom"));
+    }
+
+    #[test]
+    fn test_client_ip_untrusted_proxy() {
+        let peer: std::net::SocketAddr = "10.0.0.1:4000".parse().unwrap();
+        let mut headers = hyper::HeaderMap::new();
+        headers.insert("X-Forwarded-For", "203.0.113.7".parse().unwrap());
+        headers.insert("Forwarded", "for=198.51.100.2".parse().unwrap());
+        assert_eq!(requests::client_ip(&headers, peer, 0), peer.ip());
+    }
+
+    #[test]
+    fn test_client_ip_trusted_proxy() {
+        let peer: std::net::SocketAddr = "10.0.0.1:4000".parse().unwrap();
+        let mut headers = hyper::HeaderMap::new();
+        assert_eq!(requests::client_ip(&headers, peer, 1), peer.ip());
+        // The client spoofs the first entry, the proxy appends the address it received from.
+        headers.insert("X-Forwarded-For", "192.0.2.1, 203.0.113.7".parse().unwrap());
+        let ip = requests::client_ip(&headers, peer, 1);
+        assert_eq!(ip.to_string(), "203.0.113.7");
+        // Behind two proxies, the second appends the address of the first.
+        headers.insert(
+            "X-Forwarded-For",
+            "192.0.2.1, 203.0.113.7, 10.0.0.2".parse().unwrap(),
+        );
+        let ip = requests::client_ip(&headers, peer, 2);
+        assert_eq!(ip.to_string(), "203.0.113.7");
+        // With fewer entries than proxies, the leftmost entry is the best guess.
+        let ip = requests::client_ip(&headers, peer, 5);
+        assert_eq!(ip.to_string(), "192.0.2.1");
+        headers.insert(
+            "Forwarded",
+            "for=192.0.2.1, for=\"[2001:db8:cafe::17]:4711\";proto=https"
+                .parse()
+                .unwrap(),
+        );
+        let ip = requests::client_ip(&headers, peer, 1);
+        assert_eq!(ip.to_string(), "2001:db8:cafe::17");
+    }
+
+    #[test]
+    fn test_trailing_slash_ignore() {
+        let policy = TrailingSlashPolicy::Ignore;
+        let about = "/about".parse().unwrap();
+        let about_slash = "/about/".parse().unwrap();
+        assert_eq!(
+            resolve_trailing_slash(policy, &about),
+            WXSlashResolution::Resolve
+        );
+        assert_eq!(
+            resolve_trailing_slash(policy, &about_slash),
+            WXSlashResolution::Resolve
+        );
+    }
+
+    #[test]
+    fn test_trailing_slash_strict() {
+        let policy = TrailingSlashPolicy::Strict;
+        let about = "/about".parse().unwrap();
+        let about_slash = "/about/".parse().unwrap();
+        let root = "/".parse().unwrap();
+        assert_eq!(
+            resolve_trailing_slash(policy, &about),
+            WXSlashResolution::Resolve
+        );
+        assert_eq!(
+            resolve_trailing_slash(policy, &about_slash),
+            WXSlashResolution::Resolve
+        );
+        assert_eq!(
+            resolve_trailing_slash(policy, &root),
+            WXSlashResolution::Resolve
+        );
+    }
+
+    #[test]
+    fn test_trailing_slash_strict_routes() {
+        let module = parse_webx_source(
+            &PathBuf::from("slash.webx"),
+            "get /about/ {}\nget /contact {}\nget / {}\n",
+        )
+        .unwrap();
+        let policy = BodyFormatPolicy {
+            allow_delete_body: false,
+        };
+        let routes = WXRouteMap::from_modules(&[module], &policy, true).unwrap();
+        let resolves = |routes: &WXRouteMap, path: &str| {
+            routes
+                .resolve(&hyper::Method::GET, &path.parse().unwrap())
+                .found()
+                .is_some()
+        };
+        // Routes match with and without a trailing slash by default.
+        for path in ["/about/", "/about", "/contact", "/contact/", "/"] {
+            assert!(resolves(&routes, path), "{}", path);
+        }
+        // Strict routes only match the declared form.
+        let routes = routes.with_trailing_slash(TrailingSlashPolicy::Strict);
+        for (path, expected) in [
+            ("/about/", true),
+            ("/about", false),
+            ("/contact", true),
+            ("/contact/", false),
+            ("/", true),
+        ] {
+            assert_eq!(resolves(&routes, path), expected, "{}", path);
+        }
+    }
+
+    #[test]
+    fn test_trailing_slash_redirect() {
+        let policy = TrailingSlashPolicy::Redirect;
+        let about = "/about".parse().unwrap();
+        let about_slash = "/about/?page=2".parse().unwrap();
+        assert_eq!(
+            resolve_trailing_slash(policy, &about),
+            WXSlashResolution::Resolve
+        );
+        assert_eq!(
+            resolve_trailing_slash(policy, &about_slash),
+            WXSlashResolution::Redirect("/about?page=2".into())
+        );
+        let response = responses::moved_permanently(WXMode::Prod, "/about");
+        assert_eq!(response.status(), hyper::StatusCode::MOVED_PERMANENTLY);
+        assert_eq!(response.headers()["Location"], "/about");
+    }
+
+    #[test]
+    fn test_case_sensitive_routes() {
+        let route = WXUrlPath(vec![WXUrlPathSegment::Literal("about".into())]);
+        let url = "/About".parse().unwrap();
+        assert_eq!(
+            route.matches(&url, true, &Default::default()),
+            WXPathResolution::None
+        );
+        assert!(matches!(
+            route.matches(&"/about".parse().unwrap(), true, &Default::default()),
+            WXPathResolution::Perfect(_)
+        ));
+    }
+
+    #[test]
+    fn test_case_insensitive_routes() {
+        let route = WXUrlPath(vec![WXUrlPathSegment::Literal("about".into())]);
+        let url = "/ABOUT".parse().unwrap();
+        assert!(matches!(
+            route.matches(&url, false, &Default::default()),
+            WXPathResolution::Perfect(_)
+        ));
+        let url = "/abouts".parse().unwrap();
+        assert_eq!(
+            route.matches(&url, false, &Default::default()),
+            WXPathResolution::None
+        );
+    }
+
+    #[test]
+    fn test_regex_capture_groups() {
+        use http_body_util::BodyExt;
+
+        let route = WXUrlPath(vec![WXUrlPathSegment::Regex(
+            "ver".into(),
+            r"^v(\d+)(?:\.(?P<minor>\d+))?$".into(),
+        )]);
+        let regexes = route.compile_regexes();
+        let WXPathResolution::Perfect(params) =
+            route.matches(&"/v2".parse().unwrap(), true, &regexes)
+        else {
+            panic!("expected /v2 to match");
+        };
+        assert_eq!(params["ver"], "v2");
+        assert_eq!(params["ver_1"], "2");
+        assert_eq!(params["ver_2"], serde_json::Value::Null);
+        assert_eq!(params["minor"], serde_json::Value::Null);
+        assert_eq!(
+            route.matches(&"/x2".parse().unwrap(), true, &regexes),
+            WXPathResolution::None
+        );
+        // Patterns match whole segments, even without anchors.
+        let unanchored = WXUrlPath(vec![WXUrlPathSegment::Regex("ver".into(), r"v\d+".into())]);
+        let regexes = unanchored.compile_regexes();
+        for (url, matches) in [("/v2", true), ("/dev2", false), ("/v2beta", false)] {
+            let resolution = unanchored.matches(&url.parse().unwrap(), true, &regexes);
+            assert_eq!(resolution != WXPathResolution::None, matches, "{}", url);
+        }
+        // Regex segments without a compiled regex never match.
+        assert_eq!(
+            unanchored.matches(&"/v2".parse().unwrap(), true, &Default::default()),
+            WXPathResolution::None
+        );
+
+        // The groups are bound by name in the handler.
+        let root = PathBuf::from("examples/todo");
+        let source = "get /api/version/info text {\n    return `${ver}:${ver_1}:${minor}`;\n}\n";
+        let mut module = parse_webx_source(&root.join("regex.webx"), source).unwrap();
+        module.scope.routes[0].path.0[1] = route.0[0].clone();
+        let mode = WXMode::Dev(DebugLevel::Low);
+        let config = load_project_config(&get_project_config_file_path(&root), None);
+        let info = WXRuntimeInfo::new(&root, &config, mode);
+        spawn_runtime_with_modules(mode, info, vec![module]).test(|rt_tx| async move {
+            let response = request(&rt_tx, "/api/v3.1/info").await.unwrap().unwrap();
+            assert_eq!(response.status(), hyper::StatusCode::OK);
+            let body = response.into_body().collect().await.unwrap().to_bytes();
+            assert_eq!(&body[..], b"v3.1:3:1");
+        });
+    }
+
+    #[test]
+    fn test_route_map_resolution_order() {
+        let module = parse_webx_source(
+            &PathBuf::from("order.webx"),
+            "get /todos {}\nget /about {}\nget /todos/(id: String)/edit {}\nget /todos/(id: String) {}\n",
+        )
+        .unwrap();
+        let policy = BodyFormatPolicy {
+            allow_delete_body: false,
+        };
+        let routes = WXRouteMap::from_modules(&[module], &policy, true).unwrap();
+        // The order is computed once when the map is built,
+        // as `resolve` only borrows the map it can not re-sort it per call.
+        let order = [
+            "/todos/(id: String)/edit",
+            "/todos/(id: String)",
+            "/about",
+            "/todos",
+        ];
+        let paths = routes.paths(&hyper::Method::GET);
+        assert_eq!(
+            paths.iter().map(|p| p.to_string()).collect::<Vec<_>>(),
+            order
+        );
+        for _ in 0..2 {
+            let (path, _, _) = routes
+                .resolve(&hyper::Method::GET, &"/todos/5".parse().unwrap())
+                .found()
+                .unwrap();
+            assert_eq!(path.to_string(), "/todos/(id: String)");
+        }
+        let (path, _, _) = routes
+            .resolve(&hyper::Method::HEAD, &"/todos".parse().unwrap())
+            .found()
+            .unwrap();
+        assert_eq!(path.to_string(), "/todos");
+        assert!(routes.paths(&hyper::Method::POST).is_empty());
+    }
+
+    #[test]
+    fn test_iter_routes() {
+        let root = Path::new("examples/todo");
+        let config = load_project_config(&get_project_config_file_path(root), None);
+        let webx_modules = load_modules(&config.source_root(root));
+        let routes =
+            WXRouteMap::from_modules(&webx_modules, &BodyFormatPolicy::new(&config), true).unwrap();
+        let listed = routes
+            .iter_routes()
+            .map(|(method, path, route)| {
+                assert_eq!(route.module_path().module_name(), "index");
+                (method.to_string(), path.to_string())
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(listed.len(), routes.count());
+        assert_eq!(routes.count(), 10);
+        for (method, path) in [
+            ("GET", "/"),
+            ("GET", "/about"),
+            ("GET", "/todos/list"),
+            ("POST", "/todos/add"),
+            ("POST", "/todos/toggle"),
+        ] {
+            assert!(
+                listed.contains(&(method.to_string(), path.to_string())),
+                "missing {} {}",
+                method,
+                path
+            );
+        }
+        // Grouped by method, in the order each method resolves its routes.
+        let (get, post) = listed.split_at(6);
+        assert!(get.iter().all(|(method, _)| method == "GET"));
+        assert!(post.iter().all(|(method, _)| method == "POST"));
+        assert_eq!(
+            get.iter().map(|(_, path)| path.clone()).collect::<Vec<_>>(),
+            routes
+                .paths(&hyper::Method::GET)
+                .iter()
+                .map(|path| path.to_string())
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_route_precedence() {
+        let module = parse_webx_source(
+            &PathBuf::from("precedence.webx"),
+            "get /user/(id: Int) {}\nget /user/me {}\n",
+        )
+        .unwrap();
+        let policy = BodyFormatPolicy {
+            allow_delete_body: false,
+        };
+        let routes = WXRouteMap::from_modules(&[module], &policy, true).unwrap();
+        // Literal segments take precedence over parameters, regardless of their names.
+        let resolve = |url: &str| {
+            let (path, _, _) = routes
+                .resolve(&hyper::Method::GET, &url.parse().unwrap())
+                .found()
+                .unwrap();
+            path.to_string()
+        };
+        assert_eq!(resolve("/user/me"), "/user/me");
+        assert_eq!(resolve("/user/5"), "/user/(id: Int)");
+    }
+
+    #[test]
+    fn test_route_priority() {
+        let policy = BodyFormatPolicy {
+            allow_delete_body: false,
+        };
+        let resolve = |source: &str, url: &str| {
+            let module = parse_webx_source(&PathBuf::from("priority.webx"), source).unwrap();
+            let routes = WXRouteMap::from_modules(&[module], &policy, true).unwrap();
+            let (path, _, _) = routes
+                .resolve(&hyper::Method::GET, &url.parse().unwrap())
+                .found()
+                .unwrap();
+            path.to_string()
+        };
+        // By default, the literal segment takes precedence.
+        let source = "get /(slug: String) {}\nget /about {}\n";
+        assert_eq!(resolve(source, "/about"), "/about");
+        // A higher priority makes the catch-all win over the more specific route.
+        let source = "get /(slug: String) priority(1) {}\nget /about {}\n";
+        assert_eq!(resolve(source, "/about"), "/(slug: String)");
+        let source = "get /about priority(-1) {}\nget /(slug: String) {}\n";
+        assert_eq!(resolve(source, "/about"), "/(slug: String)");
+        // Priorities do not reorder paths of different lengths.
+        let source = "get /(slug: String) priority(5) {}\nget /docs/about {}\n";
+        assert_eq!(resolve(source, "/docs/about"), "/docs/about");
+    }
+
+    #[test]
+    fn test_catch_all_routes() {
+        let module = parse_webx_source(
+            &PathBuf::from("fallback.webx"),
+            "get /* {}\nget /files/* {}\nget /about {}\nget /docs/(page: String)? {}\n",
+        )
+        .unwrap();
+        let policy = BodyFormatPolicy {
+            allow_delete_body: false,
+        };
+        let routes = WXRouteMap::from_modules(&[module], &policy, true).unwrap();
+        let resolve = |url: &str| {
+            routes
+                .resolve(&hyper::Method::GET, &url.parse().unwrap())
+                .found()
+                .map(|(path, params, _)| (path.to_string(), params))
+        };
+        // Known routes win over the catch-alls, even when they leave out a parameter.
+        assert_eq!(resolve("/about").unwrap().0, "/about");
+        assert_eq!(resolve("/docs").unwrap().0, "/docs/(page: String)?");
+        // Unknown paths fall back to the most specific catch-all, bound to the rest of the path.
+        let (path, params) = resolve("/files/a/b.txt").unwrap();
+        assert_eq!(path, "/files/(*)");
+        assert_eq!(params["g0"], "a/b.txt");
+        let (path, params) = resolve("/about/team").unwrap();
+        assert_eq!(path, "/(*)");
+        assert_eq!(params["g0"], "about/team");
+        // A wildcard matches one segment or more.
+        assert!(resolve("/").is_none());
+        let paths = routes.paths(&hyper::Method::GET);
+        assert_eq!(
+            paths[2..].iter().map(|p| p.to_string()).collect::<Vec<_>>(),
+            ["/files/(*)", "/(*)"]
+        );
+    }
+
+    #[test]
+    fn test_partial_path_resolution() {
+        let module = parse_webx_source(
+            &PathBuf::from("partial.webx"),
+            "get /todos/(page: Int)? {}\nget /users/(id: Int) {}\nget /about/(tab: String)? {}\nget /about {}\n",
+        )
+        .unwrap();
+        let policy = BodyFormatPolicy {
+            allow_delete_body: false,
+        };
+        let routes = WXRouteMap::from_modules(&[module], &policy, true).unwrap();
+        let resolve = |url: &str| {
+            routes
+                .resolve(&hyper::Method::GET, &url.parse().unwrap())
+                .found()
+                .map(|(path, params, _)| (path.to_string(), serde_json::Value::from(params)))
+        };
+        // An optional last parameter may be left out, and is bound as `null`.
+        assert_eq!(
+            resolve("/todos"),
+            Some((
+                "/todos/(page: Int)?".into(),
+                serde_json::json!({ "page": null })
+            ))
+        );
+        assert_eq!(
+            resolve("/todos/2"),
+            Some((
+                "/todos/(page: Int)?".into(),
+                serde_json::json!({ "page": 2 })
+            ))
+        );
+        // Required parameters can not be left out.
+        assert_eq!(resolve("/users"), None);
+        // A perfect match wins over leaving out an optional parameter.
+        assert_eq!(
+            resolve("/about"),
+            Some(("/about".into(), serde_json::json!({})))
+        );
+        assert_eq!(
+            resolve("/about/team"),
+            Some((
+                "/about/(tab: String)?".into(),
+                serde_json::json!({ "tab": "team" })
+            ))
+        );
+        // Only the last segment can be optional.
+        assert!(parse_webx_source(
+            &PathBuf::from("partial.webx"),
+            "get /(page: Int)?/todos {}\n"
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_static_path_traversal() {
+        let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+        let path = resolve_project_path("static", root, "src/main.rs").unwrap();
+        assert!(path.ends_with("src/main.rs"));
+        let forbidden = ["../", "src/../../", "/etc/passwd", "src/main.rs\0"];
+        for relative_path in forbidden {
+            let err = resolve_project_path("static", root, relative_path).unwrap_err();
+            assert_eq!(err.code, ERROR_FORBIDDEN, "{}", relative_path);
+            assert_eq!(err.http_status, 403, "{}", relative_path);
+        }
+    }
+
+    #[test]
+    fn test_security_headers() {
+        let mode = WXMode::Prod;
+        let config = SecurityHeadersConfig {
+            hsts: Some(true),
+            csp: Some("default-src 'self'".into()),
+            frame_options: Some("DENY".into()),
+            no_sniff: Some(true),
+        };
+        let response = responses::forbidden(mode);
+        let response = responses::with_security_headers(mode, response, Some(&config), true);
+        let headers = response.headers();
+        assert_eq!(headers["Content-Security-Policy"], "default-src 'self'");
+        assert_eq!(headers["X-Frame-Options"], "DENY");
+        assert_eq!(headers["X-Content-Type-Options"], "nosniff");
+        assert!(headers.contains_key("Strict-Transport-Security"));
+        // HSTS is only sent over HTTPS.
+        let response = responses::with_security_headers(
+            mode,
+            responses::moved_permanently(mode, "/"),
+            Some(&config),
+            false,
+        );
+        assert!(!response.headers().contains_key("Strict-Transport-Security"));
+        assert_eq!(response.headers()["X-Frame-Options"], "DENY");
+    }
+
+    #[test]
+    fn test_max_body_size() {
+        let read = |headers: hyper::HeaderMap, body: &'static str| {
+            let body = http_body_util::Full::new(hyper::body::Bytes::from(body));
+            block_on(requests::read_body(&headers, body, 8))
+        };
+        let body = read(hyper::HeaderMap::new(), "{\"a\":1}").unwrap();
+        assert_eq!(body, "{\"a\":1}");
+        let result = read(hyper::HeaderMap::new(), "{\"a\":12345}");
+        assert!(matches!(result, Err(requests::WXBodyError::TooLarge)));
+        // Rejected upfront by the declared length.
+        let mut headers = hyper::HeaderMap::new();
+        headers.insert("Content-Length", "1000000".parse().unwrap());
+        let result = read(headers, "{}");
+        assert!(matches!(result, Err(requests::WXBodyError::TooLarge)));
+        let response = responses::payload_too_large(WXMode::Prod, 8);
+        assert_eq!(response.status(), hyper::StatusCode::PAYLOAD_TOO_LARGE);
+    }
+
+    #[test]
+    fn test_header_read_timeout() {
+        use std::time::Duration;
+        use tokio::io::AsyncWriteExt;
+
+        block_on(async {
+            let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let mut client = tokio::net::TcpStream::connect(listener.local_addr().unwrap())
+                .await
+                .unwrap();
+            let (stream, _) = listener.accept().await.unwrap();
+            // Trickle an incomplete request and never finish the headers.
+            client.write_all(b"GET / HTTP/1.1\r\nHost: ").await.unwrap();
+            let svc = hyper::service::service_fn(|_| async {
+                Ok::<_, std::convert::Infallible>(hyper::Response::new(http_body_util::Full::new(
+                    hyper::body::Bytes::new(),
+                )))
+            });
+            let limits = WXConnectionLimits {
+                header_read_timeout: Duration::from_millis(100),
+                max_header_size: 8192,
+                keep_alive: true,
+            };
+            let served = tokio::time::timeout(
+                Duration::from_secs(2),
+                serve_connection(
+                    hyper_util::rt::TokioIo::new(stream),
+                    svc,
+                    limits,
+                    tokio::sync::watch::channel(false).1,
+                ),
+            )
+            .await
+            .expect("the connection was not dropped");
+            assert!(served.is_err());
+        });
+    }
+
+    #[test]
+    fn test_request_timeout() {
+        use http_body_util::BodyExt;
+        use std::time::Duration;
+
+        block_on(async {
+            let slow = async {
+                tokio::time::sleep(Duration::from_secs(2)).await;
+                Ok(hyper::Response::new(WXResponseBody::from("late")))
+            };
+            let response = respond_within(WXMode::Prod, Duration::from_millis(50), slow)
+                .await
+                .unwrap();
+            assert_eq!(response.status(), hyper::StatusCode::SERVICE_UNAVAILABLE);
+            // Sending the body of a response in time is not limited.
+            let (tx, rx) = tokio::sync::mpsc::channel(1);
+            let fast = async { Ok(hyper::Response::new(WXResponseBody::EventStream(rx))) };
+            let response = respond_within(WXMode::Prod, Duration::from_millis(50), fast)
+                .await
+                .unwrap();
+            assert_eq!(response.status(), hyper::StatusCode::OK);
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            tx.send(hyper::body::Bytes::from("done")).await.unwrap();
+            drop(tx);
+            let body = response.into_body().collect().await.unwrap().to_bytes();
+            assert_eq!(body, "done");
+        });
+    }
+
+    /// Serve a connection on a local listener and connect a client to it.
+    async fn connect_keep_alive(
+        keep_alive: bool,
+    ) -> (
+        hyper::client::conn::http1::SendRequest<http_body_util::Empty<hyper::body::Bytes>>,
+        tokio::task::JoinHandle<Result<(), String>>,
+    ) {
+        use std::time::Duration;
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let client = tokio::net::TcpStream::connect(listener.local_addr().unwrap())
+            .await
+            .unwrap();
+        let (stream, _) = listener.accept().await.unwrap();
+        let svc = hyper::service::service_fn(|req: hyper::Request<_>| async move {
+            let body = hyper::body::Bytes::from(req.uri().path().to_string());
+            let len = body.len();
+            Ok::<_, std::convert::Infallible>(responses::ok_html(
+                WXResponseBody::from(body),
+                len,
+                WXMode::Prod,
+            ))
+        });
+        let limits = WXConnectionLimits {
+            header_read_timeout: Duration::from_secs(5),
+            max_header_size: 8192,
+            keep_alive,
+        };
+        let server = tokio::spawn(serve_connection(
+            hyper_util::rt::TokioIo::new(stream),
+            svc,
+            limits,
+            tokio::sync::watch::channel(false).1,
+        ));
+        let (sender, connection) =
+            hyper::client::conn::http1::handshake(hyper_util::rt::TokioIo::new(client))
+                .await
+                .unwrap();
+        tokio::spawn(connection);
+        (sender, server)
+    }
+
+    #[test]
+    fn test_keep_alive() {
+        use http_body_util::{BodyExt, Empty};
+        use std::time::Duration;
+
+        block_on(async {
+            let (mut sender, server) = connect_keep_alive(true).await;
+            for path in ["/first", "/second"] {
+                sender.ready().await.unwrap();
+                let request = hyper::Request::get(path).body(Empty::new()).unwrap();
+                let response = sender.send_request(request).await.unwrap();
+                assert_eq!(response.headers()["Content-Length"], path.len().to_string());
+                assert!(response.headers().get("Connection").is_none());
+                let body = response.into_body().collect().await.unwrap().to_bytes();
+                assert_eq!(body, path.as_bytes());
+            }
+            // Both requests were served over the same open connection.
+            assert!(!server.is_finished());
+            drop(sender);
+            let served = tokio::time::timeout(Duration::from_secs(2), server)
+                .await
+                .expect("the connection was not closed");
+            assert!(served.unwrap().is_ok());
+        });
+    }
+
+    #[test]
+    fn test_keep_alive_disabled() {
+        use http_body_util::{BodyExt, Empty};
+        use std::time::Duration;
+
+        block_on(async {
+            let (mut sender, server) = connect_keep_alive(false).await;
+            let request = hyper::Request::get("/only").body(Empty::new()).unwrap();
+            let response = sender.send_request(request).await.unwrap();
+            response.into_body().collect().await.unwrap();
+            // The server closes the connection after the first response.
+            let served = tokio::time::timeout(Duration::from_secs(2), server)
+                .await
+                .expect("the connection was kept alive");
+            assert!(served.unwrap().is_ok());
+            assert!(sender.ready().await.is_err());
+        });
+    }
+
+    #[test]
+    fn test_redact_authorization_header() {
+        let request = hyper::Request::builder()
+            .uri("/todos")
+            .header("Authorization", "Bearer secret-token")
+            .header("Accept", "application/json")
+            .body(())
+            .unwrap();
+        let mut config = load_project_config(
+            &get_project_config_file_path(Path::new("./examples/todo/")),
+            None,
+        );
+        let serialized = requests::serialize(&request, &redacted_headers(&config));
+        assert!(serialized.contains("authorization: ***\r\n"));
+        assert!(!serialized.contains("secret-token"));
+        assert!(serialized.contains("accept: application/json\r\n"));
+        // Configured headers are masked in addition to the defaults.
+        config.redact_headers = Some(vec!["Accept".into()]);
+        let serialized = requests::serialize(&request, &redacted_headers(&config));
+        assert!(serialized.contains("authorization: ***\r\n"));
+        assert!(serialized.contains("accept: ***\r\n"));
+        // Full output only with an explicit opt-in.
+        config.log_secrets = Some(true);
+        let serialized = requests::serialize(&request, &redacted_headers(&config));
+        assert!(serialized.contains("Bearer secret-token"));
+    }
+
+    #[test]
+    fn test_metrics_counters() {
+        use std::time::Duration;
+
+        let metrics = WXMetrics::new("/metrics");
+        let scrape = |metrics: &WXMetrics| {
+            let response = responses::metrics(WXMode::Prod, metrics.render());
+            assert!(response.headers()["Content-Type"]
+                .to_str()
+                .unwrap()
+                .starts_with("text/plain"));
+            String::from_utf8(response.body().to_vec()).unwrap()
+        };
+        assert!(scrape(&metrics).contains("webx_requests_total 0\n"));
+        for status in [200, 200, 404] {
+            metrics.record_request();
+            let route = (status == 200).then_some("/todos");
+            let method = hyper::Method::GET;
+            metrics.record_response(&method, route, status, Duration::from_millis(20));
+        }
+        let body = scrape(&metrics);
+        assert!(body.contains("webx_requests_total 3\n"));
+        assert!(body.contains("webx_responses_total{status=\"200\"} 2\n"));
+        assert!(body.contains("webx_responses_total{status=\"404\"} 1\n"));
+        assert!(body.contains(
+            "webx_route_duration_seconds_bucket{method=\"GET\",route=\"/todos\",le=\"0.01\"} 0\n"
+        ));
+        assert!(body.contains(
+            "webx_route_duration_seconds_bucket{method=\"GET\",route=\"/todos\",le=\"0.025\"} 2\n"
+        ));
+        assert!(body
+            .contains("webx_route_duration_seconds_count{method=\"GET\",route=\"unmatched\"} 1\n"));
+    }
+
+    /// A runtime running on its own thread, see `spawn_runtime`.
+    /// The runtime is stopped and its thread joined when this is dropped,
+    /// also when an assertion of the test panics.
+    struct WXSpawnedRuntime {
+        rt_tx: std::sync::mpsc::Sender<WXRuntimeMessage>,
+        running: Arc<AtomicBool>,
+        thread: Option<std::thread::JoinHandle<()>>,
+    }
+
+    impl WXSpawnedRuntime {
+        /// Run an async test with a sender to the runtime, then stop the runtime.
+        fn test<F, Fut>(self, test: F) -> Fut::Output
+        where
+            F: FnOnce(std::sync::mpsc::Sender<WXRuntimeMessage>) -> Fut,
+            Fut: std::future::Future,
+        {
+            block_on(test(self.rt_tx.clone()))
+        }
+    }
+
+    impl Drop for WXSpawnedRuntime {
+        fn drop(&mut self) {
+            use std::sync::atomic::Ordering;
+
+            self.running.store(false, Ordering::SeqCst);
+            if let Some(thread) = self.thread.take() {
+                let joined = thread.join();
+                // Panicking again while a failed assertion unwinds would abort the tests.
+                if !std::thread::panicking() {
+                    joined.unwrap();
+                }
+            }
+        }
+    }
+
+    /// Run a future to completion on a single-threaded executor.
+    fn block_on<F: std::future::Future>(future: F) -> F::Output {
+        tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(future)
+    }
+
+    /// Run an async test against a runtime with the given module in the todo example,
+    /// see `spawn_runtime` and `WXSpawnedRuntime::test`.
+    fn with_runtime<F, Fut>(file: &str, source: &str, test: F) -> Fut::Output
+    where
+        F: FnOnce(std::sync::mpsc::Sender<WXRuntimeMessage>) -> Fut,
+        Fut: std::future::Future,
+    {
+        spawn_runtime(PathBuf::from("examples/todo"), file, source).test(test)
+    }
+
+    /// Run a runtime with the given module on its own thread,
+    /// using the configuration of the todo example.
+    fn spawn_runtime(root: PathBuf, file: &str, source: &str) -> WXSpawnedRuntime {
+        spawn_runtime_in(WXMode::Dev(DebugLevel::Low), root, file, source)
+    }
+
+    /// Run a runtime in the given mode, see `spawn_runtime`.
+    fn spawn_runtime_in(mode: WXMode, root: PathBuf, file: &str, source: &str) -> WXSpawnedRuntime {
+        let config = load_project_config(
+            &get_project_config_file_path(Path::new("examples/todo")),
+            None,
+        );
+        spawn_runtime_with(mode, root, config, file, source)
+    }
+
+    /// Run a runtime with the given configuration, see `spawn_runtime`.
+    fn spawn_runtime_with(
+        mode: WXMode,
+        root: PathBuf,
+        config: ProjectConfig,
+        file: &str,
+        source: &str,
+    ) -> WXSpawnedRuntime {
+        let info = WXRuntimeInfo::new(&root, &config, mode);
+        spawn_runtime_with_info(mode, info, file, source)
+    }
+
+    /// Run a runtime with the given runtime info, see `spawn_runtime`.
+    fn spawn_runtime_with_info(
+        mode: WXMode,
+        info: WXRuntimeInfo,
+        file: &str,
+        source: &str,
+    ) -> WXSpawnedRuntime {
+        let module = parse_webx_source(&info.project_root.join(file), source).unwrap();
+        spawn_runtime_with_modules(mode, info, vec![module])
+    }
+
+    /// Run a runtime with the given modules, see `spawn_runtime`.
+    fn spawn_runtime_with_modules(
+        mode: WXMode,
+        info: WXRuntimeInfo,
+        modules: Vec<WXModule>,
+    ) -> WXSpawnedRuntime {
+        let (rt_tx, rt_rx) = std::sync::mpsc::channel();
+        let running = Arc::new(AtomicBool::new(true));
+        let runtime_running = running.clone();
+        let thread = std::thread::spawn(move || {
+            let mut runtime = WXRuntime::new(rt_rx, mode, info);
+            runtime.load_modules(modules);
+            runtime.run(runtime_running, Arc::new(AtomicBool::new(false)));
+        });
+        WXSpawnedRuntime {
+            rt_tx,
+            running,
+            thread: Some(thread),
+        }
+    }
+
+    type WXRuntimeResponse = tokio::sync::oneshot::Receiver<
+        Result<hyper::Response<WXResponseBody>, crate::engine::runtime::WXRuntimeError>,
+    >;
+
+    /// Send a request to a runtime.
+    fn send(
+        rt_tx: &std::sync::mpsc::Sender<WXRuntimeMessage>,
+        request: hyper::Request<hyper::body::Bytes>,
+    ) -> WXRuntimeResponse {
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        rt_tx
+            .send(WXRuntimeMessage::ExecuteRoute {
+                request,
+                addr: "127.0.0.1:8080".parse().unwrap(),
+                respond_to: tx,
+            })
+            .unwrap();
+        rx
+    }
+
+    /// Send a `GET` request for the given path to a runtime.
+    fn request(rt_tx: &std::sync::mpsc::Sender<WXRuntimeMessage>, path: &str) -> WXRuntimeResponse {
+        let request = hyper::Request::get(path)
+            .body(hyper::body::Bytes::new())
+            .unwrap();
+        send(rt_tx, request)
+    }
+
+    #[test]
+    fn test_event_stream() {
+        use http_body_util::BodyExt;
+        use std::time::Duration;
+
+        async fn next_frame(body: &mut WXResponseBody) -> String {
+            let frame = body.frame().await.unwrap().unwrap();
+            String::from_utf8(frame.into_data().unwrap().to_vec()).unwrap()
+        }
+
+        let source = r#"
+global {
+    let closed = false;
+}
+
+get /events {
+    return (async function* () {
+        try {
+            yield "hello";
+            let n = 0;
+            while (true) {
+                n++;
+                yield { event: "tick", id: n, data: { n } };
+            }
+        } finally {
+            closed = true;
+        }
+    })();
+}
+
+get /closed {
+    return closed ? "closed" : "open";
+}
+"#;
+        with_runtime("events.webx", source, |rt_tx| async move {
+            let response = request(&rt_tx, "/events").await.unwrap().unwrap();
+            assert_eq!(response.headers()["Content-Type"], "text/event-stream");
+            let mut body = response.into_body();
+            assert_eq!(next_frame(&mut body).await, "data: hello\n\n");
+            assert_eq!(
+                next_frame(&mut body).await,
+                "event: tick\nid: 1\ndata: {\"n\":1}\n\n"
+            );
+            // Disconnect the client, which should close the generator.
+            drop(body);
+            let mut closed = String::new();
+            for _ in 0..50 {
+                let response = request(&rt_tx, "/closed").await.unwrap().unwrap();
+                closed = String::from_utf8(response.body().bytes().unwrap().to_vec()).unwrap();
+                if closed == "closed" {
+                    break;
+                }
+                tokio::time::sleep(Duration::from_millis(20)).await;
+            }
+            assert_eq!(closed, "closed");
+        });
+    }
+
+    #[test]
+    fn test_websocket_echo() {
+        use futures_util::{SinkExt, StreamExt};
+        use tokio_tungstenite::{
+            tungstenite::{protocol::Role, Message},
+            WebSocketStream,
+        };
+
+        let mode = WXMode::Dev(DebugLevel::Low);
+        let source = r#"
+get /echo (<p>Echo</p>)
+
+ws /echo {
+    socket.onMessage((message) => socket.send("echo: " + message));
+}
+"#;
+        with_runtime("echo.webx", source, |rt_tx| async move {
+            let addr = "127.0.0.1:8080".parse().unwrap();
+            // HTTP routes on the same path are unaffected.
+            let response = request(&rt_tx, "/echo").await.unwrap().unwrap();
+            assert_eq!(response.body().bytes(), Some(&b"<p>Echo</p>"[..]));
+            let (tx, rx) = tokio::sync::oneshot::channel();
+            rt_tx
+                .send(WXRuntimeMessage::OpenSocket {
+                    request: hyper::Request::get("/echo")
+                        .body(hyper::body::Bytes::new())
+                        .unwrap(),
+                    addr,
+                    respond_to: tx,
+                })
+                .unwrap();
+            let WXSocketOpen::Accepted { id, frames } = rx.await.unwrap() else {
+                panic!("WebSocket handshake was rejected");
+            };
+            let (client_io, server_io) = tokio::io::duplex(4096);
+            let server = tokio::spawn(serve_websocket(
+                server_io,
+                id,
+                frames,
+                Arc::new(rt_tx.clone()),
+                mode,
+            ));
+            let mut client = WebSocketStream::from_raw_socket(client_io, Role::Client, None).await;
+            client.send(Message::Text("hello".into())).await.unwrap();
+            let echo = client.next().await.unwrap().unwrap();
+            assert_eq!(echo, Message::Text("echo: hello".into()));
+            client.close(None).await.unwrap();
+            server.await.unwrap();
+        });
+    }
+
+    #[test]
+    fn test_stream_static_file() {
+        use crate::engine::http::FILE_CHUNK_SIZE;
+        use http_body_util::BodyExt;
+        use hyper::body::Body;
+
+        const FIXTURE_SIZE: usize = 4 * 1024 * 1024;
+        let root = std::env::temp_dir().join(format!("webx-stream-{}", std::process::id()));
+        std::fs::create_dir_all(&root).unwrap();
+        let fixture: Vec<u8> = (0..FIXTURE_SIZE).map(|i| (i % 251) as u8).collect();
+        std::fs::write(root.join("fixture.bin"), &fixture).unwrap();
+        spawn_runtime(
+            root.clone(),
+            "download.webx",
+            "get /download -> static(\"fixture.bin\")\n",
+        )
+        .test(|rt_tx| async move {
+            let response = request(&rt_tx, "/download").await.unwrap().unwrap();
+            assert_eq!(
+                response.headers()["Content-Length"],
+                FIXTURE_SIZE.to_string()
+            );
+            assert_eq!(
+                response.headers()["Content-Type"],
+                "application/octet-stream"
+            );
+            let mut body = response.into_body();
+            assert!(body.bytes().is_none());
+            assert_eq!(body.size_hint().exact(), Some(FIXTURE_SIZE as u64));
+            // Read the body chunk by chunk, as the server would.
+            let (mut frames, mut offset) = (0, 0);
+            while let Some(frame) = body.frame().await {
+                let chunk = frame.unwrap().into_data().unwrap();
+                assert!(chunk.len() <= FILE_CHUNK_SIZE);
+                assert_eq!(&chunk[..], &fixture[offset..offset + chunk.len()]);
+                offset += chunk.len();
+                frames += 1;
+            }
+            assert_eq!(offset, FIXTURE_SIZE);
+            assert!(frames > 1);
+            assert!(body.is_end_stream());
+        });
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_conditional_get() {
+        let conditional = |name: &str, value: &str| {
+            hyper::Request::get("/page")
+                .header(name, value)
+                .body(hyper::body::Bytes::new())
+                .unwrap()
+        };
+        let source = "get /page (<p>Page</p>)\n";
+        with_runtime("page.webx", source, |rt_tx| async move {
+            // Miss: the full response is tagged.
+            let response = request(&rt_tx, "/page").await.unwrap().unwrap();
+            assert_eq!(response.status(), hyper::StatusCode::OK);
+            let etag = response.headers()["ETag"].to_str().unwrap().to_string();
+            assert_eq!(etag, responses::etag(b"<p>Page</p>"));
+            // Hit: weak comparison ignores the `W/` prefix.
+            let hit = conditional("If-None-Match", &format!("\"other\", W/{}", etag));
+            let response = send(&rt_tx, hit).await.unwrap().unwrap();
+            assert_eq!(response.status(), hyper::StatusCode::NOT_MODIFIED);
+            assert_eq!(response.headers()["ETag"], etag.as_str());
+            assert!(response.headers().get("Content-Length").is_none());
+            assert_eq!(response.body().bytes(), Some(&b""[..]));
+            // A stale tag gets the full response again.
+            let miss = conditional("If-None-Match", "\"stale\"");
+            let response = send(&rt_tx, miss).await.unwrap().unwrap();
+            assert_eq!(response.status(), hyper::StatusCode::OK);
+            assert_eq!(response.body().bytes(), Some(&b"<p>Page</p>"[..]));
+        });
+    }
+
+    #[test]
+    fn test_live_reload() {
+        use http_body_util::BodyExt;
+
+        let source = r#"
+get /page (<html><body><p>Page</p></body></html>)
+
+get /data {
+    return { ok: true };
+}
+"#;
+        let body = |response: &hyper::Response<WXResponseBody>| {
+            String::from_utf8(response.body().bytes().unwrap().to_vec()).unwrap()
+        };
+        // Dev: HTML responses reload the page once a module is swapped.
+        with_runtime("page.webx", source, |rt_tx| async move {
+            let response = request(&rt_tx, "/page").await.unwrap().unwrap();
+            let page = body(&response);
+            let script = format!(
+                "<script>new EventSource(\"{}\")",
+                responses::LIVE_RELOAD_PATH
+            );
+            assert!(page.starts_with("<html><body><p>Page</p><script>"));
+            assert!(page.contains(&script));
+            assert!(page.ends_with("</script></body></html>"));
+            assert_eq!(response.headers()["Content-Length"], page.len().to_string());
+            let response = request(&rt_tx, "/data").await.unwrap().unwrap();
+            assert!(!body(&response).contains("<script>"));
+            let response = request(&rt_tx, responses::LIVE_RELOAD_PATH)
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(response.headers()["Content-Type"], "text/event-stream");
+            let module = parse_webx_source(
+                &PathBuf::from("examples/todo/page.webx"),
+                "get /page (<p>Swapped</p>)\n",
+            )
+            .unwrap();
+            rt_tx.send(WXRuntimeMessage::Swap(module)).unwrap();
+            let frame = response.into_body().frame().await.unwrap().unwrap();
+            assert_eq!(
+                frame.into_data().unwrap(),
+                responses::event_frame(Some("reload"), None, "")
+            );
+        });
+        // Prod: responses are left untouched.
+        spawn_runtime_in(
+            WXMode::Prod,
+            PathBuf::from("examples/todo"),
+            "page.webx",
+            source,
+        )
+        .test(|rt_tx| async move {
+            let response = request(&rt_tx, "/page").await.unwrap().unwrap();
+            assert_eq!(body(&response), "<html><body><p>Page</p></body></html>");
+            let response = request(&rt_tx, responses::LIVE_RELOAD_PATH)
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(response.status(), hyper::StatusCode::NOT_FOUND);
+        });
+    }
+
+    #[test]
+    fn test_reload_included_asset() {
+        let root = std::env::temp_dir().join(format!("webx-asset-{}", std::process::id()));
+        std::fs::create_dir_all(&root).unwrap();
+        let asset = root.join("helpers.ts");
+        std::fs::write(
+            &asset,
+            "function greet(): string {\n    return \"hello\";\n}\n",
+        )
+        .unwrap();
+        let source = "include \"helpers.ts\"\n\nget /greet -> greet(): greeting text {\n    return greeting;\n}\n";
+        std::fs::write(root.join("index.webx"), source).unwrap();
+        spawn_runtime(root.clone(), "index.webx", source).test(|rt_tx| async move {
+            let response = request(&rt_tx, "/greet").await.unwrap().unwrap();
+            assert_eq!(response.body().bytes(), Some(&b"hello"[..]));
+            // Editing the asset reloads the module including it.
+            std::fs::write(
+                &asset,
+                "function greet(): string {\n    return \"hi\";\n}\n",
+            )
+            .unwrap();
+            rt_tx.send(WXRuntimeMessage::Asset(asset.clone())).unwrap();
+            let response = request(&rt_tx, "/greet").await.unwrap().unwrap();
+            assert_eq!(response.body().bytes(), Some(&b"hi"[..]));
+        });
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_dev_error_overlay() {
+        let root = std::env::temp_dir().join(format!("webx-overlay-{}", std::process::id()));
+        std::fs::create_dir_all(&root).unwrap();
+        let source = "// Failing routes\n\nget /fail {\n    throw new Error(\"boom <3\");\n}\n";
+        std::fs::write(root.join("fail.webx"), source).unwrap();
+        let location = format!("{}:3", root.join("fail.webx").display());
+        let fail = |mode: WXMode| {
+            let response = spawn_runtime_in(mode, root.clone(), "fail.webx", source)
+                .test(|rt_tx| async move { request(&rt_tx, "/fail").await.unwrap().unwrap() });
+            assert_eq!(response.status(), hyper::StatusCode::INTERNAL_SERVER_ERROR);
+            String::from_utf8(response.body().bytes().unwrap().to_vec()).unwrap()
+        };
+        let page = fail(WXMode::Dev(DebugLevel::Low));
+        assert!(page.contains(&location));
+        assert!(page.contains("boom &lt;3"));
+        assert!(page.contains(r#"<span class="highlight">   3 | get /fail {</span>"#));
+        assert!(page.contains("   1 | // Failing routes"));
+        let page = fail(WXMode::Prod);
+        assert!(!page.contains(&location));
+        assert!(!page.contains("boom"));
+        assert!(page.contains("500 Internal Server Error"));
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_route_cache_control() {
+        let source = "get /cached cache(1h) (<p>Cached</p>)\nget /fresh (<p>Fresh</p>)\n";
+        with_runtime("cache.webx", source, |rt_tx| async move {
+            let response = request(&rt_tx, "/cached").await.unwrap().unwrap();
+            assert_eq!(response.headers()["Cache-Control"], "public, max-age=3600");
+            // Revalidated responses keep the caching policy.
+            let etag = response.headers()["ETag"].clone();
+            let revalidate = hyper::Request::get("/cached")
+                .header("If-None-Match", etag)
+                .body(hyper::body::Bytes::new())
+                .unwrap();
+            let response = send(&rt_tx, revalidate).await.unwrap().unwrap();
+            assert_eq!(response.status(), hyper::StatusCode::NOT_MODIFIED);
+            assert_eq!(response.headers()["Cache-Control"], "public, max-age=3600");
+            let response = request(&rt_tx, "/fresh").await.unwrap().unwrap();
+            assert_eq!(response.headers()["Cache-Control"], "no-cache");
+        });
+    }
+
+    #[test]
+    fn test_raw_string_body() {
+        let source = r##"
+get /about """
+<h1>"About" {us}</h1>
+    <p>\n (unbalanced</p>
+"""
+"##;
+        spawn_runtime_in(
+            WXMode::Prod,
+            PathBuf::from("examples/todo"),
+            "about.webx",
+            source,
+        )
+        .test(|rt_tx| async move {
+            let response = request(&rt_tx, "/about").await.unwrap().unwrap();
+            assert_eq!(
+                response.headers()["Content-Type"],
+                "text/html; charset=utf-8"
+            );
+            assert_eq!(
+                response.body().bytes(),
+                Some(&b"\n<h1>\"About\" {us}</h1>\n    <p>\\n (unbalanced</p>\n"[..])
+            );
+        });
+    }
+
+    #[test]
+    fn test_json_text_bodies() {
+        let source = r#"
+get /json json {
+    return "quoted";
+}
+
+get /text text {
+    return { n: 1 }.n + 1;
+}
+"#;
+        with_runtime("bodies.webx", source, |rt_tx| async move {
+            let response = request(&rt_tx, "/json").await.unwrap().unwrap();
+            assert_eq!(response.headers()["Content-Type"], "application/json");
+            assert_eq!(response.body().bytes(), Some(&b"\"quoted\""[..]));
+            let response = request(&rt_tx, "/text").await.unwrap().unwrap();
+            assert_eq!(
+                response.headers()["Content-Type"],
+                "text/plain; charset=utf-8"
+            );
+            assert_eq!(response.body().bytes(), Some(&b"2"[..]));
+        });
+    }
+
+    #[test]
+    fn test_env_handler() {
+        std::env::set_var("WEBX_TEST_ENV_FLAG", "on");
+        std::env::remove_var("WEBX_TEST_ENV_UNSET");
+        let source = r#"
+get /flag -> env("WEBX_TEST_ENV_FLAG"): flag, env("WEBX_TEST_ENV_UNSET", "off"): fallback, env("WEBX_TEST_ENV_UNSET"): missing text {
+    return flag + "," + fallback + "," + typeof missing;
+}
+"#;
+        with_runtime("env.webx", source, |rt_tx| async move {
+            let response = request(&rt_tx, "/flag").await.unwrap().unwrap();
+            assert_eq!(response.body().bytes(), Some(&b"on,off,undefined"[..]));
+        });
+        let allowlist = ["WEBX_TEST_ENV_FLAG".to_string()];
+        assert_eq!(
+            read_env_var("WEBX_TEST_ENV_FLAG", Some(&allowlist)).unwrap(),
+            Some("on".into())
+        );
+        let err = read_env_var("PATH", Some(&allowlist)).unwrap_err();
+        assert_eq!(err.message, "env: access to 'PATH' is not allowed");
+    }
+
+    #[test]
+    fn test_fetch_handler() {
+        use http_body_util::{BodyExt, Full};
+
+        let executor = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        let listener = executor
+            .block_on(tokio::net::TcpListener::bind("127.0.0.1:0"))
+            .unwrap();
+        let port = listener.local_addr().unwrap().port();
+        // A mock API echoing the method and body of requests.
+        executor.spawn(async move {
+            loop {
+                let (stream, _) = listener.accept().await.unwrap();
+                let svc = hyper::service::service_fn(
+                    |req: hyper::Request<hyper::body::Incoming>| async move {
+                        let method = req.method().to_string();
+                        let body = req.into_body().collect().await.unwrap().to_bytes();
+                        let body = format!("{} {}", method, String::from_utf8_lossy(&body));
+                        let response = hyper::Response::builder()
+                            .header("X-Mock", "yes")
+                            .body(Full::new(hyper::body::Bytes::from(body)))
+                            .unwrap();
+                        Ok::<_, std::convert::Infallible>(response)
+                    },
+                );
+                tokio::spawn(
+                    hyper::server::conn::http1::Builder::new()
+                        .serve_connection(hyper_util::rt::TokioIo::new(stream), svc),
+                );
+            }
+        });
+        let source = format!(
+            r#"
+get /proxy -> fetch("http://127.0.0.1:{}/echo", {{ method: "post", body: "ping" }}): res text {{
+    return res.status + " " + res.headers["x-mock"] + " " + res.body;
+}}
+
+get /both text {{
+    const url = "http://127.0.0.1:{}/echo";
+    const [a, b] = await Promise.all([
+        fetch(url, {{ method: "post", body: "a" }}),
+        fetch(url, {{ method: "post", body: "b" }}),
+    ]);
+    return a.body + "|" + b.body;
+}}
+"#,
+            port, port
+        );
+        let runtime = spawn_runtime(PathBuf::from("examples/todo"), "fetch.webx", &source);
+        let rt_tx = runtime.rt_tx.clone();
+        // The mock API is served by this executor, so the requests are sent from it too.
+        executor.block_on(async {
+            let response = request(&rt_tx, "/proxy").await.unwrap().unwrap();
+            assert_eq!(response.body().bytes(), Some(&b"200 yes POST ping"[..]));
+            // Requests are sent in the background, so both can be awaited at once.
+            let response = request(&rt_tx, "/both").await.unwrap().unwrap();
+            assert_eq!(response.body().bytes(), Some(&b"POST a|POST b"[..]));
+        });
+    }
+
+    #[test]
+    fn test_fetch_limits() {
+        use std::{io::Write, time::Duration};
+
+        let executor = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        // Accepted by the OS, but never answered.
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!(
+            "http://127.0.0.1:{}/",
+            listener.local_addr().unwrap().port()
+        );
+        let limits = WXFetchLimits {
+            timeout: Duration::from_millis(50),
+            allowed_hosts: Some(vec!["127.0.0.1".into()]),
+            ..Default::default()
+        };
+        let err = executor
+            .block_on(fetch(&url, Default::default(), &limits))
+            .unwrap_err();
+        assert_eq!(
+            err.message,
+            format!("fetch: request to '{}' timed out after 50ms", url)
+        );
+        let limits = WXFetchLimits {
+            allowed_hosts: Some(vec!["api.example.com".into()]),
+            ..Default::default()
+        };
+        let err = executor
+            .block_on(fetch(&url, Default::default(), &limits))
+            .unwrap_err();
+        assert_eq!(err.message, "fetch: host '127.0.0.1' is not allowed");
+        let err = executor
+            .block_on(fetch(
+                "ftp://files.example.com/",
+                Default::default(),
+                &Default::default(),
+            ))
+            .unwrap_err();
+        assert_eq!(
+            err.message,
+            "fetch: unsupported URL 'ftp://files.example.com/', only http and https URLs are supported"
+        );
+
+        // A response body larger than the maximum body size is rejected.
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!(
+            "http://127.0.0.1:{}/",
+            listener.local_addr().unwrap().port()
+        );
+        let server = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let body = "x".repeat(64);
+            let response = format!("HTTP/1.1 200 OK\r\nContent-Length: 64\r\n\r\n{}", body);
+            stream.write_all(response.as_bytes()).unwrap();
+        });
+        let limits = WXFetchLimits {
+            max_body_size: 16,
+            ..Default::default()
+        };
+        let err = executor
+            .block_on(fetch(&url, Default::default(), &limits))
+            .unwrap_err();
+        assert_eq!(
+            err.message,
+            format!(
+                "fetch: request to '{}' failed: response body exceeds 16 bytes",
+                url
+            )
+        );
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn test_crypto_hash() {
+        assert_eq!(
+            crypto::hash("sha256", b"abc").unwrap(),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        assert_eq!(
+            crypto::hash("sha256", b"").unwrap(),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+        assert_eq!(
+            crypto::hash("sha512", b"abc").unwrap(),
+            "ddaf35a193617abacc417349ae20413112e6fa4e89a97ea20a9eeee64b55d39a\
+             2192992a274fc1a836ba3c23a3feebbd454d4423643ce80e2a9ac94fa54ca49f"
+        );
+        // RFC 4231, test case 2.
+        assert_eq!(
+            crypto::hmac("sha256", b"Jefe", b"what do ya want for nothing?").unwrap(),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+        let err = crypto::hash("md5", b"abc").unwrap_err();
+        assert_eq!(
+            err.message,
+            "hash: unsupported algorithm 'md5', expected one of: sha256, sha512"
+        );
+    }
+
+    #[test]
+    fn test_crypto_random() {
+        let uuid = regex::Regex::new(
+            "^[0-9a-f]{8}-[0-9a-f]{4}-4[0-9a-f]{3}-[89ab][0-9a-f]{3}-[0-9a-f]{12}$",
+        )
+        .unwrap();
+        let (a, b) = (crypto::random_uuid(), crypto::random_uuid());
+        assert_ne!(a, b);
+        // The version and variant bits are set whatever the random bytes are.
+        for _ in 0..64 {
+            let id = crypto::random_uuid();
+            assert!(uuid.is_match(&id), "not a v4 UUID: {}", id);
         }
+        assert_eq!(crypto::random_bytes(16).unwrap().len(), 32);
+        assert!(crypto::random_bytes(0).is_err());
+    }
+
+    #[test]
+    fn test_error_status() {
+        let source = r#"
+get /todos/(id: Int) text {
+    if (id !== 1) return error(`Todo ${id} not found.`, 404);
+    return "todo";
+}
+get /invalid text {
+    return error("Invalid todo.", 422);
+}
+get /failed text {
+    return error("Failed.");
+}
+get /status text {
+    return error("Not an error status.", 200);
+}
+"#;
+        with_runtime("status.webx", source, |rt_tx| async move {
+            let status = |path: &'static str| {
+                let response = request(&rt_tx, path);
+                async move { response.await.unwrap().unwrap().status().as_u16() }
+            };
+            assert_eq!(status("/todos/1").await, 200);
+            assert_eq!(status("/todos/2").await, 404);
+            assert_eq!(status("/invalid").await, 422);
+            assert_eq!(status("/failed").await, 400);
+            assert_eq!(status("/status").await, 500);
+            // The status is reset for every request.
+            assert_eq!(status("/todos/1").await, 200);
+        });
+    }
+
+    #[test]
+    fn test_handler_error_location() {
+        let source = r#"
+global {
+    function auth(user_id) {
+        throw new Error("No database connection.");
+    }
+}
+on error(e) text {
+    return e.message;
+}
+get /todos -> auth(1) text {
+    return "todos";
+}
+get /notes text {
+    throw new Error("No notes.");
+}
+"#;
+        with_runtime("located.webx", source, |rt_tx| async move {
+            let response = request(&rt_tx, "/todos").await.unwrap().unwrap();
+            let message = String::from_utf8_lossy(response.body().bytes().unwrap()).to_string();
+            assert!(
+                message.starts_with("Handler `auth` in examples/todo/located.webx:10 threw"),
+                "{}",
+                message
+            );
+            assert!(message.contains("No database connection."));
+            let response = request(&rt_tx, "/notes").await.unwrap().unwrap();
+            let message = String::from_utf8_lossy(response.body().bytes().unwrap()).to_string();
+            assert!(
+                message.starts_with("Route body in examples/todo/located.webx:13 threw"),
+                "{}",
+                message
+            );
+        });
+    }
+
+    #[test]
+    fn test_module_error_skips_routes() {
+        let root = PathBuf::from("examples/todo");
+        let broken = r#"
+global {
+    throw new Error("No database connection.");
+}
+get /broken text {
+    return "broken";
+}
+"#;
+        let working = r#"
+get /working text {
+    return "working";
+}
+"#;
+        let modules = vec![
+            parse_webx_source(&root.join("broken.webx"), broken).unwrap(),
+            parse_webx_source(&root.join("working.webx"), working).unwrap(),
+        ];
+        let mode = WXMode::Dev(DebugLevel::Low);
+        let info = WXRuntimeInfo::new(&root, &ProjectConfig::new("webx".into()), mode);
+        spawn_runtime_with_modules(mode, info, modules).test(|rt_tx| async move {
+            let response = request(&rt_tx, "/working").await.unwrap().unwrap();
+            assert_eq!(response.status(), hyper::StatusCode::OK);
+            assert_eq!(response.body().bytes(), Some(&b"working"[..]));
+            let response = request(&rt_tx, "/broken").await.unwrap().unwrap();
+            assert_eq!(response.status(), hyper::StatusCode::NOT_FOUND);
+        });
+    }
+
+    #[test]
+    fn test_error_stops_handlers() {
+        let source = r#"
+global {
+    let calls = 0;
+    function auth(user_id) {
+        if (user_id === 0) return error("You are not logged in.", 401);
+    }
+    function count() {
+        calls++;
+    }
+}
+get /(user_id: Int)/todos -> auth(user_id), count() text {
+    return `${calls}`;
+}
+"#;
+        with_runtime("auth.webx", source, |rt_tx| async move {
+            let response = request(&rt_tx, "/0/todos").await.unwrap().unwrap();
+            assert_eq!(response.status(), hyper::StatusCode::UNAUTHORIZED);
+            let body = String::from_utf8_lossy(response.body().bytes().unwrap()).to_string();
+            assert!(body.contains("You are not logged in."));
+
+            let request_json = hyper::Request::get("/0/todos")
+                .header("Accept", "application/json")
+                .body(hyper::body::Bytes::new())
+                .unwrap();
+            let response = send(&rt_tx, request_json).await.unwrap().unwrap();
+            assert_eq!(response.status(), hyper::StatusCode::UNAUTHORIZED);
+            assert_eq!(
+                response.body().bytes(),
+                Some(&br#"{"error":"You are not logged in."}"#[..])
+            );
+
+            // The rejected requests did not run the later handlers or the body.
+            let response = request(&rt_tx, "/1/todos").await.unwrap().unwrap();
+            assert_eq!(response.status(), hyper::StatusCode::OK);
+            assert_eq!(response.body().bytes(), Some(&b"1"[..]));
+        });
+    }
+
+    #[test]
+    fn test_error_handler_status() {
+        let source = r#"
+on error(e) text {
+    return `${e.status}: ${e.message.includes("Todo not found.")}`;
+}
+get /todo text {
+    return error("Todo not found.", 404);
+}
+"#;
+        with_runtime("handler.webx", source, |rt_tx| async move {
+            let response = request(&rt_tx, "/todo").await.unwrap().unwrap();
+            assert_eq!(response.status(), hyper::StatusCode::NOT_FOUND);
+            assert_eq!(response.body().bytes(), Some(&b"404: true"[..]));
+        });
+    }
+
+    #[test]
+    fn test_error_handler_throws() {
+        let source = r#"
+on error(e) text {
+    throw new Error("Handler failed.");
+}
+get /fail text {
+    throw new Error("Route failed.");
+}
+"#;
+        spawn_runtime_in(
+            WXMode::Prod,
+            PathBuf::from("examples/todo"),
+            "throws.webx",
+            source,
+        )
+        .test(|rt_tx| async move {
+            // The default error page is sent in place of the failed handler.
+            let response = request(&rt_tx, "/fail").await.unwrap().unwrap();
+            assert_eq!(response.status(), hyper::StatusCode::INTERNAL_SERVER_ERROR);
+            let page = String::from_utf8_lossy(response.body().bytes().unwrap()).to_string();
+            assert!(page.contains("500 Internal Server Error"), "{}", page);
+            assert!(!page.contains("failed"), "{}", page);
+        });
+    }
+
+    #[test]
+    fn test_date_helpers() {
+        // The todo of the default project template.
+        let source = r#"
+global {
+    const todo = {
+        title: "My Todo",
+        completed: false,
+        createdAt: new Date(Date.now() - 2 * 60 * 1000),
+    };
+    function formatDate(date) {
+        return "custom";
+    }
+}
+get /todo text {
+    return `${todo.title} - ${getTimeDiff(todo.createdAt)}`;
+}
+get /formatted text {
+    return formatDate(todo.createdAt);
+}
+"#;
+        with_runtime("dates.webx", source, |rt_tx| async move {
+            let response = request(&rt_tx, "/todo").await.unwrap().unwrap();
+            assert_eq!(
+                response.body().bytes(),
+                Some(&b"My Todo - 2 minutes ago"[..])
+            );
+            // User code replaces the helpers of the same name.
+            let response = request(&rt_tx, "/formatted").await.unwrap().unwrap();
+            assert_eq!(response.body().bytes(), Some(&b"custom"[..]));
+        });
+
+        let mut rt = new_stdlib_js_runtime(WXMode::Dev(DebugLevel::Low));
+        let value = rt
+            .execute_script(
+                "[test]",
+                r#"[
+                    formatDate(new Date(Date.UTC(2024, 0, 2, 3, 4, 5)), "YYYY-MM-DD HH:mm:ss"),
+                    getTimeDiff(new Date(0), new Date(3 * 24 * 60 * 60 * 1000)),
+                    getTimeDiff(new Date(60 * 60 * 1000), new Date(0)),
+                    getTimeDiff(new Date(0), new Date(0)),
+                ].join("|")"#
+                    .to_string()
+                    .into(),
+            )
+            .unwrap();
+        let scope = &mut rt.handle_scope();
+        let value = deno_core::v8::Local::new(scope, value).to_rust_string_lossy(scope);
+        assert_eq!(value, "2024-01-02 03:04:05|3 days ago|in 1 hour|just now");
+    }
+
+    #[test]
+    fn test_request_object() {
+        let source = r#"
+global {
+    function describe(req) {
+        return `${req.method} ${req.path} ${typeof req.params.id} ${req.params.id} ${req.query.page}`;
+    }
+}
+get /todos/(id: Int) -> describe(req): description text {
+    return `${description} ${id === req.params.id}`;
+}
+post /todos json(title: String) json {
+    return { title: req.body.title, type: req.headers["content-type"], ip: req.ip };
+}
+"#;
+        with_runtime("req.webx", source, |rt_tx| async move {
+            let response = request(&rt_tx, "/todos/7?page=2&sort=asc")
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(
+                response.body().bytes(),
+                Some(&b"GET /todos/7 number 7 2 true"[..])
+            );
+
+            let request_json = hyper::Request::post("/todos")
+                .header("Content-Type", "application/json")
+                .body(hyper::body::Bytes::from(r#"{"title":"My Todo"}"#))
+                .unwrap();
+            let response = send(&rt_tx, request_json).await.unwrap().unwrap();
+            assert_eq!(
+                response.body().bytes(),
+                Some(&br#"{"title":"My Todo","type":"application/json","ip":"127.0.0.1"}"#[..])
+            );
+        });
+    }
+
+    #[test]
+    fn test_invalid_body_status() {
+        let source = r#"
+global {
+    function save(title) {
+        throw new Error("No database connection.");
+    }
+}
+post /todos json(title: String, done: Bool) text {
+    return `${req.body.title} ${req.body.done}`;
+}
+post /todos/form form(title: String, priority: Int) text {
+    return `${req.body.title} ${typeof req.body.priority}`;
+}
+post /todos/save json(title: String) -> save(req.body.title) text {
+    return "saved";
+}
+"#;
+        let post = |path: &str, content_type: &str, body: &'static str| {
+            hyper::Request::post(path)
+                .header("Content-Type", content_type)
+                .body(hyper::body::Bytes::from(body))
+                .unwrap()
+        };
+        with_runtime("body.webx", source, |rt_tx| async move {
+            let json = "application/json";
+            let response = send(&rt_tx, post("/todos", json, r#"{"title":"A","done":true}"#))
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(response.status(), hyper::StatusCode::OK);
+            assert_eq!(response.body().bytes(), Some(&b"A true"[..]));
+
+            for body in [
+                r#"{"title":"A","#,
+                r#"{"title":"A"}"#,
+                r#"{"title":1,"done":true}"#,
+            ] {
+                let response = send(&rt_tx, post("/todos", json, body))
+                    .await
+                    .unwrap()
+                    .unwrap();
+                assert_eq!(
+                    response.status(),
+                    hyper::StatusCode::BAD_REQUEST,
+                    "{}",
+                    body
+                );
+            }
+
+            let form = "application/x-www-form-urlencoded";
+            let response = send(&rt_tx, post("/todos/form", form, "title=A&priority=2"))
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(response.body().bytes(), Some(&b"A number"[..]));
+            let response = send(&rt_tx, post("/todos/form", form, "title=A&priority=high"))
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(response.status(), hyper::StatusCode::BAD_REQUEST);
+
+            // A failing handler is still a server error.
+            let response = send(&rt_tx, post("/todos/save", json, r#"{"title":"A"}"#))
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(response.status(), hyper::StatusCode::INTERNAL_SERVER_ERROR);
+        });
+    }
+
+    #[test]
+    fn test_patch_delete_body_binding() {
+        let source = r#"
+global {
+    function describe(id, title, done) {
+        return `${id} ${title} ${done}`;
+    }
+}
+patch /todos/(id: Int) json(title: String, done: Bool) -> describe(id, title, done): result text {
+    return result;
+}
+delete /todos json(ids: Int[]) text {
+    return `${ids.length}`;
+}
+"#;
+        let mut config = ProjectConfig::new("webx".into());
+        config.allow_delete_body = Some(true);
+        spawn_runtime_with(
+            WXMode::Dev(DebugLevel::Low),
+            PathBuf::from("examples/todo"),
+            config,
+            "patch.webx",
+            source,
+        )
+        .test(|rt_tx| async move {
+            let patch = hyper::Request::patch("/todos/3")
+                .header("Content-Type", "application/json")
+                .body(hyper::body::Bytes::from(
+                    r#"{"title":"My Todo","done":true}"#,
+                ))
+                .unwrap();
+            assert!(requests::serialize(&patch, &[]).contains("My Todo"));
+            let response = send(&rt_tx, patch).await.unwrap().unwrap();
+            assert_eq!(response.status(), hyper::StatusCode::OK);
+            assert_eq!(response.body().bytes(), Some(&b"3 My Todo true"[..]));
+
+            let delete = hyper::Request::delete("/todos")
+                .header("Content-Type", "application/json")
+                .header("Content-Length", "13")
+                .body(hyper::body::Bytes::from(r#"{"ids":[1,2]}"#))
+                .unwrap();
+            assert!(requests::has_body(&delete));
+            let response = send(&rt_tx, delete).await.unwrap().unwrap();
+            assert_eq!(response.body().bytes(), Some(&b"2"[..]));
+        });
+        assert!(!requests::has_body(
+            &hyper::Request::delete("/todos").body(()).unwrap()
+        ));
+    }
+
+    #[test]
+    fn test_context_isolation() {
+        let source = r#"
+get /first/(secret: String) -> String(secret): copy text {
+    return secret + "," + copy;
+}
+get /second text {
+    return typeof secret + "," + typeof copy;
+}
+get /shadow/(formatDate: String) text {
+    return formatDate;
+}
+get /date text {
+    return typeof formatDate;
+}
+"#;
+        with_runtime("context.webx", source, |rt_tx| async move {
+            let response = request(&rt_tx, "/first/abc").await.unwrap().unwrap();
+            assert_eq!(response.body().bytes(), Some(&b"abc,abc"[..]));
+            // Parameters and handler outputs of earlier requests are not visible.
+            let response = request(&rt_tx, "/second").await.unwrap().unwrap();
+            assert_eq!(response.body().bytes(), Some(&b"undefined,undefined"[..]));
+            // A parameter named like a global only shadows it for its own request.
+            let response = request(&rt_tx, "/shadow/x").await.unwrap().unwrap();
+            assert_eq!(response.body().bytes(), Some(&b"x"[..]));
+            let response = request(&rt_tx, "/date").await.unwrap().unwrap();
+            assert_eq!(response.body().bytes(), Some(&b"function"[..]));
+        });
+    }
+
+    #[test]
+    fn test_crypto_handlers() {
+        let source = r#"
+get /crypto -> hash("sha256", "abc"): digest, randomUUID(): id, randomBytes(8): bytes text {
+    return [digest, id, bytes.length].join(",");
+}
+"#;
+        with_runtime("crypto.webx", source, |rt_tx| async move {
+            let response = request(&rt_tx, "/crypto").await.unwrap().unwrap();
+            let body = String::from_utf8(response.body().bytes().unwrap().to_vec()).unwrap();
+            let parts = body.split(',').collect::<Vec<_>>();
+            assert_eq!(
+                parts[0],
+                "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+            );
+            let uuid = regex::Regex::new(
+                "^[0-9a-f]{8}-[0-9a-f]{4}-4[0-9a-f]{3}-[89ab][0-9a-f]{3}-[0-9a-f]{12}$",
+            )
+            .unwrap();
+            assert!(uuid.is_match(parts[1]), "not a v4 UUID: {}", parts[1]);
+            assert_eq!(parts[2], "16");
+        });
+    }
+
+    #[test]
+    fn test_native_registry() {
+        let source = r#"
+get /double -> double(21): doubled text {
+    return `${doubled}`;
+}
+get /answer -> answer(): value text {
+    return `${value}`;
+}
+get /arity -> answer(1): value text {
+    return `${value}`;
+}
+get /hash -> hash("sha256", "abc"): digest text {
+    return digest;
+}
+"#;
+        let mut natives = WXNativeFunctions::new();
+        let double: WXNativeFunction = Arc::new(|args| {
+            let n = args[0].as_f64().ok_or("expected a number")?;
+            Ok((n * 2.0).into())
+        });
+        natives.insert("double".into(), double);
+        // Built-in functions cannot be replaced.
+        let replaced: WXNativeFunction = Arc::new(|_| Ok("replaced".into()));
+        natives.insert("hash".into(), replaced);
+        let mut registry = WXNativeRegistry::new(&natives);
+        registry.register(
+            "answer",
+            0..=0,
+            Box::new(|_, rt, _| {
+                let scope = &mut rt.handle_scope();
+                let value: deno_core::v8::Local<deno_core::v8::Value> =
+                    deno_core::v8::Integer::new(scope, 42).into();
+                Ok(deno_core::v8::Global::new(scope, value))
+            }),
+        );
+        let mode = WXMode::Dev(DebugLevel::Low);
+        let config = ProjectConfig::new("natives".into());
+        let mut info = WXRuntimeInfo::new(Path::new("examples/todo"), &config, mode);
+        info.natives = Arc::new(registry);
+        spawn_runtime_with_info(mode, info, "natives.webx", source).test(|rt_tx| async move {
+            let response = request(&rt_tx, "/double").await.unwrap().unwrap();
+            assert_eq!(response.body().bytes(), Some(&b"42"[..]));
+            let response = request(&rt_tx, "/answer").await.unwrap().unwrap();
+            assert_eq!(response.body().bytes(), Some(&b"42"[..]));
+            let response = request(&rt_tx, "/arity").await.unwrap().unwrap();
+            assert_eq!(response.status(), hyper::StatusCode::INTERNAL_SERVER_ERROR);
+            let response = request(&rt_tx, "/hash").await.unwrap().unwrap();
+            assert_eq!(
+                response.body().bytes(),
+                Some(&b"ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"[..])
+            );
+        });
+    }
+
+    #[test]
+    fn test_route_panic() {
+        let source = r#"
+get /panic -> explode(): value text {
+    return `${value}`;
+}
+get /ok text {
+    return "ok";
+}
+"#;
+        let mut registry = WXNativeRegistry::new(&WXNativeFunctions::new());
+        registry.register("explode", 0..=0, Box::new(|_, _, _| panic!("boom")));
+        let mode = WXMode::Dev(DebugLevel::Low);
+        let config = ProjectConfig::new("panic".into());
+        let mut info = WXRuntimeInfo::new(Path::new("examples/todo"), &config, mode);
+        info.natives = Arc::new(registry);
+        spawn_runtime_with_info(mode, info, "panic.webx", source).test(|rt_tx| async move {
+            let response = request(&rt_tx, "/panic").await.unwrap().unwrap();
+            assert_eq!(response.status(), hyper::StatusCode::INTERNAL_SERVER_ERROR);
+            // The same runtime keeps serving requests after the panic.
+            let response = request(&rt_tx, "/ok").await.unwrap().unwrap();
+            assert_eq!(response.status(), hyper::StatusCode::OK);
+            assert_eq!(response.body().bytes(), Some(&b"ok"[..]));
+        });
+    }
+
+    #[test]
+    fn test_session() {
+        let source = r#"
+get /visit text {
+    session.visits = (session.visits ?? 0) + 1;
+    return session.visits;
+}
+
+post /login text {
+    session.user = "ada";
+    session.regenerate();
+    return "ok";
+}
+
+get /whoami text {
+    return session.user ?? "anonymous";
+}
+
+post /logout text {
+    session.destroy();
+    return "bye";
+}
+"#;
+        let mut config = load_project_config(
+            &get_project_config_file_path(Path::new("examples/todo")),
+            None,
+        );
+        config.session = Some(SessionConfig {
+            secret: "a test secret that is long enough".into(),
+            max_age: Some("1h".into()),
+        });
+        let runtime = spawn_runtime_with(
+            WXMode::Dev(DebugLevel::Low),
+            PathBuf::from("examples/todo"),
+            config,
+            "session.webx",
+            source,
+        );
+        let rt_tx = runtime.rt_tx.clone();
+        // Send a request with a session cookie.
+        // Responds with the body and the new session cookie, if any.
+        let call = |method: &str, path: &str, cookie: Option<&str>| {
+            let mut request = hyper::Request::builder().method(method).uri(path);
+            if let Some(cookie) = cookie {
+                request = request.header("Cookie", cookie);
+            }
+            let rx = send(&rt_tx, request.body(hyper::body::Bytes::new()).unwrap());
+            let response = block_on(rx).unwrap().unwrap();
+            let set_cookie = response.headers().get("Set-Cookie").map(|value| {
+                let value = value.to_str().unwrap();
+                value.split(';').next().unwrap().to_string()
+            });
+            let body = response.body().bytes().unwrap().to_vec();
+            (String::from_utf8(body).unwrap(), set_cookie)
+        };
+        // Sessions are only created once written to.
+        assert_eq!(call("GET", "/whoami", None), ("anonymous".into(), None));
+        let (body, cookie) = call("GET", "/visit", None);
+        assert_eq!(body, "1");
+        let cookie = cookie.expect("a session cookie");
+        assert!(cookie.starts_with("webx_session="));
+        assert_eq!(call("GET", "/visit", Some(&cookie)), ("2".into(), None));
+        // Tampered cookies start a new session.
+        let tampered = format!("{}0", cookie);
+        assert_eq!(call("GET", "/visit", Some(&tampered)).0, "1");
+        // Logging in rotates the session id.
+        let (_, login_cookie) = call("POST", "/login", Some(&cookie));
+        let login_cookie = login_cookie.expect("a rotated session cookie");
+        assert_ne!(login_cookie, cookie);
+        assert_eq!(call("GET", "/whoami", Some(&login_cookie)).0, "ada");
+        assert_eq!(call("GET", "/whoami", Some(&cookie)).0, "anonymous");
+        assert_eq!(call("GET", "/visit", Some(&login_cookie)).0, "3");
+        // Logging out clears the cookie.
+        let (_, logout_cookie) = call("POST", "/logout", Some(&login_cookie));
+        assert_eq!(logout_cookie.as_deref(), Some("webx_session="));
+        assert_eq!(call("GET", "/whoami", Some(&login_cookie)).0, "anonymous");
+    }
+
+    #[test]
+    fn test_csrf() {
+        let source = r#"
+get /form text {
+    return csrfToken;
+}
+
+post /submit text {
+    return "ok";
+}
+
+post /webhooks/github text {
+    return "hook";
+}
+"#;
+        let mut config = load_project_config(
+            &get_project_config_file_path(Path::new("examples/todo")),
+            None,
+        );
+        config.session = Some(SessionConfig {
+            secret: "a test secret that is long enough".into(),
+            max_age: None,
+        });
+        config.csrf = Some(CsrfConfig {
+            enabled: true,
+            exempt: Some(vec!["/webhooks/*".into()]),
+        });
+        let runtime = spawn_runtime_with(
+            WXMode::Dev(DebugLevel::Low),
+            PathBuf::from("examples/todo"),
+            config,
+            "csrf.webx",
+            source,
+        );
+        let rt_tx = runtime.rt_tx.clone();
+        let call = |request: hyper::http::request::Builder, body: &'static str| {
+            let rx = send(
+                &rt_tx,
+                request.body(hyper::body::Bytes::from(body)).unwrap(),
+            );
+            block_on(rx).unwrap().unwrap()
+        };
+        let response = call(hyper::Request::get("/form"), "");
+        let token = String::from_utf8(response.body().bytes().unwrap().to_vec()).unwrap();
+        let cookie = response.headers()["Set-Cookie"].to_str().unwrap();
+        let cookie = cookie.split(';').next().unwrap().to_string();
+        let submit = || hyper::Request::post("/submit").header("Cookie", &cookie);
+        // Missing and invalid tokens are rejected.
+        let response = call(submit(), "");
+        assert_eq!(response.status(), hyper::StatusCode::FORBIDDEN);
+        let response = call(submit().header("X-CSRF-Token", "invalid"), "");
+        assert_eq!(response.status(), hyper::StatusCode::FORBIDDEN);
+        let response = call(
+            hyper::Request::post("/submit").header("X-CSRF-Token", &token),
+            "",
+        );
+        assert_eq!(response.status(), hyper::StatusCode::FORBIDDEN);
+        // Valid tokens are accepted from the header or a form field.
+        let response = call(submit().header("X-CSRF-Token", &token), "");
+        assert_eq!(response.body().bytes(), Some(&b"ok"[..]));
+        let form = format!("name=a+b&_csrf={}", token);
+        let request = submit().header("Content-Type", "application/x-www-form-urlencoded");
+        let response = call(request, &form);
+        assert_eq!(response.body().bytes(), Some(&b"ok"[..]));
+        // Exempt paths are not protected.
+        let response = call(hyper::Request::post("/webhooks/github"), "");
+        assert_eq!(response.body().bytes(), Some(&b"hook"[..]));
+    }
+
+    #[test]
+    fn test_form_field() {
+        let body = b"name=Ada+Lovelace&note=1%2B1%3D2&empty=";
+        assert_eq!(
+            requests::form_field(body, "name").as_deref(),
+            Some("Ada Lovelace")
+        );
+        assert_eq!(requests::form_field(body, "note").as_deref(), Some("1+1=2"));
+        assert_eq!(requests::form_field(body, "empty").as_deref(), Some(""));
+        assert_eq!(requests::form_field(body, "missing"), None);
+    }
+
+    #[test]
+    fn test_render_template() {
+        let data = serde_json::json!({
+            "title": "<b>Tom & Jerry's</b>",
+            "user": { "name": "Ada" },
+            "count": 2,
+        });
+        assert_eq!(
+            render_template("<h1>{{ title }}</h1>", &data),
+            "<h1>&lt;b&gt;Tom &amp; Jerry&#39;s&lt;/b&gt;</h1>"
+        );
+        assert_eq!(
+            render_template("{{raw(title)}}", &data),
+            "<b>Tom & Jerry's</b>"
+        );
+        assert_eq!(
+            render_template("{{ user.name }}: {{ count }}{{ missing }}", &data),
+            "Ada: 2"
+        );
+        assert_eq!(render_template("{{ unterminated", &data), "{{ unterminated");
+    }
+
+    #[test]
+    fn test_render_handler() {
+        let root = std::env::temp_dir().join(format!("webx-render-{}", std::process::id()));
+        std::fs::create_dir_all(root.join("views")).unwrap();
+        std::fs::write(
+            root.join("views/page.html"),
+            "<p>{{ name }}</p>{{ raw(html) }}",
+        )
+        .unwrap();
+        let source = r#"
+get /page -> render("views/page.html", { name: "<Ada>", html: "<i>hi</i>" })
+get /escape -> render("../page.html", {})
+"#;
+        spawn_runtime(root.clone(), "render.webx", source).test(|rt_tx| async move {
+            let response = request(&rt_tx, "/page").await.unwrap().unwrap();
+            assert_eq!(
+                response.headers()["Content-Type"],
+                "text/html; charset=utf-8"
+            );
+            assert_eq!(
+                response.body().bytes(),
+                Some(&b"<p>&lt;Ada&gt;</p><i>hi</i>"[..])
+            );
+            let response = request(&rt_tx, "/escape").await.unwrap().unwrap();
+            assert_eq!(response.status(), hyper::StatusCode::FORBIDDEN);
+        });
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_set_header() {
+        let source = r#"
+get /custom text {
+    setHeader("X-Custom", "1");
+    setHeader("Cache-Control", "public, max-age=60");
+    return "ok";
+}
+
+get /handler -> setHeader("x-custom", "2"), static("index.html")
+
+get /injection text {
+    setHeader("X-Custom", "1\r\nSet-Cookie: admin=1");
+    return "ok";
+}
+
+get /length text {
+    setHeader("Content-Length", "1");
+    return "ok";
+}
+
+get /chunked text {
+    setHeader("transfer-encoding", "chunked");
+    return "ok";
+}
+
+get /plain text {
+    return "ok";
+}
+"#;
+        with_runtime("headers.webx", source, |rt_tx| async move {
+            let response = request(&rt_tx, "/custom").await.unwrap().unwrap();
+            assert_eq!(response.headers()["X-Custom"], "1");
+            assert_eq!(response.headers()["Cache-Control"], "public, max-age=60");
+            let response = request(&rt_tx, "/handler").await.unwrap().unwrap();
+            assert_eq!(response.headers()["X-Custom"], "2");
+            let response = request(&rt_tx, "/injection").await.unwrap().unwrap();
+            assert_eq!(response.status(), hyper::StatusCode::INTERNAL_SERVER_ERROR);
+            assert!(response.headers().get("X-Custom").is_none());
+            assert!(response.headers().get("Set-Cookie").is_none());
+            // Framing headers cannot be overridden.
+            for path in ["/length", "/chunked"] {
+                let response = request(&rt_tx, path).await.unwrap().unwrap();
+                assert_eq!(response.status(), hyper::StatusCode::INTERNAL_SERVER_ERROR);
+                assert!(response.headers().get("Transfer-Encoding").is_none());
+                assert_ne!(response.headers()["Content-Length"], "1");
+            }
+            // Headers do not carry over to later requests.
+            let response = request(&rt_tx, "/plain").await.unwrap().unwrap();
+            assert!(response.headers().get("X-Custom").is_none());
+        });
+    }
+
+    #[test]
+    fn test_server_address_overrides() {
+        use std::sync::atomic::Ordering;
+
+        let mode = WXMode::Dev(DebugLevel::Low);
+        let mut config = load_project_config(
+            &get_project_config_file_path(Path::new("examples/todo")),
+            None,
+        );
+        assert_eq!(config.port, 8080);
+        // Find a free port to override the configured one with.
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let overrides = WXConfigOverrides {
+            host: Some("127.0.0.1".into()),
+            port: Some(port),
+            threads: None,
+        };
+        overrides.apply(&mut config);
+        let (rt_tx, _rt_rx) = std::sync::mpsc::channel();
+        let mut server = WXServer::new(mode, config, rt_tx, Arc::new(AtomicBool::new(false)));
+        let addr = std::net::SocketAddr::from(([127, 0, 0, 1], port));
+        assert_eq!(server.addrs().unwrap(), vec![addr]);
+
+        let running = Arc::new(AtomicBool::new(true));
+        let server_running = running.clone();
+        let server = std::thread::spawn(move || server.run(server_running));
+        let connected = (0..50).any(|_| {
+            std::thread::sleep(std::time::Duration::from_millis(20));
+            std::net::TcpStream::connect(addr).is_ok()
+        });
+        running.store(false, Ordering::SeqCst);
+        server.join().unwrap().unwrap();
+        assert!(connected, "the server did not listen on {}", addr);
+    }
+
+    #[test]
+    fn test_parse_debug_level() {
+        // Numeric levels.
+        assert_eq!(DebugLevel::parse("1"), Ok(DebugLevel::Low));
+        assert_eq!(DebugLevel::parse("4"), Ok(DebugLevel::Max));
+        // Named levels, ignoring case.
+        assert_eq!(DebugLevel::parse("medium"), Ok(DebugLevel::Medium));
+        assert_eq!(DebugLevel::parse(" High "), Ok(DebugLevel::High));
+        // Invalid levels are rejected instead of falling back to a default.
+        for level in ["9", "0", "verbose", ""] {
+            let err = DebugLevel::parse(level).unwrap_err();
+            assert!(err.contains("is not a debug level"), "{}", err);
+        }
+        // Numbers out of range are clamped to the nearest level.
+        assert_eq!(DebugLevel::from_u8(0), DebugLevel::Low);
+        assert_eq!(DebugLevel::from_u8(3), DebugLevel::High);
+        assert_eq!(DebugLevel::from_u8(9), DebugLevel::Max);
+    }
+
+    #[test]
+    fn test_worker_threads() {
+        let mode = WXMode::Dev(DebugLevel::Low);
+        let mut config = ProjectConfig::new("threads".into());
+        let overrides = WXConfigOverrides {
+            threads: Some(3),
+            ..Default::default()
+        };
+        overrides.apply(&mut config);
+        assert_eq!(config.worker_threads, Some(3));
+        let (rt_tx, _rt_rx) = std::sync::mpsc::channel();
+        let server = WXServer::new(mode, config, rt_tx, Arc::new(AtomicBool::new(false)));
+        let runtime = server.build_runtime().unwrap();
+        assert_eq!(runtime.metrics().num_workers(), 3);
+        let name = runtime.block_on(async {
+            tokio::spawn(async { std::thread::current().name().map(String::from) })
+                .await
+                .unwrap()
+        });
+        assert_eq!(name.as_deref(), Some("webx-server"));
+
+        let mut config = ProjectConfig::new("threads".into());
+        config.worker_threads = Some(0);
+        let errors = config.validate(Path::new(".")).unwrap_err();
+        assert!(errors.contains(&"\"workerThreads\" must be at least 1, got 0".to_string()));
+    }
+
+    #[test]
+    fn test_runtime_workers() {
+        use std::{collections::BTreeMap, sync::atomic::Ordering};
+
+        let root = PathBuf::from("examples/todo");
+        // Both handlers keep their runtime busy, and return when they ran.
+        let busy = r#"
+global {
+    function busy() {
+        const start = Date.now();
+        while (Date.now() - start < 300) {}
+        return [start, Date.now()];
+    }
+    function worker() { return "NAME"; }
+}
+get /NAME json {
+    return busy();
+}
+"#;
+        let modules = vec![
+            parse_webx_source(&root.join("a.webx"), &busy.replace("NAME", "a")).unwrap(),
+            parse_webx_source(
+                &root.join("b.webx"),
+                &busy
+                    .replace("NAME", "b")
+                    .replace("worker()", "secondWorker()"),
+            )
+            .unwrap(),
+        ];
+        let mut config = ProjectConfig::new("workers".into());
+        config.graphql = Some(GraphQLConfig {
+            path: None,
+            query: Some(BTreeMap::from([(
+                "secondWorker".to_string(),
+                "String".to_string(),
+            )])),
+        });
+        let mode = WXMode::Dev(DebugLevel::Low);
+        let info = WXRuntimeInfo::new(&root, &config, mode);
+        let (rt_tx, rt_rx) = std::sync::mpsc::channel();
+        let running = Arc::new(AtomicBool::new(true));
+        let runtime_running = running.clone();
+        let runtime = std::thread::spawn(move || {
+            run_runtime(
+                rt_rx,
+                mode,
+                info,
+                modules,
+                2,
+                runtime_running,
+                Arc::new(AtomicBool::new(false)),
+            )
+        });
+        let span = |response: hyper::Response<WXResponseBody>| {
+            serde_json::from_slice::<(f64, f64)>(response.body().bytes().unwrap()).unwrap()
+        };
+        block_on(async {
+            let (a, b) = tokio::join!(request(&rt_tx, "/a"), request(&rt_tx, "/b"));
+            let (a, b) = (span(a.unwrap().unwrap()), span(b.unwrap().unwrap()));
+            // The modules are executed by different workers, so the handlers overlap.
+            assert!(
+                a.0 < b.1 && b.0 < a.1,
+                "{:?} and {:?} ran one after another",
+                a,
+                b
+            );
+            // Requests without a route are answered by the primary worker.
+            let response = request(&rt_tx, "/missing").await.unwrap().unwrap();
+            assert_eq!(response.status(), hyper::StatusCode::NOT_FOUND);
+            // GraphQL resolvers are called in the worker of their module.
+            let response = request(&rt_tx, "/graphql?query=%7B%20secondWorker%20%7D")
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(
+                serde_json::from_slice::<serde_json::Value>(response.body().bytes().unwrap())
+                    .unwrap(),
+                serde_json::json!({ "data": { "secondWorker": "b" } })
+            );
+        });
+        running.store(false, Ordering::SeqCst);
+        runtime.join().unwrap();
+
+        let mut config = ProjectConfig::new("workers".into());
+        config.runtime_workers = Some(0);
+        let errors = config.validate(Path::new(".")).unwrap_err();
+        assert!(errors.contains(&"\"runtimeWorkers\" must be at least 1, got 0".to_string()));
+    }
+
+    #[test]
+    fn test_single_file() {
+        let root = std::env::temp_dir().join(format!("webx-single-{}", std::process::id()));
+        std::fs::create_dir_all(&root).unwrap();
+        let file = root.join("hello.webx");
+        std::fs::write(&file, "get /hello text {\n    return \"hello\";\n}\n").unwrap();
+        assert!(is_module_file(&file));
+        assert!(!is_module_file(&root));
+        let (project_root, config, modules) = load_module_file_project(&file);
+        assert_eq!(project_root, root.canonicalize().unwrap());
+        assert_eq!(config.name, "hello");
+        assert_eq!(config.port, 8080);
+        assert_eq!(modules.len(), 1);
+
+        let mode = WXMode::Dev(DebugLevel::Low);
+        let info = WXRuntimeInfo::new(&project_root, &config, mode);
+        let response = spawn_runtime_with_modules(mode, info, modules)
+            .test(|rt_tx| async move { request(&rt_tx, "/hello").await.unwrap().unwrap() });
+        std::fs::remove_dir_all(&root).unwrap();
+        assert_eq!(response.status(), hyper::StatusCode::OK);
+        assert_eq!(response.body().bytes(), Some(&b"hello"[..]));
+    }
+
+    #[test]
+    fn test_graceful_shutdown() {
+        use std::{
+            io::{Read, Write},
+            sync::atomic::Ordering,
+            time::Duration,
+        };
+
+        let source = r#"
+get /slow text {
+    const end = Date.now() + 300;
+    while (Date.now() < end) {}
+    return "done";
+}
+"#;
+        let root = PathBuf::from("examples/todo");
+        let module = parse_webx_source(&root.join("shutdown.webx"), source).unwrap();
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let mut config = ProjectConfig::new("shutdown".into());
+        config.host = Some("127.0.0.1".into());
+        config.port = port;
+        let mode = WXMode::Dev(DebugLevel::Low);
+        let running = Arc::new(AtomicBool::new(true));
+        let server_running = running.clone();
+        let server = std::thread::spawn(move || {
+            let natives = WXNativeFunctions::new();
+            serve(
+                &root,
+                mode,
+                config,
+                vec![module],
+                None,
+                natives,
+                None,
+                server_running,
+            );
+        });
+        let mut stream = (0..50)
+            .find_map(|_| {
+                std::thread::sleep(Duration::from_millis(20));
+                std::net::TcpStream::connect(("127.0.0.1", port)).ok()
+            })
+            .expect("the server did not start");
+        stream
+            .write_all(b"GET /slow HTTP/1.1\r\nHost: localhost\r\n\r\n")
+            .unwrap();
+        // Shut down while the request is in progress.
+        std::thread::sleep(Duration::from_millis(100));
+        let stopped_at = std::time::Instant::now();
+        running.store(false, Ordering::SeqCst);
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        server.join().unwrap();
+        assert!(response.starts_with("HTTP/1.1 200 OK"), "{}", response);
+        assert!(response.ends_with("done"), "{}", response);
+        // The connection is closed after the response instead of kept alive.
+        assert!(stopped_at.elapsed() < Duration::from_secs(1));
+    }
+
+    #[test]
+    fn test_embedded_server() {
+        use std::{
+            io::{Read, Write},
+            time::Duration,
+        };
+
+        let source = r#"
+get /greet -> greet("Ada"): greeting text {
+    return greeting;
+}
+"#;
+        let root = PathBuf::from("examples/todo");
+        let module = parse_webx_source(&root.join("embed.webx"), source).unwrap();
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let mut config = ProjectConfig::new("embed".into());
+        config.host = Some("127.0.0.1".into());
+        config.port = port;
+        let handle = crate::WebXServer::builder()
+            .modules(root, vec![module])
+            .mode(WXMode::Dev(DebugLevel::Low))
+            .config(config)
+            .native("greet", |args| {
+                let name = args[0].as_str().ok_or("expected a name")?;
+                Ok(format!("Hello, {}!", name).into())
+            })
+            .build()
+            .unwrap()
+            .start();
+        let mut stream = (0..50)
+            .find_map(|_| {
+                std::thread::sleep(Duration::from_millis(20));
+                std::net::TcpStream::connect(("127.0.0.1", port)).ok()
+            })
+            .expect("the server did not start");
+        stream
+            .write_all(b"GET /greet HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+            .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 200 OK"), "{}", response);
+        assert!(response.ends_with("Hello, Ada!"), "{}", response);
+        assert!(!handle.is_finished());
+        handle.shutdown();
+    }
+
+    #[test]
+    fn test_embedded_server_invalid_modules() {
+        let source = r#"
+get /greet -> greet("Ada"): greeting text {
+    return greeting;
+}
+"#;
+        let root = PathBuf::from("examples/todo");
+        let module = parse_webx_source(&root.join("embed.webx"), source).unwrap();
+        // Without the `greet` native, the handler is unknown.
+        let Err(errors) = crate::WebXServer::builder()
+            .modules(root, vec![module])
+            .build()
+        else {
+            panic!("expected the unknown handler to be reported");
+        };
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].code, ERROR_UNKNOWN_HANDLER);
+    }
+
+    #[test]
+    fn test_request_id() {
+        use std::{
+            io::{Read, Write},
+            time::Duration,
+        };
+
+        let source = "get /id text {\n    return req.id;\n}\n";
+        let root = PathBuf::from("examples/todo");
+        let module = parse_webx_source(&root.join("id.webx"), source).unwrap();
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let mut config = ProjectConfig::new("id".into());
+        config.host = Some("127.0.0.1".into());
+        config.port = port;
+        let handle = crate::WebXServer::builder()
+            .modules(root, vec![module])
+            .mode(WXMode::Dev(DebugLevel::Low))
+            .config(config)
+            .build()
+            .start();
+        let get = |headers: &str| {
+            let mut stream = (0..50)
+                .find_map(|_| {
+                    std::thread::sleep(Duration::from_millis(20));
+                    std::net::TcpStream::connect(("127.0.0.1", port)).ok()
+                })
+                .expect("the server did not start");
+            let request = format!(
+                "GET /id HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n{}\r\n",
+                headers
+            );
+            stream.write_all(request.as_bytes()).unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).unwrap();
+            response
+        };
+        // A new id is generated, and bound as `req.id`.
+        let response = get("");
+        let id = response
+            .lines()
+            .find_map(|line| line.strip_prefix("x-request-id: "))
+            .expect("the response has a request id")
+            .to_string();
+        assert_eq!(id.len(), 36);
+        assert!(response.ends_with(&id), "{}", response);
+        // An incoming id is preserved.
+        let response = get("X-Request-Id: trace-42\r\n");
+        assert!(
+            response.contains("x-request-id: trace-42\r\n"),
+            "{}",
+            response
+        );
+        assert!(response.ends_with("trace-42"), "{}", response);
+        handle.shutdown();
+
+        let mut headers = hyper::HeaderMap::new();
+        headers.insert("X-Request-Id", "not an id".parse().unwrap());
+        assert_eq!(requests::request_id(&headers), None);
+    }
+
+    #[test]
+    fn test_open_browser() {
+        use std::sync::Mutex;
+
+        let opened = |mode: WXMode, open: bool| {
+            let root = PathBuf::from("examples/todo");
+            let module = parse_webx_source(&root.join("open.webx"), "get / {}\n").unwrap();
+            let port = std::net::TcpListener::bind("127.0.0.1:0")
+                .unwrap()
+                .local_addr()
+                .unwrap()
+                .port();
+            let mut config = ProjectConfig::new("open".into());
+            config.host = Some("127.0.0.1".into());
+            config.port = port;
+            let urls = Arc::new(Mutex::new(Vec::new()));
+            let opened_urls = urls.clone();
+            let handle = crate::WebXServer::builder()
+                .modules(root, vec![module])
+                .mode(mode)
+                .config(config)
+                .open_browser(open)
+                .opener(move |url| opened_urls.lock().unwrap().push(url.to_string()))
+                .build()
+                .start();
+            // The URL is opened right after listening, before any connection is accepted.
+            handle.shutdown();
+            let urls = urls.lock().unwrap().clone();
+            (urls, port)
+        };
+        let (urls, port) = opened(WXMode::Dev(DebugLevel::Low), true);
+        assert_eq!(urls, vec![format!("http://127.0.0.1:{}/", port)]);
+        let (urls, _) = opened(WXMode::Dev(DebugLevel::Low), false);
+        assert!(urls.is_empty());
+        let (urls, _) = opened(WXMode::Prod, true);
+        assert!(urls.is_empty());
+    }
+
+    #[test]
+    fn test_shutdown_signal() {
+        use std::time::{Duration, Instant};
+
+        let source = "get /idle text {\n    return \"idle\";\n}\n";
+        let root = PathBuf::from("examples/todo");
+        let module = parse_webx_source(&root.join("idle.webx"), source).unwrap();
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let mut config = ProjectConfig::new("idle".into());
+        config.host = Some("127.0.0.1".into());
+        config.port = port;
+        let mode = WXMode::Dev(DebugLevel::Low);
+        let handle = crate::WebXServer::builder()
+            .modules(root, vec![module])
+            .mode(mode)
+            .config(config)
+            .build()
+            .start();
+        (0..50)
+            .find_map(|_| {
+                std::thread::sleep(Duration::from_millis(20));
+                std::net::TcpStream::connect(("127.0.0.1", port)).ok()
+            })
+            .expect("the server did not start");
+        // An idle server stops right away, instead of once the running flag is polled.
+        let stopped_at = Instant::now();
+        handle.shutdown();
+        let elapsed = stopped_at.elapsed();
+        assert!(elapsed < timeout_duration(mode) / 10, "{:?}", elapsed);
+    }
+
+    #[test]
+    fn test_server_security_headers() {
+        use std::{
+            io::{Read, Write},
+            sync::atomic::Ordering,
+            time::Duration,
+        };
+
+        let source = "get /a text {\n    return \"a\";\n}\n";
+        let runtime = spawn_runtime(PathBuf::from("examples/todo"), "headers.webx", source);
+        let rt_tx = runtime.rt_tx.clone();
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let mut config = ProjectConfig::new("headers".into());
+        config.host = Some("127.0.0.1".into());
+        config.port = port;
+        config.max_body_size = Some(4);
+        config.security_headers = Some(SecurityHeadersConfig {
+            frame_options: Some("DENY".into()),
+            ..Default::default()
+        });
+        let mode = WXMode::Dev(DebugLevel::Low);
+        let mut server = WXServer::new(mode, config, rt_tx, Arc::new(AtomicBool::new(true)));
+        let running = Arc::new(AtomicBool::new(true));
+        let server_running = running.clone();
+        let server = std::thread::spawn(move || server.run(server_running));
+        let send = |request: &[u8]| {
+            let mut stream = (0..50)
+                .find_map(|_| {
+                    std::thread::sleep(Duration::from_millis(20));
+                    std::net::TcpStream::connect(("127.0.0.1", port)).ok()
+                })
+                .expect("the server did not start");
+            stream.write_all(request).unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).unwrap();
+            response
+        };
+        // Responses of routes, and responses generated by the server itself.
+        let requests: [&[u8]; 3] = [
+            b"GET /a HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+            b"GET /missing HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+            b"POST /a HTTP/1.1\r\nHost: localhost\r\nContent-Length: 5\r\nConnection: close\r\n\r\nhello",
+        ];
+        for (request, status) in requests.into_iter().zip(["200", "404", "413"]) {
+            let response = send(request);
+            assert!(
+                response.starts_with(&format!("HTTP/1.1 {}", status)),
+                "{}",
+                response
+            );
+            assert!(
+                response
+                    .to_ascii_lowercase()
+                    .contains("x-frame-options: deny"),
+                "{}",
+                response
+            );
+        }
+
+        running.store(false, Ordering::SeqCst);
+        server.join().unwrap().unwrap();
+    }
+
+    #[test]
+    fn test_max_connections() {
+        use std::{
+            io::{Read, Write},
+            sync::atomic::Ordering,
+            time::Duration,
+        };
+
+        let source = "get /a text {\n    return \"a\";\n}\n";
+        let runtime = spawn_runtime(PathBuf::from("examples/todo"), "limit.webx", source);
+        let rt_tx = runtime.rt_tx.clone();
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let mut config = ProjectConfig::new("limit".into());
+        config.host = Some("127.0.0.1".into());
+        config.port = port;
+        config.max_connections = Some(1);
+        let mode = WXMode::Dev(DebugLevel::Low);
+        let mut server = WXServer::new(mode, config, rt_tx, Arc::new(AtomicBool::new(true)));
+        let running = Arc::new(AtomicBool::new(true));
+        let server_running = running.clone();
+        let server = std::thread::spawn(move || server.run(server_running));
+        let connect = || {
+            (0..50)
+                .find_map(|_| {
+                    std::thread::sleep(Duration::from_millis(20));
+                    std::net::TcpStream::connect(("127.0.0.1", port)).ok()
+                })
+                .expect("the server did not start")
+        };
+        let request = b"GET /a HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n";
+        // The first connection takes the only slot while it is open.
+        let first = connect();
+        std::thread::sleep(Duration::from_millis(100));
+        let mut second = connect();
+        second.write_all(request).unwrap();
+        second
+            .set_read_timeout(Some(Duration::from_millis(300)))
+            .unwrap();
+        let mut buf = [0; 1];
+        assert!(
+            second.read(&mut buf).is_err(),
+            "the second connection was served"
+        );
+        // Closing the first connection frees its slot for the second one.
+        drop(first);
+        second
+            .set_read_timeout(Some(Duration::from_secs(2)))
+            .unwrap();
+        let mut response = String::new();
+        second.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 200 OK"), "{}", response);
+        assert!(response.ends_with("a"), "{}", response);
+
+        running.store(false, Ordering::SeqCst);
+        server.join().unwrap().unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_unix_socket() {
+        use std::{
+            io::{Read, Write},
+            os::unix::net::{UnixListener, UnixStream},
+            sync::atomic::Ordering,
+            time::Duration,
+        };
+
+        let source = "get /a text {\n    return \"unix\";\n}\n";
+        let runtime = spawn_runtime(PathBuf::from("examples/todo"), "unix.webx", source);
+        let rt_tx = runtime.rt_tx.clone();
+        let path = std::env::temp_dir().join(format!("webx-{}.sock", std::process::id()));
+        // Leave a stale socket behind, as if a previous server crashed.
+        drop(UnixListener::bind(&path).unwrap());
+        assert!(path.exists());
+        let mut config = ProjectConfig::new("unix".into());
+        config.listen = Some(crate::file::project::ListenConfig {
+            unix: Some(path.clone()),
+        });
+        let mode = WXMode::Dev(DebugLevel::Low);
+        let mut server = WXServer::new(mode, config, rt_tx, Arc::new(AtomicBool::new(true)));
+        let running = Arc::new(AtomicBool::new(true));
+        let server_running = running.clone();
+        let server = std::thread::spawn(move || server.run(server_running));
+        let mut stream = (0..50)
+            .find_map(|_| {
+                std::thread::sleep(Duration::from_millis(20));
+                UnixStream::connect(&path).ok()
+            })
+            .expect("the server did not listen on the socket");
+        stream
+            .write_all(b"GET /a HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+            .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 200 OK"), "{}", response);
+        assert!(response.ends_with("unix"), "{}", response);
+
+        running.store(false, Ordering::SeqCst);
+        server.join().unwrap().unwrap();
+        assert!(!path.exists(), "the socket was not removed on shutdown");
+    }
+
+    #[test]
+    fn test_preferred_type() {
+        let offered = ["application/json", "text/html"];
+        let preferred = |accept: &str| {
+            let mut headers = hyper::HeaderMap::new();
+            headers.insert("Accept", accept.parse().unwrap());
+            requests::preferred_type(&headers, &offered)
+        };
+        assert_eq!(
+            requests::preferred_type(&hyper::HeaderMap::new(), &offered),
+            Some("application/json")
+        );
+        assert_eq!(preferred("text/html"), Some("text/html"));
+        assert_eq!(preferred("*/*"), Some("application/json"));
+        assert_eq!(
+            preferred("text/html,application/xhtml+xml,*/*;q=0.8"),
+            Some("text/html")
+        );
+        assert_eq!(
+            preferred("application/json;q=0.5, text/*;q=0.9"),
+            Some("text/html")
+        );
+        assert_eq!(preferred("*/*, text/html;q=0"), Some("application/json"));
+        assert_eq!(preferred("image/png"), None);
+    }
+
+    #[test]
+    fn test_graphql_parse() {
+        let variables = serde_json::json!({ "id": 2 });
+        let variables = variables.as_object().unwrap();
+        let source = r#"
+# Look up a user by id.
+query User($id: Int!, $full: Boolean = true) {
+    author: user(id: $id, full: $full, role: ADMIN, tags: ["a", "b"]) { name __typename }
+}
+mutation Rename { rename(name: "Ada") }
+"#;
+        let operation = parse_operation(source, Some("User"), variables).unwrap();
+        assert_eq!(operation.operation_type, WXGraphQLOperationType::Query);
+        let field = &operation.selection[0];
+        assert_eq!(field.key(), "author");
+        assert_eq!(field.name, "user");
+        assert_eq!(
+            serde_json::Value::from(field.arguments.clone()),
+            serde_json::json!({ "id": 2, "full": true, "role": "ADMIN", "tags": ["a", "b"] })
+        );
+        let selection = field.selection.iter().map(|f| f.key()).collect::<Vec<_>>();
+        assert_eq!(selection, ["name", "__typename"]);
+
+        let operation = parse_operation(source, Some("Rename"), variables).unwrap();
+        assert_eq!(operation.operation_type, WXGraphQLOperationType::Mutation);
+        assert_eq!(
+            parse_operation(source, None, variables).unwrap_err(),
+            "Must provide operation name if query contains multiple operations."
+        );
+        assert_eq!(
+            parse_operation(
+                "query($id: Int!) { user(id: $id) }",
+                None,
+                &Default::default()
+            )
+            .unwrap_err(),
+            "Variable \"$id\" of required type \"Int!\" was not provided."
+        );
+        assert_eq!(
+            parse_operation("{ user(id: 1) { name }", None, &Default::default()).unwrap_err(),
+            "Syntax Error: Expected a name, found <EOF>."
+        );
+        assert_eq!(
+            parse_operation("{ ...UserFields }", None, &Default::default()).unwrap_err(),
+            "Fragments are not supported"
+        );
+    }
+
+    #[test]
+    fn test_graphql_endpoint() {
+        use std::collections::BTreeMap;
+
+        let source = r#"
+model User {
+    id: Int,
+    name: String,
+}
+global {
+    const db = [
+        { id: 1, name: "Ada", password: "secret" },
+        { id: 2, name: "Grace", password: "secret" },
+    ];
+    function users() { return db; }
+    async function user({ id }) { return db.find(u => u.id === id) ?? null; }
+    function broken() { throw new Error("broken"); }
+}
+"#;
+        let mut config = ProjectConfig::new("graphql".into());
+        config.graphql = Some(GraphQLConfig {
+            path: None,
+            query: Some(BTreeMap::from([
+                ("users".to_string(), "User[]".to_string()),
+                ("user".to_string(), "User".to_string()),
+                ("broken".to_string(), "String".to_string()),
+            ])),
+        });
+        let runtime = spawn_runtime_with(
+            WXMode::Dev(DebugLevel::Low),
+            PathBuf::from("examples/todo"),
+            config,
+            "graphql.webx",
+            source,
+        );
+        let rt_tx = runtime.rt_tx.clone();
+        let query = |body: serde_json::Value| {
+            let request = hyper::Request::post("/graphql")
+                .header("Content-Type", "application/json")
+                .body(hyper::body::Bytes::from(body.to_string()))
+                .unwrap();
+            send(&rt_tx, request)
+        };
+        let json = |response: &hyper::Response<WXResponseBody>| {
+            serde_json::from_slice::<serde_json::Value>(response.body().bytes().unwrap()).unwrap()
+        };
+        block_on(async {
+            let response = query(serde_json::json!({
+                "query": "query($id: Int) { users { name } first: user(id: $id) { id __typename } }",
+                "variables": { "id": 2 },
+            }))
+            .await
+            .unwrap()
+            .unwrap();
+            assert_eq!(response.status(), hyper::StatusCode::OK);
+            assert_eq!(response.headers()["Content-Type"], "application/json");
+            assert_eq!(
+                json(&response),
+                serde_json::json!({
+                    "data": {
+                        "users": [{ "name": "Ada" }, { "name": "Grace" }],
+                        "first": { "id": 2, "__typename": "User" },
+                    }
+                })
+            );
+
+            // Only the fields of the model can be selected.
+            let response = query(serde_json::json!({ "query": "{ users { password } }" }))
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(response.status(), hyper::StatusCode::BAD_REQUEST);
+            assert_eq!(
+                json(&response),
+                serde_json::json!({
+                    "errors": [{ "message": "Cannot query field \"password\" on type \"User\"." }]
+                })
+            );
+
+            // Failing resolvers are reported per field.
+            let response = query(serde_json::json!({ "query": "{ broken user(id: 1) { name } }" }))
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(response.status(), hyper::StatusCode::OK);
+            let body = json(&response);
+            assert_eq!(
+                body["data"],
+                serde_json::json!({ "broken": null, "user": { "name": "Ada" } })
+            );
+            assert_eq!(body["errors"][0]["path"], serde_json::json!(["broken"]));
+
+            // Queries can also be sent as a query parameter.
+            let uri = "/graphql?query=%7B%20user(id%3A%201)%20%7B%20name%20%7D%20%7D";
+            let response = request(&rt_tx, uri).await.unwrap().unwrap();
+            assert_eq!(
+                json(&response),
+                serde_json::json!({ "data": { "user": { "name": "Ada" } } })
+            );
+        });
+    }
+
+    #[test]
+    fn test_content_negotiation() {
+        let root = std::env::temp_dir().join(format!("webx-negotiate-{}", std::process::id()));
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(root.join("user.html"), "<h1>{{ name }}</h1>").unwrap();
+        let source = r#"
+get /user -> negotiate({ name: "Ada" }, "user.html")
+get /data -> negotiate({ name: "Ada" })
+"#;
+        let runtime = spawn_runtime(root.clone(), "negotiate.webx", source);
+        let rt_tx = runtime.rt_tx.clone();
+        let get = |path: &str, accept: &str| {
+            let request = hyper::Request::get(path)
+                .header("Accept", accept)
+                .body(hyper::body::Bytes::new())
+                .unwrap();
+            send(&rt_tx, request)
+        };
+        block_on(async {
+            let response = get("/user", "text/html").await.unwrap().unwrap();
+            assert_eq!(
+                response.headers()["Content-Type"],
+                "text/html; charset=utf-8"
+            );
+            assert_eq!(response.headers()["Vary"], "Accept");
+            assert_eq!(response.body().bytes(), Some(&b"<h1>Ada</h1>"[..]));
+
+            let response = get("/user", "application/json").await.unwrap().unwrap();
+            assert_eq!(response.headers()["Content-Type"], "application/json");
+            assert_eq!(response.headers()["Vary"], "Accept");
+            assert_eq!(response.body().bytes(), Some(&br#"{"name":"Ada"}"#[..]));
+
+            // Without a template, data is always sent as JSON.
+            let response = get("/data", "text/html").await.unwrap().unwrap();
+            assert_eq!(response.headers()["Content-Type"], "application/json");
+        });
+        drop(runtime);
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_conditional_static_file() {
+        let root = std::env::temp_dir().join(format!("webx-modified-{}", std::process::id()));
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(root.join("style.css"), "body { margin: 0; }").unwrap();
+        let since = |date: &str| {
+            hyper::Request::get("/style.css")
+                .header("If-Modified-Since", date)
+                .body(hyper::body::Bytes::new())
+                .unwrap()
+        };
+        spawn_runtime(
+            root.clone(),
+            "style.webx",
+            "get /style.css -> static(\"style.css\")\n",
+        )
+        .test(|rt_tx| async move {
+            let response = request(&rt_tx, "/style.css").await.unwrap().unwrap();
+            assert_eq!(response.status(), hyper::StatusCode::OK);
+            assert_eq!(
+                response.headers()["Content-Type"],
+                "text/css; charset=utf-8"
+            );
+            let modified = response.headers()["Last-Modified"]
+                .to_str()
+                .unwrap()
+                .to_string();
+            // Hit: the file is unchanged since the client fetched it.
+            let response = send(&rt_tx, since(&modified)).await.unwrap().unwrap();
+            assert_eq!(response.status(), hyper::StatusCode::NOT_MODIFIED);
+            assert!(response.body().bytes().is_some());
+            // Miss: the client holds an older version.
+            let stale = since("Thu, 01 Jan 1970 00:00:00 GMT");
+            let response = send(&rt_tx, stale).await.unwrap().unwrap();
+            assert_eq!(response.status(), hyper::StatusCode::OK);
+            assert_eq!(
+                response.headers()["Content-Length"],
+                "body { margin: 0; }".len().to_string()
+            );
+        });
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_static_file_range() {
+        use http_body_util::BodyExt;
+
+        let root = std::env::temp_dir().join(format!("webx-range-{}", std::process::id()));
+        std::fs::create_dir_all(&root).unwrap();
+        let data = (0..=255).collect::<Vec<u8>>();
+        std::fs::write(root.join("data.bin"), &data).unwrap();
+        let range = |range: &str| {
+            hyper::Request::get("/data.bin")
+                .header("Range", range)
+                .body(hyper::body::Bytes::new())
+                .unwrap()
+        };
+        spawn_runtime(
+            root.clone(),
+            "range.webx",
+            "get /data.bin -> static(\"data.bin\")\n",
+        )
+        .test(|rt_tx| async move {
+            let response = request(&rt_tx, "/data.bin").await.unwrap().unwrap();
+            assert_eq!(response.status(), hyper::StatusCode::OK);
+            assert_eq!(response.headers()["Accept-Ranges"], "bytes");
+
+            let response = send(&rt_tx, range("bytes=10-19")).await.unwrap().unwrap();
+            assert_eq!(response.status(), hyper::StatusCode::PARTIAL_CONTENT);
+            assert_eq!(response.headers()["Content-Range"], "bytes 10-19/256");
+            assert_eq!(response.headers()["Content-Length"], "10");
+            let body = response.into_body().collect().await.unwrap().to_bytes();
+            assert_eq!(&body[..], &data[10..20]);
+
+            // The last bytes, binary-safe.
+            let response = send(&rt_tx, range("bytes=-6")).await.unwrap().unwrap();
+            assert_eq!(response.headers()["Content-Range"], "bytes 250-255/256");
+            let body = response.into_body().collect().await.unwrap().to_bytes();
+            assert_eq!(&body[..], &data[250..]);
+
+            let response = send(&rt_tx, range("bytes=256-300")).await.unwrap().unwrap();
+            assert_eq!(response.status(), hyper::StatusCode::RANGE_NOT_SATISFIABLE);
+            assert_eq!(response.headers()["Content-Range"], "bytes */256");
+
+            // Multiple ranges fall back to the whole file.
+            let response = send(&rt_tx, range("bytes=0-1, 4-5"))
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(response.status(), hyper::StatusCode::OK);
+            assert_eq!(response.headers()["Content-Length"], "256");
+        });
+        std::fs::remove_dir_all(&root).unwrap();
+
+        let mut headers = hyper::HeaderMap::new();
+        headers.insert("Range", "bytes=5-".parse().unwrap());
+        assert_eq!(
+            requests::byte_range(&headers, 10),
+            requests::WXByteRange::Partial(5, 9)
+        );
+        headers.insert("Range", "bytes=9-5".parse().unwrap());
+        assert_eq!(
+            requests::byte_range(&headers, 10),
+            requests::WXByteRange::Full
+        );
+    }
+
+    #[test]
+    fn test_favicon() {
+        use http_body_util::BodyExt;
+
+        let root = std::env::temp_dir().join(format!("webx-favicon-{}", std::process::id()));
+        std::fs::create_dir_all(root.join("static")).unwrap();
+        std::fs::write(root.join("static/icon.ico"), b"icon").unwrap();
+        let mut config = ProjectConfig::new("favicon".into());
+        config.favicon = Some(PathBuf::from("static/icon.ico"));
+        assert!(config.validate(&root).is_ok());
+        // The configured favicon is served before the routes.
+        let source = "get /favicon.ico (<p>Route</p>)\n";
+        spawn_runtime_with(
+            WXMode::Dev(DebugLevel::Low),
+            root.clone(),
+            config,
+            "favicon.webx",
+            source,
+        )
+        .test(|rt_tx| async move {
+            let response = request(&rt_tx, "/favicon.ico").await.unwrap().unwrap();
+            assert_eq!(response.status(), hyper::StatusCode::OK);
+            assert_eq!(response.headers()["Content-Type"], "image/x-icon");
+            assert_eq!(response.headers()["Cache-Control"], "public, max-age=86400");
+            let body = response.into_body().collect().await.unwrap().to_bytes();
+            assert_eq!(&body[..], b"icon");
+        });
+
+        // Without a favicon, browsers get an empty response instead of a 404.
+        let source = "get /ok (<p>Ok</p>)\n";
+        spawn_runtime(root.clone(), "favicon.webx", source).test(|rt_tx| async move {
+            let response = request(&rt_tx, "/favicon.ico").await.unwrap().unwrap();
+            assert_eq!(response.status(), hyper::StatusCode::NO_CONTENT);
+            let body = response.into_body().collect().await.unwrap().to_bytes();
+            assert!(body.is_empty());
+        });
+        std::fs::remove_dir_all(&root).unwrap();
+
+        let mut config = ProjectConfig::new("favicon".into());
+        config.favicon = Some(PathBuf::from("missing.ico"));
+        let errors = config.validate(Path::new(".")).unwrap_err();
+        assert!(errors.contains(&"\"favicon\" file 'missing.ico' does not exist".to_string()));
+    }
+
+    #[test]
+    fn test_content_type() {
+        assert_eq!(
+            responses::content_type(Path::new("static/index.HTML")),
+            "text/html; charset=utf-8"
+        );
+        assert_eq!(
+            responses::content_type(Path::new("logo.svg")),
+            "image/svg+xml"
+        );
+        assert_eq!(
+            responses::content_type(Path::new("archive")),
+            "application/octet-stream"
+        );
+    }
+
+    #[test]
+    fn test_websocket_handshake() {
+        let request = hyper::Request::get("/echo")
+            .header("Connection", "keep-alive, Upgrade")
+            .header("Upgrade", "websocket")
+            .header("Sec-WebSocket-Key", "dGhlIHNhbXBsZSBub25jZQ==")
+            .body(())
+            .unwrap();
+        assert_eq!(
+            requests::websocket_key(&request),
+            Some("dGhlIHNhbXBsZSBub25jZQ==")
+        );
+        let request = hyper::Request::get("/echo")
+            .header("Sec-WebSocket-Key", "dGhlIHNhbXBsZSBub25jZQ==")
+            .body(())
+            .unwrap();
+        assert_eq!(requests::websocket_key(&request), None);
+        let response = responses::switching_protocols(WXMode::Prod, "s3pPLMBiTxaQ9kYGzzhZRbK+xOo=");
+        assert_eq!(response.status(), hyper::StatusCode::SWITCHING_PROTOCOLS);
+        assert_eq!(
+            response.headers()["Sec-WebSocket-Accept"],
+            "s3pPLMBiTxaQ9kYGzzhZRbK+xOo="
+        );
+    }
+
+    #[test]
+    fn test_event_frame() {
+        let frame = responses::event_frame(Some("update"), Some("7"), "line 1\nline 2");
+        assert_eq!(
+            frame,
+            "event: update\nid: 7\ndata: line 1\ndata: line 2\n\n"
+        );
+        // Line breaks can not inject fields.
+        let frame = responses::event_frame(Some("a\ndata: b"), None, "");
+        assert_eq!(frame, "event: adata: b\ndata: \n\n");
+    }
+
+    #[test]
+    fn test_readiness_probe() {
+        use std::sync::atomic::Ordering;
+
+        let ready = Arc::new(AtomicBool::new(false));
+        let health = WXHealth::new("/healthz", "/readyz", ready.clone());
+        let probe = |path: &str| health.respond(WXMode::Prod, path).map(|res| res.status());
+        // Modules are still loading.
+        assert_eq!(probe("/healthz"), Some(hyper::StatusCode::OK));
+        assert_eq!(
+            probe("/readyz"),
+            Some(hyper::StatusCode::SERVICE_UNAVAILABLE)
+        );
+        ready.store(true, Ordering::SeqCst);
+        assert_eq!(probe("/healthz"), Some(hyper::StatusCode::OK));
+        assert_eq!(probe("/readyz"), Some(hyper::StatusCode::OK));
+        assert_eq!(probe("/todos"), None);
+        let response = health.respond(WXMode::Prod, "/readyz").unwrap();
+        let body: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+        assert_eq!(body["status"], "ok");
+        assert_eq!(body["version"], env!("CARGO_PKG_VERSION"));
+    }
+
+    #[test]
+    fn test_options_allow_header() {
+        let mode = WXMode::Dev(DebugLevel::Max);
+        let allow = [hyper::Method::OPTIONS, hyper::Method::POST];
+        let response = responses::options(mode, &allow, None, None);
+        assert_eq!(response.status(), hyper::StatusCode::NO_CONTENT);
+        assert_eq!(response.headers()["Allow"], "OPTIONS, POST");
+        assert!(response
+            .headers()
+            .get("Access-Control-Allow-Origin")
+            .is_none());
+        let cors = CorsConfig {
+            allow_origin: "*".into(),
+        };
+        let response = responses::options(mode, &allow, Some(&cors), Some("Content-Type"));
+        assert_eq!(response.headers()["Access-Control-Allow-Origin"], "*");
+        assert_eq!(
+            response.headers()["Access-Control-Allow-Methods"],
+            "OPTIONS, POST"
+        );
+        assert_eq!(
+            response.headers()["Access-Control-Allow-Headers"],
+            "Content-Type"
+        );
+    }
+
+    #[test]
+    fn test_method_not_allowed() {
+        let source = "get /todos text {\n    return \"todos\";\n}\n";
+        with_runtime("methods.webx", source, |rt_tx| async move {
+            let post = hyper::Request::post("/todos")
+                .body(hyper::body::Bytes::new())
+                .unwrap();
+            let response = send(&rt_tx, post).await.unwrap().unwrap();
+            assert_eq!(response.status(), hyper::StatusCode::METHOD_NOT_ALLOWED);
+            assert_eq!(response.headers()["Allow"], "GET, HEAD, OPTIONS");
+            // Paths without any route are still not found.
+            let post = hyper::Request::post("/notes")
+                .body(hyper::body::Bytes::new())
+                .unwrap();
+            let response = send(&rt_tx, post).await.unwrap().unwrap();
+            assert_eq!(response.status(), hyper::StatusCode::NOT_FOUND);
+            let options = hyper::Request::options("/todos")
+                .body(hyper::body::Bytes::new())
+                .unwrap();
+            let response = send(&rt_tx, options).await.unwrap().unwrap();
+            assert_eq!(response.status(), hyper::StatusCode::NO_CONTENT);
+            assert_eq!(response.headers()["Allow"], "GET, HEAD, OPTIONS");
+        });
+    }
+
+    #[test]
+    fn test_base_path() {
+        use http_body_util::BodyExt;
+
+        assert_eq!(parse_base_path("/app/v1/").to_string(), "/app/v1");
+        assert_eq!(parse_base_path("/").segments(), 0);
+        let source = "get / text {\n    return \"home\";\n}\nget /page (<p>Page</p>)\nlocation todos {\n    get /list text {\n        return \"list\";\n    }\n}\n";
+        let config = ProjectConfig {
+            base_path: Some("/app/".into()),
+            ..ProjectConfig::new("app".into())
+        };
+        spawn_runtime_with(
+            WXMode::Dev(DebugLevel::Low),
+            PathBuf::from("examples/todo"),
+            config,
+            "app.webx",
+            source,
+        )
+        .test(|rt_tx| async move {
+            // Routes only match under the base path.
+            for (path, body) in [("/app", "home"), ("/app/todos/list", "list")] {
+                let response = request(&rt_tx, path).await.unwrap().unwrap();
+                assert_eq!(response.status(), hyper::StatusCode::OK);
+                let bytes = response.into_body().collect().await.unwrap().to_bytes();
+                assert_eq!(&bytes[..], body.as_bytes());
+            }
+            for path in ["/", "/todos/list", "/page", "/application/page"] {
+                let response = request(&rt_tx, path).await.unwrap().unwrap();
+                assert_eq!(response.status(), hyper::StatusCode::NOT_FOUND, "{}", path);
+            }
+            // The live-reload script listens under the base path as well.
+            let response = request(&rt_tx, "/app/page").await.unwrap().unwrap();
+            let bytes = response.into_body().collect().await.unwrap().to_bytes();
+            let page = String::from_utf8_lossy(&bytes);
+            assert!(page.contains(&format!("\"/app{}\"", responses::LIVE_RELOAD_PATH)));
+        });
+    }
+
+    #[test]
+    fn test_trace_disabled_in_prod() {
+        let root = PathBuf::from("examples/todo");
+        let source = "trace /echo text {\n    return \"traced\";\n}\nget /echo {}\n";
+        let trace = || {
+            hyper::Request::builder()
+                .method(hyper::Method::TRACE)
+                .uri("/echo")
+                .body(hyper::body::Bytes::new())
+                .unwrap()
+        };
+        let config = ProjectConfig::new("trace".into());
+        let allowed = ProjectConfig {
+            allow_trace_connect: Some(true),
+            ..ProjectConfig::new("trace".into())
+        };
+        for (mode, config, status) in [
+            (
+                WXMode::Prod,
+                config.clone(),
+                hyper::StatusCode::METHOD_NOT_ALLOWED,
+            ),
+            (WXMode::Prod, allowed, hyper::StatusCode::OK),
+            (WXMode::Dev(DebugLevel::Low), config, hyper::StatusCode::OK),
+        ] {
+            let runtime = spawn_runtime_with(mode, root.clone(), config, "trace.webx", source);
+            runtime.test(|rt_tx| async move {
+                let response = send(&rt_tx, trace()).await.unwrap().unwrap();
+                assert_eq!(response.status(), status);
+                if status == hyper::StatusCode::METHOD_NOT_ALLOWED {
+                    assert_eq!(response.headers()["Allow"], "GET, HEAD, OPTIONS");
+                }
+                // Disabled methods are not listed as allowed.
+                let options = hyper::Request::options("/echo")
+                    .body(hyper::body::Bytes::new())
+                    .unwrap();
+                let response = send(&rt_tx, options).await.unwrap().unwrap();
+                let allows_trace = response.headers()["Allow"]
+                    .to_str()
+                    .unwrap()
+                    .contains("TRACE");
+                assert_eq!(allows_trace, status == hyper::StatusCode::OK);
+            });
+        }
+    }
+
+    #[test]
+    fn test_common_headers() {
+        let mode = WXMode::Dev(DebugLevel::Max);
+        let mut rt = deno_core::JsRuntime::new(Default::default());
+        let json = rt
+            .execute_script("[test]", "({ ok: true })".to_string().into())
+            .unwrap();
+        let scope = &mut rt.handle_scope();
+        let epoch = || std::time::UNIX_EPOCH + std::time::Duration::from_secs(784111777);
+        let headers = responses::with_clock(epoch, || {
+            let pages = WXErrorPages::default();
+            let get = hyper::Method::GET;
+            [
+                responses::ok_html((), 8, mode),
+                responses::ok_json(&json, scope, mode).map(|_| ()),
+                responses::ok_text(hyper::body::Bytes::from("a"), mode).map(|_| ()),
+                responses::options(mode, &[get.clone()], None, None).map(|_| ()),
+                responses::moved_permanently(mode, "/a").map(|_| ()),
+                responses::not_found(mode, &pages, &get, "/a".into()).map(|_| ()),
+                responses::internal_server_error(mode, &pages, "a".into()).map(|_| ()),
+                responses::forbidden(mode).map(|_| ()),
+                responses::payload_too_large(mode, 1).map(|_| ()),
+                responses::metrics(mode, String::new()).map(|_| ()),
+                responses::switching_protocols(mode, "a").map(|_| ()),
+            ]
+            .map(|response| {
+                let headers = response.headers();
+                (headers["Server"].clone(), headers["Date"].clone())
+            })
+        });
+        for (server, date) in headers {
+            assert_eq!(server, format!("webx/{}", env!("CARGO_PKG_VERSION")));
+            assert_eq!(date, "Sun, 06 Nov 1994 08:49:37 GMT");
+        }
+    }
+
+    #[test]
+    fn test_resolve_async_handler() {
+        let mut rt = deno_core::JsRuntime::new(Default::default());
+        let value = rt
+            .execute_script(
+                "[test]",
+                "(async () => await Promise.resolve(42))()"
+                    .to_string()
+                    .into(),
+            )
+            .unwrap();
+        let value = resolve_promise(&mut rt, value).unwrap();
+        let scope = &mut rt.handle_scope();
+        let value = deno_core::v8::Local::new(scope, value);
+        assert_eq!(value.integer_value(scope), Some(42));
+    }
+
+    #[test]
+    fn test_stdlib_snapshot() {
+        let mut rt = new_stdlib_js_runtime(WXMode::Dev(DebugLevel::Max));
+        let value = rt
+            .execute_script("[test]", "typeof webx.log".to_string().into())
+            .unwrap();
+        let scope = &mut rt.handle_scope();
+        let value = deno_core::v8::Local::new(scope, value);
+        assert_eq!(value.to_rust_string_lossy(scope), "function");
+    }
+
+    #[test]
+    fn test_transpile_typescript() {
+        let js = transpile_ts("const x: number = 1;\nx + 1;").unwrap();
+        assert!(!js.contains("sourceMappingURL"), "{}", js);
+        let mut rt = deno_core::JsRuntime::new(Default::default());
+        let value = rt.execute_script("[test]", js.into()).unwrap();
+        let scope = &mut rt.handle_scope();
+        let value = deno_core::v8::Local::new(scope, value);
+        assert_eq!(value.integer_value(scope), Some(2));
+    }
+
+    #[test]
+    fn test_transpile_cache_eviction() {
+        let mut cache = TranspileCache::default();
+        for i in 0..=TRANSPILE_CACHE_SIZE {
+            cache.insert(
+                format!("let x{}: number = {};", i, i),
+                format!("let x{} = {};", i, i),
+            );
+        }
+        // The oldest source is evicted, the newest ones are kept.
+        assert_eq!(cache.get("let x0: number = 0;"), None);
+        assert_eq!(cache.get("let x1: number = 1;"), Some("let x1 = 1;".into()));
+        let newest = format!("let x{0}: number = {0};", TRANSPILE_CACHE_SIZE);
+        assert!(cache.get(&newest).is_some());
+        // Inserting a cached source again does not evict anything.
+        cache.insert(newest, "let x = 0;".into());
+        assert!(cache.get("let x1: number = 1;").is_some());
+    }
+
+    /// Watch the `webx` source directory of `root` on its own thread,
+    /// with a short debounce window. Returns once the watcher is likely registered.
+    fn spawn_watcher(
+        root: &Path,
+    ) -> (
+        std::sync::mpsc::Receiver<WXRuntimeMessage>,
+        Arc<AtomicBool>,
+        std::thread::JoinHandle<()>,
+    ) {
+        std::fs::create_dir_all(root.join("webx")).unwrap();
+        let config = ProjectConfig {
+            watch_debounce_ms: Some(20),
+            ..ProjectConfig::new("watch".into())
+        };
+        let (rt_tx, rt_rx) = std::sync::mpsc::channel();
+        let running = Arc::new(AtomicBool::new(true));
+        let watcher_running = running.clone();
+        let (source_root, config_file) = (root.join("webx"), root.join("webx.config.json"));
+        let watcher_root = root.to_path_buf();
+        let watcher = std::thread::spawn(move || {
+            WXFileWatcher::run(
+                WXMode::Dev(DebugLevel::Low),
+                watcher_root,
+                source_root,
+                config_file,
+                config,
+                rt_tx,
+                watcher_running,
+            )
+        });
+        std::thread::sleep(std::time::Duration::from_millis(200));
+        (rt_rx, running, watcher)
+    }
+
+    #[test]
+    fn test_nested_global_scopes() {
+        use http_body_util::BodyExt;
+
+        // The nested global runs after the module global, so it may use its declarations.
+        let source = r#"
+global {
+    const start = 10;
+}
+
+location /counter {
+    global {
+        let count = start;
+    }
+
+    get /next text {
+        count += 1;
+        return `${count}`;
+    }
+}
+"#;
+        with_runtime("counter.webx", source, |rt_tx| async move {
+            for expected in ["11", "12"] {
+                let response = request(&rt_tx, "/counter/next").await.unwrap().unwrap();
+                assert_eq!(response.status(), hyper::StatusCode::OK);
+                let body = response.into_body().collect().await.unwrap().to_bytes();
+                assert_eq!(&body[..], expected.as_bytes());
+            }
+        });
+    }
+
+    #[test]
+    fn test_watch_wx_modules() {
+        use std::{sync::atomic::Ordering, time::Duration};
+
+        let root = std::env::temp_dir().join(format!("webx-watch-wx-{}", std::process::id()));
+        let (rt_rx, running, watcher) = spawn_watcher(&root);
+        let next = || rt_rx.recv_timeout(Duration::from_secs(5)).unwrap();
+        // `.wx` modules are loaded and swapped like `.webx` modules.
+        for file in ["todos.wx", "notes.webx"] {
+            let path = root.join("webx").join(file);
+            std::fs::write(&path, "get /a {}\n").unwrap();
+            let WXRuntimeMessage::New(module) = next() else {
+                panic!("expected {} to be loaded", file);
+            };
+            assert_eq!(module.path.name(), file.split('.').next().unwrap());
+            std::thread::sleep(Duration::from_millis(100));
+            std::fs::write(&path, "get /a {}\nget /b {}\n").unwrap();
+            let WXRuntimeMessage::Swap(module) = next() else {
+                panic!("expected {} to be swapped", file);
+            };
+            assert_eq!(module.scope.routes.len(), 2);
+        }
+        running.store(false, Ordering::SeqCst);
+        watcher.join().unwrap();
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_watch_config() {
+        use std::{sync::atomic::Ordering, time::Duration};
+
+        let root = std::env::temp_dir().join(format!("webx-watch-config-{}", std::process::id()));
+        let (rt_rx, running, watcher) = spawn_watcher(&root);
+        let write_config = |origin: &str, port: u16| {
+            let config = ProjectConfig {
+                port,
+                cors: Some(CorsConfig {
+                    allow_origin: origin.into(),
+                }),
+                ..ProjectConfig::new("watch".into())
+            };
+            let json = serde_json::to_string(&config).unwrap();
+            std::fs::write(root.join("webx.config.json"), json).unwrap();
+        };
+        let next_config = || match rt_rx.recv_timeout(Duration::from_secs(5)).unwrap() {
+            WXRuntimeMessage::Config(config) => config,
+            _ => panic!("expected the configuration to be reloaded"),
+        };
+        write_config("https://a.example", 8080);
+        let config = next_config();
+        assert_eq!(
+            config.cors.as_ref().unwrap().allow_origin,
+            "https://a.example"
+        );
+
+        // The reloaded CORS origin is used by the runtime.
+        let runtime = spawn_runtime(
+            PathBuf::from("examples/todo"),
+            "cors.webx",
+            "get /todos text {\n    return \"todos\";\n}\n",
+        );
+        let rt_tx = runtime.rt_tx.clone();
+        rt_tx.send(WXRuntimeMessage::Config(config)).unwrap();
+        block_on(async {
+            let request = hyper::Request::options("/todos")
+                .body(hyper::body::Bytes::new())
+                .unwrap();
+            let response = send(&rt_tx, request).await.unwrap().unwrap();
+            assert_eq!(
+                response.headers()["Access-Control-Allow-Origin"],
+                "https://a.example"
+            );
+        });
+
+        // An invalid configuration is not passed on, the previous one is kept.
+        std::thread::sleep(Duration::from_millis(100));
+        write_config("https://b.example", 0);
+        std::thread::sleep(Duration::from_millis(300));
+        assert!(rt_rx.try_recv().is_err());
+        write_config("https://c.example", 8080);
+        let config = next_config();
+        assert_eq!(
+            config.cors.as_ref().unwrap().allow_origin,
+            "https://c.example"
+        );
+        running.store(false, Ordering::SeqCst);
+        watcher.join().unwrap();
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_watch_ignores_unrelated_files() {
+        use std::{sync::atomic::Ordering, time::Duration};
+
+        for temp in [
+            ".index.webx.swp",
+            ".#index.webx",
+            "#index.webx#",
+            "index.webx~",
+        ] {
+            assert!(is_editor_temp_file(Path::new(temp)), "{}", temp);
+        }
+        assert!(!is_editor_temp_file(Path::new("webx/index.webx")));
+
+        let root = std::env::temp_dir().join(format!("webx-watch-txt-{}", std::process::id()));
+        let (rt_rx, running, watcher) = spawn_watcher(&root);
+        // Valid modules in unrelated and temporary files would be loaded if they were parsed.
+        for file in ["notes.txt", ".#index.webx", "index.webx~", "debug.log"] {
+            std::fs::write(root.join("webx").join(file), "get /a {}\n").unwrap();
+        }
+        std::thread::sleep(Duration::from_millis(200));
+        std::fs::write(root.join("webx/index.webx"), "get /a {}\n").unwrap();
+        let message = rt_rx.recv_timeout(Duration::from_secs(5)).unwrap();
+        let WXRuntimeMessage::New(module) = message else {
+            panic!("expected only index.webx to be loaded");
+        };
+        assert_eq!(module.path.name(), "index");
+        std::thread::sleep(Duration::from_millis(200));
+        assert!(rt_rx.try_recv().is_err());
+        running.store(false, Ordering::SeqCst);
+        watcher.join().unwrap();
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_debounce_rapid_events() {
+        use notify::event::{CreateKind, EventKind, ModifyKind, RenameMode};
+        use std::time::{Duration, Instant};
+
+        let window = Duration::from_millis(100);
+        let mut debouncer = FSWDebouncer::new(window);
+        let path = Path::new("webx/index.webx");
+        let start = Instant::now();
+        // A typical editor save: create, write, and rename in quick succession.
+        debouncer.push(&EventKind::Create(CreateKind::File), path, start);
+        debouncer.push(
+            &EventKind::Modify(ModifyKind::Any),
+            path,
+            start + Duration::from_millis(5),
+        );
+        debouncer.push(
+            &EventKind::Modify(ModifyKind::Name(RenameMode::To)),
+            path,
+            start + Duration::from_millis(10),
+        );
+        assert!(debouncer
+            .drain(start + Duration::from_millis(50))
+            .is_empty());
+        let changes = debouncer.drain(start + Duration::from_millis(110));
+        assert_eq!(changes, vec![(path.to_path_buf(), FSWChange::Created)]);
+        assert!(debouncer
+            .drain(start + Duration::from_millis(500))
+            .is_empty());
     }
 }
@@ -0,0 +1,92 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Mutex, OnceLock},
+};
+
+use deno_ast::{EmitOptions, MediaType, ParseParams, SourceTextInfo};
+
+use super::runtime::WXRuntimeError;
+
+/// The maximum number of transpiled sources kept in the cache.
+pub const TRANSPILE_CACHE_SIZE: usize = 4096;
+
+/// Transpiled JavaScript keyed by the original TypeScript source.
+/// Once full, the oldest entries are evicted first, so bodies removed
+/// by hot-swaps do not stay in memory for the lifetime of the process.
+#[derive(Default)]
+pub(crate) struct TranspileCache {
+    entries: HashMap<String, String>,
+    order: VecDeque<String>,
+}
+
+impl TranspileCache {
+    pub(crate) fn get(&self, source: &str) -> Option<String> {
+        self.entries.get(source).cloned()
+    }
+
+    pub(crate) fn insert(&mut self, source: String, js: String) {
+        if self.entries.insert(source.clone(), js).is_some() {
+            return;
+        }
+        self.order.push_back(source);
+        while self.order.len() > TRANSPILE_CACHE_SIZE {
+            if let Some(oldest) = self.order.pop_front() {
+                self.entries.remove(&oldest);
+            }
+        }
+    }
+}
+
+static TRANSPILE_CACHE: OnceLock<Mutex<TranspileCache>> = OnceLock::new();
+
+/// Transpile TypeScript code to JavaScript by stripping all types.
+/// The output is cached by the source code, so unchanged bodies are
+/// only transpiled once, even across hot-swaps.
+/// The cache holds at most `TRANSPILE_CACHE_SIZE` sources.
+///
+/// ## Note
+/// The code is parsed as a module, so top-level `return` statements
+/// must be wrapped in a function before transpiling.
+pub fn transpile_ts(source: &str) -> Result<String, WXRuntimeError> {
+    let cache = TRANSPILE_CACHE.get_or_init(Default::default);
+    if let Some(js) = cache.lock().unwrap().get(source) {
+        return Ok(js);
+    }
+    let parsed = deno_ast::parse_module(ParseParams {
+        specifier: "file:///webx.ts".to_string(),
+        text_info: SourceTextInfo::from_string(source.to_string()),
+        media_type: MediaType::TypeScript,
+        capture_tokens: false,
+        scope_analysis: false,
+        maybe_syntax: None,
+    })
+    .map_err(|err| WXRuntimeError {
+        code: 500,
+        http_status: 500,
+        message: format!("Failed to parse TypeScript:\n{}", err),
+    })?;
+    let js = parsed
+        .transpile(&EmitOptions {
+            // Errors are reported against the WebX source, not the transpiled code.
+            inline_source_map: false,
+            inline_sources: false,
+            ..Default::default()
+        })
+        .map_err(|err| WXRuntimeError {
+            code: 500,
+            http_status: 500,
+            message: format!("Failed to transpile TypeScript:\n{}", err),
+        })?
+        .text;
+    cache.lock().unwrap().insert(source.to_string(), js.clone());
+    Ok(js)
+}
+
+/// Add already transpiled JavaScript to the cache, e.g. from a build artifact.
+pub fn preload(transpiled: Vec<(String, String)>) {
+    let cache = TRANSPILE_CACHE.get_or_init(Default::default);
+    let mut cache = cache.lock().unwrap();
+    for (source, js) in transpiled {
+        cache.insert(source, js);
+    }
+}
@@ -0,0 +1,159 @@
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::file::{
+    project::{locate_files, ProjectConfig},
+    webx::{WXModule, WXModulePath, WXScope},
+};
+
+/// The build artifact of a project, relative to the project root.
+pub const ARTIFACT_FILE: &str = ".webx/build.bin";
+
+/// The version of WebX an artifact was built with.
+/// Artifacts of other versions are stale, as the module format may have changed.
+const ARTIFACT_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// The parsed and analyzed modules of a project, built by `webx build`.
+/// Loaded by `webx run --prod` instead of parsing the sources on every start.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WXArtifact {
+    /// The WebX version the artifact was built with.
+    pub version: String,
+    /// The hash of the sources the artifact was built from.
+    pub hash: String,
+    /// The modules, with paths relative to the source root.
+    pub modules: Vec<WXModule>,
+    /// The transpiled JavaScript of the global scopes, keyed by their TypeScript source.
+    pub transpiled: Vec<(String, String)>,
+}
+
+/// Why an artifact could not be loaded.
+#[derive(Debug)]
+pub enum WXArtifactError {
+    /// There is no artifact, or it could not be read.
+    Missing(std::io::Error),
+    /// The artifact is not a valid artifact of this WebX version.
+    Invalid(String),
+    /// The artifact was built by another WebX version or from other sources.
+    Stale,
+}
+
+impl std::fmt::Display for WXArtifactError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WXArtifactError::Missing(err) => write!(f, "Failed to read build artifact: {}", err),
+            WXArtifactError::Invalid(err) => write!(f, "Invalid build artifact: {}", err),
+            WXArtifactError::Stale => write!(f, "The build artifact is out of date"),
+        }
+    }
+}
+
+/// The build artifact file of the project at `root`.
+pub fn artifact_path(root: &Path) -> PathBuf {
+    root.join(ARTIFACT_FILE)
+}
+
+/// Hash all WebX sources of a project, and the configuration the modules are analyzed with.
+/// Any change to a source file name or its content changes the hash.
+pub fn source_hash(source_root: &Path, config: &ProjectConfig) -> String {
+    let source_root = canonical(source_root);
+    let mut files = locate_files(&source_root);
+    files.sort();
+    let mut hasher = Sha256::new();
+    hasher.update(ARTIFACT_VERSION);
+    hasher.update([config.allow_delete_body.unwrap_or(false) as u8]);
+    for file in files.iter() {
+        let name = file.strip_prefix(&source_root).unwrap_or(file);
+        hasher.update(name.to_string_lossy().as_bytes());
+        hasher.update([0]);
+        hasher.update(fs::read(file).unwrap_or_default());
+        hasher.update([0]);
+    }
+    format!("{:x}", hasher.finalize())
+}
+
+/// Write the build artifact of a project.
+///
+/// ## Arguments
+/// - `path` - The artifact file, its directory is created if missing.
+/// - `source_root` - The source root, module paths are stored relative to it.
+/// - `hash` - The source hash of the project, see `source_hash`.
+/// - `modules` - The parsed and analyzed modules.
+/// - `transpiled` - The transpiled global scopes of the modules.
+pub fn write_artifact(
+    path: &Path,
+    source_root: &Path,
+    hash: String,
+    modules: &[WXModule],
+    transpiled: Vec<(String, String)>,
+) -> std::io::Result<()> {
+    let source_root = canonical(source_root);
+    let mut modules = modules.to_vec();
+    for module in modules.iter_mut() {
+        rebase_module(module, &|path| {
+            path.strip_prefix(&source_root)
+                .unwrap_or(path)
+                .to_path_buf()
+        });
+    }
+    let artifact = WXArtifact {
+        version: ARTIFACT_VERSION.to_string(),
+        hash,
+        modules,
+        transpiled,
+    };
+    let bytes = bincode::serialize(&artifact).map_err(std::io::Error::other)?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(path, bytes)
+}
+
+/// Read the build artifact of a project, if it is up to date with the sources.
+///
+/// ## Arguments
+/// - `path` - The artifact file.
+/// - `source_root` - The source root, module paths are resolved relative to it.
+/// - `hash` - The current source hash of the project, see `source_hash`.
+pub fn read_artifact(
+    path: &Path,
+    source_root: &Path,
+    hash: &str,
+) -> Result<WXArtifact, WXArtifactError> {
+    let bytes = fs::read(path).map_err(WXArtifactError::Missing)?;
+    let mut artifact: WXArtifact =
+        bincode::deserialize(&bytes).map_err(|err| WXArtifactError::Invalid(err.to_string()))?;
+    if artifact.version != ARTIFACT_VERSION || artifact.hash != hash {
+        return Err(WXArtifactError::Stale);
+    }
+    let source_root = canonical(source_root);
+    for module in artifact.modules.iter_mut() {
+        rebase_module(module, &|path| source_root.join(path));
+    }
+    Ok(artifact)
+}
+
+/// The canonical source root, as the located module files are canonical.
+fn canonical(source_root: &Path) -> PathBuf {
+    source_root
+        .canonicalize()
+        .unwrap_or_else(|_| source_root.to_path_buf())
+}
+
+/// Map all source file paths of a module.
+fn rebase_module(module: &mut WXModule, rebase: &impl Fn(&Path) -> PathBuf) {
+    fn rebase_scope(scope: &mut WXScope, rebase: &impl Fn(&Path) -> PathBuf) {
+        for route in scope.routes.iter_mut() {
+            route.info.path = WXModulePath::new(rebase(&route.info.path.inner));
+        }
+        for scope in scope.scopes.iter_mut() {
+            rebase_scope(scope, rebase);
+        }
+    }
+    module.path = WXModulePath::new(rebase(&module.path.inner));
+    rebase_scope(&mut module.scope, rebase);
+}
@@ -1,3 +1,5 @@
+pub mod artifact;
 pub mod parser;
 pub mod project;
+mod test;
 pub mod webx;
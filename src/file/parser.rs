@@ -1,13 +1,14 @@
-use crate::file::webx::WXModule;
+use crate::file::{project::parse_duration, webx::WXModule};
 use std::{
     fmt::Display,
-    io::{BufReader, Read},
-    path::PathBuf,
+    path::{Path, PathBuf},
+    time::Duration,
 };
 
 use super::webx::{
-    WXBody, WXBodyType, WXHandler, WXInfoField, WXModel, WXModulePath, WXRoute, WXRouteHandlerCall,
-    WXRouteReqBody, WXScope, WXTypedIdentifier, WXUrlPath, WXUrlPathSegment, WXROOT_PATH,
+    websocket_method, WXBody, WXBodyType, WXErrorHandler, WXHandler, WXInfoField, WXMiddleware,
+    WXModel, WXModulePath, WXRoute, WXRouteHandlerCall, WXRouteReqBody, WXScope, WXTypedIdentifier,
+    WXUrlPath, WXUrlPathSegment, WILDCARD, WXROOT_PATH,
 };
 
 // ======================== Errors ========================
@@ -115,74 +116,177 @@ impl WebXParserError {
 
 // ======================== Parser ========================
 
+/// The keywords of scope statements, other than routes.
+const SCOPE_KEYWORDS: [&str; 8] = [
+    "include",
+    "location",
+    "model",
+    "middleware",
+    "use",
+    "handler",
+    "global",
+    "on error",
+];
+
+/// The route keywords and their HTTP methods.
+/// WebSocket routes use the `ws` keyword, see `route_method`.
+const ROUTE_METHODS: [(&str, hyper::Method); 9] = [
+    ("get", hyper::Method::GET),
+    ("post", hyper::Method::POST),
+    ("put", hyper::Method::PUT),
+    ("patch", hyper::Method::PATCH),
+    ("delete", hyper::Method::DELETE),
+    ("head", hyper::Method::HEAD),
+    ("options", hyper::Method::OPTIONS),
+    ("connect", hyper::Method::CONNECT),
+    ("trace", hyper::Method::TRACE),
+];
+
+/// The column a tab advances the indentation of a code block to the next multiple of.
+const TAB_WIDTH: usize = 4;
+
+/// The width of the indentation of a line in columns,
+/// where a tab advances to the next multiple of `TAB_WIDTH`.
+fn indent_width(line: &str) -> usize {
+    line.chars()
+        .take_while(|c| matches!(c, ' ' | '\t'))
+        .fold(0, |width, c| match c {
+            '\t' => (width / TAB_WIDTH + 1) * TAB_WIDTH,
+            _ => width + 1,
+        })
+}
+
+/// Strip `width` columns of indentation from a line, emptying lines of only whitespace.
+/// A tab reaching past `width` is replaced by the spaces it indents beyond it.
+fn strip_indent(line: &str, width: usize) -> String {
+    let mut column = 0;
+    for (index, c) in line.char_indices() {
+        if column >= width || !matches!(c, ' ' | '\t') {
+            if line[index..].trim().is_empty() {
+                break;
+            }
+            return " ".repeat(column.saturating_sub(width)) + &line[index..];
+        }
+        column = match c {
+            '\t' => (column / TAB_WIDTH + 1) * TAB_WIDTH,
+            _ => column + 1,
+        };
+    }
+    String::new()
+}
+
+/// Whether an include path is a pattern with `*` or `?` wildcards.
+fn is_glob(path: &str) -> bool {
+    path.contains(['*', '?'])
+}
+
+/// Whether a file name matches a pattern, where `*` matches any
+/// sequence of characters and `?` matches a single character.
+fn matches_glob(pattern: &[char], name: &[char]) -> bool {
+    match pattern.split_first() {
+        None => name.is_empty(),
+        Some((&'*', rest)) => (0..=name.len()).any(|skip| matches_glob(rest, &name[skip..])),
+        Some((&c, rest)) => name
+            .split_first()
+            .is_some_and(|(&n, name)| (c == '?' || c == n) && matches_glob(rest, name)),
+    }
+}
+
+/// Expand a `/`-separated path pattern relative to `dir`.
+/// Wildcards match within a single path segment.
+/// Returns the sorted relative paths of all matching files.
+fn expand_glob(dir: &Path, pattern: &str) -> Vec<String> {
+    let mut matches = vec![String::new()];
+    for segment in pattern.split('/') {
+        let pattern = segment.chars().collect::<Vec<_>>();
+        matches = matches
+            .into_iter()
+            .flat_map(|prefix: String| {
+                let join = |name: &str| {
+                    if prefix.is_empty() {
+                        name.to_string()
+                    } else {
+                        format!("{}/{}", prefix, name)
+                    }
+                };
+                if !is_glob(segment) {
+                    return vec![join(segment)];
+                }
+                let Ok(entries) = std::fs::read_dir(dir.join(&prefix)) else {
+                    return vec![];
+                };
+                entries
+                    .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+                    .filter(|name| matches_glob(&pattern, &name.chars().collect::<Vec<_>>()))
+                    .map(|name| join(&name))
+                    .collect()
+            })
+            .collect();
+    }
+    matches.retain(|path| dir.join(path).is_file());
+    matches.sort();
+    matches
+}
+
+/// The method of a route keyword, or `None` if it is not a route keyword.
+fn route_method(keyword: &str) -> Option<hyper::Method> {
+    match keyword {
+        "ws" => Some(websocket_method()),
+        _ => ROUTE_METHODS
+            .iter()
+            .find(|(name, _)| *name == keyword)
+            .map(|(_, method)| method.clone()),
+    }
+}
+
 struct WebXFileParser<'a> {
     file: &'a PathBuf,
-    _content: &'a String,
-    reader: BufReader<&'a [u8]>,
+    content: &'a str,
+    /// The byte offset of the next character in the content.
+    position: usize,
+    /// The line and column of the next character, counted in characters from 1.
     line: usize,
     column: usize,
-    peeked_index: u64, // "next index"
-    next_index: u64,   // "current index"
-    peeked: Option<char>,
+    /// The line and column of the last consumed character,
+    /// where errors about the character found are reported.
+    consumed: (usize, usize),
 }
 
 impl<'a> WebXFileParser<'a> {
-    fn new(file: &'a PathBuf, content: &'a String) -> WebXFileParser<'a> {
-        let mut p = WebXFileParser {
+    fn new(file: &'a PathBuf, content: &'a str) -> WebXFileParser<'a> {
+        WebXFileParser {
             file,
-            _content: content,
-            reader: BufReader::new(content.as_bytes()),
+            content,
+            position: 0,
             line: 1,
             column: 1,
-            peeked_index: 0,
-            next_index: 0,
-            peeked: None,
-        };
-        p.peeked = p.__raw_next().expect("Failed to read from file");
-        p
+            consumed: (1, 1),
+        }
     }
 
-    fn __update_line_column(&mut self, c: char) {
+    fn peek(&self) -> Option<char> {
+        self.content[self.position..].chars().next()
+    }
+    /// The character after the peeked one, without consuming anything.
+    fn peek_second(&self) -> Option<char> {
+        self.content[self.position..].chars().nth(1)
+    }
+    /// Consume the next character, or None if EOF is reached.
+    /// Advances the line and column to the character after it.
+    fn next(&mut self) -> Result<Option<char>, WebXParserError> {
+        let Some(c) = self.peek() else {
+            return Ok(None);
+        };
+        self.position += c.len_utf8();
+        self.consumed = (self.line, self.column);
         if c == '\n' {
             self.line += 1;
             self.column = 1;
         } else {
             self.column += 1;
         }
-    }
-
-    /// Returns the next character in the file, or None if EOF is reached.
-    /// Increments the line and column counters.
-    ///
-    /// # Errors
-    /// If the file cannot be read, an error is returned and the program exits.
-    fn __raw_next(&mut self) -> Result<Option<char>, WebXParserError> {
-        let mut buf = [0; 1];
-        let bytes_read = self
-            .reader
-            .read(&mut buf)
-            .map_err(|err| WebXParserError::IoError(err, self.file.clone()))?;
-        if bytes_read == 0 {
-            return Ok(None);
-        }
-        let c = buf[0] as char;
-        self.peeked_index += 1;
-        // Index of the character returned by the next call to `next`.
-        self.next_index = self.peeked_index - 1;
         Ok(Some(c))
     }
-
-    fn peek(&self) -> Option<char> {
-        self.peeked
-    }
-    fn next(&mut self) -> Result<Option<char>, WebXParserError> {
-        let c = self.peeked;
-        self.peeked = self.__raw_next()?;
-        if let Some(c) = c {
-            self.__update_line_column(c);
-        }
-        Ok(c)
-    }
     fn expect(&mut self, context: &str) -> Result<char, WebXParserError> {
         let nc = self.next()?;
         self.expect_not_eof(nc, context)
@@ -213,12 +317,13 @@ impl<'a> WebXFileParser<'a> {
     ) -> Result<char, WebXParserError> {
         let nc = self.expect_not_eof(nc, context)?;
         if nc != expected {
+            let (line, column) = self.consumed;
             Err(WebXParserError::expected_but_found(
                 expected,
                 nc,
                 context,
-                self.line,
-                self.column,
+                line,
+                column,
                 self.file.clone(),
             ))
         } else {
@@ -243,12 +348,13 @@ impl<'a> WebXFileParser<'a> {
     ) -> Result<(), WebXParserError> {
         for c in expected.chars().skip(already_read) {
             if self.expect(context)? != c {
+                let (line, column) = self.consumed;
                 return Err(WebXParserError::expected_but_found(
                     expected,
                     c,
                     context,
-                    self.line,
-                    self.column,
+                    line,
+                    column,
                     self.file.clone(),
                 ));
             }
@@ -269,12 +375,13 @@ impl<'a> WebXFileParser<'a> {
     ) -> Result<char, WebXParserError> {
         let nc = self.expect_not_eof(nc, context)?;
         if !cs.contains(&nc) {
+            let (line, column) = self.consumed;
             return Err(WebXParserError::expected_any_of_but_found(
                 &cs,
                 nc,
                 context,
-                self.line,
-                self.column,
+                line,
+                column,
                 self.file.clone(),
             ));
         }
@@ -290,6 +397,8 @@ impl<'a> WebXFileParser<'a> {
         self.expect_any_of(nc, cs, context)
     }
 
+    /// Skip whitespace and comments.
+    /// A line comment ends before its newline, which is only skipped if `skip_newlines` is set.
     fn skip_whitespace(&mut self, skip_newlines: bool) {
         loop {
             let c = self.peek();
@@ -299,12 +408,16 @@ impl<'a> WebXFileParser<'a> {
             let c = c.unwrap();
             if c == ' ' || c == '\t' || c == '\r' || (skip_newlines && c == '\n') {
                 self.next().expect("Failed to skip whitespace");
+            } else if c == '/' && matches!(self.peek_second(), Some('/' | '*')) {
+                self.next().expect("Failed to skip comment");
+                self.skip_comment();
             } else {
                 break;
             }
         }
     }
 
+    /// Consume and return the next character that is not whitespace or part of a comment.
     fn next_skip_whitespace(
         &mut self,
         skip_newlines: bool,
@@ -318,11 +431,40 @@ impl<'a> WebXFileParser<'a> {
             if c == ' ' || c == '\t' || c == '\r' || (skip_newlines && c == '\n') {
                 continue;
             }
+            if c == '/' && matches!(self.peek(), Some('/' | '*')) {
+                self.skip_comment();
+                continue;
+            }
             return Ok(Some(c)); // Return the first non-whitespace character.
         }
         Ok(None)
     }
 
+    /// Skip the rest of a comment after its leading `/`.
+    /// Line comments end before the newline, block comments after the closing `*/`.
+    /// An unterminated block comment ends at EOF.
+    fn skip_comment(&mut self) {
+        let skip = |parser: &mut Self| parser.next().expect("Failed to skip comment");
+        match skip(self) {
+            Some('/') => {
+                while !matches!(self.peek(), None | Some('\n')) {
+                    skip(self);
+                }
+            }
+            Some('*') => loop {
+                match skip(self) {
+                    None => break,
+                    Some('*') if self.peek() == Some('/') => {
+                        skip(self);
+                        break;
+                    }
+                    _ => {}
+                }
+            },
+            _ => {}
+        }
+    }
+
     fn read_while<F: Fn(char) -> bool>(&mut self, f: F) -> Result<String, WebXParserError> {
         let mut s = String::new();
         loop {
@@ -371,34 +513,37 @@ impl<'a> WebXFileParser<'a> {
         Ok(s)
     }
 
+    /// Whether the next characters are the `"""` delimiter of a raw string literal.
+    fn at_raw_string_delimiter(&self) -> bool {
+        self.content[self.position..].starts_with("\"\"\"")
+    }
+
+    /// Parse a raw multiline string literal, after its opening `"""`.
+    /// The content up to the closing `"""` is kept as is, without escape processing.
+    ///
+    /// ## Example
+    /// ```ignore
+    /// """
+    /// <h1>Hello, "world"!</h1>
+    /// """
+    /// ```
+    fn parse_raw_string(&mut self) -> Result<String, WebXParserError> {
+        let context = "parsing a raw string literal";
+        let mut s = String::new();
+        while !self.at_raw_string_delimiter() {
+            let c = self.expect(context)?;
+            s.push(c);
+        }
+        self.expect_specific_str("\"\"\"", 0, context)?;
+        Ok(s)
+    }
+
     fn parse_comment(&mut self) -> Result<(), WebXParserError> {
-        match self.expect_next_any_of(vec!['/', '*'], "parsing the beginning of a comment")? {
-            '/' => loop {
-                let c = self.next()?;
-                if c.is_none() {
-                    break;
-                }
-                if c.unwrap() == '\n' {
-                    break;
-                }
-            },
-            '*' => loop {
-                let c = self.next()?;
-                if c.is_none() {
-                    break;
-                }
-                if c.unwrap() == '*' {
-                    let c = self.next()?;
-                    if c.is_none() {
-                        break;
-                    }
-                    if c.unwrap() == '/' {
-                        break;
-                    }
-                }
-            },
-            _ => unreachable!(),
+        if !matches!(self.peek(), Some('/' | '*')) {
+            let nc = self.next()?;
+            self.expect_any_of(nc, vec!['/', '*'], "parsing the beginning of a comment")?;
         }
+        self.skip_comment();
         Ok(())
     }
 
@@ -439,26 +584,73 @@ impl<'a> WebXFileParser<'a> {
         Ok(s)
     }
 
-    /// Parse an include statement.
+    /// Parse an include statement with one or more comma-separated paths.
+    /// Paths with `*` or `?` wildcards are expanded to all matching files,
+    /// relative to the directory of the module.
     ///
     /// ## Example
     /// ```
     /// include "path/to/file.webx";
+    /// include "a.webx", "b.webx";
+    /// include "lib/*.webx";
     /// ```
-    fn parse_include(&mut self) -> Result<String, WebXParserError> {
+    fn parse_include(&mut self) -> Result<Vec<String>, WebXParserError> {
         let context = "parsing an include statement";
-        self.expect_specific_str("include", 1, context)?;
-        self.expect_next_specific('"', context)?;
-        let path = self.parse_string()?;
-        let nc = self.next_skip_whitespace(false)?;
-        self.expect_any_of(nc, vec!['\n', ';'], context)?;
-        Ok(path)
+        // The keyword is separated from the first path, e.g. `include"a.webx"` is rejected.
+        match self.peek() {
+            Some(' ' | '\t') => {}
+            Some(c) => {
+                return Err(WebXParserError::expected_but_found(
+                    "whitespace",
+                    c,
+                    context,
+                    self.line,
+                    self.column,
+                    self.file.clone(),
+                ))
+            }
+            None => {
+                return Err(WebXParserError::unexpected_eof(
+                    context,
+                    self.line,
+                    self.column,
+                    self.file.clone(),
+                ))
+            }
+        }
+        let mut paths = vec![];
+        loop {
+            // Paths after a comma may continue on the next line.
+            self.skip_whitespace(!paths.is_empty());
+            let (line, column) = (self.line, self.column);
+            self.expect_next_specific('"', context)?;
+            let path = self.parse_string()?;
+            if is_glob(&path) {
+                let dir = self.file.parent().unwrap_or(Path::new(""));
+                let matches = expand_glob(dir, &path);
+                if matches.is_empty() {
+                    return Err(WebXParserError::SyntaxError(
+                        WebXParserError::at_lc(
+                            format!("Include pattern \"{}\" matched no files", path),
+                            line,
+                            column,
+                        ),
+                        self.file.clone(),
+                    ));
+                }
+                paths.extend(matches);
+            } else {
+                paths.push(path);
+            }
+            let nc = self.next_skip_whitespace(false)?;
+            if self.expect_any_of(nc, vec![',', '\n', ';'], context)? != ',' {
+                return Ok(paths);
+            }
+        }
     }
 
     fn parse_location(&mut self) -> Result<WXScope, WebXParserError> {
         let context = "parsing a location statement";
-        self.expect_specific_str("location", 1, context)?;
-        self.skip_whitespace(true);
         let path = self.parse_url_path()?;
         self.skip_whitespace(true);
         self.expect_next_specific('{', context)?;
@@ -477,16 +669,30 @@ impl<'a> WebXFileParser<'a> {
         Ok(WXTypedIdentifier { name, type_ })
     }
 
+    /// Parse a comma separated list of type pairs, such as the fields of a model.
+    /// Names must be unique within the list.
     fn parse_type_pairs(
         &mut self,
         allow_stray_comma: bool,
+        context: &str,
     ) -> Result<Vec<WXTypedIdentifier>, WebXParserError> {
-        let mut pairs = vec![];
+        let mut pairs: Vec<WXTypedIdentifier> = vec![];
         loop {
+            self.skip_whitespace(true);
+            let (line, column) = (self.line, self.column);
             let pair = self.parse_type_pair()?;
             if pair.name.is_empty() {
                 break;
             } // Empty name means end of type pairs.
+            if pairs.iter().any(|other| other.name == pair.name) {
+                return Err(WebXParserError::unexpected(
+                    format!("duplicate name '{}'", pair.name),
+                    context,
+                    line,
+                    column,
+                    self.file.clone(),
+                ));
+            }
             pairs.push(pair);
             let nc = self.peek();
             if nc.is_none() {
@@ -515,6 +721,13 @@ impl<'a> WebXFileParser<'a> {
             if nc == until {
                 break;
             }
+            if self.at_raw_string_delimiter() {
+                // Passed to TypeScript as an ordinary string literal.
+                self.expect_specific_str("\"\"\"", 0, "parsing a raw string literal")?;
+                let raw = self.parse_raw_string()?;
+                s.push_str(&serde_json::to_string(&raw).unwrap());
+                continue;
+            }
             let nc = self.next()?.unwrap();
             if let Some((_, end)) = GROUPING.iter().find(|(start, _)| *start == nc) {
                 s.push(nc);
@@ -529,37 +742,66 @@ impl<'a> WebXFileParser<'a> {
 
     fn parse_model(&mut self) -> Result<WXModel, WebXParserError> {
         let context = "parsing a model statement";
-        self.expect_specific_str("model", 1, context)?;
-        let name = self.read_until('{')?.trim().to_string();
+        self.skip_whitespace(true);
+        let name = self.parse_identifier()?;
+        self.skip_whitespace(true);
         self.expect_next_specific('{', context)?;
-        let fields = self.parse_type_pairs(true)?;
+        let fields = self.parse_type_pairs(true, context)?;
         self.expect_next_specific('}', context)?;
         Ok(WXModel { name, fields })
     }
 
+    /// De-indent a code block by the indentation common to its lines, like Python's
+    /// `textwrap.dedent`. Indentation is measured in columns, see `indent_width`,
+    /// so lines indented with tabs and spaces are de-indented consistently.
+    /// The first line follows the opening delimiter, it is only trimmed at the start.
+    /// Lines of only whitespace are emptied, and do not count towards the common indentation.
     fn de_indent_block(s: String) -> String {
-        let initial_indent = s
-            .lines()
-            .last()
-            .unwrap_or("")
-            .chars()
-            .take_while(|c| c.is_whitespace())
-            .count();
-        s.lines()
-            .map(|l| {
-                let line_indent = l.chars().take_while(|c| c.is_whitespace()).count();
-                if line_indent >= initial_indent {
-                    l.chars().skip(initial_indent).collect::<String>()
-                } else {
-                    l.to_string()
-                }
-            })
+        let mut lines = s.lines();
+        let first = lines.next().unwrap_or("").trim_start();
+        let rest = lines.collect::<Vec<_>>();
+        let indent = rest
+            .iter()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| indent_width(line))
+            .min()
+            .unwrap_or(0);
+        std::iter::once(first.to_string())
+            .chain(rest.iter().map(|line| strip_indent(line, indent)))
             .collect::<Vec<String>>()
             .join("\n")
     }
 
+    /// The body type of a `json {` or `text {` body at the current position, if any.
+    /// Nothing is consumed.
+    fn peek_body_keyword(&self) -> Option<WXBodyType> {
+        self.peek()?;
+        let rest = &self.content[self.position..];
+        let keyword = rest
+            .split(|c: char| !c.is_alphanumeric() && c != '_')
+            .next()?;
+        let body_type = match keyword {
+            "json" => WXBodyType::Json,
+            "text" => WXBodyType::Text,
+            _ => return None,
+        };
+        rest[keyword.len()..]
+            .trim_start()
+            .starts_with('{')
+            .then_some(body_type)
+    }
+
     fn parse_code_body(&mut self) -> Result<Option<WXBody>, WebXParserError> {
         self.skip_whitespace(true);
+        if let Some(body_type) = self.peek_body_keyword() {
+            self.parse_identifier()?;
+            self.skip_whitespace(true);
+            self.expect_next_specific('{', "parsing a code body")?;
+            return Ok(Some(WXBody {
+                body_type,
+                body: Self::de_indent_block(self.parse_block('{', '}')?),
+            }));
+        }
         Ok(match self.peek() {
             Some('{') => {
                 self.next()?;
@@ -575,6 +817,14 @@ impl<'a> WebXFileParser<'a> {
                     body: Self::de_indent_block(self.parse_block('(', ')')?),
                 })
             }
+            Some('"') => {
+                let context = "parsing a raw string body";
+                self.expect_specific_str("\"\"\"", 0, context)?;
+                Some(WXBody {
+                    body_type: WXBodyType::Raw,
+                    body: self.parse_raw_string()?,
+                })
+            }
             _ => None,
         })
     }
@@ -596,9 +846,10 @@ impl<'a> WebXFileParser<'a> {
     fn parse_handler(&mut self) -> Result<WXHandler, WebXParserError> {
         let context = "parsing a handler statement";
         self.skip_whitespace(true);
-        let name = self.read_until('(')?.trim().to_string();
+        let name = self.parse_identifier()?;
+        self.skip_whitespace(true);
         self.expect_next_specific('(', context)?;
-        let params = self.parse_type_pairs(false)?;
+        let params = self.parse_type_pairs(false, context)?;
         self.expect_next_specific(')', context)?;
         let body = self.parse_code_body()?;
         if body.is_none() {
@@ -614,43 +865,103 @@ impl<'a> WebXFileParser<'a> {
         Ok(WXHandler { name, params, body })
     }
 
+    /// Parses an error handler statement.
+    /// The binding name defaults to `error` if left empty.
+    ///
+    /// ## Example
+    /// ```ignore
+    /// on error(e) {
+    ///     return `<h1>${e.code}</h1><p>${e.message}</p>`;
+    /// }
+    /// ```
+    fn parse_error_handler(&mut self) -> Result<WXErrorHandler, WebXParserError> {
+        let context = "parsing an error handler statement";
+        self.skip_whitespace(true);
+        self.expect_specific_str("error", 0, context)?;
+        self.skip_whitespace(true);
+        self.expect_next_specific('(', context)?;
+        self.skip_whitespace(true);
+        let binding = self.parse_identifier()?;
+        self.skip_whitespace(true);
+        self.expect_next_specific(')', context)?;
+        let Some(body) = self.parse_code_body()? else {
+            return Err(WebXParserError::unexpected(
+                "error handler body",
+                context,
+                self.line,
+                self.column,
+                self.file.clone(),
+            ));
+        };
+        Ok(WXErrorHandler {
+            binding: if binding.is_empty() {
+                "error".to_string()
+            } else {
+                binding
+            },
+            body,
+        })
+    }
+
     /// Parse a URL path.
     /// ## Supporting syntax:
     /// - Static path segments
     /// - Dynamic path segments (arguments)
-    /// - Optional path segments
+    /// - Optional path segments, only as the last segment
     /// - Wildcard path segments
     /// - Regex path segments
     ///
     /// ## Example:
     /// ```ignore
-    /// /path/to/(arg: string)?/*
+    /// /path/to/*/(arg: string)?
     /// ```
     fn parse_url_path(&mut self) -> Result<WXUrlPath, WebXParserError> {
+        self.parse_url_path_with_slash().map(|(path, _)| path)
+    }
+
+    /// Parse a URL path, see `parse_url_path`,
+    /// and whether it is declared with a trailing slash, e.g. `/about/`.
+    fn parse_url_path_with_slash(&mut self) -> Result<(WXUrlPath, bool), WebXParserError> {
         let context = "parsing an endpoint URL path";
+        let mut trailing_slash = false;
         let mut segments: Vec<WXUrlPathSegment> = vec![];
-        self.skip_whitespace(true);
+        // Not `skip_whitespace`, as `/*` starts a path with a wildcard, not a comment.
+        while matches!(self.peek(), Some(' ' | '\t' | '\r' | '\n')) {
+            self.next()?;
+        }
         let mut regex_counter = 0;
         loop {
             match self.expect(context)? {
                 '(' => {
-                    segments.push(WXUrlPathSegment::Parameter(self.parse_type_pair()?));
+                    let param = self.parse_type_pair()?;
                     self.expect_next_specific(')', context)?;
+                    if self.peek() == Some('?') {
+                        self.next()?;
+                        segments.push(WXUrlPathSegment::Optional(param));
+                    } else {
+                        segments.push(WXUrlPathSegment::Parameter(param));
+                    }
                 }
                 '*' => {
                     segments.push(WXUrlPathSegment::Regex(
                         format!("g{}", regex_counter),
-                        "*".to_string(),
+                        WILDCARD.to_string(),
                     ));
                     regex_counter += 1;
                 }
                 '/' => {
                     let nc = self.peek();
+                    if nc == Some('/') {
+                        // A line comment ends the path.
+                        self.skip_comment();
+                        break;
+                    }
                     if let Some(nc) = nc {
                         if nc.is_alphanumeric() {
                             segments.push(WXUrlPathSegment::Literal(self.parse_identifier()?));
                         } else if nc.is_whitespace() {
                             // Allow root path to be empty. E.g. `get / ... `.
+                            trailing_slash = !segments.is_empty();
                             segments.push(WXUrlPathSegment::Literal("".to_string()));
                         }
                     }
@@ -671,10 +982,101 @@ impl<'a> WebXFileParser<'a> {
                 true
             }
         });
-        Ok(WXUrlPath(segments))
+        let path = WXUrlPath(segments);
+        if let Some(param) = path.misplaced_optional() {
+            return Err(WebXParserError::unexpected(
+                format!(
+                    "optional parameter '{}' before the last segment",
+                    param.name
+                ),
+                context,
+                self.line,
+                self.column,
+                self.file.clone(),
+            ));
+        }
+        Ok((path, trailing_slash))
+    }
+
+    /// Parse the annotations between the URL path and the handlers of a route:
+    /// an optional `cache(duration)`, an optional `priority(n)`,
+    /// and an optional request body format, in any order.
+    fn parse_route_annotations(
+        &mut self,
+    ) -> Result<(Option<Duration>, Option<i32>, Option<WXRouteReqBody>), WebXParserError> {
+        let (mut cache, mut priority, mut body_format) = (None, None, None);
+        loop {
+            self.skip_whitespace(true);
+            let nc = self.peek();
+            if nc.is_none() || !char::is_alphabetic(nc.unwrap()) {
+                break;
+            }
+            if self.peek_body_keyword().is_some() {
+                break; // A `json {` or `text {` response body.
+            }
+            let (line, column) = (self.line, self.column);
+            let name = self.parse_identifier()?;
+            let duplicate = if name == "cache" {
+                cache.replace(self.parse_cache_duration()?).is_some()
+            } else if name == "priority" {
+                priority.replace(self.parse_priority()?).is_some()
+            } else {
+                body_format
+                    .replace(self.parse_body_format(name.clone())?)
+                    .is_some()
+            };
+            if duplicate {
+                return Err(WebXParserError::unexpected(
+                    format!("second route annotation '{}'", name),
+                    "parsing a route",
+                    line,
+                    column,
+                    self.file.clone(),
+                ));
+            }
+        }
+        Ok((cache, priority, body_format))
+    }
+
+    /// Parse the `(n)` of a `priority` annotation, such as `(-1)`.
+    fn parse_priority(&mut self) -> Result<i32, WebXParserError> {
+        let context = "parsing a route priority";
+        self.expect_next_specific('(', context)?;
+        let (line, column) = (self.line, self.column);
+        let priority = self.read_until(')')?;
+        self.expect_next_specific(')', context)?;
+        priority.trim().parse().map_err(|_| {
+            WebXParserError::expected_but_found(
+                "an integer priority such as -1 or 10",
+                priority.trim(),
+                context,
+                line,
+                column,
+                self.file.clone(),
+            )
+        })
     }
 
-    /// Parse a request body format.
+    /// Parse the `(duration)` of a `cache` annotation, such as `(1h)`.
+    fn parse_cache_duration(&mut self) -> Result<Duration, WebXParserError> {
+        let context = "parsing a cache duration";
+        self.expect_next_specific('(', context)?;
+        let (line, column) = (self.line, self.column);
+        let duration = self.read_until(')')?;
+        self.expect_next_specific(')', context)?;
+        parse_duration(duration.trim()).ok_or_else(|| {
+            WebXParserError::expected_but_found(
+                "a duration such as 30s, 10m, 1h, or 1d",
+                duration.trim(),
+                context,
+                line,
+                column,
+                self.file.clone(),
+            )
+        })
+    }
+
+    /// Parse a request body format, given its already parsed name.
     /// ## Supporting syntax:
     /// - pre-defined formats (json, form, text, html)
     ///     - <name>(<field>: <type>, <field>: <type>, ...)
@@ -687,25 +1089,18 @@ impl<'a> WebXFileParser<'a> {
     /// form(name: string, age: number)
     /// User
     /// ```
-    fn parse_body_format(&mut self) -> Result<Option<WXRouteReqBody>, WebXParserError> {
+    fn parse_body_format(&mut self, name: String) -> Result<WXRouteReqBody, WebXParserError> {
         let context = "parsing a request body format";
-        self.skip_whitespace(true);
         let nc = self.peek();
-        Ok(if nc.is_some() && char::is_alphabetic(nc.unwrap()) {
-            let name = self.parse_identifier()?;
-            let nc = self.peek();
-            if nc.is_some() && nc.unwrap() == '(' {
-                // Custom format with fields.
-                self.expect(context)?; // Consume the '('.
-                let fields = self.parse_type_pairs(true)?;
-                self.expect_next_specific(')', context)?;
-                Some(WXRouteReqBody::Definition(name, fields))
-            } else {
-                // User-defined model name reference.
-                Some(WXRouteReqBody::ModelReference(name))
-            }
+        Ok(if nc.is_some() && nc.unwrap() == '(' {
+            // Custom format with fields.
+            self.expect(context)?; // Consume the '('.
+            let fields = self.parse_type_pairs(true, context)?;
+            self.expect_next_specific(')', context)?;
+            WXRouteReqBody::Definition(name, fields)
         } else {
-            None
+            // User-defined model name reference.
+            WXRouteReqBody::ModelReference(name)
         })
     }
 
@@ -754,32 +1149,121 @@ impl<'a> WebXFileParser<'a> {
         })
     }
 
+    /// Parse a middleware declaration.
+    /// The name must be followed by at least one handler call.
+    ///
+    /// ## Example
+    /// ```ignore
+    /// middleware auth -> authenticate(user_id): user, logRequest(user)
+    /// ```
+    fn parse_middleware(&mut self) -> Result<WXMiddleware, WebXParserError> {
+        let context = "parsing a middleware statement";
+        self.skip_whitespace(false);
+        let name = self.parse_identifier()?;
+        if name.is_empty() {
+            return Err(WebXParserError::unexpected(
+                "missing middleware name",
+                context,
+                self.line,
+                self.column,
+                self.file.clone(),
+            ));
+        }
+        let handlers = self.parse_route_handlers()?;
+        if handlers.is_empty() {
+            return Err(WebXParserError::unexpected(
+                "middleware without handler calls",
+                context,
+                self.line,
+                self.column,
+                self.file.clone(),
+            ));
+        }
+        Ok(WXMiddleware { name, handlers })
+    }
+
+    /// Parse a use statement, attaching middleware to the enclosing scope.
+    ///
+    /// ## Example
+    /// ```ignore
+    /// use logRequests, auth
+    /// ```
+    fn parse_use(&mut self) -> Result<Vec<String>, WebXParserError> {
+        let context = "parsing a use statement";
+        let mut names = vec![];
+        loop {
+            self.skip_whitespace(false);
+            let name = self.parse_identifier()?;
+            if name.is_empty() {
+                return Err(WebXParserError::unexpected(
+                    "missing middleware name",
+                    context,
+                    self.line,
+                    self.column,
+                    self.file.clone(),
+                ));
+            }
+            names.push(name);
+            self.skip_whitespace(false);
+            if self.peek() != Some(',') {
+                break;
+            }
+            self.next()?;
+        }
+        match self.next_skip_whitespace(false)? {
+            None | Some('\n') | Some(';') => Ok(names),
+            Some(c) => Err(WebXParserError::expected_any_of_but_found(
+                &["','", "a newline", "';'"],
+                c,
+                context,
+                self.consumed.0,
+                self.consumed.1,
+                self.file.clone(),
+            )),
+        }
+    }
+
     /// Parse a route statement.
     /// ## Supporting syntax:
-    /// - HTTP method (get, post, put, patch, delete, connect, options, trace, head)
+    /// - HTTP method (get, post, put, patch, delete, connect, options, trace, head) or `ws`
     /// - URL path with arguments
     /// - Request body format (json, form, text, html, or user-defined model)
+    /// - Cache annotation, e.g. `cache(1h)`
+    /// - Priority annotation among overlapping routes, e.g. `priority(-1)`
     /// - Pre and post handlers
     /// - Response body
     ///     - TypeScript code (TS): Using `{}` delimiters
     ///     - HTML template (TSX): Using `()` delimiters
+    ///     - Raw string: Using `"""` delimiters
+    ///     - JSON or plain text from TypeScript code: Using `json {}` or `text {}`
     ///
     /// ## Example:
     /// ```ignore
     /// get /path/to/route (<h1>My page</h1>)
+    /// get /assets/(name: string) cache(1h) -> static(name)
+    /// get /(slug: string) priority(-1) (<h1>Page</h1>)
     /// post /path/to/(arg: string)/route json(text: string, n: number) -> handler(arg, text) {
     ///     // ...
     /// }
+    /// ws /path/to/socket {
+    ///     socket.onMessage((message) => socket.send(message));
+    /// }
     /// ```
     fn parse_route(&mut self, method: hyper::Method) -> Result<WXRoute, WebXParserError> {
+        let info = WXInfoField {
+            path: WXModulePath::new(self.file.clone()),
+            line: self.line,
+        };
+        let (path, trailing_slash) = self.parse_url_path_with_slash()?;
+        let (cache, priority, body_format) = self.parse_route_annotations()?;
         Ok(WXRoute {
-            info: WXInfoField {
-                path: WXModulePath::new(self.file.clone()),
-                line: self.line,
-            },
+            info,
             method,
-            path: self.parse_url_path()?,
-            body_format: self.parse_body_format()?,
+            path,
+            trailing_slash,
+            body_format,
+            cache,
+            priority: priority.unwrap_or_default(),
             pre_handlers: self.parse_route_handlers()?,
             body: self.parse_code_body()?,
             post_handlers: self.parse_route_handlers()?,
@@ -789,7 +1273,7 @@ impl<'a> WebXFileParser<'a> {
     /// Parse either the global module scope, or a location scope.
     /// The function parses all basic components making up a webx
     /// module scope such as includes, nested locations, handlers,
-    /// routes, and models.
+    /// middleware, routes, and models.
     ///
     /// # Arguments
     /// * `is_global` - Whether the scope is global or not.
@@ -805,7 +1289,10 @@ impl<'a> WebXFileParser<'a> {
             includes: vec![],
             models: vec![],
             handlers: vec![],
+            middleware: vec![],
+            uses: vec![],
             routes: vec![],
+            error_handler: None,
             scopes: vec![],
         };
         loop {
@@ -823,7 +1310,7 @@ impl<'a> WebXFileParser<'a> {
                     ));
                 }
             }
-            // Keywords: handler, include, location, module, { } and all HTTP methods.
+            // Keywords: handler, include, location, middleware, model, use, ws, { } and all HTTP methods.
             // Only expect a keyword at the start of a line, whitespace, or // comments.
             // Pass to dedicated parser function, otherwise error.
             let c = c.unwrap();
@@ -842,99 +1329,63 @@ impl<'a> WebXFileParser<'a> {
                     }
                 }
                 '/' => self.parse_comment()?,
-                'i' => scope.includes.push(self.parse_include()?),
-                'l' => scope.scopes.push(self.parse_location()?),
-                'm' => scope.models.push(self.parse_model()?),
-                'h' => match self.expect(context)? {
-                    'a' => {
-                        self.expect_specific_str("handler", 2, context)?;
-                        scope.handlers.push(self.parse_handler()?);
-                    }
-                    'e' => {
-                        self.expect_specific_str("head", 2, context)?;
-                        scope.routes.push(self.parse_route(hyper::Method::HEAD)?);
-                    }
-                    c => {
-                        return Err(WebXParserError::expected_any_of_but_found(
-                            &["handler", "head"],
-                            c,
-                            context,
-                            self.line,
-                            self.column,
-                            self.file.clone(),
-                        ))
-                    }
-                },
-                'g' => match self.expect(context)? {
-                    'e' => {
-                        self.expect_specific_str("get", 2, context)?;
-                        scope.routes.push(self.parse_route(hyper::Method::GET)?);
-                    }
-                    'l' => {
-                        self.expect_specific_str("global", 2, context)?;
-                        self.skip_whitespace(true);
-                        self.expect_next_specific('{', context)?;
-                        scope.global_ts = self.parse_block('{', '}')?;
-                    }
-                    c => {
-                        return Err(WebXParserError::expected_any_of_but_found(
-                            &["get", "global"],
-                            c,
-                            context,
-                            self.line,
-                            self.column,
-                            self.file.clone(),
-                        ))
-                    }
-                },
-                'p' => match self.expect(context)? {
-                    'o' => {
-                        self.expect_specific_str("post", 2, context)?;
-                        scope.routes.push(self.parse_route(hyper::Method::POST)?);
-                    }
-                    'u' => {
-                        self.expect_specific_str("put", 2, context)?;
-                        scope.routes.push(self.parse_route(hyper::Method::PUT)?);
-                    }
-                    'a' => {
-                        self.expect_specific_str("patch", 2, context)?;
-                        scope.routes.push(self.parse_route(hyper::Method::PATCH)?);
-                    }
-                    c => {
-                        return Err(WebXParserError::expected_any_of_but_found(
-                            &["post", "put", "patch"],
-                            c,
-                            context,
-                            self.line,
-                            self.column,
-                            self.file.clone(),
-                        ))
+                c if c.is_alphabetic() => {
+                    let (line, column) = self.consumed;
+                    let keyword = format!("{}{}", c, self.parse_identifier()?);
+                    match keyword.as_str() {
+                        "include" => scope.includes.extend(self.parse_include()?),
+                        "location" => scope.scopes.push(self.parse_location()?),
+                        "model" => scope.models.push(self.parse_model()?),
+                        "middleware" => scope.middleware.push(self.parse_middleware()?),
+                        "use" => scope.uses.extend(self.parse_use()?),
+                        "handler" => scope.handlers.push(self.parse_handler()?),
+                        "global" => {
+                            self.skip_whitespace(true);
+                            self.expect_next_specific('{', context)?;
+                            scope.global_ts = self.parse_block('{', '}')?;
+                        }
+                        "on" => {
+                            if !is_global || scope.error_handler.is_some() {
+                                return Err(WebXParserError::unexpected(
+                                    "error handler, only one is allowed in the global scope",
+                                    context,
+                                    self.line,
+                                    self.column,
+                                    self.file.clone(),
+                                ));
+                            }
+                            scope.error_handler = Some(self.parse_error_handler()?);
+                        }
+                        keyword => match route_method(keyword) {
+                            Some(method) => scope.routes.push(self.parse_route(method)?),
+                            None => {
+                                let expected = SCOPE_KEYWORDS
+                                    .iter()
+                                    .copied()
+                                    .chain(ROUTE_METHODS.iter().map(|(keyword, _)| *keyword))
+                                    .chain(["ws"])
+                                    .collect::<Vec<_>>();
+                                return Err(WebXParserError::expected_any_of_but_found(
+                                    &expected,
+                                    keyword,
+                                    context,
+                                    line,
+                                    column,
+                                    self.file.clone(),
+                                ));
+                            }
+                        },
                     }
-                },
-                'd' => {
-                    self.expect_specific_str("delete", 1, context)?;
-                    scope.routes.push(self.parse_route(hyper::Method::DELETE)?);
-                }
-                'c' => {
-                    self.expect_specific_str("connect", 1, context)?;
-                    scope.routes.push(self.parse_route(hyper::Method::CONNECT)?);
-                }
-                'o' => {
-                    self.expect_specific_str("options", 1, context)?;
-                    scope.routes.push(self.parse_route(hyper::Method::OPTIONS)?);
-                }
-                't' => {
-                    self.expect_specific_str("trace", 1, context)?;
-                    scope.routes.push(self.parse_route(hyper::Method::TRACE)?);
                 }
                 _ => {
+                    let (line, column) = self.consumed;
                     return Err(WebXParserError::unexpected_char(
                         c,
                         context,
-                        self.line,
-                        self.column,
+                        line,
+                        column,
                         self.file.clone(),
-                    ))
+                    ));
                 }
             }
         }
@@ -952,6 +1403,11 @@ impl<'a> WebXFileParser<'a> {
 pub fn parse_webx_file(file: &PathBuf) -> Result<WXModule, WebXParserError> {
     let file_contents =
         std::fs::read_to_string(file).map_err(|err| WebXParserError::IoError(err, file.clone()))?;
-    let mut parser = WebXFileParser::new(file, &file_contents);
+    parse_webx_source(file, &file_contents)
+}
+
+/// Parse WebX source code as if it was read from the given `file`.
+pub fn parse_webx_source(file: &PathBuf, source: &str) -> Result<WXModule, WebXParserError> {
+    let mut parser = WebXFileParser::new(file, source);
     parser.parse_module()
 }
@@ -1,12 +1,16 @@
 use serde::{Deserialize, Serialize};
 use std::{
-    ffi::OsStr,
+    collections::BTreeMap,
     fs,
     path::{Path, PathBuf},
+    time::Duration,
 };
 
 use crate::{
-    file::{parser::parse_webx_file, webx::WXModule},
+    file::{
+        parser::parse_webx_file,
+        webx::{is_module_path, WXModule, MODULE_EXTENSIONS},
+    },
     reporting::{
         error::{
             error_code, exit_error, exit_error_hint, DateTimeSpecifier, ERROR_PARSE_IO,
@@ -50,10 +54,74 @@ use super::parser::WebXParserError;
 ///     "cache": {
 ///         "strategy": "memory",
 ///         "duration": "10m"
+///     },
+///     "errorPages": {
+///         "404": "./errors/404.html",
+///         "500": "./errors/500.html"
+///     },
+///     "allowDeleteBody": false,
+///     "allowTraceConnect": false,
+///     "watchDebounceMs": 100,
+///     "watchExtensions": ["ts", "js"],
+///     "trustProxy": false,
+///     "trustedProxies": 1,
+///     "trailingSlash": "ignore",
+///     "caseSensitiveRoutes": true,
+///     "basePath": "/app",
+///     "securityHeaders": {
+///         "hsts": true,
+///         "csp": "default-src 'self'",
+///         "frameOptions": "DENY",
+///         "noSniff": true
+///     },
+///     "maxBodySize": 1048576,
+///     "headerReadTimeoutMs": 10000,
+///     "requestTimeoutMs": 60000,
+///     "maxHeaderSize": 65536,
+///     "keepAlive": true,
+///     "maxConnections": 1024,
+///     "listen": {
+///         "unix": "/tmp/webx.sock"
+///     },
+///     "redactHeaders": ["x-api-key"],
+///     "logSecrets": false,
+///     "metrics": {
+///         "enabled": true,
+///         "path": "/metrics"
+///     },
+///     "health": {
+///         "enabled": true,
+///         "livenessPath": "/healthz",
+///         "readinessPath": "/readyz"
+///     },
+///     "envAllowlist": ["API_URL", "FEATURE_FLAGS"],
+///     "fetch": {
+///         "timeoutMs": 10000,
+///         "allowedHosts": ["api.example.com"]
+///     },
+///     "session": {
+///         "secret": "a random secret of at least 32 characters",
+///         "maxAge": "1d"
+///     },
+///     "csrf": {
+///         "enabled": true,
+///         "exempt": ["/webhooks/*"]
+///     },
+///     "graphql": {
+///         "path": "/graphql",
+///         "query": {
+///             "todos": "Todo[]"
+///         }
+///     },
+///     "profiles": {
+///         "prod": {
+///             "port": 80,
+///             "host": "0.0.0.0"
+///         }
 ///     }
 /// }
 /// ```
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ProjectConfig {
     pub name: String,
@@ -61,6 +129,8 @@ pub struct ProjectConfig {
     pub description: Option<String>,
     pub port: u16,
     pub host: Option<String>,
+    /// The directory of the WebX modules, relative to the project root.
+    /// Defaults to the project root.
     pub src: Option<PathBuf>,
     pub log_level: Option<String>,
     pub migrations_path: Option<PathBuf>,
@@ -68,17 +138,335 @@ pub struct ProjectConfig {
     pub rate_limit: Option<RateLimitConfig>,
     pub database: Option<DatabaseConfig>,
     pub cache: Option<CacheConfig>,
+    pub error_pages: Option<ErrorPagesConfig>,
+    /// The icon served at `/favicon.ico`, relative to the project root.
+    pub favicon: Option<PathBuf>,
+    pub allow_delete_body: Option<bool>,
+    /// Serve `TRACE` and `CONNECT` routes in production, default: `false`.
+    pub allow_trace_connect: Option<bool>,
+    pub watch_debounce_ms: Option<u64>,
+    pub watch_extensions: Option<Vec<String>>,
+    pub trust_proxy: Option<bool>,
+    /// The number of reverse proxies in front of the server if `trust_proxy` is set, default: 1.
+    /// The client is the forwarded address added by the outermost of them.
+    pub trusted_proxies: Option<usize>,
+    pub trailing_slash: Option<TrailingSlashPolicy>,
+    pub case_sensitive_routes: Option<bool>,
+    /// The path all routes are served under, e.g. `/app` behind a proxy.
+    /// Defaults to the root path. The metrics and health endpoints are not affected.
+    pub base_path: Option<String>,
+    pub security_headers: Option<SecurityHeadersConfig>,
+    pub max_body_size: Option<usize>,
+    pub header_read_timeout_ms: Option<u64>,
+    pub request_timeout_ms: Option<u64>,
+    pub max_header_size: Option<usize>,
+    pub keep_alive: Option<bool>,
+    /// The most connections served at once, further connections wait to be accepted.
+    pub max_connections: Option<usize>,
+    /// The number of threads serving connections, defaults to the number of CPU cores.
+    pub worker_threads: Option<usize>,
+    /// The number of threads executing routes, each owning a subset of the modules.
+    /// Defaults to a single runtime thread.
+    pub runtime_workers: Option<usize>,
+    pub listen: Option<ListenConfig>,
+    /// Headers masked in debug logs, in addition to `DEFAULT_REDACTED_HEADERS`.
+    pub redact_headers: Option<Vec<String>>,
+    pub log_secrets: Option<bool>,
+    pub metrics: Option<MetricsConfig>,
+    pub health: Option<HealthConfig>,
+    /// The environment variables readable with `env()` from handlers.
+    /// All variables are readable if omitted.
+    pub env_allowlist: Option<Vec<String>>,
+    pub fetch: Option<FetchConfig>,
+    pub session: Option<SessionConfig>,
+    pub csrf: Option<CsrfConfig>,
+    pub graphql: Option<GraphQLConfig>,
+    /// Exit in production when the global scope of a module throws.
+    /// Otherwise the routes of the module are skipped and the other modules are served.
+    pub exit_on_module_error: Option<bool>,
+    /// Named sets of fields merged over the rest of the configuration when selected.
+    pub profiles: Option<BTreeMap<String, serde_json::Value>>,
+    /// The name of the profile merged into the configuration, if any.
+    #[serde(skip)]
+    pub active_profile: Option<String>,
+}
+
+/// The minimum length of the secret session cookies are signed with.
+pub const MIN_SESSION_SECRET_LENGTH: usize = 32;
+
+impl ProjectConfig {
+    /// A configuration with the given name and defaults for everything else,
+    /// such as for running a single module file without a project.
+    pub fn new(name: String) -> Self {
+        ProjectConfig {
+            name,
+            version: "1.0.0".to_string(),
+            description: None,
+            port: 8080,
+            host: None,
+            src: None,
+            log_level: None,
+            migrations_path: None,
+            cors: None,
+            rate_limit: None,
+            database: None,
+            cache: None,
+            error_pages: None,
+            favicon: None,
+            allow_delete_body: None,
+            allow_trace_connect: None,
+            watch_debounce_ms: None,
+            watch_extensions: None,
+            trust_proxy: None,
+            trusted_proxies: None,
+            trailing_slash: None,
+            case_sensitive_routes: None,
+            base_path: None,
+            security_headers: None,
+            max_body_size: None,
+            header_read_timeout_ms: None,
+            request_timeout_ms: None,
+            max_header_size: None,
+            keep_alive: None,
+            max_connections: None,
+            worker_threads: None,
+            runtime_workers: None,
+            listen: None,
+            redact_headers: None,
+            log_secrets: None,
+            metrics: None,
+            health: None,
+            env_allowlist: None,
+            fetch: None,
+            session: None,
+            csrf: None,
+            graphql: None,
+            exit_on_module_error: None,
+            profiles: None,
+            active_profile: None,
+        }
+    }
+
+    /// Deep-merge the profile `name` over the configuration.
+    /// Objects are merged field by field, all other values of the profile replace the base value.
+    /// Fields not set in the profile keep their base value.
+    ///
+    /// ## Errors
+    /// If the profile does not exist, or the merged configuration is invalid.
+    pub fn apply_profile(&mut self, name: &str) -> Result<(), String> {
+        let profiles = self.profiles.as_ref();
+        let Some(profile) = profiles.and_then(|profiles| profiles.get(name)).cloned() else {
+            let available = profiles
+                .map(|profiles| profiles.keys().cloned().collect::<Vec<_>>())
+                .unwrap_or_default();
+            if available.is_empty() {
+                return Err(format!(
+                    "Profile '{}' does not exist, no profiles are defined",
+                    name
+                ));
+            }
+            return Err(format!(
+                "Profile '{}' does not exist, expected one of: {}",
+                name,
+                available.join(", ")
+            ));
+        };
+        let mut config = serde_json::to_value(&*self).unwrap();
+        merge_json(&mut config, profile);
+        let mut merged = serde_json::from_value::<ProjectConfig>(config)
+            .map_err(|err| format!("Invalid profile '{}': {}", name, err))?;
+        merged.active_profile = Some(name.to_string());
+        *self = merged;
+        Ok(())
+    }
+
+    /// The directory of the WebX modules of the project at `root`.
+    pub fn source_root(&self, root: &Path) -> PathBuf {
+        match &self.src {
+            Some(src) => root.join(src),
+            None => root.to_path_buf(),
+        }
+    }
+
+    /// Check the invariants of the configuration that can not be expressed
+    /// by its types, relative to the project `root` directory.
+    ///
+    /// ## Errors
+    /// A human-readable message for every invalid field.
+    pub fn validate(&self, root: &Path) -> Result<(), Vec<String>> {
+        let mut errors = vec![];
+        if self.port == 0 {
+            errors.push("\"port\" must be between 1 and 65535, got 0".to_string());
+        }
+        if let Some(unix) = self.listen.as_ref().and_then(|listen| listen.unix.as_ref()) {
+            if unix.as_os_str().is_empty() {
+                errors.push("\"listen.unix\" must not be empty".to_string());
+            }
+            if self.host.is_some() {
+                errors.push("\"listen.unix\" can not be combined with \"host\"".to_string());
+            }
+        }
+        if self.trusted_proxies == Some(0) {
+            errors.push("\"trustedProxies\" must be at least 1, got 0".to_string());
+        }
+        if self.max_connections == Some(0) {
+            errors.push("\"maxConnections\" must be at least 1, got 0".to_string());
+        }
+        if self.worker_threads == Some(0) {
+            errors.push("\"workerThreads\" must be at least 1, got 0".to_string());
+        }
+        if self.runtime_workers == Some(0) {
+            errors.push("\"runtimeWorkers\" must be at least 1, got 0".to_string());
+        }
+        if self
+            .host
+            .as_ref()
+            .is_some_and(|host| host.trim().is_empty())
+        {
+            errors.push("\"host\" must not be empty".to_string());
+        }
+        if let Some(src) = self
+            .src
+            .as_ref()
+            .filter(|_| !self.source_root(root).is_dir())
+        {
+            errors.push(format!(
+                "\"src\" directory '{}' does not exist",
+                src.display()
+            ));
+        }
+        if let Some(favicon) = self
+            .favicon
+            .as_ref()
+            .filter(|favicon| !root.join(favicon).is_file())
+        {
+            errors.push(format!(
+                "\"favicon\" file '{}' does not exist",
+                favicon.display()
+            ));
+        }
+        if let Some(base_path) = self.base_path.as_ref().filter(|base_path| {
+            !base_path.starts_with('/') || base_path.contains(['(', ')', '?', '#'])
+        }) {
+            errors.push(format!(
+                "\"basePath\" must be a literal path starting with '/', such as /app, got '{}'",
+                base_path
+            ));
+        }
+        if let Some(cache) = &self.cache {
+            if !["memory", "redis", "none"].contains(&cache.strategy.as_str()) {
+                errors.push(format!(
+                    "\"cache.strategy\" must be one of memory, redis, or none, got '{}'",
+                    cache.strategy
+                ));
+            }
+            if parse_duration(&cache.duration).is_none() {
+                errors.push(format!(
+                    "\"cache.duration\" must be a number followed by s, m, h, or d (e.g. 10m), got '{}'",
+                    cache.duration
+                ));
+            }
+        }
+        if let Some(cors) = &self.cors {
+            if !is_valid_origin(&cors.allow_origin) {
+                errors.push(format!(
+                    "\"cors.allowOrigin\" must be '*' or an origin such as https://example.com, got '{}'",
+                    cors.allow_origin
+                ));
+            }
+        }
+        if let Some(session) = &self.session {
+            if session.secret.len() < MIN_SESSION_SECRET_LENGTH {
+                errors.push(format!(
+                    "\"session.secret\" must be at least {} characters long",
+                    MIN_SESSION_SECRET_LENGTH
+                ));
+            }
+            if let Some(max_age) = session
+                .max_age
+                .as_ref()
+                .filter(|max_age| parse_duration(max_age).is_none())
+            {
+                errors.push(format!(
+                    "\"session.maxAge\" must be a number followed by s, m, h, or d (e.g. 1d), got '{}'",
+                    max_age
+                ));
+            }
+        }
+        if self.csrf.as_ref().is_some_and(|csrf| csrf.enabled) && self.session.is_none() {
+            errors.push("\"csrf\" requires \"session\" to be configured".to_string());
+        }
+        if let Some(graphql) = &self.graphql {
+            if let Some(path) = graphql.path.as_ref().filter(|path| !path.starts_with('/')) {
+                errors.push(format!(
+                    "\"graphql.path\" must start with '/', got '{}'",
+                    path
+                ));
+            }
+            for field in graphql.query.iter().flat_map(|query| query.keys()) {
+                if !is_graphql_name(field) {
+                    errors.push(format!(
+                        "\"graphql.query\" field '{}' must be a valid GraphQL name",
+                        field
+                    ));
+                }
+            }
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+/// Parse a duration such as `10s`, `10m`, `1h`, or `1d`.
+pub fn parse_duration(duration: &str) -> Option<Duration> {
+    let unit = duration.chars().last()?;
+    let value: u64 = duration[..duration.len() - unit.len_utf8()].parse().ok()?;
+    let seconds = match unit {
+        's' => 1,
+        'm' => 60,
+        'h' => 60 * 60,
+        'd' => 24 * 60 * 60,
+        _ => return None,
+    };
+    Some(Duration::from_secs(value.checked_mul(seconds)?))
+}
+
+/// Whether a name is a valid GraphQL name, such as `todos` or `_count`.
+fn is_graphql_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Whether a value is allowed in the `Access-Control-Allow-Origin` header:
+/// either `*`, or a `http(s)://host[:port]` origin without a path.
+fn is_valid_origin(origin: &str) -> bool {
+    if origin == "*" {
+        return true;
+    }
+    let Ok(uri) = origin.parse::<hyper::Uri>() else {
+        return false;
+    };
+    matches!(uri.scheme_str(), Some("http" | "https"))
+        && uri.authority().is_some_and(|a| !a.host().is_empty())
+        && !origin.ends_with('/')
+        && uri.path_and_query().map_or(true, |p| p.as_str() == "/")
 }
 
 /// The configuration for the CORS middleware.
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct CorsConfig {
     pub allow_origin: String,
 }
 
 /// The configuration for the rate limit middleware.
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct RateLimitConfig {
     pub window_ms: u64,
@@ -86,7 +474,7 @@ pub struct RateLimitConfig {
 }
 
 /// The configuration for the database.
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct DatabaseConfig {
     pub database_type: String,
@@ -98,45 +486,279 @@ pub struct DatabaseConfig {
 }
 
 /// The configuration for the cache.
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct CacheConfig {
     pub strategy: String,
     pub duration: String,
 }
 
+/// Custom error page templates relative to the project root.
+///
+/// ## Placeholders
+/// - `{{method}}` and `{{url}}` of the requested resource, the URL only in development mode (404)
+/// - `{{message}}` of the error, only in development mode (500)
+/// - `{{server}}` banner
+///
+/// The values are HTML-escaped.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ErrorPagesConfig {
+    #[serde(rename = "404")]
+    pub not_found: Option<PathBuf>,
+    #[serde(rename = "500")]
+    pub internal_server_error: Option<PathBuf>,
+}
+
+/// Security headers added to every response.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SecurityHeadersConfig {
+    /// `Strict-Transport-Security`, only sent over HTTPS.
+    pub hsts: Option<bool>,
+    /// `Content-Security-Policy`
+    pub csp: Option<String>,
+    /// `X-Frame-Options`
+    pub frame_options: Option<String>,
+    /// `X-Content-Type-Options: nosniff`
+    pub no_sniff: Option<bool>,
+}
+
+/// The configuration for the Prometheus metrics endpoint.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MetricsConfig {
+    pub enabled: bool,
+    pub path: Option<String>,
+}
+
+/// The configuration for the liveness and readiness probes.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HealthConfig {
+    pub enabled: bool,
+    pub liveness_path: Option<String>,
+    pub readiness_path: Option<String>,
+}
+
+/// The configuration of outbound requests with `fetch()` from handlers.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FetchConfig {
+    pub timeout_ms: Option<u64>,
+    /// The only hosts requests can be sent to, all hosts are allowed if omitted.
+    pub allowed_hosts: Option<Vec<String>>,
+}
+
+/// The configuration of server-side sessions.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionConfig {
+    /// The key the session cookies are signed with.
+    pub secret: String,
+    /// How long sessions last after they were last written, e.g. `1h`.
+    pub max_age: Option<String>,
+}
+
+/// Where the server listens instead of the TCP host and port.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ListenConfig {
+    /// The path of a Unix domain socket, e.g. for a reverse proxy on the same host.
+    /// The `port` is ignored while listening on a Unix socket.
+    pub unix: Option<PathBuf>,
+}
+
+/// The configuration of CSRF protection, which requires sessions.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CsrfConfig {
+    pub enabled: bool,
+    /// Paths that are not protected, such as webhooks.
+    /// A trailing `*` matches any suffix.
+    pub exempt: Option<Vec<String>>,
+}
+
+/// The configuration of the GraphQL endpoint.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GraphQLConfig {
+    pub path: Option<String>,
+    /// The fields of the root `Query` type with their types, e.g. `"todos": "Todo[]"`.
+    /// Each field is resolved by the handler of the same name.
+    pub query: Option<BTreeMap<String, String>>,
+}
+
+/// How request paths with a trailing slash are matched against routes.
+/// The canonical form of a path has no trailing slash, except for the root `/`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TrailingSlashPolicy {
+    /// `/about` and `/about/` match the same route.
+    #[default]
+    Ignore,
+    /// `/about/` only matches a route declared as `/about/`, and `/about` only one without.
+    Strict,
+    /// `/about/` is permanently redirected to `/about`.
+    Redirect,
+}
+
+/// An error while loading the project configuration.
+#[derive(Debug)]
+pub enum ProjectConfigError {
+    /// The configuration file does not exist.
+    NotFound(PathBuf),
+    /// The configuration file exists, but could not be read.
+    Io(PathBuf, std::io::Error),
+    /// The configuration is not valid JSON, or does not match the schema.
+    Invalid {
+        path: PathBuf,
+        line: usize,
+        column: usize,
+        message: String,
+    },
+}
+
+impl std::fmt::Display for ProjectConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProjectConfigError::NotFound(path) => write!(
+                f,
+                "WebX configuration file '{}' was not found",
+                path.display()
+            ),
+            ProjectConfigError::Io(path, err) => write!(
+                f,
+                "Failed to open WebX configuration file '{}' due to: {}",
+                path.display(),
+                err
+            ),
+            ProjectConfigError::Invalid {
+                path,
+                line,
+                column,
+                message,
+            } => write!(
+                f,
+                "Invalid WebX configuration at {}:{}:{}: {}",
+                path.display(),
+                line,
+                column,
+                message
+            ),
+        }
+    }
+}
+
+/// Recursively merge the fields of the `overlay` object into the `base` object.
+/// Values that are not both objects are replaced by the overlay.
+fn merge_json(base: &mut serde_json::Value, overlay: serde_json::Value) {
+    match (base, overlay) {
+        (serde_json::Value::Object(base), serde_json::Value::Object(overlay)) => {
+            for (key, value) in overlay {
+                match base.get_mut(&key) {
+                    Some(existing) => merge_json(existing, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, overlay) => *base = overlay,
+    }
+}
+
+/// Read and parse the project configuration from a given filepath.
+///
+/// ## Errors
+/// Distinguishes a missing file from an unreadable file and from invalid JSON,
+/// which is reported with the line and column of the first error.
+pub fn read_project_config(config_file: &Path) -> Result<ProjectConfig, ProjectConfigError> {
+    let txt = fs::read_to_string(config_file).map_err(|err| match err.kind() {
+        std::io::ErrorKind::NotFound => ProjectConfigError::NotFound(config_file.to_path_buf()),
+        _ => ProjectConfigError::Io(config_file.to_path_buf(), err),
+    })?;
+    serde_json::from_str::<ProjectConfig>(&txt).map_err(|err| {
+        // Strip the location serde_json appends, as it is reported separately.
+        let location = format!(" at line {} column {}", err.line(), err.column());
+        let message = err.to_string();
+        ProjectConfigError::Invalid {
+            path: config_file.to_path_buf(),
+            line: err.line(),
+            column: err.column(),
+            message: message
+                .strip_suffix(&location)
+                .unwrap_or(&message)
+                .to_string(),
+        }
+    })
+}
+
 /// Parse the project configuration from a given filepath.
+/// Exits the program with a hint on how to fix the configuration if it fails.
 ///
 /// ## Arguments
 /// - `config` - The path to the project configuration file.
+/// - `profile` - The name of a profile to merge over the configuration.
 ///
 /// ## Returns
 /// The project configuration.
-pub fn load_project_config(config_file: &PathBuf) -> ProjectConfig {
-    let Ok(txt) = fs::read_to_string(config_file) else {
-        exit_error_hint(
-            &format!(
-                "Failed to open WebX configuration file '{}'",
-                config_file.display()
-            ),
+pub fn load_project_config(config_file: &PathBuf, profile: Option<&str>) -> ProjectConfig {
+    match read_project_config(config_file) {
+        Ok(mut config) => {
+            if let Some(profile) = profile {
+                if let Err(err) = config.apply_profile(profile) {
+                    exit_error_hint(
+                        &err,
+                        &["Define the profile in the \"profiles\" of the configuration"],
+                        ERROR_PROJECT,
+                        DateTimeSpecifier::None,
+                    )
+                }
+            }
+            config
+        }
+        Err(err @ ProjectConfigError::NotFound(_)) => exit_error_hint(
+            &err.to_string(),
             &[
-                "Have you created a WebX project?",
+                "Create a new project with `webx new <name>`",
                 "Are you in the project root directory?",
             ],
             ERROR_PROJECT,
             DateTimeSpecifier::None,
-        );
-    };
-    match serde_json::from_str::<ProjectConfig>(&txt) {
-        Ok(config) => config,
-        Err(err) => exit_error(
-            format!("Failed to parse WebX configuration due to: {}", err),
+        ),
+        Err(err @ ProjectConfigError::Io(..)) => exit_error_hint(
+            &err.to_string(),
+            &["Check that the file is readable by the current user"],
+            ERROR_PROJECT,
+            DateTimeSpecifier::None,
+        ),
+        Err(err @ ProjectConfigError::Invalid { .. }) => exit_error_hint(
+            &err.to_string(),
+            &[
+                "Fix the configuration at the given line and column",
+                "Add a \"$schema\" field for editor validation, see https://raw.githubusercontent.com/webx-net/webx/main/schemas/webx.config.schema.json",
+            ],
             ERROR_PROJECT,
             DateTimeSpecifier::None,
         ),
     }
 }
 
+/// Reload the project configuration from a given filepath while running,
+/// with the given profile merged over it.
+/// Unlike `load_project_config`, failures are returned to the caller
+/// instead of exiting the program.
+pub fn reload_project_config(
+    config_file: &Path,
+    profile: Option<&str>,
+) -> Result<ProjectConfig, String> {
+    let mut config = read_project_config(config_file).map_err(|err| err.to_string())?;
+    if let Some(profile) = profile {
+        config.apply_profile(profile)?;
+    }
+    Ok(config)
+}
+
 /// Recursively find all `.webx` or `.wx` files in a given directory.
 ///
 /// ## Arguments
@@ -163,11 +785,10 @@ pub fn locate_files(src: &Path) -> Vec<PathBuf> {
     let mut files = Vec::new();
     for entry in fs::read_dir(src).unwrap() {
         let path = entry.unwrap().path();
-        let cmp_ext = |ext: &str| path.extension() == Some(OsStr::new(ext));
         if path.is_dir() {
             // Recursively find all .webx files in the directory.
             files.append(&mut locate_files(&path));
-        } else if cmp_ext("webx") || cmp_ext("wx") {
+        } else if is_module_path(&path) {
             // Add the WebX module to the list of files.
             files.push(path.canonicalize().unwrap());
         }
@@ -184,7 +805,12 @@ pub fn locate_files(src: &Path) -> Vec<PathBuf> {
 /// This function does not perform any static analysis on the modules
 /// such as detecting circular dependencies.
 pub fn load_modules(src: &Path) -> Vec<WXModule> {
-    let files = locate_files(src);
+    load_module_files(&locate_files(src))
+}
+
+/// Load and parse the given WebX module files.
+/// If any of the files fail to parse, an error is reported and the program exits.
+pub fn load_module_files(files: &[PathBuf]) -> Vec<WXModule> {
     let webx_modules = files.iter().map(parse_webx_file).collect::<Vec<_>>();
     let errors = webx_modules
         .iter()
@@ -224,6 +850,7 @@ pub fn load_modules(src: &Path) -> Vec<WXModule> {
 /// ## Arguments
 /// - `root_dir` - The path to the root directory of the project.
 /// - `override_existing` - Whether or not to override an existing project.
+/// - `extension` - The extension of the module files, `webx` or `wx`, see `MODULE_EXTENSIONS`.
 ///
 /// ## File Structure
 /// The following files are added to the root directory:
@@ -235,17 +862,35 @@ pub fn load_modules(src: &Path) -> Vec<WXModule> {
 /// ```
 /// The `webx.config.json` file contains the default configuration for the project.
 /// The `webx/` directory contains all of the WebX source files.
-/// The `index.webx` file contains some default example code.
+/// The `index.webx` (or `index.wx`) file contains some default example code.
 ///
 /// ## Warning
 /// If a `webx.config.json` file already exists in the root directory,
 /// and `override_existing` is set to `false`, then a warning is printed and
 /// the function returns.
-pub fn create_new_project(mode: WXMode, name: String, root_dir: &Path, override_existing: bool) {
+pub fn create_new_project(
+    mode: WXMode,
+    name: String,
+    root_dir: &Path,
+    override_existing: bool,
+    extension: &str,
+) {
+    let extension = extension.trim_start_matches('.');
+    if !MODULE_EXTENSIONS.contains(&extension) {
+        warning(
+            mode,
+            format!(
+                "Unknown module extension '{}', expected one of: {}",
+                extension,
+                MODULE_EXTENSIONS.join(", ")
+            ),
+        );
+        return;
+    }
     let root_dir = root_dir.to_path_buf().join(&name);
     let config_file = root_dir.join("webx.config.json");
     let src_dir = root_dir.join("webx");
-    let index_file = src_dir.join("index.webx");
+    let index_file = src_dir.join(format!("index.{}", extension));
 
     if config_file.exists() && !override_existing {
         warning(
@@ -256,20 +901,12 @@ pub fn create_new_project(mode: WXMode, name: String, root_dir: &Path, override_
     }
 
     let default_config = ProjectConfig {
-        name: format!("My {} WebX Project", name),
-        version: "1.0.0".to_string(),
         description: Some("An example WebX project.".to_string()),
-        port: 8080,
-        host: None,
         src: Some(PathBuf::from("./webx/")),
-        log_level: None,
-        migrations_path: None,
         cors: Some(CorsConfig {
             allow_origin: "*".to_string(),
         }),
-        rate_limit: None,
-        database: None,
-        cache: None,
+        ..ProjectConfig::new(format!("My {} WebX Project", name))
     };
 
     const DEFAULT_INDEX_FILE_CONTENTS: &str = r#"// This is an example WebX todo app project.
@@ -293,7 +930,7 @@ handler renderAllTodos(todos: Todo[])
 (<ul class="todos">{todos.map(renderTodo)}</ul>)
 
 handler auth(user_id: Int) {
-    if (user_id === 0) return error("You are not logged in.");
+    if (user_id === 0) return error("You are not logged in.", 401);
 }
 
 get about/ (<div>
@@ -309,7 +946,7 @@ location /todo {
     // { title: "My Todo" }
     // returns HTML
     post (user_id: Int)/add json(title: String) -> auth(user_id) {
-        const newTodo = { title, completed: false };
+        const newTodo = { title, completed: false, createdAt: new Date() };
         todos.push(newTodo);
     } -> renderTodo(newTodo)
 
@@ -321,7 +958,7 @@ location /todo {
         if (todo) {
             todo.completed = !todo.completed;
         } else {
-            return error("Todo not found.");
+            return error("Todo not found.", 404);
         }
     } -> renderTodo(todo)
 }
@@ -0,0 +1,683 @@
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+    use std::time::Duration;
+
+    use crate::{
+        file::{
+            artifact::{read_artifact, source_hash, write_artifact, WXArtifactError},
+            parser::{parse_webx_source, WebXParserError},
+            project::{
+                create_new_project, load_modules, parse_duration, read_project_config, CacheConfig,
+                CorsConfig, GraphQLConfig, ListenConfig, ProjectConfig, ProjectConfigError,
+                SessionConfig,
+            },
+            webx::{is_module_path, websocket_method, WXBodyType, WXModule},
+        },
+        runner::{resolve_project_config_file_path, WXMode},
+    };
+
+    fn parse(name: &str, source: &str) -> Result<WXModule, WebXParserError> {
+        parse_webx_source(&PathBuf::from(format!("{}.webx", name)), source)
+    }
+
+    #[test]
+    fn test_parse_error_handler() {
+        let module = parse(
+            "error_handler",
+            "on error(e) {\n    return `<h1>${e.code}</h1>`;\n}\nget /a (<p>a</p>)\n",
+        )
+        .unwrap();
+        let handler = module.scope.error_handler.expect("Missing error handler");
+        assert_eq!(handler.binding, "e");
+        assert!(handler.body.body_type == WXBodyType::Ts);
+        assert_eq!(module.scope.routes.len(), 1);
+    }
+
+    #[test]
+    fn test_parse_error_handler_fallback() {
+        let module = parse("error_handler_none", "get /a (<p>a</p>)\n").unwrap();
+        assert!(module.scope.error_handler.is_none());
+        let nested = parse(
+            "error_handler_nested",
+            "location /a {\n    on error(e) (<p>error</p>)\n}\n",
+        );
+        assert!(matches!(nested, Err(WebXParserError::SyntaxError(..))));
+    }
+
+    #[test]
+    fn test_parse_websocket_route() {
+        let module = parse(
+            "websocket",
+            "get /chat (<p>chat</p>)\nws /chat {\n    socket.onMessage((m) => socket.send(m));\n}\n",
+        )
+        .unwrap();
+        assert_eq!(module.scope.routes.len(), 2);
+        let route = &module.scope.routes[1];
+        assert_eq!(route.method, websocket_method());
+        assert_eq!(route.path.to_string(), "/chat");
+        assert!(route.body.as_ref().unwrap().body_type == WXBodyType::Ts);
+    }
+
+    #[test]
+    fn test_parse_middleware() {
+        let module = parse(
+            "middleware",
+            "middleware auth -> authenticate(user_id): user, logRequest(user)\nmodel User {\n    name: string\n}\nlocation /admin {\n    use logRequests, auth;\n    get /a (<p>a</p>)\n}\n",
+        )
+        .unwrap();
+        let middleware = &module.scope.middleware[0];
+        assert_eq!(middleware.name, "auth");
+        assert_eq!(middleware.handlers.len(), 2);
+        assert_eq!(middleware.handlers[0].output.as_deref(), Some("user"));
+        assert_eq!(module.scope.models.len(), 1);
+        assert_eq!(module.scope.scopes[0].uses, ["logRequests", "auth"]);
+        assert_eq!(module.scope.scopes[0].routes.len(), 1);
+        assert!(parse("middleware_empty", "middleware auth\nget /a {}\n").is_err());
+    }
+
+    #[test]
+    fn test_parse_cache_annotation() {
+        let module = parse(
+            "cache",
+            "get /assets/(name: String) cache(1h) (<p>Asset</p>)\n\
+             post /todos cache(30s) json(text: String) {}\n\
+             post /notes json(text: String) cache(1d) {}\n\
+             get /live (<p>Live</p>)\n",
+        )
+        .unwrap();
+        let routes = &module.scope.routes;
+        assert_eq!(routes[0].cache, Some(Duration::from_secs(3600)));
+        assert_eq!(routes[1].cache, Some(Duration::from_secs(30)));
+        assert!(routes[1].body_format.is_some());
+        assert_eq!(routes[2].cache, Some(Duration::from_secs(86400)));
+        assert!(routes[2].body_format.is_some());
+        assert_eq!(routes[3].cache, None);
+        let Err(WebXParserError::SyntaxError(message, _)) =
+            parse("cache_invalid", "get /a cache(1 hour) (<p>A</p>)\n")
+        else {
+            panic!("Expected a syntax error");
+        };
+        assert!(message.contains("found '1 hour' while parsing a cache duration"));
+        assert!(parse("cache_twice", "get /a cache(1h) cache(2h) (<p>A</p>)\n").is_err());
+    }
+
+    #[test]
+    fn test_parse_route_priority() {
+        let module = parse(
+            "priority",
+            "get /(slug: String) priority(-1) cache(1h) (<p>Page</p>)\n\
+             post /a priority( 10 ) json(text: String) {}\n\
+             get /b {}\n",
+        )
+        .unwrap();
+        let routes = &module.scope.routes;
+        assert_eq!(routes[0].priority, -1);
+        assert_eq!(routes[0].cache, Some(Duration::from_secs(3600)));
+        assert_eq!(routes[1].priority, 10);
+        assert!(routes[1].body_format.is_some());
+        assert_eq!(routes[2].priority, 0);
+        let Err(WebXParserError::SyntaxError(message, _)) =
+            parse("priority_invalid", "get /a priority(high) (<p>A</p>)\n")
+        else {
+            panic!("Expected a syntax error");
+        };
+        assert!(message.contains("found 'high' while parsing a route priority"));
+        assert!(parse("priority_twice", "get /a priority(1) priority(2) {}\n").is_err());
+    }
+
+    #[test]
+    fn test_parse_duplicate_names() {
+        let Err(WebXParserError::SyntaxError(message, _)) =
+            parse("model", "model User {\n    id: Int,\n    id: String,\n}\n")
+        else {
+            panic!("Expected a syntax error");
+        };
+        assert!(message.contains("duplicate name 'id' while parsing a model statement"));
+        assert!(message.ends_with("at line 3, column 5"));
+        let Err(WebXParserError::SyntaxError(message, _)) = parse(
+            "handler",
+            "handler auth(user_id: Int, user_id: Int) {\n    return user_id;\n}\n",
+        ) else {
+            panic!("Expected a syntax error");
+        };
+        assert!(message.contains("duplicate name 'user_id' while parsing a handler statement"));
+        assert!(parse("body", "post /a json(text: String, text: String) {}\n").is_err());
+        assert!(parse("unique", "model User { id: Int, name: String }\n").is_ok());
+    }
+
+    #[test]
+    fn test_parse_route_methods() {
+        let methods = [
+            ("get", hyper::Method::GET),
+            ("post", hyper::Method::POST),
+            ("put", hyper::Method::PUT),
+            ("patch", hyper::Method::PATCH),
+            ("delete", hyper::Method::DELETE),
+            ("head", hyper::Method::HEAD),
+            ("options", hyper::Method::OPTIONS),
+            ("connect", hyper::Method::CONNECT),
+            ("trace", hyper::Method::TRACE),
+            ("ws", websocket_method()),
+        ];
+        for (keyword, method) in methods {
+            let source = format!("{} /a {{}}\n", keyword);
+            let module = parse(keyword, &source).unwrap();
+            assert_eq!(module.scope.routes[0].method, method, "{}", keyword);
+        }
+    }
+
+    #[test]
+    fn test_parse_unknown_method() {
+        let Err(WebXParserError::SyntaxError(message, _)) = parse("unknown", "\nfetch /a {}\n")
+        else {
+            panic!("Expected a syntax error");
+        };
+        assert!(message.contains("but found 'fetch' while parsing a scope"));
+        assert!(
+            message.contains("get, post, put, patch, delete, head, options, connect, trace, or ws")
+        );
+        assert!(message.ends_with("at line 2, column 1"));
+        // Keywords are matched as whole words.
+        assert!(parse("prefix", "gets /a {}\n").is_err());
+        assert!(parse("suffix", "handlers /a {}\n").is_err());
+    }
+
+    #[test]
+    fn test_parse_comments_in_routes() {
+        let source = r#"
+model /* users */ User // a user
+{
+    name: String, // the display name
+    /* the age */ age: Number,
+}
+
+handler greet(/* who */ name: String) (<p>Hi</p>)
+
+get /users/(id: /* numeric */ Number)// one user
+    json(a: String, /* b: String, */ c: Number) // the body
+    -> greet(id) /* then */ (<p>User</p>)
+
+get /files/* (<p>File</p>)
+"#;
+        let module = parse("comments", source).unwrap();
+        let model = &module.scope.models[0];
+        assert_eq!(model.name, "User");
+        assert_eq!(format!("{:?}", model.fields), "[name: String, age: Number]");
+        assert_eq!(module.scope.handlers[0].params.len(), 1);
+        let route = &module.scope.routes[0];
+        assert_eq!(route.path.to_string(), "/users/(id: Number)");
+        assert_eq!(
+            route.body_format.as_ref().unwrap().to_string(),
+            "json(a: String, c: Number)"
+        );
+        assert_eq!(route.pre_handlers.len(), 1);
+        assert_eq!(route.info.line, 10);
+        assert_eq!(module.scope.routes[1].path.to_string(), "/files/(*)");
+        assert_eq!(module.scope.routes[1].info.line, 14);
+    }
+
+    #[test]
+    fn test_comment_position_tracking() {
+        let Err(WebXParserError::SyntaxError(message, _)) =
+            parse("position", "get /a /* one\ntwo */ json(a: String,, b) {}\n")
+        else {
+            panic!("Expected a syntax error");
+        };
+        assert!(message.ends_with("at line 2, column 23"), "{}", message);
+    }
+
+    #[test]
+    fn test_error_positions() {
+        // Errors point at the offending character, columns count characters, not bytes.
+        let cases = [
+            ("get /a {}\n  %\n", "at line 2, column 3"),
+            ("get /a {} /* é */ %\n", "at line 1, column 19"),
+            (
+                "model Ünïcode { naïve: String, naïve: Int }\n",
+                "at line 1, column 32",
+            ),
+            ("\tget /a json(a: String,, b) {}\n", "at line 1, column 24"),
+            ("include \"a.webx\" %\n", "at line 1, column 18"),
+        ];
+        for (source, position) in cases {
+            let Err(WebXParserError::SyntaxError(message, _)) = parse("position", source) else {
+                panic!("Expected a syntax error for {:?}", source);
+            };
+            assert!(message.ends_with(position), "{:?}: {}", source, message);
+        }
+        let module = parse("unicode", "get /café (<p>Crème brûlée</p>)\n").unwrap();
+        let route = &module.scope.routes[0];
+        assert_eq!(route.path.to_string(), "/café");
+        assert_eq!(route.body.as_ref().unwrap().body, "<p>Crème brûlée</p>");
+    }
+
+    #[test]
+    fn test_parse_raw_string() {
+        let source = r##"get /about """
+<h1>"About" \n {us}</h1>
+  <p>(</p>
+"""
+get /greet -> greet("""Hi, "you"
+!""", 1) {}
+"##;
+        let module = parse("raw", source).unwrap();
+        let body = module.scope.routes[0].body.as_ref().unwrap();
+        assert!(body.body_type == WXBodyType::Raw);
+        assert_eq!(body.body, "\n<h1>\"About\" \\n {us}</h1>\n  <p>(</p>\n");
+        let route = &module.scope.routes[1];
+        assert_eq!(route.info.line, 5);
+        assert_eq!(route.pre_handlers[0].args, r#""Hi, \"you\"\n!", 1"#);
+        let Err(WebXParserError::SyntaxError(message, _)) =
+            parse("raw_unterminated", "get /a \"\"\"\nno end\n")
+        else {
+            panic!("Expected a syntax error");
+        };
+        assert!(message.contains("EOF while parsing a raw string literal at line 3"));
+    }
+
+    #[test]
+    fn test_parse_include_list() {
+        let module = parse(
+            "includes",
+            "include \"a.webx\", \"b.ts\",\n    \"c.js\";\ninclude \"d.webx\"\n",
+        )
+        .unwrap();
+        assert_eq!(module.scope.includes, ["a.webx", "b.ts", "c.js", "d.webx"]);
+        assert!(parse("includes_trailing", "include \"a.webx\",;\n").is_err());
+        // The keyword needs whitespace before the first path.
+        let Err(WebXParserError::SyntaxError(message, _)) =
+            parse("includes_glued", "include\"a.webx\"\n")
+        else {
+            panic!("Expected a syntax error");
+        };
+        assert!(message.starts_with("Expected whitespace"), "{}", message);
+        assert!(message.ends_with("at line 1, column 8"), "{}", message);
+    }
+
+    #[test]
+    fn test_parse_include_glob() {
+        let root = std::env::temp_dir().join(format!("webx-glob-{}", std::process::id()));
+        std::fs::create_dir_all(root.join("lib/nested")).unwrap();
+        for file in ["lib/b.webx", "lib/a.webx", "lib/c.ts", "lib/nested/d.webx"] {
+            std::fs::write(root.join(file), "").unwrap();
+        }
+        let module = parse_webx_source(
+            &root.join("main.webx"),
+            "include \"lib/*.webx\", \"lib/?.ts\", \"*/nested/*\";\n",
+        )
+        .unwrap();
+        let missing = parse_webx_source(&root.join("main.webx"), "\ninclude \"src/*.webx\"\n");
+        std::fs::remove_dir_all(&root).unwrap();
+        assert_eq!(
+            module.scope.includes,
+            ["lib/a.webx", "lib/b.webx", "lib/c.ts", "lib/nested/d.webx"]
+        );
+        let Err(WebXParserError::SyntaxError(message, _)) = missing else {
+            panic!("Expected a syntax error");
+        };
+        assert_eq!(
+            message,
+            "Include pattern \"src/*.webx\" matched no files at line 2, column 9"
+        );
+    }
+
+    #[test]
+    fn test_wx_modules() {
+        let root = std::env::temp_dir().join(format!("webx-wx-{}", std::process::id()));
+        std::fs::create_dir_all(root.join("api")).unwrap();
+        std::fs::write(root.join("index.webx"), "get / {}\n").unwrap();
+        std::fs::write(root.join("api/todos.wx"), "get /todos {}\n").unwrap();
+        std::fs::write(root.join("api/todos.v2.wx"), "get /v2/todos {}\n").unwrap();
+        std::fs::write(root.join("notes.txt"), "").unwrap();
+        let mut names = load_modules(&root)
+            .iter()
+            .map(|module| (module.path.name().to_string(), module.scope.routes.len()))
+            .collect::<Vec<_>>();
+        names.sort();
+        assert_eq!(
+            names,
+            [
+                ("index".into(), 1),
+                ("todos".into(), 1),
+                ("todos.v2".into(), 1)
+            ]
+        );
+        assert!(is_module_path(Path::new("api/todos.wx")));
+        assert!(!is_module_path(Path::new("notes.txt")));
+
+        create_new_project(WXMode::Prod, "scaffold".into(), &root, false, "wx");
+        let src = root.join("scaffold/webx");
+        assert!(src.join("index.wx").is_file());
+        assert_eq!(load_modules(&src)[0].path.name(), "index");
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_parse_json_text_bodies() {
+        let source = r#"
+get /data json {
+    return "quoted";
+}
+post /echo json(text: String) text
+{
+    return text;
+}
+get /model json
+"#;
+        let module = parse("bodies", source).unwrap();
+        let routes = &module.scope.routes;
+        let body = routes[0].body.as_ref().unwrap();
+        assert!(body.body_type == WXBodyType::Json);
+        assert_eq!(body.body.trim(), "return \"quoted\";");
+        assert!(routes[1].body_format.is_some());
+        assert!(routes[1].body.as_ref().unwrap().body_type == WXBodyType::Text);
+        // Without a code block, `json` is still a request body format.
+        assert!(routes[2].body.is_none());
+        assert_eq!(routes[2].body_format.as_ref().unwrap().to_string(), "json");
+    }
+
+    #[test]
+    fn test_de_indent_bodies() {
+        // Indented with a tab and spaces, the closing delimiter is not at the base indent.
+        let source = "get /pre (\n\t<pre>\n\t  a\n\t\tb\n\n    </pre>\n  )\nget /ts {\n        if (x) {\n            return 1;\n        }\n    }\n";
+        let module = parse("indent", source).unwrap();
+        let routes = &module.scope.routes;
+        let body = routes[0].body.as_ref().unwrap();
+        assert!(body.body_type == WXBodyType::Tsx);
+        assert_eq!(body.body, "\n<pre>\n  a\n\tb\n\n</pre>\n");
+        let body = routes[1].body.as_ref().unwrap();
+        assert_eq!(body.body, "\nif (x) {\n    return 1;\n}\n");
+        // A body on the line of its delimiters is only trimmed at the start.
+        let module = parse("inline", "get /page (  <p>Page</p>)\n").unwrap();
+        assert_eq!(
+            module.scope.routes[0].body.as_ref().unwrap().body,
+            "<p>Page</p>"
+        );
+    }
+
+    #[test]
+    fn test_missing_project_config() {
+        let path = std::env::temp_dir()
+            .join("webx-missing")
+            .join("webx.config.json");
+        let err = read_project_config(&path).unwrap_err();
+        assert!(matches!(err, ProjectConfigError::NotFound(ref p) if *p == path));
+        assert!(err.to_string().contains("was not found"));
+    }
+
+    #[test]
+    fn test_invalid_project_config() {
+        let dir = std::env::temp_dir().join(format!("webx-invalid-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("webx.config.json");
+        std::fs::write(&path, "{\n\t\"name\": \"app\",\n\t\"port\": 80,,\n}\n").unwrap();
+        let err = read_project_config(&path).unwrap_err();
+        std::fs::remove_dir_all(&dir).unwrap();
+        let ProjectConfigError::Invalid {
+            line,
+            column,
+            ref message,
+            ..
+        } = err
+        else {
+            panic!("Expected an invalid configuration, got: {:?}", err);
+        };
+        assert_eq!((line, column), (3, 13));
+        assert!(!message.contains("at line"));
+        assert!(err.to_string().contains("webx.config.json:3:13"));
+    }
+
+    fn example_config() -> (PathBuf, ProjectConfig) {
+        let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("examples/todo");
+        let config = read_project_config(&root.join("webx.config.json")).unwrap();
+        (root, config)
+    }
+
+    fn validation_errors(edit: impl FnOnce(&mut ProjectConfig)) -> Vec<String> {
+        let (root, mut config) = example_config();
+        edit(&mut config);
+        config.validate(&root).unwrap_err()
+    }
+
+    #[test]
+    fn test_validate_project_config() {
+        let (root, config) = example_config();
+        assert!(config.validate(&root).is_ok());
+    }
+
+    #[test]
+    fn test_validate_port() {
+        let errors = validation_errors(|config| config.port = 0);
+        assert_eq!(errors, ["\"port\" must be between 1 and 65535, got 0"]);
+    }
+
+    #[test]
+    fn test_validate_host() {
+        let errors = validation_errors(|config| config.host = Some(" ".into()));
+        assert_eq!(errors, ["\"host\" must not be empty"]);
+    }
+
+    #[test]
+    fn test_validate_src() {
+        let errors = validation_errors(|config| config.src = Some("missing".into()));
+        assert_eq!(errors, ["\"src\" directory 'missing' does not exist"]);
+    }
+
+    #[test]
+    fn test_missing_src_uses_project_root() {
+        let root = std::env::temp_dir().join(format!("webx-nosrc-{}", std::process::id()));
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(
+            root.join("webx.config.json"),
+            "{\n\t\"name\": \"app\",\n\t\"version\": \"1.0.0\",\n\t\"port\": 8080\n}\n",
+        )
+        .unwrap();
+        std::fs::write(root.join("main.webx"), "get /a (<p>a</p>)\n").unwrap();
+        let config = read_project_config(&root.join("webx.config.json")).unwrap();
+        let src = config.source_root(&root);
+        let validation = config.validate(&root);
+        let modules = load_modules(&src);
+        std::fs::remove_dir_all(&root).unwrap();
+        assert_eq!(config.src, None);
+        assert_eq!(src, root);
+        assert!(validation.is_ok());
+        assert_eq!(modules.len(), 1);
+    }
+
+    #[test]
+    fn test_alternate_config_file() {
+        let root = std::env::temp_dir().join(format!("webx-config-{}", std::process::id()));
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(
+            root.join("webx.prod.json"),
+            "{\n\t\"name\": \"prod\",\n\t\"version\": \"1.0.0\",\n\t\"port\": 80\n}\n",
+        )
+        .unwrap();
+        let default_file = resolve_project_config_file_path(&root, None);
+        let config_file =
+            resolve_project_config_file_path(&root, Some(Path::new("webx.prod.json")));
+        let config = read_project_config(&config_file);
+        std::fs::remove_dir_all(&root).unwrap();
+        assert_eq!(default_file, root.join("webx.config.json"));
+        assert_eq!(config_file, root.join("webx.prod.json"));
+        let config = config.unwrap();
+        assert_eq!(config.name, "prod");
+        assert_eq!(config.port, 80);
+    }
+
+    #[test]
+    fn test_config_profiles() {
+        let config = r#"{
+            "name": "app",
+            "version": "1.0.0",
+            "port": 8080,
+            "host": "localhost",
+            "fetch": { "timeoutMs": 1000, "allowedHosts": ["api.local"] },
+            "profiles": {
+                "prod": { "port": 80, "host": "0.0.0.0", "fetch": { "timeoutMs": 5000 } },
+                "staging": { "port": 8081 }
+            }
+        }"#;
+        let base = serde_json::from_str::<ProjectConfig>(config).unwrap();
+
+        let mut prod = base.clone();
+        prod.apply_profile("prod").unwrap();
+        assert_eq!(prod.port, 80);
+        assert_eq!(prod.host.as_deref(), Some("0.0.0.0"));
+        assert_eq!(prod.active_profile.as_deref(), Some("prod"));
+        let fetch = prod.fetch.unwrap();
+        assert_eq!(fetch.timeout_ms, Some(5000));
+        assert_eq!(fetch.allowed_hosts, Some(vec!["api.local".to_string()]));
+
+        let mut staging = base.clone();
+        staging.apply_profile("staging").unwrap();
+        assert_eq!(staging.port, 8081);
+        assert_eq!(staging.host.as_deref(), Some("localhost"));
+        assert_eq!(staging.name, "app");
+
+        let mut missing = base.clone();
+        let err = missing.apply_profile("test").unwrap_err();
+        assert_eq!(
+            err,
+            "Profile 'test' does not exist, expected one of: prod, staging"
+        );
+        assert_eq!(missing.port, 8080);
+    }
+
+    #[test]
+    fn test_validate_listen() {
+        let errors = validation_errors(|config| {
+            config.host = Some("localhost".into());
+            config.listen = Some(ListenConfig {
+                unix: Some(PathBuf::from("/tmp/webx.sock")),
+            });
+        });
+        assert_eq!(
+            errors,
+            ["\"listen.unix\" can not be combined with \"host\""]
+        );
+    }
+
+    #[test]
+    fn test_validate_graphql() {
+        let errors = validation_errors(|config| {
+            config.graphql = Some(GraphQLConfig {
+                path: Some("graphql".into()),
+                query: Some([("all-users".to_string(), "User[]".to_string())].into()),
+            });
+        });
+        assert_eq!(
+            errors,
+            [
+                "\"graphql.path\" must start with '/', got 'graphql'",
+                "\"graphql.query\" field 'all-users' must be a valid GraphQL name",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_validate_cache() {
+        let errors = validation_errors(|config| {
+            config.cache = Some(CacheConfig {
+                strategy: "disk".into(),
+                duration: "10 minutes".into(),
+            })
+        });
+        assert_eq!(errors.len(), 2);
+        assert!(errors[0].starts_with("\"cache.strategy\" must be one of"));
+        assert!(errors[0].ends_with("got 'disk'"));
+        assert!(errors[1].starts_with("\"cache.duration\" must be a number"));
+        assert!(errors[1].ends_with("got '10 minutes'"));
+    }
+
+    #[test]
+    fn test_validate_cors_origin() {
+        for origin in [
+            "example.com",
+            "ftp://example.com",
+            "https://example.com/app",
+        ] {
+            let errors = validation_errors(|config| {
+                config.cors = Some(CorsConfig {
+                    allow_origin: origin.into(),
+                })
+            });
+            assert_eq!(
+                errors,
+                [format!(
+                    "\"cors.allowOrigin\" must be '*' or an origin such as https://example.com, got '{}'",
+                    origin
+                )]
+            );
+        }
+    }
+
+    #[test]
+    fn test_validate_session() {
+        let errors = validation_errors(|config| {
+            config.session = Some(SessionConfig {
+                secret: "short".into(),
+                max_age: Some("forever".into()),
+            })
+        });
+        assert_eq!(
+            errors,
+            [
+                "\"session.secret\" must be at least 32 characters long",
+                "\"session.maxAge\" must be a number followed by s, m, h, or d (e.g. 1d), got 'forever'"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_validate_aggregates_errors() {
+        let errors = validation_errors(|config| {
+            config.port = 0;
+            config.host = Some(String::new());
+        });
+        assert_eq!(errors.len(), 2);
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("30s"), Some(Duration::from_secs(30)));
+        assert_eq!(parse_duration("10m"), Some(Duration::from_secs(600)));
+        assert_eq!(parse_duration("2h"), Some(Duration::from_secs(7200)));
+        assert_eq!(parse_duration("1d"), Some(Duration::from_secs(86400)));
+        assert_eq!(parse_duration("m"), None);
+        assert_eq!(parse_duration("10"), None);
+        assert_eq!(parse_duration("-1s"), None);
+        assert_eq!(parse_duration("10w"), None);
+    }
+
+    #[test]
+    fn test_artifact_round_trip() {
+        let root = Path::new("./examples/todo/");
+        let config = read_project_config(&root.join("webx.config.json")).unwrap();
+        let source_root = config.source_root(root);
+        let hash = source_hash(&source_root, &config);
+        let modules = load_modules(&source_root);
+        let transpiled = vec![("let a: number = 1;".into(), "let a = 1;".into())];
+        let path = std::env::temp_dir()
+            .join(format!("webx-build-{}", std::process::id()))
+            .join("build.bin");
+        write_artifact(&path, &source_root, hash.clone(), &modules, transpiled).unwrap();
+        let artifact = read_artifact(&path, &source_root, &hash);
+        let stale = read_artifact(&path, &source_root, "other");
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+        let artifact = artifact.unwrap();
+        // The loaded modules and their routes are identical to the parsed ones.
+        assert_eq!(format!("{:?}", artifact.modules), format!("{:?}", modules));
+        for (loaded, parsed) in artifact.modules.iter().zip(modules.iter()) {
+            assert_eq!(loaded.path, parsed.path);
+            assert!(loaded.scope.routes == parsed.scope.routes);
+        }
+        assert_eq!(artifact.transpiled[0].1, "let a = 1;");
+        assert!(matches!(stale, Err(WXArtifactError::Stale)));
+        // The configuration the modules are analyzed with is part of the hash.
+        let mut changed = config.clone();
+        changed.allow_delete_body = Some(true);
+        assert_ne!(source_hash(&source_root, &changed), hash);
+    }
+}
@@ -1,11 +1,13 @@
+use serde::{Deserialize, Serialize};
 use std::{
     fmt::{self, Debug, Display, Formatter},
     hash::{Hash, Hasher},
     io,
-    path::PathBuf,
+    path::{Path, PathBuf},
+    time::Duration,
 };
 
-#[derive(Debug, Clone, Hash, PartialEq, Eq)]
+#[derive(Debug, Clone, Hash, PartialEq, Eq, Serialize, Deserialize)]
 pub struct WXInfoField {
     pub path: WXModulePath,
     pub line: usize,
@@ -13,7 +15,7 @@ pub struct WXInfoField {
 
 pub type WXType = String;
 
-#[derive(Clone, Hash, PartialEq, Eq)]
+#[derive(Clone, Hash, PartialEq, Eq, Serialize, Deserialize)]
 pub struct WXTypedIdentifier {
     pub name: String,
     pub type_: WXType,
@@ -25,14 +27,28 @@ impl fmt::Debug for WXTypedIdentifier {
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum WXUrlPathSegment {
     Literal(String),
     Parameter(WXTypedIdentifier),
+    /// A parameter that may be left out, only allowed as the last segment, e.g. `(page: Int)?`.
+    Optional(WXTypedIdentifier),
     Regex(String, String), // Name, Regex
 }
 
-#[derive(PartialEq, Eq, Clone)]
+impl WXUrlPathSegment {
+    /// How specific the segment is, lower is more specific.
+    fn rank(&self) -> u8 {
+        match self {
+            WXUrlPathSegment::Literal(_) => 0,
+            WXUrlPathSegment::Regex(..) => 1,
+            WXUrlPathSegment::Parameter(_) => 2,
+            WXUrlPathSegment::Optional(_) => 3,
+        }
+    }
+}
+
+#[derive(PartialEq, Eq, Clone, Serialize, Deserialize)]
 pub struct WXUrlPath(pub Vec<WXUrlPathSegment>);
 
 impl Display for WXUrlPath {
@@ -45,6 +61,9 @@ impl Display for WXUrlPath {
                 WXUrlPathSegment::Parameter(WXTypedIdentifier { name, type_ }) => {
                     format!("({}: {})", name, type_)
                 }
+                WXUrlPathSegment::Optional(WXTypedIdentifier { name, type_ }) => {
+                    format!("({}: {})?", name, type_)
+                }
                 WXUrlPathSegment::Regex(_, regex) => format!("({})", regex),
             })
             .collect::<Vec<_>>();
@@ -74,6 +93,11 @@ impl Hash for WXUrlPath {
                     name.hash(state);
                     type_.hash(state);
                 }
+                WXUrlPathSegment::Optional(WXTypedIdentifier { name, type_ }) => {
+                    name.hash(state);
+                    type_.hash(state);
+                    '?'.hash(state);
+                }
                 WXUrlPathSegment::Regex(regex_name, regex) => {
                     format!("{}{}", regex_name, regex).hash(state)
                 }
@@ -92,13 +116,94 @@ impl WXUrlPath {
     pub fn segments(&self) -> usize {
         self.0.len()
     }
+
+    /// The name of the trailing wildcard of a catch-all path such as `/files/*`.
+    /// It matches one or more remaining segments, and is bound to all of them,
+    /// e.g. `a/b.txt` for `/files/a/b.txt`.
+    pub fn catch_all(&self) -> Option<&str> {
+        match self.0.last()? {
+            WXUrlPathSegment::Regex(name, regex) if regex == WILDCARD => Some(name),
+            _ => None,
+        }
+    }
+
+    /// The order routes are resolved in, most specific first.
+    /// Catch-all paths come last, as fallbacks for the paths no other route matches.
+    /// Longer paths come first, then paths are compared segment by segment,
+    /// where literals come before regex segments, which come before parameters.
+    /// Equally specific paths are ordered by name, to resolve deterministically.
+    pub fn precedence(&self) -> (bool, std::cmp::Reverse<usize>, Vec<u8>, String) {
+        let ranks = self.0.iter().map(WXUrlPathSegment::rank).collect();
+        (
+            self.catch_all().is_some(),
+            std::cmp::Reverse(self.segments()),
+            ranks,
+            self.to_string(),
+        )
+    }
+
+    /// Whether a request path of the same length could match both paths.
+    /// Parameters match any segment, and regex segments any literal they match.
+    /// A catch-all path never overlaps other paths, as it only matches what they do not.
+    pub fn overlaps(&self, other: &WXUrlPath) -> bool {
+        self.catch_all().is_some() == other.catch_all().is_some()
+            && self.segments() == other.segments()
+            && self.0.iter().zip(other.0.iter()).all(|pair| match pair {
+                (WXUrlPathSegment::Literal(a), WXUrlPathSegment::Literal(b)) => a == b,
+                (WXUrlPathSegment::Literal(literal), WXUrlPathSegment::Regex(_, regex))
+                | (WXUrlPathSegment::Regex(_, regex), WXUrlPathSegment::Literal(literal)) => {
+                    // Invalid regexes, such as wildcards, are assumed to match.
+                    segment_regex(regex)
+                        .map(|regex| regex.is_match(literal))
+                        .unwrap_or(true)
+                }
+                _ => true,
+            })
+    }
+
+    /// The patterns of the regex segments of the path, without wildcards.
+    pub fn regex_patterns(&self) -> impl Iterator<Item = &str> {
+        self.0.iter().filter_map(|segment| match segment {
+            WXUrlPathSegment::Regex(_, regex) if regex != WILDCARD => Some(regex.as_str()),
+            _ => None,
+        })
+    }
+
+    /// The optional parameter of the path, if it is not the last segment.
+    /// Paths combined from a scope and its routes may end up with one.
+    pub fn misplaced_optional(&self) -> Option<&WXTypedIdentifier> {
+        let (_, segments) = self.0.split_last()?;
+        segments.iter().find_map(|segment| match segment {
+            WXUrlPathSegment::Optional(param) => Some(param),
+            _ => None,
+        })
+    }
 }
 
 pub const WXROOT_PATH: WXUrlPath = WXUrlPath(vec![]);
 
+/// The regex of a wildcard `*` path segment, which matches any segment.
+pub const WILDCARD: &str = "*";
+
+/// Compile the pattern of a regex segment, anchored to match the whole segment,
+/// e.g. `v\d+` matches `v2` but not `dev2`.
+pub fn segment_regex(pattern: &str) -> Result<regex::Regex, regex::Error> {
+    regex::Regex::new(&format!("^(?:{})$", pattern))
+}
+
+/// The file extensions of WebX modules, the first is used by default.
+pub const MODULE_EXTENSIONS: [&str; 2] = ["webx", "wx"];
+
+/// Whether a path has the extension of a WebX module, see `MODULE_EXTENSIONS`.
+pub fn is_module_path(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| MODULE_EXTENSIONS.contains(&ext))
+}
+
 /// # WebX module
 /// A file data structure for WebX files.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct WXModule {
     /// The path to the file.
     pub path: WXModulePath,
@@ -106,7 +211,7 @@ pub struct WXModule {
     pub scope: WXScope,
 }
 
-#[derive(Debug, Clone, Hash, PartialEq, Eq)]
+#[derive(Debug, Clone, Hash, PartialEq, Eq, Serialize, Deserialize)]
 pub struct WXModulePath {
     pub inner: PathBuf,
 }
@@ -135,13 +240,12 @@ impl WXModulePath {
     }
 
     /// "/path/to/file.webx" -> "file"
+    /// "/path/to/file.wx" -> "file"
+    /// "/path/to/file.v2.webx" -> "file.v2"
     pub fn name(&self) -> &str {
-        match self.inner.file_name() {
+        match self.inner.file_stem() {
             Some(name) => match name.to_str() {
-                Some(name) => match name.split('.').next() {
-                    Some(name) => name,
-                    None => panic!("Failed to extract file module name of {:?}", self.inner),
-                },
+                Some(name) => name,
                 None => panic!("Failed to convert file name to string of {:?}", self.inner),
             },
             None => panic!("Failed to get file name of {:?}", self.inner),
@@ -156,9 +260,24 @@ impl WXModulePath {
             self.name().to_string()
         }
     }
+
+    /// "/path/to/file.webx" -> "path/to/file.webx"
+    /// Relative to the working directory, if the file is inside of it.
+    pub fn display_path(&self) -> String {
+        let path = self
+            .inner
+            .canonicalize()
+            .unwrap_or_else(|_| self.inner.clone());
+        let relative = std::env::current_dir()
+            .and_then(|cwd| cwd.canonicalize())
+            .ok()
+            .and_then(|cwd| path.strip_prefix(cwd).ok().map(|path| path.to_path_buf()))
+            .unwrap_or(path);
+        relative.to_string_lossy().replace('\\', "/")
+    }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct WXScope {
     pub path: WXUrlPath,
     /// The dependencies of the scope.
@@ -169,14 +288,47 @@ pub struct WXScope {
     pub models: Vec<WXModel>,
     /// Handler functions
     pub handlers: Vec<WXHandler>,
+    /// Middleware declarations, visible in this and all nested scopes.
+    pub middleware: Vec<WXMiddleware>,
+    /// Names of the middleware run by all routes in this and nested scopes.
+    /// Attached with the `use` keyword.
+    pub uses: Vec<String>,
     /// Route endpoints
     pub routes: Vec<WXRoute>,
+    /// Module-wide error handler.
+    /// Only allowed in the global module scope.
+    pub error_handler: Option<WXErrorHandler>,
     /// Nested scopes.
     /// Created by root and the `location` keyword.
     pub scopes: Vec<WXScope>,
 }
 
-#[derive(Debug, Clone, Hash, PartialEq, Eq)]
+impl WXScope {
+    /// The non-empty global code blocks of this and all nested scopes,
+    /// with the full path of the scope declaring them.
+    ///
+    /// ## Order
+    /// Outer scopes come before the scopes nested in them, siblings in source order.
+    /// All blocks share the global scope of the module, so a nested block
+    /// may use the declarations of the blocks before it, but not redeclare them.
+    pub fn globals(&self) -> Vec<(WXUrlPath, &str)> {
+        let mut globals = Vec::new();
+        self.collect_globals(&WXUrlPath(vec![]), &mut globals);
+        globals
+    }
+
+    fn collect_globals<'a>(&'a self, parent: &WXUrlPath, globals: &mut Vec<(WXUrlPath, &'a str)>) {
+        let path = parent.combine(&self.path);
+        if !self.global_ts.trim().is_empty() {
+            globals.push((path.clone(), self.global_ts.as_str()));
+        }
+        for scope in self.scopes.iter() {
+            scope.collect_globals(&path, globals);
+        }
+    }
+}
+
+#[derive(Debug, Clone, Hash, PartialEq, Eq, Serialize, Deserialize)]
 pub struct WXModel {
     /// The name of the model.
     pub name: String,
@@ -184,7 +336,7 @@ pub struct WXModel {
     pub fields: Vec<WXTypedIdentifier>,
 }
 
-#[derive(Debug, Clone, Hash, PartialEq, Eq)]
+#[derive(Debug, Clone, Hash, PartialEq, Eq, Serialize, Deserialize)]
 pub struct WXHandler {
     /// The name of the handler.
     pub name: String,
@@ -194,11 +346,38 @@ pub struct WXHandler {
     pub body: WXBody,
 }
 
-#[derive(Hash, PartialEq, Eq, Clone)]
+/// A `middleware name -> handler(args), ...` declaration.
+/// A named chain of handler calls, run before the pre-handlers
+/// of every route in a scope that uses it.
+#[derive(Debug, Clone, Hash, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WXMiddleware {
+    /// The name of the middleware.
+    pub name: String,
+    /// The handler calls of the middleware, in order.
+    pub handlers: Vec<WXRouteHandlerCall>,
+}
+
+/// An `on error(e) { ... }` block.
+/// Executed with the error bound to `binding` whenever a route
+/// in the same module fails to produce a response.
+#[derive(Debug, Clone, Hash, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WXErrorHandler {
+    /// The name the error is bound to.
+    pub binding: String,
+    /// The body producing the error response.
+    pub body: WXBody,
+}
+
+#[derive(Hash, PartialEq, Eq, Clone, Serialize, Deserialize)]
 pub enum WXBodyType {
     Ts,
     Tsx,
-    // TODO: JSON and TEXT
+    /// A `"""` raw string literal, sent as is.
+    Raw,
+    /// A `json { ... }` TypeScript body, whose result is sent as JSON.
+    Json,
+    /// A `text { ... }` TypeScript body, whose result is sent as plain text.
+    Text,
 }
 
 impl Display for WXBodyType {
@@ -206,11 +385,14 @@ impl Display for WXBodyType {
         match self {
             WXBodyType::Ts => write!(f, "ts"),
             WXBodyType::Tsx => write!(f, "tsx"),
+            WXBodyType::Raw => write!(f, "raw"),
+            WXBodyType::Json => write!(f, "json"),
+            WXBodyType::Text => write!(f, "text"),
         }
     }
 }
 
-#[derive(Hash, PartialEq, Eq, Clone)]
+#[derive(Hash, PartialEq, Eq, Clone, Serialize, Deserialize)]
 pub struct WXBody {
     pub body_type: WXBodyType,
     pub body: String,
@@ -222,7 +404,7 @@ impl fmt::Debug for WXBody {
     }
 }
 
-#[derive(Hash, PartialEq, Eq, Clone)]
+#[derive(Hash, PartialEq, Eq, Clone, Serialize, Deserialize)]
 pub enum WXRouteReqBody {
     ModelReference(String),
     Definition(String, Vec<WXTypedIdentifier>),
@@ -252,7 +434,7 @@ impl Debug for WXRouteReqBody {
     }
 }
 
-#[derive(Hash, PartialEq, Eq, Clone)]
+#[derive(Hash, PartialEq, Eq, Clone, Serialize, Deserialize)]
 pub struct WXRouteHandlerCall {
     pub name: String,
     /// Evaluate wrapped in [] to allow for empty arguments.
@@ -270,15 +452,54 @@ impl fmt::Debug for WXRouteHandlerCall {
     }
 }
 
-#[derive(Debug, Clone)]
+/// The route method of `ws` routes.
+/// WebSocket routes are stored alongside HTTP routes under this extension method,
+/// so a `ws` and a `get` route may share the same path.
+pub const WEBSOCKET_METHOD: &str = "WS";
+
+/// The route method of `ws` routes as a `hyper::Method`.
+pub fn websocket_method() -> hyper::Method {
+    hyper::Method::from_bytes(WEBSOCKET_METHOD.as_bytes()).unwrap()
+}
+
+/// (De)serialize route methods by their name, e.g. in build artifacts.
+mod method_serde {
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(
+        method: &hyper::Method,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(method.as_str())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<hyper::Method, D::Error> {
+        let method = String::deserialize(deserializer)?;
+        hyper::Method::from_bytes(method.as_bytes()).map_err(D::Error::custom)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct WXRoute {
     pub info: WXInfoField,
-    /// HTTP method of the route.
+    /// HTTP method of the route, or `WS` for WebSocket routes.
+    #[serde(with = "method_serde")]
     pub method: hyper::Method,
     /// The path of the route.
     pub path: WXUrlPath,
+    /// Whether the path is declared with a trailing slash, e.g. `get /about/`.
+    /// Only distinguishes routes under the `strict` trailing slash policy.
+    pub trailing_slash: bool,
     /// Request body format.
     pub body_format: Option<WXRouteReqBody>,
+    /// How long clients may cache responses, from a `cache(...)` annotation.
+    /// Responses must be revalidated by default.
+    pub cache: Option<Duration>,
+    /// The priority of the route among overlapping routes, from a `priority(...)` annotation.
+    /// Default: 0.
+    pub priority: i32,
     /// The pre-handler functions of the route.
     pub pre_handlers: Vec<WXRouteHandlerCall>,
     /// The code block of the route.
@@ -287,6 +508,31 @@ pub struct WXRoute {
     pub post_handlers: Vec<WXRouteHandlerCall>,
 }
 
+impl WXRoute {
+    /// The precedence of the route at its full path, see `WXUrlPath::precedence`.
+    /// Of paths with the same number of segments, a higher priority takes precedence
+    /// before more specific segments do.
+    pub fn precedence(
+        &self,
+        path: &WXUrlPath,
+    ) -> (
+        bool,
+        std::cmp::Reverse<usize>,
+        std::cmp::Reverse<i32>,
+        Vec<u8>,
+        String,
+    ) {
+        let (catch_all, segments, ranks, name) = path.precedence();
+        (
+            catch_all,
+            segments,
+            std::cmp::Reverse(self.priority),
+            ranks,
+            name,
+        )
+    }
+}
+
 impl Hash for WXRoute {
     fn hash<H: Hasher>(&self, state: &mut H) {
         self.method.hash(state);
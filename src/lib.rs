@@ -0,0 +1,26 @@
+//! # WebX
+//! A language and framework for building web application endpoints.
+//!
+//! The `webx` CLI is a thin wrapper over this library,
+//! which can also be used to embed a WebX server in another application:
+//!
+//! ```no_run
+//! let handle = webx::WebXServer::builder()
+//!     .project("path/to/project")
+//!     .native("greet", |args| Ok(format!("Hello, {}!", args[0]).into()))
+//!     .build()
+//!     .expect("Invalid WebX project")
+//!     .start();
+//! // ...
+//! handle.shutdown();
+//! ```
+
+pub mod analysis;
+mod embed;
+pub mod engine;
+pub mod file;
+pub mod reporting;
+pub mod runner;
+
+pub use embed::{WebXHandle, WebXServer, WebXServerBuilder};
+pub use engine::stdlib::WXNativeFunction;
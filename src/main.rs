@@ -1,35 +1,32 @@
-mod analysis;
-mod engine;
-mod file;
-mod reporting;
-mod runner;
-
-use std::{
-    ops::Add,
-    path::PathBuf,
-    sync::{
-        atomic::{AtomicBool, Ordering},
-        Arc,
-    },
-    time::Duration,
-};
+use std::{net::ToSocketAddrs, ops::Add, path::PathBuf, time::Duration};
 
 use clap::{Arg, ArgAction, Command};
 use colored::*;
-use reporting::error::{error_code, DateTimeSpecifier, ERROR_PROJECT};
-use runner::{DebugLevel, WXMode};
+use webx::{
+    engine::server::WXShutdown,
+    file,
+    reporting::{
+        debug::{is_quiet, set_quiet},
+        error::{error_code, DateTimeSpecifier, ERROR_PROJECT},
+        warning::warning,
+    },
+    runner::{self, timeout_duration, DebugLevel, WXConfigOverrides, WXMode},
+    WebXServer,
+};
 
 const VERSION: &str = env!("CARGO_PKG_VERSION");
 const NAME: &str = "webx";
 const DESCRIPTION: &str = env!("CARGO_PKG_DESCRIPTION");
 const AUTHOR: &str = env!("CARGO_PKG_AUTHORS");
-const TIMEOUT_DURATION_DEV: Duration = Duration::from_secs(1);
-const TIMEOUT_DURATION_PROD: Duration = Duration::from_secs(30);
 
-fn timeout_duration(mode: WXMode) -> Duration {
-    match mode {
-        WXMode::Dev(_) => TIMEOUT_DURATION_DEV,
-        WXMode::Prod => TIMEOUT_DURATION_PROD,
+/// Disable colored output if requested by the `NO_COLOR` environment variable
+/// (see https://no-color.org) or the global `--no-color` flag.
+/// Applied before the CLI is built, as its help texts are colored eagerly.
+fn apply_color_choice() {
+    let no_color_env = std::env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty());
+    let no_color_flag = std::env::args_os().any(|arg| arg == "--no-color");
+    if no_color_env || no_color_flag {
+        colored::control::set_override(false);
     }
 }
 
@@ -39,12 +36,19 @@ fn cli() -> Command {
         .version(VERSION)
         .author(AUTHOR)
         .about(DESCRIPTION)
+        .arg(
+            Arg::new("no-color")
+                .long("no-color")
+                .global(true)
+                .action(ArgAction::SetTrue)
+                .help("Disable colored output, also disabled by setting NO_COLOR"),
+        )
         .subcommand(
             Command::new("run")
                 .about("Run the project web server")
                 .arg(
                     Arg::new("project")
-                        .help("The project directory, default: current directory")
+                        .help("The project directory or .webx file, default: current directory")
                         .required(false),
                 )
                 .arg(
@@ -59,7 +63,57 @@ fn cli() -> Command {
                         .short('l')
                         .long("level")
                         .required(false)
-                        .help("Set the debug verbosity level [1-4], default: 2"),
+                        .value_parser(parse_level)
+                        .help("Set the debug verbosity level [1-4] or low, medium, high, max, default: 2"),
+                )
+                .arg(
+                    Arg::new("config")
+                        .short('c')
+                        .long("config")
+                        .required(false)
+                        .help("The project configuration file, default: webx.config.json"),
+                )
+                .arg(
+                    Arg::new("profile")
+                        .long("profile")
+                        .required(false)
+                        .help("Merge the named profile of the configuration over the rest"),
+                )
+                .arg(
+                    Arg::new("host")
+                        .long("host")
+                        .required(false)
+                        .value_parser(parse_host)
+                        .help("Listen on this host instead of the configured one"),
+                )
+                .arg(
+                    Arg::new("port")
+                        .long("port")
+                        .required(false)
+                        .value_parser(clap::value_parser!(u16).range(1..))
+                        .help("Listen on this port [1-65535] instead of the configured one"),
+                )
+                .arg(
+                    Arg::new("threads")
+                        .long("threads")
+                        .required(false)
+                        .value_parser(parse_threads)
+                        .help(
+                            "Serve connections with this many threads instead of one per CPU core",
+                        ),
+                )
+                .arg(
+                    Arg::new("open")
+                        .long("open")
+                        .action(ArgAction::SetTrue)
+                        .help("Open the server in the default browser once it is listening (dev mode only)"),
+                )
+                .arg(
+                    Arg::new("quiet")
+                        .short('q')
+                        .long("quiet")
+                        .action(ArgAction::SetTrue)
+                        .help("Suppress the startup banner and info logs, but not errors"),
                 ),
         )
         .subcommand(
@@ -76,6 +130,74 @@ fn cli() -> Command {
                         .long("override")
                         .action(ArgAction::SetTrue)
                         .help("Override existing files"),
+                )
+                .arg(
+                    Arg::new("extension")
+                        .short('e')
+                        .long("extension")
+                        .value_parser(file::webx::MODULE_EXTENSIONS)
+                        .default_value(file::webx::MODULE_EXTENSIONS[0])
+                        .help("The extension of the module files"),
+                ),
+        )
+        .subcommand(
+            Command::new("build")
+                .about("Build the project into an artifact for faster production starts")
+                .arg(
+                    Arg::new("project")
+                        .help("The project directory, default: current directory")
+                        .required(false),
+                )
+                .arg(
+                    Arg::new("config")
+                        .short('c')
+                        .long("config")
+                        .required(false)
+                        .help("The project configuration file, default: webx.config.json"),
+                )
+                .arg(
+                    Arg::new("profile")
+                        .long("profile")
+                        .required(false)
+                        .help("Merge the named profile of the configuration over the rest"),
+                ),
+        )
+        .subcommand(
+            Command::new("openapi")
+                .about("Generate an OpenAPI 3 specification of the project routes")
+                .arg(
+                    Arg::new("project")
+                        .help("The project directory, default: current directory")
+                        .required(false),
+                )
+                .arg(
+                    Arg::new("output")
+                        .short('o')
+                        .long("output")
+                        .required(false)
+                        .help("Write the specification to a file, default: stdout"),
+                ),
+        )
+        .subcommand(
+            Command::new("export")
+                .about("Export the project routes as a Postman collection")
+                .arg(
+                    Arg::new("format")
+                        .help("The collection format, Insomnia imports Postman collections")
+                        .value_parser(["postman"])
+                        .required(true),
+                )
+                .arg(
+                    Arg::new("project")
+                        .help("The project directory, default: current directory")
+                        .required(false),
+                )
+                .arg(
+                    Arg::new("output")
+                        .short('o')
+                        .long("output")
+                        .required(false)
+                        .help("Write the collection to a file, default: stdout"),
                 ),
         )
         .subcommand(
@@ -89,7 +211,11 @@ fn cli() -> Command {
                         .help("Test in production mode"),
                 ),
         )
-        .color(clap::ColorChoice::Auto)
+        .color(if colored::control::SHOULD_COLORIZE.should_colorize() {
+            clap::ColorChoice::Auto
+        } else {
+            clap::ColorChoice::Never
+        })
         .override_usage(format!(
             "{name} [command] (options)",
             name = NAME.bright_white()
@@ -108,21 +234,60 @@ fn cli() -> Command {
 }
 
 fn parse_debug_level(matches: &clap::ArgMatches) -> DebugLevel {
-    if let Some(value) = matches.get_one::<String>("level") {
-        if let Ok(level) = value.parse::<u8>() {
-            return DebugLevel::from_u8(level);
-        }
+    matches
+        .get_one::<DebugLevel>("level")
+        .copied()
+        .unwrap_or(DebugLevel::Medium)
+}
+
+/// Parse a `--level` option, by number [1-4] or name.
+/// Numbers out of range are clamped to the nearest level with a warning.
+fn parse_level(level: &str) -> Result<DebugLevel, String> {
+    let Ok(number) = level.trim().parse::<u64>() else {
+        return DebugLevel::parse(level);
+    };
+    let clamped = DebugLevel::from_u8(number.min(u8::MAX as u64) as u8);
+    if !(1..=4).contains(&number) {
+        warning(
+            WXMode::MAX,
+            format!(
+                "Debug level {} is out of range [1-4], using {} ({})",
+                number,
+                clamped as u8,
+                clamped.name()
+            ),
+        );
+    }
+    Ok(clamped)
+}
+
+/// Parse a `--host` option, which has to resolve to at least one address.
+fn parse_host(host: &str) -> Result<String, String> {
+    match (host, 0).to_socket_addrs() {
+        Ok(mut addrs) if addrs.next().is_some() => Ok(host.to_string()),
+        Ok(_) => Err(format!("'{}' does not resolve to any address", host)),
+        Err(err) => Err(format!("'{}' does not resolve: {}", host, err)),
+    }
+}
+
+/// Parse a `--threads` option, which has to be at least 1.
+fn parse_threads(threads: &str) -> Result<usize, String> {
+    match threads.parse::<usize>() {
+        Ok(0) => Err("at least 1 thread is required".to_string()),
+        Ok(threads) => Ok(threads),
+        Err(err) => Err(format!("'{}' is not a number: {}", threads, err)),
     }
-    DebugLevel::Medium
 }
 
-fn register_ctrlc(mode: WXMode, running: Arc<AtomicBool>) {
+fn register_ctrlc(mode: WXMode, shutdown: WXShutdown) {
     ctrlc::set_handler(move || {
-        println!(
-            "CTRL+C pressed, shutting down... (up to {:?})",
-            timeout_duration(mode)
-        );
-        running.store(false, Ordering::SeqCst);
+        if !is_quiet() {
+            println!(
+                "CTRL+C pressed, finishing requests in progress... (up to {:?})",
+                timeout_duration(mode)
+            );
+        }
+        shutdown.trigger();
         std::thread::sleep(timeout_duration(mode).add(Duration::from_secs(2)));
         println!("This is taking longer than expected, force quitting...");
         std::process::exit(1);
@@ -131,6 +296,7 @@ fn register_ctrlc(mode: WXMode, running: Arc<AtomicBool>) {
 }
 
 fn main() {
+    apply_color_choice();
     let matches = cli().get_matches();
 
     if let Some(matches) = matches.subcommand_matches("new") {
@@ -147,11 +313,13 @@ fn main() {
             }
         };
         let override_existing = matches.get_flag("override");
+        let extension = matches.get_one::<String>("extension").unwrap();
         file::project::create_new_project(
             WXMode::MAX,
             name,
             &std::env::current_dir().unwrap(),
             override_existing,
+            extension,
         );
     } else if let Some(matches) = matches.subcommand_matches("run") {
         let mode = if matches.get_flag("production") {
@@ -164,10 +332,58 @@ fn main() {
         } else {
             std::env::current_dir().unwrap()
         };
-        let running = Arc::new(AtomicBool::new(true));
-        register_ctrlc(mode, running.clone());
-        runner::run(&project, mode, running);
-        println!("Goodbye!");
+        let overrides = WXConfigOverrides {
+            host: matches.get_one::<String>("host").cloned(),
+            port: matches.get_one::<u16>("port").copied(),
+            threads: matches.get_one::<usize>("threads").copied(),
+        };
+        set_quiet(matches.get_flag("quiet"));
+        let mut builder = WebXServer::builder()
+            .project(project)
+            .mode(mode)
+            .overrides(overrides)
+            .open_browser(matches.get_flag("open"));
+        if let Some(config_path) = matches.get_one::<String>("config") {
+            builder = builder.config_file(config_path);
+        }
+        if let Some(profile) = matches.get_one::<String>("profile") {
+            builder = builder.profile(profile);
+        }
+        let server = builder
+            .build()
+            .unwrap_or_else(|errors| runner::exit_errors(errors));
+        let shutdown = WXShutdown::default();
+        register_ctrlc(mode, shutdown.clone());
+        server.run(shutdown);
+        if !is_quiet() {
+            println!("Goodbye!");
+        }
+    } else if let Some(matches) = matches.subcommand_matches("build") {
+        let project = if let Some(project) = matches.get_one::<String>("project") {
+            PathBuf::from(project)
+        } else {
+            std::env::current_dir().unwrap()
+        };
+        let config_path = matches.get_one::<String>("config").map(PathBuf::from);
+        let profile = matches.get_one::<String>("profile").map(String::as_str);
+        runner::build(&project, config_path, profile);
+    } else if let Some(matches) = matches.subcommand_matches("openapi") {
+        let project = if let Some(project) = matches.get_one::<String>("project") {
+            PathBuf::from(project)
+        } else {
+            std::env::current_dir().unwrap()
+        };
+        let output = matches.get_one::<String>("output").map(PathBuf::from);
+        runner::openapi(&project, output.as_deref());
+    } else if let Some(matches) = matches.subcommand_matches("export") {
+        let format = matches.get_one::<String>("format").unwrap();
+        let project = if let Some(project) = matches.get_one::<String>("project") {
+            PathBuf::from(project)
+        } else {
+            std::env::current_dir().unwrap()
+        };
+        let output = matches.get_one::<String>("output").map(PathBuf::from);
+        runner::export(&project, format, output.as_deref());
     } else if let Some(_matches) = matches.subcommand_matches("test") {
         todo!("Test command not implemented.");
     } else {
@@ -1,13 +1,66 @@
+use std::{
+    cell::RefCell,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
 use chrono::prelude::*;
 use colored::Colorize;
 
 use crate::runner::WXMode;
 
+/// Whether non-error output is suppressed, set by the `--quiet` flag.
+static QUIET: AtomicBool = AtomicBool::new(false);
+
+/// Suppress the startup banner and info logs.
+/// Warnings and errors are still printed.
+pub fn set_quiet(quiet: bool) {
+    QUIET.store(quiet, Ordering::Relaxed);
+}
+
+pub fn is_quiet() -> bool {
+    QUIET.load(Ordering::Relaxed)
+}
+
+thread_local! {
+    /// The id of the request handled on this thread, see `with_request_id`.
+    static REQUEST_ID: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+/// Restores the request id of the thread once dropped, even if the request panicked.
+struct WXRequestIdGuard(Option<String>);
+
+impl Drop for WXRequestIdGuard {
+    fn drop(&mut self) {
+        REQUEST_ID.with(|current| *current.borrow_mut() = self.0.take());
+    }
+}
+
+/// Tag all info, warning and error logs printed on this thread by `f` with a request id.
+pub fn with_request_id<T>(id: &str, f: impl FnOnce() -> T) -> T {
+    let previous = REQUEST_ID.with(|current| current.replace(Some(id.to_string())));
+    let _guard = WXRequestIdGuard(previous);
+    f()
+}
+
+/// Prefix a log message with the id of the request handled on this thread, if any.
+pub fn tag_request(message: &str) -> String {
+    REQUEST_ID.with(|current| match current.borrow().as_deref() {
+        Some(id) => format!("[{}] {}", id, message),
+        None => message.to_string(),
+    })
+}
+
+/// Whether `info` prints anything in a mode,
+/// so messages that are costly to build can be skipped on the request path.
+pub fn is_info_enabled(mode: WXMode) -> bool {
+    mode.is_dev() && mode.debug_level().is_medium() && !is_quiet()
+}
+
 pub fn info(mode: WXMode, text: &str) {
-    if mode.is_dev() && mode.debug_level().is_medium() {
+    if is_info_enabled(mode) {
         let now = Local::now();
         let time = now.format("%d/%m %H:%M:%S");
         let prefix = format!("Info (T{})", time);
-        println!("{}: {}", prefix.bright_cyan(), text);
+        println!("{}: {}", prefix.bright_cyan(), tag_request(text));
     }
 }
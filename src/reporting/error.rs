@@ -1,7 +1,7 @@
 use chrono::prelude::*;
 use colored::*;
 
-use crate::file::webx::WXInfoField;
+use crate::{file::webx::WXInfoField, reporting::debug::tag_request};
 
 // Error codes:
 pub const ERROR_READ_WEBX_FILES: i32 = 1;
@@ -13,6 +13,12 @@ pub const ERROR_DUPLICATE_ROUTE: i32 = 6;
 pub const ERROR_INVALID_ROUTE: i32 = 7;
 pub const ERROR_HANDLER_CALL: i32 = 8;
 pub const ERROR_EXEC_ROUTE: i32 = 9;
+pub const ERROR_UNKNOWN_MIDDLEWARE: i32 = 10;
+pub const ERROR_BAD_REQUEST: i32 = 11;
+pub const ERROR_UNKNOWN_HANDLER: i32 = 12;
+/// Forbidden requests, such as file access outside of the project root or an invalid CSRF token.
+/// Answered with `403 Forbidden`.
+pub const ERROR_FORBIDDEN: i32 = 13;
 
 pub fn code_to_name(code: i32) -> String {
     match code {
@@ -22,6 +28,10 @@ pub fn code_to_name(code: i32) -> String {
         ERROR_DUPLICATE_ROUTE => "Duplicate Route".to_owned(),
         ERROR_INVALID_ROUTE => "Invalid Route".to_owned(),
         ERROR_EXEC_ROUTE => "Execute Route".to_owned(),
+        ERROR_UNKNOWN_MIDDLEWARE => "Unknown Middleware".to_owned(),
+        ERROR_UNKNOWN_HANDLER => "Unknown Handler".to_owned(),
+        ERROR_FORBIDDEN => "Forbidden".to_owned(),
+        ERROR_BAD_REQUEST => "Bad Request".to_owned(),
         ERROR_HANDLER_CALL => "Handler Call".to_owned(),
         ERROR_PARSE_IO => "Parse IO".to_owned(),
         ERROR_SYNTAX => "Syntax".to_owned(),
@@ -63,7 +73,7 @@ fn exit_error_generic_code(message: String, code: i32, date: DateTimeSpecifier)
 }
 
 pub fn error_code(message: String, code: i32, date: DateTimeSpecifier) {
-    error_generic_code(message, code, date);
+    error_generic_code(tag_request(&message), code, date);
 }
 
 pub fn exit_error(message: String, code: i32, date: DateTimeSpecifier) -> ! {
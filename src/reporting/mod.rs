@@ -1,4 +1,5 @@
 pub mod debug;
 pub mod error;
 pub mod route;
+mod test;
 pub mod warning;
@@ -1,11 +1,52 @@
-use crate::file::webx::WXUrlPath;
+use crate::file::webx::{WXUrlPath, WEBSOCKET_METHOD};
 use colored::*;
 use hyper::Method;
 
+/// Color a method name, padded to `width`, by the kind of the method.
+fn color_method(method: &Method, width: usize) -> ColoredString {
+    let name = format!("{:<width$}", method.as_str(), width = width);
+    match *method {
+        Method::GET | Method::HEAD => name.bright_green(),
+        Method::POST => name.bright_blue(),
+        Method::PUT | Method::PATCH => name.bright_magenta(),
+        Method::DELETE => name.bright_red(),
+        _ if method.as_str() == WEBSOCKET_METHOD => name.bright_cyan(),
+        _ => name.bright_white(),
+    }
+}
+
 pub fn print_route(method: &Method, path: &WXUrlPath) -> String {
     format!(
         "{} {}",
-        method.to_string().bright_green(),
+        color_method(method, 0),
         path.to_string().bright_yellow(),
     )
 }
+
+/// Format routes as a table, with one route per line and aligned columns
+/// for the method, the path, and the module defining the route.
+/// Routes are sorted by path, then by method.
+pub fn format_route_table(routes: &[(&Method, &WXUrlPath, String)]) -> String {
+    let mut rows = routes
+        .iter()
+        .map(|(method, path, module)| (*method, path.to_string(), module.as_str()))
+        .collect::<Vec<_>>();
+    rows.sort_by(|(a_method, a_path, _), (b_method, b_path, _)| {
+        a_path
+            .cmp(b_path)
+            .then_with(|| a_method.as_str().cmp(b_method.as_str()))
+    });
+    let method_width = rows.iter().map(|(m, _, _)| m.as_str().len()).max();
+    let path_width = rows.iter().map(|(_, p, _)| p.len()).max();
+    rows.iter()
+        .map(|(method, path, module)| {
+            format!(
+                " - {} {} {}",
+                color_method(method, method_width.unwrap_or(0)),
+                format!("{:<width$}", path, width = path_width.unwrap_or(0)).bright_yellow(),
+                module,
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
@@ -0,0 +1,122 @@
+#[cfg(test)]
+mod tests {
+    use std::{
+        path::{Path, PathBuf},
+        sync::{Mutex, MutexGuard, PoisonError},
+        time::Duration,
+    };
+
+    use hyper::Method;
+
+    use crate::{
+        file::{
+            parser::parse_webx_source,
+            project::load_project_config,
+            webx::{websocket_method, WXTypedIdentifier, WXUrlPath, WXUrlPathSegment},
+        },
+        reporting::{
+            debug::{is_info_enabled, set_quiet},
+            route::{format_route_table, print_route},
+        },
+        runner::{get_project_config_file_path, print_start_info, DebugLevel, WXMode},
+    };
+
+    /// Serializes the tests changing process-wide output settings,
+    /// such as the quiet flag and the color override.
+    static OUTPUT_SETTINGS: Mutex<()> = Mutex::new(());
+
+    /// Hold the output settings until dropped, even if another test holding them failed.
+    fn lock_output_settings() -> MutexGuard<'static, ()> {
+        OUTPUT_SETTINGS
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn path(segments: &[&str]) -> WXUrlPath {
+        WXUrlPath(
+            segments
+                .iter()
+                .map(|segment| match segment.strip_prefix(':') {
+                    Some(name) => WXUrlPathSegment::Parameter(WXTypedIdentifier {
+                        name: name.to_string(),
+                        type_: "String".to_string(),
+                    }),
+                    None => WXUrlPathSegment::Literal(segment.to_string()),
+                })
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn test_format_route_table() {
+        let (root, todos, todo) = (path(&[]), path(&["todos"]), path(&["todos", ":id"]));
+        let (post, delete, ws) = (Method::POST, Method::DELETE, websocket_method());
+        let routes = [
+            (&delete, &todo, "todos".to_string()),
+            (&Method::GET, &todos, "todos".to_string()),
+            (&ws, &todos, "live".to_string()),
+            (&Method::GET, &root, "index".to_string()),
+            (&post, &todos, "todos".to_string()),
+        ];
+        let table = format_route_table(&routes);
+        let ansi = regex::Regex::new("\x1b\\[[0-9;]*m").unwrap();
+        assert_eq!(
+            ansi.replace_all(&table, ""),
+            [
+                " - GET    /                   index",
+                " - GET    /todos              todos",
+                " - POST   /todos              todos",
+                " - WS     /todos              live",
+                " - DELETE /todos/(id: String) todos",
+            ]
+            .join("\n")
+        );
+        assert_eq!(format_route_table(&[]), "");
+    }
+
+    #[test]
+    fn test_print_route_without_color() {
+        let route = path(&["todos", ":id"]);
+        let _settings = lock_output_settings();
+        colored::control::set_override(false);
+        let printed = print_route(&Method::DELETE, &route);
+        let table = format_route_table(&[(&Method::DELETE, &route, "todos".to_string())]);
+        colored::control::unset_override();
+        assert!(!printed.contains('\x1b'));
+        assert_eq!(printed, "DELETE /todos/(id: String)");
+        assert!(!table.contains('\x1b'));
+    }
+
+    #[test]
+    fn test_quiet_start_info() {
+        let config = load_project_config(
+            &get_project_config_file_path(Path::new("examples/todo")),
+            None,
+        );
+        let modules = [parse_webx_source(&PathBuf::from("index.webx"), "get / {}\n").unwrap()];
+        let print = || {
+            let mut out = Vec::new();
+            let mode = WXMode::Dev(DebugLevel::Medium);
+            print_start_info(&mut out, &modules, mode, &config, Duration::ZERO).unwrap();
+            String::from_utf8(out).unwrap()
+        };
+        let _settings = lock_output_settings();
+        set_quiet(true);
+        let quiet = print();
+        let info_enabled = is_info_enabled(WXMode::Dev(DebugLevel::Max));
+        set_quiet(false);
+        assert!(!info_enabled);
+        assert_eq!(quiet, "");
+        let banner = print();
+        assert!(banner.contains("Project"));
+        assert!(banner.contains(&config.name));
+    }
+
+    #[test]
+    fn test_info_disabled_below_medium() {
+        // Request and response logs are not even formatted at these levels.
+        let _settings = lock_output_settings();
+        assert!(!is_info_enabled(WXMode::Prod));
+        assert!(!is_info_enabled(WXMode::Dev(DebugLevel::Low)));
+    }
+}
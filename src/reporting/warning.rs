@@ -1,7 +1,7 @@
 use chrono::prelude::*;
 use colored::*;
 
-use crate::runner::WXMode;
+use crate::{reporting::debug::tag_request, runner::WXMode};
 
 fn warning_generic(mode: WXMode, message: String, warning_name: &str) {
     if mode.is_dev() && mode.debug_level().is_high() {
@@ -12,5 +12,9 @@ fn warning_generic(mode: WXMode, message: String, warning_name: &str) {
 pub fn warning(mode: WXMode, message: String) {
     let now = Local::now();
     let time = now.format("%d/%m %H:%M:%S");
-    warning_generic(mode, message, format!("Warn (T{})", time).as_str());
+    warning_generic(
+        mode,
+        tag_request(&message),
+        format!("Warn (T{})", time).as_str(),
+    );
 }
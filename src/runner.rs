@@ -2,24 +2,56 @@ use chrono::offset::Local;
 use chrono::DateTime;
 use chrono::{self};
 use colored::Colorize;
+use std::io::{self, Write};
 use std::path::{Path, PathBuf};
-use std::sync::atomic::AtomicBool;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
-use crate::analysis::{dependencies::analyze_module_deps, routes::analyze_module_routes};
+use crate::analysis::{
+    dependencies::analyze_module_deps,
+    openapi::generate_openapi,
+    postman::generate_postman,
+    routes::{analyze_module_routes, analyze_unknown_handlers, BodyFormatPolicy},
+};
 use crate::engine::filewatcher::WXFileWatcher;
-use crate::engine::runtime::{WXRuntime, WXRuntimeInfo};
-use crate::engine::server::WXServer;
-use crate::file::project::{load_modules, load_project_config, ProjectConfig};
-use crate::file::webx::WXModule;
-use crate::reporting::error::DateTimeSpecifier;
+use crate::engine::pool::run_runtime;
+use crate::engine::runtime::{WXRuntimeError, WXRuntimeInfo, WXRuntimeMessage};
+use crate::engine::server::{WXServer, WXShutdown};
+use crate::engine::stdlib::{WXNativeFunctions, WXNativeRegistry};
+use crate::engine::transpiler::{preload as preload_transpiled, transpile_ts};
+use crate::file::artifact::{artifact_path, read_artifact, source_hash, write_artifact};
+use crate::file::project::{load_module_files, load_modules, load_project_config, ProjectConfig};
+use crate::file::webx::{is_module_path, WXModule};
+use crate::reporting::debug::{info, is_quiet};
+use crate::reporting::error::{error_code, exit_error, DateTimeSpecifier, ERROR_PROJECT};
 use crate::reporting::warning::warning;
 
 pub fn get_project_config_file_path(root: &Path) -> PathBuf {
     root.join("webx.config.json")
 }
 
+/// The configuration file of the project at `root`.
+/// A given `config_path` is resolved relative to the project root,
+/// otherwise the default `webx.config.json` is used.
+pub fn resolve_project_config_file_path(root: &Path, config_path: Option<&Path>) -> PathBuf {
+    match config_path {
+        Some(config_path) => root.join(config_path),
+        None => get_project_config_file_path(root),
+    }
+}
+
+const TIMEOUT_DURATION_DEV: Duration = Duration::from_secs(1);
+const TIMEOUT_DURATION_PROD: Duration = Duration::from_secs(30);
+
+/// How long requests in progress may take to finish on shutdown.
+pub fn timeout_duration(mode: WXMode) -> Duration {
+    match mode {
+        WXMode::Dev(_) => TIMEOUT_DURATION_DEV,
+        WXMode::Prod => TIMEOUT_DURATION_PROD,
+    }
+}
+
 /// Output verbosity level
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub enum DebugLevel {
@@ -34,13 +66,27 @@ pub enum DebugLevel {
 }
 
 impl DebugLevel {
+    /// The level of a number, clamped to the nearest level if out of range [1-4].
     pub fn from_u8(level: u8) -> Self {
         match level {
-            1 => Self::Low,
+            0 | 1 => Self::Low,
             2 => Self::Medium,
             3 => Self::High,
-            4 => Self::Max,
-            _ => Self::Low,
+            _ => Self::Max,
+        }
+    }
+
+    /// Parse a level by number [1-4] or name, e.g. `3` or `high`, ignoring case.
+    pub fn parse(level: &str) -> Result<Self, String> {
+        match level.trim().to_ascii_lowercase().as_str() {
+            "1" | "low" => Ok(Self::Low),
+            "2" | "medium" => Ok(Self::Medium),
+            "3" | "high" => Ok(Self::High),
+            "4" | "max" => Ok(Self::Max),
+            _ => Err(format!(
+                "'{}' is not a debug level, expected 1-4 or one of low, medium, high, max",
+                level
+            )),
         }
     }
 
@@ -109,47 +155,55 @@ impl PartialEq<WXMode> for WXMode {
     }
 }
 
-fn print_start_info(
+/// Print the startup banner, unless output is suppressed with `--quiet`.
+pub fn print_start_info(
+    out: &mut impl Write,
     modules: &[WXModule],
     mode: WXMode,
     config: &ProjectConfig,
     start_duration: std::time::Duration,
-) {
+) -> io::Result<()> {
+    if is_quiet() {
+        return Ok(());
+    }
     let width = 28;
-    println!(
+    writeln!(
+        out,
         "{}{} Web{} {}",
         "+".bright_black(),
         "-".repeat(3).bright_black(),
         "X".bright_blue(),
         "-".repeat(width - 6 - 3).bright_black()
-    );
+    )?;
     let prefix = "|".bright_black();
     // Project Name
-    println!("{} {}: {}", prefix, "Project".bold(), config.name);
+    writeln!(out, "{} {}: {}", prefix, "Project".bold(), config.name)?;
     // Modules
     if modules.is_empty() {
-        println!("{} No modules found", prefix);
-        return;
+        writeln!(out, "{} No modules found", prefix)?;
+        return Ok(());
     } else if modules.len() == 1 {
-        println!(
+        writeln!(
+            out,
             "{} {}: {}",
             prefix,
             "Module".bold(),
             modules[0].path.module_name()
-        );
+        )?;
     } else {
-        println!("{} {} ({}):", prefix, "Modules".bold(), modules.len());
+        writeln!(out, "{} {} ({}):", prefix, "Modules".bold(), modules.len())?;
         let mut names = modules
             .iter()
             .map(|module| module.path.module_name())
             .collect::<Vec<_>>();
         names.sort();
         for name in names.iter() {
-            println!("{}   - {}", prefix, name);
+            writeln!(out, "{}   - {}", prefix, name)?;
         }
     }
     // Mode
-    println!(
+    writeln!(
+        out,
         "{} {}: {}",
         prefix,
         "Mode".bold(),
@@ -158,102 +212,504 @@ fn print_start_info(
         } else {
             "development"
         }
-    );
+    )?;
     // Debug level
     if mode.is_dev() {
-        println!(
+        writeln!(
+            out,
             "{} {}: {}",
             prefix,
             "Debug".bold(),
             mode.debug_level().name()
-        );
+        )?;
     }
     // Build duration
-    println!("{} {}: {:?}", prefix, "Took".bold(), start_duration);
+    writeln!(out, "{} {}: {:?}", prefix, "Took".bold(), start_duration)?;
     // Build time
     let now: DateTime<Local> = Local::now();
     let time = now.time().format("%H:%M");
-    println!(
+    writeln!(
+        out,
         "{} {}: {:?} at {}",
         prefix,
         "Build".bold(),
         now.date_naive(),
         time
-    );
+    )?;
     // WebX version
-    println!(
+    writeln!(
+        out,
         "{} {}: {}",
         prefix,
         "Version".bold(),
         env!("CARGO_PKG_VERSION")
-    );
+    )?;
     // WebX homepage
-    println!(
+    writeln!(
+        out,
         "{} {}: {}",
         prefix,
         "Homepage".bold(),
         env!("CARGO_PKG_HOMEPAGE")
-    );
-    println!("{}{}", "+".bright_black(), "-".repeat(width).bright_black());
+    )?;
+    writeln!(
+        out,
+        "{}{}",
+        "+".bright_black(),
+        "-".repeat(width).bright_black()
+    )?;
+    Ok(())
+}
+
+/// Opens the URL of a running server, see `webx run --open`.
+pub type WXOpener = Arc<dyn Fn(&str) + Send + Sync>;
+
+/// Environment variables marking a headless environment, where no browser is opened.
+/// `CI` is set by most CI services, `WEBX_NO_BROWSER` can be set anywhere else.
+const HEADLESS_ENV_VARS: [&str; 2] = ["CI", "WEBX_NO_BROWSER"];
+
+/// Whether there is likely no browser to open, see `HEADLESS_ENV_VARS`.
+pub fn is_headless() -> bool {
+    HEADLESS_ENV_VARS
+        .iter()
+        .any(|var| std::env::var_os(var).is_some_and(|value| !value.is_empty()))
+}
+
+/// Open a URL in the default browser of the platform, without waiting for it.
+pub fn open_in_browser(mode: WXMode, url: &str) {
+    #[cfg(target_os = "macos")]
+    let opened = std::process::Command::new("open").arg(url).spawn();
+    #[cfg(windows)]
+    let opened = std::process::Command::new("cmd")
+        .args(["/C", "start", "", url])
+        .spawn();
+    #[cfg(not(any(target_os = "macos", windows)))]
+    let opened = std::process::Command::new("xdg-open").arg(url).spawn();
+    match opened {
+        Ok(mut child) => {
+            // Reap the launcher once it exits.
+            std::thread::spawn(move || child.wait());
+        }
+        Err(err) => warning(
+            mode,
+            format!("Failed to open {} in a browser: {}", url, err),
+        ),
+    }
 }
 
-/// Run a WebX **project** from the given root path.
+/// Overrides of the project configuration given on the command line.
+#[derive(Debug, Default, Clone)]
+pub struct WXConfigOverrides {
+    pub host: Option<String>,
+    pub port: Option<u16>,
+    pub threads: Option<usize>,
+}
+
+impl WXConfigOverrides {
+    /// Replace the configured values that are overridden.
+    pub fn apply(&self, config: &mut ProjectConfig) {
+        if let Some(host) = &self.host {
+            config.host = Some(host.clone());
+        }
+        if let Some(port) = self.port {
+            config.port = port;
+        }
+        if let Some(threads) = self.threads {
+            config.worker_threads = Some(threads);
+        }
+    }
+}
+
+/// Whether the path is a single WebX module file rather than a project directory.
+pub fn is_module_file(path: &Path) -> bool {
+    path.is_file() && is_module_path(path)
+}
+
+/// Load a single WebX module file as a project of its own.
+/// The directory of the file is the project root, e.g. for `static()` paths,
+/// and the project uses the default configuration.
+///
+/// ## Returns
+/// The project root, configuration, and the module.
+pub fn load_module_file_project(file: &Path) -> (PathBuf, ProjectConfig, Vec<WXModule>) {
+    let file = file.canonicalize().unwrap_or_else(|_| file.to_path_buf());
+    let root = file.parent().map_or(PathBuf::from("."), Path::to_path_buf);
+    let name = file
+        .file_stem()
+        .map_or("webx".into(), |stem| stem.to_string_lossy());
+    let config = ProjectConfig::new(name.into_owned());
+    let webx_modules = load_module_files(&[file]);
+    (root, config, webx_modules)
+}
+
+/// A WebX project, loaded and analyzed, ready to be served.
+pub struct WXProject {
+    /// The project root, e.g. for `static()` paths.
+    pub root: PathBuf,
+    pub config: ProjectConfig,
+    pub modules: Vec<WXModule>,
+    /// The source root and configuration file, watched for changes in dev mode.
+    pub watch: Option<(PathBuf, PathBuf)>,
+}
+
+/// Load a WebX **project** from the given root path.
+/// If the path is a `.webx` or `.wx` file, only that module is loaded with the default configuration.
 ///
 /// ## Arguments
-/// - `root` - The root path of the project.
+/// - `root` - The root path of the project, or a single module file.
 /// - `mode` - The mode to run in.
-pub fn run(root: &Path, mode: WXMode, running: Arc<AtomicBool>) {
+/// - `config_path` - The configuration file, default: `webx.config.json` in the root.
+///   Not used for single module files.
+/// - `profile` - The name of a configuration profile to merge over the configuration.
+/// - `overrides` - Configuration values taking precedence over the project configuration.
+/// - `natives` - The native functions registered for the handlers, see `analyze_modules`.
+///
+/// ## Errors
+/// The errors of an invalid configuration, or all errors found by `analyze_modules`.
+pub fn load_project(
+    root: &Path,
+    mode: WXMode,
+    config_path: Option<&Path>,
+    profile: Option<&str>,
+    overrides: &WXConfigOverrides,
+    natives: &WXNativeFunctions,
+) -> Result<WXProject, Vec<WXRuntimeError>> {
     let time_start = Instant::now();
-    let config_file = get_project_config_file_path(root);
-    let config = load_project_config(&config_file);
-    let source_root = if let Some(src) = &config.src {
-        root.join(src)
+    if is_module_file(root) {
+        let (project_root, mut config, webx_modules) = load_module_file_project(root);
+        overrides.apply(&mut config);
+        analyze_modules(&webx_modules, &config, natives)?;
+        let _ = print_start_info(
+            &mut std::io::stdout(),
+            &webx_modules,
+            mode,
+            &config,
+            time_start.elapsed(),
+        );
+        info(
+            mode,
+            &format!(
+                "Running '{}' in single-file mode, hot reloading is disabled.",
+                root.display()
+            ),
+        );
+        return Ok(WXProject {
+            root: project_root,
+            config,
+            modules: webx_modules,
+            watch: None,
+        });
+    }
+    let config_file = resolve_project_config_file_path(root, config_path);
+    let mut config = load_project_config(&config_file, profile);
+    overrides.apply(&mut config);
+    validate_config(root, &config_file, &config)?;
+    let source_root = config.source_root(root);
+    let webx_modules = if mode.is_dev() {
+        load_analyzed_modules(&source_root, &config, natives)?
     } else {
-        root.to_path_buf()
+        load_build_modules(root, mode, &source_root, &config, natives)?
     };
-    let webx_modules = load_modules(&source_root);
-    analyze_module_deps(&webx_modules);
-    analyze_module_routes(&webx_modules);
-    print_start_info(&webx_modules, mode, &config, time_start.elapsed());
+    let _ = print_start_info(
+        &mut std::io::stdout(),
+        &webx_modules,
+        mode,
+        &config,
+        time_start.elapsed(),
+    );
+    Ok(WXProject {
+        root: root.to_path_buf(),
+        config,
+        modules: webx_modules,
+        watch: Some((source_root, config_file)),
+    })
+}
 
-    let (rt_tx, rt_rx) = std::sync::mpsc::channel();
-    if mode.is_dev() {
-        let fw_rt_tx = rt_tx.clone();
-        let fw_running = running.clone();
-        let fw_hnd =
-            std::thread::spawn(move || WXFileWatcher::run(mode, source_root, fw_rt_tx, fw_running));
-        let info = WXRuntimeInfo::new(root);
-        let runtime_running = running.clone();
-        let runtime_hnd = std::thread::spawn(move || {
-            let mut runtime = WXRuntime::new(rt_rx, mode, info);
-            runtime.load_modules(webx_modules);
-            runtime.run(runtime_running)
-        });
-        let sv_rt_tx = rt_tx.clone();
-        let mut server = WXServer::new(mode, config, sv_rt_tx);
-        server.run(running).expect("Failed to run server");
-        if runtime_hnd.join().is_err() {
-            warning(mode, "Failed to stop runtime".into());
-        }
-        if fw_hnd.join().is_err() {
-            warning(mode, "Failed to stop file watcher".into())
+/// Fail with all errors if the project configuration is invalid.
+fn validate_config(
+    root: &Path,
+    config_file: &Path,
+    config: &ProjectConfig,
+) -> Result<(), Vec<WXRuntimeError>> {
+    config.validate(root).map_err(|errors| {
+        vec![WXRuntimeError {
+            code: ERROR_PROJECT,
+            http_status: 500,
+            message: format!(
+                "Invalid WebX configuration in '{}':\n  - {}",
+                config_file.display(),
+                errors.join("\n  - ")
+            ),
+        }]
+    })
+}
+
+/// Analyze the dependencies, routes and handler calls of the modules of a project.
+/// Handlers may call the built-in native functions and the given registered ones.
+///
+/// ## Errors
+/// All errors found in the modules, see `exit_errors` to report them.
+pub fn analyze_modules(
+    modules: &[WXModule],
+    config: &ProjectConfig,
+    natives: &WXNativeFunctions,
+) -> Result<(), Vec<WXRuntimeError>> {
+    let errors = [
+        analyze_module_deps(modules),
+        analyze_module_routes(modules, &BodyFormatPolicy::new(config)),
+        analyze_unknown_handlers(modules, &WXNativeRegistry::new(natives)).map_err(|err| vec![err]),
+    ]
+    .into_iter()
+    .filter_map(Result::err)
+    .flatten()
+    .collect::<Vec<_>>();
+    match errors.is_empty() {
+        true => Ok(()),
+        false => Err(errors),
+    }
+}
+
+/// Report all errors and exit the process with the code of the first one.
+pub fn exit_errors(errors: Vec<WXRuntimeError>) -> ! {
+    for err in errors.iter() {
+        error_code(err.message.clone(), err.code, DateTimeSpecifier::None);
+    }
+    std::process::exit(errors.first().map_or(ERROR_PROJECT, |err| err.code));
+}
+
+/// Parse and analyze all modules in the source root.
+fn load_analyzed_modules(
+    source_root: &Path,
+    config: &ProjectConfig,
+    natives: &WXNativeFunctions,
+) -> Result<Vec<WXModule>, Vec<WXRuntimeError>> {
+    let webx_modules = load_modules(source_root);
+    analyze_modules(&webx_modules, config, natives)?;
+    Ok(webx_modules)
+}
+
+/// Load the modules from the build artifact of the project, skipping parsing and analysis.
+/// Without an artifact the sources are parsed, and stale artifacts are rebuilt.
+fn load_build_modules(
+    root: &Path,
+    mode: WXMode,
+    source_root: &Path,
+    config: &ProjectConfig,
+    natives: &WXNativeFunctions,
+) -> Result<Vec<WXModule>, Vec<WXRuntimeError>> {
+    let artifact_file = artifact_path(root);
+    if !artifact_file.exists() {
+        return load_analyzed_modules(source_root, config, natives);
+    }
+    let hash = source_hash(source_root, config);
+    match read_artifact(&artifact_file, source_root, &hash) {
+        Ok(artifact) => {
+            preload_transpiled(artifact.transpiled);
+            return Ok(artifact.modules);
         }
-    } else {
-        // If we are in production mode, run the `server` in main thread.
-        let info = WXRuntimeInfo::new(root);
-        let runtime_running = running.clone();
-        let runtime_hnd = std::thread::spawn(move || {
-            let mut runtime = WXRuntime::new(rt_rx, mode, info);
-            runtime.load_modules(webx_modules);
-            runtime.run(runtime_running);
+        Err(err) => warning(
+            mode,
+            format!("{}, rebuilding '{}'", err, artifact_file.display()),
+        ),
+    }
+    let webx_modules = load_analyzed_modules(source_root, config, natives)?;
+    if let Err(err) = build_artifact(&artifact_file, source_root, hash, &webx_modules) {
+        error_code(err, ERROR_PROJECT, mode.date_specifier());
+    }
+    Ok(webx_modules)
+}
+
+/// Transpile the global scopes of the modules and write them to a build artifact.
+fn build_artifact(
+    artifact_file: &Path,
+    source_root: &Path,
+    hash: String,
+    webx_modules: &[WXModule],
+) -> Result<(), String> {
+    let transpiled = webx_modules
+        .iter()
+        .flat_map(|module| module.scope.globals().into_iter().map(move |g| (module, g)))
+        .map(|(module, (_, ts))| {
+            transpile_ts(ts)
+                .map(|js| (ts.to_string(), js))
+                .map_err(|err| {
+                    format!(
+                        "Failed to build module '{}':\n{}",
+                        module.path.module_name(),
+                        err.message
+                    )
+                })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    write_artifact(artifact_file, source_root, hash, webx_modules, transpiled).map_err(|err| {
+        format!(
+            "Failed to write build artifact '{}': {}",
+            artifact_file.display(),
+            err
+        )
+    })
+}
+
+/// Build a WebX **project** into an artifact, loaded by `webx run --prod` without parsing.
+///
+/// ## Arguments
+/// - `root` - The root path of the project.
+/// - `config_path` - The configuration file, default: `webx.config.json` in the root.
+/// - `profile` - The name of a configuration profile to merge over the configuration.
+pub fn build(root: &Path, config_path: Option<PathBuf>, profile: Option<&str>) {
+    let time_start = Instant::now();
+    let config_file = resolve_project_config_file_path(root, config_path.as_deref());
+    let config = load_project_config(&config_file, profile);
+    validate_config(root, &config_file, &config).unwrap_or_else(|errors| exit_errors(errors));
+    let source_root = config.source_root(root);
+    let hash = source_hash(&source_root, &config);
+    let webx_modules = load_analyzed_modules(&source_root, &config, &WXNativeFunctions::new())
+        .unwrap_or_else(|errors| exit_errors(errors));
+    let artifact_file = artifact_path(root);
+    if let Err(err) = build_artifact(&artifact_file, &source_root, hash, &webx_modules) {
+        exit_error(err, ERROR_PROJECT, DateTimeSpecifier::None);
+    }
+    if !is_quiet() {
+        println!(
+            "Built {} modules into '{}' in {:?}",
+            webx_modules.len(),
+            artifact_file.display(),
+            time_start.elapsed()
+        );
+    }
+}
+
+/// Start the runtime and the server for the loaded modules, until `shutdown` is triggered.
+/// In dev mode, the given source root and config file are watched for changes.
+///
+/// On shutdown, no new connections are accepted
+/// and the requests in progress are finished before returning.
+pub fn serve(
+    root: &Path,
+    mode: WXMode,
+    config: ProjectConfig,
+    webx_modules: Vec<WXModule>,
+    watch: Option<(PathBuf, PathBuf)>,
+    natives: WXNativeFunctions,
+    opener: Option<WXOpener>,
+    shutdown: impl Into<WXShutdown>,
+) {
+    let shutdown = shutdown.into();
+    let (rt_tx, rt_rx) = std::sync::mpsc::channel();
+    let ready = Arc::new(AtomicBool::new(false));
+    let runtime_ready = ready.clone();
+    // The file watcher only runs in dev mode.
+    let fw_hnd = watch
+        .filter(|_| mode.is_dev())
+        .map(|(source_root, config_file)| {
+            let fw_rt_tx = rt_tx.clone();
+            let fw_running = shutdown.running();
+            let fw_config = config.clone();
+            let fw_root = root.to_path_buf();
+            std::thread::spawn(move || {
+                WXFileWatcher::run(
+                    mode,
+                    fw_root,
+                    source_root,
+                    config_file,
+                    fw_config,
+                    fw_rt_tx,
+                    fw_running,
+                )
+            })
         });
-        let sv_rt_tx = rt_tx.clone();
-        let mut server = WXServer::new(mode, config, sv_rt_tx);
-        server.run(running).expect("Failed to run server");
-        if runtime_hnd.join().is_err() {
-            warning(mode, "Failed to stop runtime".into())
-        }
+    let mut info = WXRuntimeInfo::new(root, &config, mode);
+    info.natives = Arc::new(WXNativeRegistry::new(&natives));
+    // The runtime keeps running until the server has drained its connections.
+    let runtime_running = Arc::new(AtomicBool::new(true));
+    let rt_running = runtime_running.clone();
+    let workers = config.runtime_workers.unwrap_or(1);
+    let runtime_hnd = std::thread::spawn(move || {
+        run_runtime(
+            rt_rx,
+            mode,
+            info,
+            webx_modules,
+            workers,
+            rt_running,
+            runtime_ready,
+        )
+    });
+    // Run the `server` in the main thread.
+    let shutdown_tx = rt_tx.clone();
+    let mut server = WXServer::new(mode, config, rt_tx, ready);
+    if let Some(opener) = opener {
+        server.open_when_listening(opener);
+    }
+    server.run(shutdown).expect("Failed to run server");
+    // Stop the runtime, which also stops once the server and file watcher let go of it.
+    drop(server);
+    runtime_running.store(false, Ordering::SeqCst);
+    let _ = shutdown_tx.send(WXRuntimeMessage::Shutdown);
+    if runtime_hnd.join().is_err() {
+        warning(mode, "Failed to stop runtime".into());
+    }
+    if fw_hnd.is_some_and(|hnd| hnd.join().is_err()) {
+        warning(mode, "Failed to stop file watcher".into())
     }
     // Check ps info: `ps | ? ProcessName -eq "webx"`
     // On interrupt, all threads are also terminated
 }
+
+/// Generate an OpenAPI 3 specification of a WebX **project** from the given root path.
+///
+/// ## Arguments
+/// - `root` - The root path of the project.
+/// - `output` - The file to write the specification to, or stdout if `None`.
+/// Load and analyze the modules of the project at `root`, for the project generators.
+fn load_analyzed_project(root: &Path) -> (ProjectConfig, Vec<WXModule>) {
+    let config = load_project_config(&get_project_config_file_path(root), None);
+    let webx_modules = load_analyzed_modules(
+        &config.source_root(root),
+        &config,
+        &WXNativeFunctions::new(),
+    )
+    .unwrap_or_else(|errors| exit_errors(errors));
+    (config, webx_modules)
+}
+
+/// Write generated JSON to the `output` file, or to stdout if no file is given.
+fn write_generated(output: Option<&Path>, generated: &serde_json::Value) {
+    let generated = serde_json::to_string_pretty(generated).unwrap();
+    match output {
+        Some(output) => {
+            if let Err(err) = std::fs::write(output, generated) {
+                exit_error(
+                    format!("Failed to write '{}': {}", output.display(), err),
+                    ERROR_PROJECT,
+                    DateTimeSpecifier::None,
+                );
+            }
+        }
+        None => println!("{}", generated),
+    }
+}
+
+pub fn openapi(root: &Path, output: Option<&Path>) {
+    let (config, webx_modules) = load_analyzed_project(root);
+    write_generated(output, &generate_openapi(&config, &webx_modules));
+}
+
+/// Export the project routes as a collection for an API client.
+///
+/// ## Arguments
+/// - `root` - The project directory.
+/// - `format` - The collection format, currently only `postman`.
+/// - `output` - The file to write the collection to, default: stdout.
+pub fn export(root: &Path, format: &str, output: Option<&Path>) {
+    let (config, webx_modules) = load_analyzed_project(root);
+    let collection = match format {
+        "postman" => generate_postman(&config, &webx_modules),
+        _ => exit_error(
+            format!("Unknown export format '{}'", format),
+            ERROR_PROJECT,
+            DateTimeSpecifier::None,
+        ),
+    };
+    write_generated(output, &collection);
+}